@@ -0,0 +1,72 @@
+//! Reading of RDF files contained in zip and tar archives.
+
+use std::fs::File;
+use std::io::Read;
+
+use anyhow::{Context, Result};
+
+use crate::{compression, format_from_path, is_rdf_file, InputFormat};
+
+const TAR_SUFFIXES: [&str; 7] = [
+    ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tar.xz", ".txz", ".tar.zst",
+];
+
+/// Checks if a file path denotes a zip or (possibly compressed) tar archive.
+pub fn is_archive(fpath: &str) -> bool {
+    let lower = fpath.to_ascii_lowercase();
+    lower.ends_with(".zip") || TAR_SUFFIXES.iter().any(|suffix| lower.ends_with(suffix))
+}
+
+/// Names an archive entry by the IRI of the archive (using the `jar:` IRI scheme).
+pub fn archive_entry_iri(archive_iri: &str, entry_name: &str) -> String {
+    format!("jar:{archive_iri}!/{}", entry_name.replace(' ', "%20"))
+}
+
+/// Reads the entries of an archive having a known RDF suffix, passing the name, input format
+/// and (decompressed) data of each to the callback (until it returns an error).
+pub fn read_archive(
+    fpath: &str,
+    mut on_entry: impl FnMut(&str, InputFormat, &mut dyn Read) -> Result<()>,
+) -> Result<()> {
+    let file = File::open(fpath).with_context(|| format!("Unable to open archive: {fpath}"))?;
+    let mut read_entry = |name: &str, reader: &mut dyn Read| -> Result<()> {
+        let Some(format) = format_from_path(name).filter(|_| is_rdf_file(name)) else {
+            return Ok(());
+        };
+        let mut reader = compression::decompress(reader)?;
+        on_entry(name, format, &mut reader)
+    };
+
+    if fpath.to_ascii_lowercase().ends_with(".zip") {
+        let mut archive =
+            zip::ZipArchive::new(file).with_context(|| format!("Invalid zip archive: {fpath}"))?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name()?.into_owned();
+            read_entry(&name, &mut entry)?;
+        }
+    } else {
+        let mut archive = tar::Archive::new(compression::decompress(file)?);
+        for entry in archive
+            .entries()
+            .with_context(|| format!("Invalid tar archive: {fpath}"))?
+        {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?;
+            let name = path
+                .strip_prefix(".")
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .into_owned();
+            read_entry(&name, &mut entry)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+//! Decompression of gzip, bzip2, xz and zstd compressed input.
+
+use std::io::{BufRead, BufReader, Read};
+
+use anyhow::Result;
+
+const COMPRESSION_EXTENSIONS: [&str; 4] = ["gz", "bz2", "xz", "zst"];
+
+/// Checks if a file extension denotes a compressed file.
+pub(crate) fn is_compression_extension(extension: &str) -> bool {
+    COMPRESSION_EXTENSIONS.contains(&extension)
+}
+
+/// Wraps a reader to decompress its data, if compressed (as detected by its magic bytes).
+pub(crate) fn decompress<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn BufRead + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let magic = reader.fill_buf()?;
+
+    Ok(if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader)))
+    } else if magic.starts_with(b"BZh") {
+        Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader)))
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Box::new(BufReader::new(xz2::bufread::XzDecoder::new_multi_decoder(
+            reader,
+        )))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(
+            reader,
+        )?))
+    } else {
+        Box::new(reader)
+    })
+}
@@ -0,0 +1,373 @@
+//! Reading of defaults from configuration files, in a subset of TOML: keys set to strings
+//! (basic, literal or multi-line), integers, booleans or arrays of those, at the top level (as
+//! options) or in the `[prefixes]`, `[queries]` and `[endpoints]` tables. Some options can also
+//! be set by environment variables (taking precedence over configuration files).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// The environment variables setting options (by name).
+pub const ENV_OPTIONS: &[(&str, &str)] = &[
+    ("OXRQ_INPUT_FORMAT", "input_format"),
+    ("OXRQ_OUTPUT_FORMAT", "output_format"),
+    ("OXRQ_BASE_IRI", "base_iri"),
+    ("OXRQ_PREFIXES_FILE", "prefixes"),
+    ("OXRQ_ENDPOINT", "endpoint"),
+    ("OXRQ_STORE", "store"),
+    ("OXRQ_MAX_MEMORY", "max_memory"),
+    ("OXRQ_TIMEOUT", "timeout"),
+    ("OXRQ_COLOR", "color"),
+];
+
+/// Defaults read from configuration files (with those of later files taking precedence) and
+/// environment variables.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// Default command-line options (by name, with `_` instead of `-`).
+    pub options: Vec<ConfigOption>,
+    /// Prefixes to use in queries and output.
+    pub prefixes: Vec<(String, String)>,
+    /// Queries by name.
+    pub queries: HashMap<String, String>,
+    /// SPARQL endpoint URLs by name.
+    pub endpoints: HashMap<String, String>,
+}
+
+/// A default command-line option, along with where it is set.
+#[derive(Clone, Debug)]
+pub struct ConfigOption {
+    pub name: String,
+    pub value: OptionValue,
+    pub source: OptionSource,
+}
+
+#[derive(Clone, Debug)]
+pub enum OptionSource {
+    File(PathBuf),
+    Env(&'static str),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionValue {
+    /// An option without a value (set to `true`).
+    Flag,
+    /// An option with one value (or several, for a repeatable option set to an array).
+    Values(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Config {
+    /// The query to run when no query is given (named `default`), if any.
+    pub fn default_query(&self) -> Option<&str> {
+        self.queries.get("default").map(String::as_str)
+    }
+
+    /// Reads those of the files that exist, in order.
+    pub fn load(files: &[PathBuf]) -> Result<Self> {
+        let mut config = Self::default();
+        for file in files.iter().filter(|file| file.is_file()) {
+            let text = fs::read_to_string(file)
+                .with_context(|| format!("Unable to read config file: {}", file.display()))?;
+            config
+                .read(&text, file)
+                .with_context(|| format!("Error in config file '{}'", file.display()))?;
+        }
+        Ok(config)
+    }
+
+    /// Reads the settings of a configuration file, taking precedence over those already read.
+    pub fn read(&mut self, text: &str, file: &Path) -> Result<()> {
+        let mut table = None;
+        let mut parser = Parser { text, pos: 0 };
+        loop {
+            parser.skip_blank();
+            if parser.at_end() {
+                return Ok(());
+            }
+            if parser.eat("[") {
+                let name = parser.key()?;
+                parser.expect("]")?;
+                if !["prefixes", "queries", "endpoints"].contains(&name.as_str()) {
+                    bail!("Unknown table on line {}: [{name}]", parser.line());
+                }
+                table = Some(name);
+                parser.end_of_line()?;
+                continue;
+            }
+            let line = parser.line();
+            let key = parser.key()?;
+            parser.expect("=")?;
+            let value = parser.value()?;
+            parser.end_of_line()?;
+            match table.as_deref() {
+                None => self.set_option(key, value, OptionSource::File(file.to_owned())),
+                Some(table) => {
+                    let Value::String(value) = value else {
+                        bail!("Expected a string on line {line}: {key}");
+                    };
+                    match table {
+                        "prefixes" => {
+                            self.prefixes.retain(|(pfx, _)| *pfx != key);
+                            self.prefixes.push((key, value));
+                        }
+                        "queries" => {
+                            self.queries.insert(key, value);
+                        }
+                        _ => {
+                            self.endpoints.insert(key, value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the options set by environment variables (unless empty), taking precedence over
+    /// those already read.
+    pub fn read_env(&mut self) {
+        for (var, name) in ENV_OPTIONS {
+            if let Some(value) = std::env::var(var).ok().filter(|value| !value.is_empty()) {
+                let value = Value::String(value);
+                self.set_option(name.to_string(), value, OptionSource::Env(var));
+            }
+        }
+    }
+
+    /// Sets an option (replacing any earlier value), or unsets it if `false` or empty.
+    fn set_option(&mut self, name: String, value: Value, source: OptionSource) {
+        let name = name.replace('-', "_");
+        self.options.retain(|option| option.name != name);
+        let value = match value {
+            Value::Boolean(true) => OptionValue::Flag,
+            Value::Boolean(false) => return,
+            Value::Array(values) if values.is_empty() => return,
+            Value::Array(values) => OptionValue::Values(values.iter().map(Value::text).collect()),
+            value => OptionValue::Values(vec![value.text()]),
+        };
+        self.options.push(ConfigOption {
+            name,
+            value,
+            source,
+        });
+    }
+}
+
+impl OptionSource {
+    /// The directory to resolve relative paths against (the one of the config file, or else the
+    /// current directory).
+    pub fn dir(&self) -> &Path {
+        match self {
+            Self::File(file) => file.parent().unwrap_or(Path::new("")),
+            Self::Env(_) => Path::new(""),
+        }
+    }
+}
+
+impl fmt::Display for OptionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(file) => write!(f, "config file '{}'", file.display()),
+            Self::Env(var) => write!(f, "environment variable {var}"),
+        }
+    }
+}
+
+impl Value {
+    fn text(&self) -> String {
+        match self {
+            Self::String(value) => value.clone(),
+            Self::Integer(value) => value.to_string(),
+            Self::Boolean(value) => value.to_string(),
+            Self::Array(values) => values.iter().map(Self::text).collect::<Vec<_>>().join(","),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.text.len()
+    }
+
+    fn line(&self) -> usize {
+        1 + self.text[..self.pos].matches('\n').count()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        self.skip_spaces();
+        if !self.eat(token) {
+            bail!("Expected '{token}' on line {}", self.line());
+        }
+        self.skip_spaces();
+        Ok(())
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+
+    /// Skips whitespace, newlines and comments.
+    fn skip_blank(&mut self) {
+        loop {
+            let rest = self.rest();
+            self.pos += rest.len() - rest.trim_start().len();
+            if !self.rest().starts_with('#') {
+                break;
+            }
+            self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_spaces();
+        if self.rest().starts_with('#') {
+            self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+        }
+        if !(self.at_end() || self.eat("\n") || self.eat("\r\n")) {
+            bail!("Unexpected text on line {}", self.line());
+        }
+        Ok(())
+    }
+
+    /// Parses a bare key (of letters, digits, `_` and `-`) or a quoted one.
+    fn key(&mut self) -> Result<String> {
+        self.skip_spaces();
+        if self.rest().starts_with(['"', '\'']) {
+            return self.string();
+        }
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            bail!("Expected a key on line {}", self.line());
+        }
+        self.pos += len;
+        Ok(rest[..len].to_owned())
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let rest = self.rest();
+        if rest.starts_with(['"', '\'']) {
+            return Ok(Value::String(self.string()?));
+        }
+        if self.eat("[") {
+            let mut values = Vec::new();
+            loop {
+                self.skip_blank();
+                if self.eat("]") {
+                    break;
+                }
+                values.push(self.value()?);
+                self.skip_blank();
+                if !self.eat(",") {
+                    self.skip_blank();
+                    if !self.eat("]") {
+                        bail!("Expected ',' or ']' on line {}", self.line());
+                    }
+                    break;
+                }
+            }
+            return Ok(Value::Array(values));
+        }
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == ',' || c == ']' || c == '#')
+            .unwrap_or(rest.len());
+        let word = &rest[..len];
+        let value = match word {
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            _ => match word.replace('_', "").parse() {
+                Ok(value) => Value::Integer(value),
+                Err(_) => bail!("Expected a value on line {}", self.line()),
+            },
+        };
+        self.pos += len;
+        Ok(value)
+    }
+
+    /// Parses a basic (`"…"`) or literal (`'…'`) string, or a multi-line one (in triple quotes,
+    /// leaving out a newline right after the opening quotes).
+    fn string(&mut self) -> Result<String> {
+        let line = self.line();
+        let rest = self.rest();
+        let quote = match rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+            true => &rest[..3],
+            false => &rest[..1],
+        };
+        let literal = quote.starts_with('\'');
+        self.pos += quote.len();
+        if quote.len() == 3 && !self.eat("\n") {
+            self.eat("\r\n");
+        }
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            if self.rest()[i..].starts_with(quote) {
+                self.pos += i + quote.len();
+                return Ok(value);
+            }
+            match c {
+                '\n' if quote.len() == 1 => break,
+                '\\' if !literal => {
+                    let escaped = match chars.next() {
+                        Some((_, 'n')) => '\n',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'r')) => '\r',
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((j, 'u')) => {
+                            let hex = self.rest().get(j + 1..j + 5).unwrap_or_default();
+                            let Some(c) =
+                                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                            else {
+                                bail!("Invalid escape on line {}", self.line());
+                            };
+                            chars.nth(3);
+                            c
+                        }
+                        Some((_, '\n')) if quote.len() == 3 => {
+                            // (A line ending backslash trims the following whitespace.)
+                            while chars.clone().next().is_some_and(|(_, c)| c.is_whitespace()) {
+                                chars.next();
+                            }
+                            continue;
+                        }
+                        _ => bail!("Invalid escape on line {}", self.line()),
+                    };
+                    value.push(escaped);
+                }
+                c => value.push(c),
+            }
+        }
+        bail!("Unclosed string on line {line}")
+    }
+}
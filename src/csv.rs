@@ -0,0 +1,160 @@
+//! CSV and TSV results output in other dialects (for tools with rigid expectations of delimiters,
+//! quoting, header rows and line terminators).
+
+use std::io::Write;
+
+use anyhow::Result;
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{Literal, Term};
+use oxigraph::sparql::Variable;
+
+/// How the fields of CSV and TSV results are quoted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quoting {
+    /// Only fields containing the delimiter, quotes or line breaks are quoted.
+    Minimal,
+    /// All fields are quoted.
+    Always,
+    /// No fields are quoted.
+    Never,
+}
+
+impl Quoting {
+    /// Looks up a quoting style by name (`minimal`, `always` or `never`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "minimal" => Some(Self::Minimal),
+            "always" => Some(Self::Always),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+}
+
+/// A dialect of CSV and TSV results output, with the standard ones used for any unset parts.
+/// (Values are written as in the standard formats: plain in CSV and as terms in TSV.)
+#[derive(Clone, Debug, Default)]
+pub struct CsvDialect {
+    /// The field delimiter (`,` for CSV and a tab for TSV by default).
+    pub delimiter: Option<char>,
+    /// How fields are quoted (minimally for CSV and never for TSV by default).
+    pub quoting: Option<Quoting>,
+    /// Whether to leave out the header row (of variable names).
+    pub no_header: bool,
+    /// The line terminator (CRLF for CSV and LF for TSV by default).
+    pub line_terminator: Option<String>,
+}
+
+/// Writes solutions as rows in a CSV dialect.
+pub(crate) struct SolutionsWriter<W: Write> {
+    writer: W,
+    tsv: bool,
+    delimiter: String,
+    quoting: Quoting,
+    line_terminator: String,
+}
+
+impl<W: Write> SolutionsWriter<W> {
+    /// Starts writing the rows (after the header row, unless left out). Values are written as
+    /// terms if `tsv` is set.
+    pub(crate) fn new(
+        writer: W,
+        dialect: &CsvDialect,
+        tsv: bool,
+        variables: &[Variable],
+    ) -> Result<Self> {
+        let mut writer = Self {
+            writer,
+            tsv,
+            delimiter: dialect
+                .delimiter
+                .unwrap_or(if tsv { '\t' } else { ',' })
+                .to_string(),
+            quoting: dialect.quoting.unwrap_or(if tsv {
+                Quoting::Never
+            } else {
+                Quoting::Minimal
+            }),
+            line_terminator: dialect
+                .line_terminator
+                .clone()
+                .unwrap_or_else(|| if tsv { "\n" } else { "\r\n" }.to_owned()),
+        };
+        if !dialect.no_header {
+            let names = variables.iter().map(|variable| match tsv {
+                true => variable.to_string(),
+                false => variable.as_str().to_owned(),
+            });
+            writer.write_row(names.collect())?;
+        }
+        Ok(writer)
+    }
+
+    /// Writes a row of values (empty for unbound variables).
+    pub(crate) fn write(&mut self, values: &[Option<Term>]) -> Result<()> {
+        let fields = values
+            .iter()
+            .map(|value| {
+                value
+                    .as_ref()
+                    .map(|term| self.field(term))
+                    .unwrap_or_default()
+            })
+            .collect();
+        self.write_row(fields)
+    }
+
+    pub(crate) fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: Vec<String>) -> Result<()> {
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(self.delimiter.as_bytes())?;
+            }
+            let quote = match self.quoting {
+                Quoting::Always => true,
+                Quoting::Never => false,
+                Quoting::Minimal => {
+                    field.contains(self.delimiter.as_str()) || field.contains(['"', '\r', '\n'])
+                }
+            };
+            if quote {
+                write!(self.writer, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                self.writer.write_all(field.as_bytes())?;
+            }
+        }
+        self.writer.write_all(self.line_terminator.as_bytes())?;
+        Ok(())
+    }
+
+    fn field(&self, term: &Term) -> String {
+        match term {
+            Term::NamedNode(node) if !self.tsv => node.as_str().to_owned(),
+            Term::Literal(literal) if !self.tsv => literal.value().to_owned(),
+            // Numbers and booleans are written as in Turtle:
+            Term::Literal(literal) if is_turtle_shorthand(literal) => literal.value().to_owned(),
+            term => term.to_string(),
+        }
+    }
+}
+
+/// Tells whether a number or boolean can be written by its value alone (as in Turtle).
+pub(crate) fn is_turtle_shorthand(literal: &Literal) -> bool {
+    let value = literal.value();
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let datatype = literal.datatype();
+    if datatype == xsd::INTEGER {
+        !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+    } else if datatype == xsd::DECIMAL {
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        !frac.is_empty() && (int.bytes().chain(frac.bytes())).all(|b| b.is_ascii_digit())
+    } else if datatype == xsd::DOUBLE {
+        value.contains(['e', 'E']) && value.parse::<f64>().is_ok()
+    } else {
+        datatype == xsd::BOOLEAN && matches!(value, "true" | "false")
+    }
+}
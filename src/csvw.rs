@@ -0,0 +1,514 @@
+//! Conversion of CSV to RDF, in the manner of CSVW (CSV on the Web) minimal mode: each row
+//! describes a subject (a blank node, or an IRI made by an `aboutUrl` template), with a triple for
+//! each non-empty cell. The columns are described by CSVW metadata, or by a minimal mapping of
+//! column names to properties (shaped like a JSON-LD context), or else get default properties
+//! (`#` and the column name, appended to the file IRI).
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use anyhow::{bail, Context, Result};
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term};
+use oxiri::Iri;
+use serde_json::Value;
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// How to convert CSV to RDF.
+#[derive(Clone, Debug)]
+pub(crate) struct Mapping {
+    delimiter: char,
+    header: bool,
+    about_url: Option<String>,
+    columns: Vec<Column>,
+    /// Whether the columns are matched to those of the CSV by title (as in a minimal mapping,
+    /// leaving out those not mapped), or else by position (as in CSVW).
+    by_title: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Column {
+    name: String,
+    titles: Vec<String>,
+    about_url: Option<String>,
+    property_url: Option<String>,
+    value_url: Option<String>,
+    datatype: Option<NamedNode>,
+    lang: Option<String>,
+    null: Vec<String>,
+    separator: Option<String>,
+    suppress_output: bool,
+    is_virtual: bool,
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header: true,
+            about_url: None,
+            columns: Vec::new(),
+            by_title: false,
+        }
+    }
+}
+
+impl Mapping {
+    /// Reads CSVW metadata (a table, or a table group of which the first table is used), or a
+    /// minimal mapping: a JSON object of column names and properties (or objects with `@id` as
+    /// property, and `@type` as datatype or `@id` for IRIs, or `@language`), along with `@id`
+    /// as the `aboutUrl` template of the rows and `@type` as their class. Prefixed names are
+    /// expanded by the given prefixes.
+    pub(crate) fn from_json(value: &Value, prefixes: &HashMap<String, String>) -> Result<Self> {
+        let Some(object) = value.as_object() else {
+            bail!("Expected a JSON object as CSV mapping");
+        };
+        let is_csvw = ["tables", "tableSchema", "url", "dialect"]
+            .iter()
+            .any(|key| object.contains_key(*key));
+        if !is_csvw {
+            return Self::from_minimal(object, prefixes);
+        }
+
+        let table = match object.get("tables") {
+            Some(tables) => tables
+                .as_array()
+                .and_then(|tables| tables.first())
+                .context("Expected an array of tables")?,
+            None => value,
+        };
+        let mut mapping = Self::default();
+        for dialect in [object.get("dialect"), table.get("dialect")]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(delimiter) = dialect.get("delimiter").and_then(Value::as_str) {
+                let mut chars = delimiter.chars();
+                let (Some(delimiter), None) = (chars.next(), chars.next()) else {
+                    bail!("Expected a single character as delimiter: {delimiter}");
+                };
+                mapping.delimiter = delimiter;
+            }
+            if let Some(header) = dialect.get("header").and_then(Value::as_bool) {
+                mapping.header = header;
+            }
+        }
+        let schema = match table.get("tableSchema").or(object.get("tableSchema")) {
+            Some(Value::String(url)) => {
+                bail!("Table schemas given by URL are not supported: {url}")
+            }
+            Some(schema) => schema,
+            None => return Ok(mapping),
+        };
+        mapping.about_url = string(schema, "aboutUrl", prefixes);
+        let columns = schema.get("columns").and_then(Value::as_array);
+        for (i, description) in columns.into_iter().flatten().enumerate() {
+            mapping
+                .columns
+                .push(Column::from_json(description, i, prefixes)?);
+        }
+        Ok(mapping)
+    }
+
+    fn from_minimal(
+        object: &serde_json::Map<String, Value>,
+        prefixes: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let mut mapping = Self {
+            by_title: true,
+            ..Self::default()
+        };
+        for (key, value) in object {
+            match key.as_str() {
+                "@id" => {
+                    mapping.about_url = value.as_str().map(|url| expand(url, prefixes));
+                    continue;
+                }
+                "@type" => {
+                    let class = value.as_str().context("Expected a class as @type")?;
+                    mapping.columns.push(Column {
+                        name: "@type".into(),
+                        property_url: Some(rdf::TYPE.as_str().into()),
+                        value_url: Some(expand(class, prefixes)),
+                        is_virtual: true,
+                        ..Column::default()
+                    });
+                    continue;
+                }
+                _ => {}
+            }
+            let (property, definition) = match value {
+                Value::String(property) => (property.as_str(), None),
+                Value::Object(definition) => {
+                    let property = definition.get("@id").and_then(Value::as_str);
+                    (
+                        property.context("Expected @id as property")?,
+                        Some(definition),
+                    )
+                }
+                _ => bail!("Expected a property for column {key}"),
+            };
+            let mut column = Column {
+                name: key.clone(),
+                titles: vec![key.clone()],
+                property_url: Some(expand(property, prefixes)),
+                null: vec![String::new()],
+                ..Column::default()
+            };
+            let field = |name| definition.and_then(|d| d.get(name)).and_then(Value::as_str);
+            match field("@type") {
+                Some("@id") => column.value_url = Some(format!("{{+{key}}}")),
+                Some(datatype) => column.datatype = Some(datatype_iri(datatype, prefixes)?),
+                None => {}
+            }
+            column.lang = field("@language").map(str::to_owned);
+            mapping.columns.push(column);
+        }
+        Ok(mapping)
+    }
+
+    /// Converts the rows of CSV to triples in the given graph, with URL templates resolved
+    /// against the base IRI.
+    pub(crate) fn convert<R: Read>(
+        &self,
+        mut reader: R,
+        base_iri: Option<&str>,
+        graph_name: &GraphName,
+    ) -> Result<Vec<Quad>> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let mut records = records(text.trim_start_matches('\u{feff}'), self.delimiter)?;
+        let header = match self.header && !records.is_empty() {
+            true => records.remove(0),
+            false => Vec::new(),
+        };
+        let width = records
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(header.len());
+        let columns = self.columns_for(&header, width);
+        let base_iri = base_iri.map(|iri| Iri::parse(iri.to_owned())).transpose()?;
+        let resolver = Resolver { base_iri };
+
+        let mut quads = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            let row = (i + 1).to_string();
+            let cells: Vec<Option<&str>> = columns
+                .iter()
+                .map(|(column, index)| {
+                    let cell = index.and_then(|index| record.get(index))?.trim();
+                    (!column.null.iter().any(|null| null == cell)).then_some(cell)
+                })
+                .collect();
+            // (Cells of unmapped columns can also be used in templates, by their titles.)
+            let mut variables: HashMap<&str, &str> = header
+                .iter()
+                .zip(record)
+                .map(|(title, cell)| (title.as_str(), cell.trim()))
+                .filter(|(_, cell)| !cell.is_empty())
+                .collect();
+            variables.extend(
+                columns
+                    .iter()
+                    .zip(&cells)
+                    .filter_map(|((column, _), cell)| Some((column.name.as_str(), (*cell)?))),
+            );
+            variables.insert("_row", &row);
+
+            let row_subject = match &self.about_url {
+                Some(template) => resolver.iri(template, &variables)?.into(),
+                None => NamedOrBlankNode::from(BlankNode::default()),
+            };
+            for ((column, _), cell) in columns.iter().zip(&cells) {
+                let is_virtual = column.is_virtual && column.value_url.is_some();
+                if column.suppress_output || (cell.is_none() && !is_virtual) {
+                    continue;
+                }
+                variables.insert("_name", &column.name);
+                let subject = match &column.about_url {
+                    Some(template) => resolver.iri(template, &variables)?.into(),
+                    None => row_subject.clone(),
+                };
+                let template = column.property_url.as_deref().unwrap_or("{#_name}");
+                let predicate = resolver.iri(template, &variables)?;
+                let values = match (cell, &column.separator) {
+                    (Some(cell), Some(separator)) => cell.split(separator.as_str()).collect(),
+                    (Some(cell), None) => vec![*cell],
+                    (None, _) => vec![""],
+                };
+                for value in values {
+                    let object = match &column.value_url {
+                        Some(template) => {
+                            variables.insert(&column.name, value);
+                            resolver.iri(template, &variables)?.into()
+                        }
+                        None => column.literal(value),
+                    };
+                    quads.push(Quad::new(
+                        subject.clone(),
+                        predicate.clone(),
+                        object,
+                        graph_name.clone(),
+                    ));
+                }
+                if let Some(cell) = cell {
+                    variables.insert(&column.name, cell);
+                }
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Gives the columns to convert, along with the index of their cells (none if virtual).
+    fn columns_for(&self, header: &[String], width: usize) -> Vec<(Column, Option<usize>)> {
+        let default_column = |i: usize| {
+            let title = header
+                .get(i)
+                .map_or_else(|| format!("_col.{}", i + 1), Clone::clone);
+            Column {
+                name: encode(&title, false),
+                titles: vec![title],
+                null: vec![String::new()],
+                ..Column::default()
+            }
+        };
+        let (virtual_columns, columns): (Vec<_>, Vec<_>) =
+            self.columns.iter().partition(|column| column.is_virtual);
+        let mut result = Vec::new();
+        if self.by_title {
+            for column in columns {
+                if let Some(i) = header
+                    .iter()
+                    .position(|title| column.titles.contains(title))
+                {
+                    result.push((column.clone(), Some(i)));
+                }
+            }
+        } else {
+            for i in 0..width {
+                let column = columns
+                    .get(i)
+                    .map_or_else(|| default_column(i), |c| (*c).clone());
+                result.push((column, Some(i)));
+            }
+        }
+        result.extend(
+            virtual_columns
+                .into_iter()
+                .map(|column| (column.clone(), None)),
+        );
+        result
+    }
+}
+
+impl Column {
+    fn from_json(
+        description: &Value,
+        index: usize,
+        prefixes: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let titles: Vec<String> = match description.get("titles") {
+            Some(Value::String(title)) => vec![title.clone()],
+            Some(Value::Array(titles)) => titles
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_owned)
+                .collect(),
+            // (Titles in several languages:)
+            Some(Value::Object(titles)) => titles
+                .values()
+                .flat_map(|titles| match titles {
+                    Value::Array(titles) => titles.iter().filter_map(Value::as_str).collect(),
+                    titles => titles.as_str().into_iter().collect::<Vec<_>>(),
+                })
+                .map(str::to_owned)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let name = match description.get("name").and_then(Value::as_str) {
+            Some(name) => name.to_owned(),
+            None => titles.first().map_or_else(
+                || format!("_col.{}", index + 1),
+                |title| encode(title, false),
+            ),
+        };
+        let datatype = match description.get("datatype") {
+            Some(Value::String(name)) => Some(datatype_iri(name, prefixes)?),
+            Some(datatype) => {
+                let name = datatype.get("@id").or(datatype.get("base"));
+                name.and_then(Value::as_str)
+                    .map(|name| datatype_iri(name, prefixes))
+                    .transpose()?
+            }
+            None => None,
+        };
+        let null = match description.get("null") {
+            Some(Value::String(null)) => vec![null.clone()],
+            Some(Value::Array(nulls)) => nulls
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_owned)
+                .collect(),
+            _ => vec![String::new()],
+        };
+        let flag = |name| description.get(name).and_then(Value::as_bool) == Some(true);
+        Ok(Self {
+            name,
+            titles,
+            about_url: string(description, "aboutUrl", prefixes),
+            property_url: string(description, "propertyUrl", prefixes),
+            value_url: string(description, "valueUrl", prefixes),
+            datatype,
+            lang: string(description, "lang", prefixes),
+            null,
+            separator: string(description, "separator", prefixes),
+            suppress_output: flag("suppressOutput"),
+            is_virtual: flag("virtual"),
+        })
+    }
+
+    fn literal(&self, value: &str) -> Term {
+        match (&self.lang, &self.datatype) {
+            (Some(lang), _) => Literal::new_language_tagged_literal(value, lang)
+                .unwrap_or_else(|_| Literal::new_simple_literal(value)),
+            (None, Some(datatype)) => Literal::new_typed_literal(value, datatype.clone()),
+            (None, None) => Literal::new_simple_literal(value),
+        }
+        .into()
+    }
+}
+
+/// Makes IRIs of URL templates (of RFC 6570, level 2).
+struct Resolver {
+    base_iri: Option<Iri<String>>,
+}
+
+impl Resolver {
+    fn iri(&self, template: &str, variables: &HashMap<&str, &str>) -> Result<NamedNode> {
+        let mut url = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            url.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                bail!("Unclosed expression in URL template: {template}");
+            };
+            let expression = &rest[start + 1..start + end];
+            let (prefix, reserved, name) = match expression.chars().next() {
+                Some('+') => ("", true, &expression[1..]),
+                Some('#') => ("#", true, &expression[1..]),
+                _ => ("", false, expression),
+            };
+            if let Some(value) = variables.get(name) {
+                url.push_str(prefix);
+                url.push_str(&encode(value, reserved));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        url.push_str(rest);
+        let iri = match &self.base_iri {
+            Some(base_iri) => base_iri.resolve(&url)?.into_inner(),
+            None => Iri::parse(url.clone())
+                .with_context(|| format!("No base IRI to resolve URL against: {url}"))?
+                .into_inner(),
+        };
+        Ok(NamedNode::new(iri)?)
+    }
+}
+
+/// Percent-encodes a value into a URL (keeping reserved characters if allowed).
+fn encode(value: &str, reserved: bool) -> String {
+    let mut encoded = String::new();
+    for c in value.chars() {
+        let keep = c.is_ascii_alphanumeric()
+            || "-._~".contains(c)
+            || (reserved && ":/?#[]@!$&'()*+,;=%".contains(c));
+        if keep {
+            encoded.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                encoded.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+    encoded
+}
+
+fn string(object: &Value, key: &str, prefixes: &HashMap<String, String>) -> Option<String> {
+    let value = object.get(key)?.as_str()?;
+    Some(match key {
+        "aboutUrl" | "propertyUrl" | "valueUrl" => expand(value, prefixes),
+        _ => value.to_owned(),
+    })
+}
+
+/// Expands a prefixed name (or else leaves the value as is).
+fn expand(value: &str, prefixes: &HashMap<String, String>) -> String {
+    value
+        .split_once(':')
+        .filter(|(_, local)| !local.starts_with("//"))
+        .and_then(|(pfx, local)| Some(format!("{}{local}", prefixes.get(pfx)?)))
+        .unwrap_or_else(|| value.to_owned())
+}
+
+/// Gives the IRI of a datatype, by a CSVW datatype name (mostly those of XSD) or else an IRI
+/// (or prefixed name).
+fn datatype_iri(name: &str, prefixes: &HashMap<String, String>) -> Result<NamedNode> {
+    let iri = match name {
+        "number" => xsd::DOUBLE.as_str().to_owned(),
+        "binary" => xsd::BASE_64_BINARY.as_str().to_owned(),
+        "datetime" => xsd::DATE_TIME.as_str().to_owned(),
+        "any" => format!("{XSD}anyAtomicType"),
+        "xml" => rdf::XML_LITERAL.as_str().to_owned(),
+        "html" => rdf::HTML.as_str().to_owned(),
+        "json" => "http://www.w3.org/ns/csvw#JSON".to_owned(),
+        name if !name.contains(':') => format!("{XSD}{name}"),
+        name => expand(name, prefixes),
+    };
+    NamedNode::new(&iri).with_context(|| format!("Invalid datatype: {name}"))
+}
+
+/// Splits CSV into records of fields (with fields in double quotes, doubling any quotes in them).
+fn records(text: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => {
+                        line += usize::from(c == '\n');
+                        field.push(c);
+                    }
+                    None => bail!("Unclosed quoted field on line {line}"),
+                }
+            },
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                line += 1;
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    // (Blank lines have no fields.)
+    records.retain(|record| !(record.len() == 1 && record[0].is_empty()));
+    Ok(records)
+}
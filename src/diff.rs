@@ -0,0 +1,366 @@
+//! Comparison (and combination by set operations) of RDF data, matching blank nodes by the
+//! structures they are part of.
+//!
+//! Quads without blank nodes are compared one by one. Quads connected by shared blank nodes are
+//! compared as whole structures (canonicalized independently of blank node labels), so a change
+//! within such a structure shows as the removal of the old and the addition of the new one.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use oxigraph::model::dataset::CanonicalizationAlgorithm;
+use oxigraph::model::{
+    BlankNode, Dataset, GraphName, Literal, NamedOrBlankNode, Quad, Term, Variable,
+};
+use oxigraph::sparql::results::{QueryResultsFormat, QueryResultsSerializer};
+
+/// The output format name (and file suffix) of RDF Patch.
+pub(crate) const PATCH_FORMAT: &str = "rdp";
+
+/// A difference: a quad (or structure of quads connected by blank nodes) removed or added.
+struct Change {
+    added: bool,
+    quads: Vec<Quad>,
+}
+
+/// Compares two sets of quads, returning the changes (ordered by their canonical form).
+fn compare(old: Vec<Quad>, new: Vec<Quad>) -> Vec<Change> {
+    let mut old_parts: HashMap<String, Vec<Vec<Quad>>> = HashMap::new();
+    for (key, quads) in partition(old) {
+        old_parts.entry(key).or_default().push(quads);
+    }
+    let mut changes = Vec::new();
+    for (key, quads) in partition(new) {
+        let is_in_old = old_parts
+            .get_mut(&key)
+            .is_some_and(|parts| parts.pop().is_some());
+        if !is_in_old {
+            changes.push((key, Change { added: true, quads }));
+        }
+    }
+    for (key, parts) in old_parts {
+        for quads in parts {
+            changes.push((
+                key.clone(),
+                Change {
+                    added: false,
+                    quads,
+                },
+            ));
+        }
+    }
+    changes.sort_by(|(a_key, a), (b_key, b)| a_key.cmp(b_key).then(a.added.cmp(&b.added)));
+
+    changes.into_iter().map(|(_, change)| change).collect()
+}
+
+/// An operation combining sets of quads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetOperation {
+    /// The quads in any of the sets.
+    Union,
+    /// The quads of the first set which are in all of the others.
+    Intersection,
+    /// The quads of the first set which are in none of the others.
+    Difference,
+}
+
+/// Combines sets of quads by an operation, without duplicates. (Quads connected by blank nodes
+/// are only in two sets if the whole structure is, apart from blank node labels.)
+pub(crate) fn combine(
+    first: Vec<Quad>,
+    others: Vec<Vec<Quad>>,
+    operation: SetOperation,
+) -> Vec<Quad> {
+    let mut seen = HashSet::new();
+    let mut quads = Vec::new();
+    if operation == SetOperation::Union {
+        for (key, part) in std::iter::once(first).chain(others).flat_map(partition) {
+            if seen.insert(key) {
+                quads.extend(with_new_blank_nodes(part));
+            }
+        }
+        return quads;
+    }
+    let other_keys: Vec<HashSet<String>> = others
+        .into_iter()
+        .map(|quads| partition(quads).into_iter().map(|(key, _)| key).collect())
+        .collect();
+    for (key, part) in partition(first) {
+        let keep = match operation {
+            SetOperation::Intersection => other_keys.iter().all(|keys| keys.contains(&key)),
+            _ => !other_keys.iter().any(|keys| keys.contains(&key)),
+        };
+        if keep && seen.insert(key) {
+            quads.extend(with_new_blank_nodes(part));
+        }
+    }
+    quads
+}
+
+/// Gives new blank nodes to a structure of quads (which has canonical labels, that other
+/// structures may share).
+fn with_new_blank_nodes(quads: Vec<Quad>) -> Vec<Quad> {
+    let mut bnodes: HashMap<BlankNode, BlankNode> = HashMap::new();
+    let mut new = |bnode: BlankNode| bnodes.entry(bnode).or_default().clone();
+    quads
+        .into_iter()
+        .map(|quad| Quad {
+            subject: match quad.subject {
+                NamedOrBlankNode::BlankNode(bnode) => new(bnode).into(),
+                subject => subject,
+            },
+            object: match quad.object {
+                Term::BlankNode(bnode) => new(bnode).into(),
+                object => object,
+            },
+            graph_name: match quad.graph_name {
+                GraphName::BlankNode(bnode) => new(bnode).into(),
+                graph_name => graph_name,
+            },
+            ..quad
+        })
+        .collect()
+}
+
+/// Splits quads into single quads without blank nodes and structures of quads connected by
+/// blank nodes, keyed by their canonical form.
+fn partition(quads: Vec<Quad>) -> Vec<(String, Vec<Quad>)> {
+    let mut parts = Vec::new();
+    let mut roots: HashMap<BlankNode, BlankNode> = HashMap::new();
+    let mut connected = Vec::new();
+
+    for quad in quads {
+        let bnodes = blank_nodes(&quad);
+        let Some((first, rest)) = bnodes.split_first() else {
+            parts.push((quad.to_string(), vec![quad]));
+            continue;
+        };
+        let root = find_root(&mut roots, first);
+        for bnode in rest {
+            let other = find_root(&mut roots, bnode);
+            if other != root {
+                roots.insert(other, root.clone());
+            }
+        }
+        connected.push(quad);
+    }
+
+    let mut structures: HashMap<BlankNode, Vec<Quad>> = HashMap::new();
+    for quad in connected {
+        let root = find_root(&mut roots, &blank_nodes(&quad)[0]);
+        structures.entry(root).or_default().push(quad);
+    }
+    for quads in structures.into_values() {
+        let mut dataset = quads.into_iter().collect::<Dataset>();
+        dataset.canonicalize(CanonicalizationAlgorithm::Unstable);
+        let mut quads = dataset
+            .iter()
+            .map(|quad| quad.into_owned())
+            .collect::<Vec<_>>();
+        quads.sort_by_cached_key(Quad::to_string);
+        let key = quads
+            .iter()
+            .map(Quad::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        parts.push((key, quads));
+    }
+
+    parts
+}
+
+fn blank_nodes(quad: &Quad) -> Vec<BlankNode> {
+    let mut bnodes = Vec::new();
+    if let NamedOrBlankNode::BlankNode(bnode) = &quad.subject {
+        bnodes.push(bnode.clone());
+    }
+    if let Term::BlankNode(bnode) = &quad.object {
+        bnodes.push(bnode.clone());
+    }
+    if let GraphName::BlankNode(bnode) = &quad.graph_name {
+        bnodes.push(bnode.clone());
+    }
+    bnodes
+}
+
+fn find_root(roots: &mut HashMap<BlankNode, BlankNode>, bnode: &BlankNode) -> BlankNode {
+    let mut root = bnode.clone();
+    while let Some(parent) = roots.get(&root) {
+        root = parent.clone();
+    }
+    if &root != bnode {
+        roots.insert(bnode.clone(), root.clone());
+    }
+    root
+}
+
+/// Labels the blank nodes of the changes in order of appearance (`_:b0`, `_:b1`, ...).
+fn relabel(changes: &mut [Change]) {
+    let mut count = 0;
+    for change in changes {
+        let mut labels = HashMap::new();
+        let mut relabel = |bnode: &BlankNode| {
+            labels
+                .entry(bnode.clone())
+                .or_insert_with(|| {
+                    count += 1;
+                    BlankNode::new_unchecked(format!("b{}", count - 1))
+                })
+                .clone()
+        };
+        for quad in &mut change.quads {
+            if let NamedOrBlankNode::BlankNode(bnode) = &quad.subject {
+                quad.subject = relabel(bnode).into();
+            }
+            if let Term::BlankNode(bnode) = &quad.object {
+                quad.object = relabel(bnode).into();
+            }
+            if let GraphName::BlankNode(bnode) = &quad.graph_name {
+                quad.graph_name = relabel(bnode).into();
+            }
+        }
+    }
+}
+
+/// Writes the differences between the old and the new quads, returning whether there were any.
+///
+/// Without an output format, changes are written as lines of N-Quads prefixed with `-` (removed)
+/// or `+` (added), in red and green if colored. With `rdp`, they are written as an RDF Patch. With
+/// a SPARQL results format, they are written as solutions binding `change` (`removed` or
+/// `added`), `s`, `p`, `o` and `g` (unless in the default graph).
+pub(crate) fn write_diff<W: Write>(
+    old: Vec<Quad>,
+    new: Vec<Quad>,
+    output_format: Option<&str>,
+    color: bool,
+    mut writer: W,
+) -> Result<bool> {
+    let mut changes = compare(old, new);
+    relabel(&mut changes);
+
+    let Some(fmt) = output_format else {
+        for change in &changes {
+            let (sign, start) = if change.added {
+                ('+', "\x1b[32m")
+            } else {
+                ('-', "\x1b[31m")
+            };
+            for quad in &change.quads {
+                if color {
+                    writeln!(writer, "{start}{sign} {quad} .\x1b[0m")?;
+                } else {
+                    writeln!(writer, "{sign} {quad} .")?;
+                }
+            }
+        }
+        return Ok(!changes.is_empty());
+    };
+
+    if fmt == PATCH_FORMAT {
+        let (added, removed): (Vec<_>, Vec<_>) = changes.iter().partition(|change| change.added);
+        write_patch(
+            removed.iter().flat_map(|change| &change.quads),
+            added.iter().flat_map(|change| &change.quads),
+            writer,
+        )?;
+        return Ok(!changes.is_empty());
+    }
+
+    let format = QueryResultsFormat::from_extension(fmt)
+        .with_context(|| format!("Unknown diff output format: {fmt}"))?;
+    let variables = ["change", "s", "p", "o", "g"].map(Variable::new_unchecked);
+    let mut serializer = QueryResultsSerializer::from_format(format)
+        .serialize_solutions_to_writer(writer, variables.to_vec())?;
+    for change in &changes {
+        let change_value =
+            Literal::new_simple_literal(if change.added { "added" } else { "removed" });
+        for quad in &change.quads {
+            let graph = match &quad.graph_name {
+                GraphName::NamedNode(node) => Some(Term::from(node.clone())),
+                GraphName::BlankNode(bnode) => Some(Term::from(bnode.clone())),
+                GraphName::DefaultGraph => None,
+            };
+            let values = [
+                Some(change_value.clone().into()),
+                Some(quad.subject.clone().into()),
+                Some(quad.predicate.clone().into()),
+                Some(quad.object.clone()),
+                graph,
+            ];
+            serializer.serialize(
+                variables
+                    .iter()
+                    .zip(&values)
+                    .filter_map(|(variable, value)| Some((variable, value.as_ref()?))),
+            )?;
+        }
+    }
+    serializer.finish()?;
+
+    Ok(!changes.is_empty())
+}
+
+/// Writes a short report of how the new quads differ from the old (if they are not isomorphic):
+/// the number of quads only in either, and the first of each, returning whether they differ.
+pub(crate) fn write_mismatch<W: Write>(
+    old: Vec<Quad>,
+    new: Vec<Quad>,
+    (old_name, new_name): (&str, &str),
+    mut writer: W,
+) -> Result<bool> {
+    let mut changes = compare(old, new);
+    if changes.is_empty() {
+        return Ok(false);
+    }
+    relabel(&mut changes);
+
+    let (added, removed): (Vec<_>, Vec<_>) = changes.iter().partition(|change| change.added);
+    let count = |changes: &[&Change]| {
+        let count = changes
+            .iter()
+            .map(|change| change.quads.len())
+            .sum::<usize>();
+        format!("{count} triple{}", if count == 1 { "" } else { "s" })
+    };
+    writeln!(
+        writer,
+        "Not isomorphic: {} only in {old_name}, {} only in {new_name}",
+        count(&removed),
+        count(&added)
+    )?;
+    if let Some(change) = removed.first() {
+        writeln!(writer, "First only in {old_name}:")?;
+        for quad in &change.quads {
+            writeln!(writer, "- {quad} .")?;
+        }
+    }
+    if let Some(change) = added.first() {
+        writeln!(writer, "First only in {new_name}:")?;
+        for quad in &change.quads {
+            writeln!(writer, "+ {quad} .")?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Writes changes as an [RDF Patch](https://afs.github.io/rdf-patch/) transaction (of deletions
+/// followed by additions).
+pub(crate) fn write_patch<'a, W: Write>(
+    removed: impl IntoIterator<Item = &'a Quad>,
+    added: impl IntoIterator<Item = &'a Quad>,
+    mut writer: W,
+) -> Result<()> {
+    writeln!(writer, "TX .")?;
+    for quad in removed {
+        writeln!(writer, "D {quad} .")?;
+    }
+    for quad in added {
+        writeln!(writer, "A {quad} .")?;
+    }
+    writeln!(writer, "TC .")?;
+
+    Ok(())
+}
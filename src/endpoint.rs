@@ -0,0 +1,236 @@
+//! Querying of remote SPARQL endpoints (and of local stores as such, as virtual endpoints).
+
+use std::io::Read;
+
+use anyhow::{Context, Result};
+use oxigraph::io::RdfFormat;
+use oxigraph::model::NamedNode;
+use oxigraph::sparql::results::{QueryResultsFormat, QueryResultsParser};
+use oxigraph::sparql::{
+    QueryEvaluationError, QueryResults, QuerySolutionIter, ServiceHandler, SparqlEvaluator,
+};
+use oxigraph::store::Store;
+use oxiri::Iri;
+use spargebra::algebra::GraphPattern;
+use spargebra::term::NamedNodePattern;
+use spargebra::Query;
+
+use crate::http;
+
+const RESULTS_ACCEPT: &str = "application/sparql-results+json, \
+    application/sparql-results+xml;q=0.9, text/tab-separated-values;q=0.8";
+
+const GRAPH_ACCEPT: &str = "text/turtle, application/n-triples;q=0.9, \
+    application/rdf+xml;q=0.8, application/ld+json;q=0.7";
+
+/// The response of an endpoint to a query.
+pub(crate) enum Response {
+    Results(QueryResults<'static>),
+    Graph(RdfFormat, Box<dyn Read>),
+}
+
+/// Sends a query to an endpoint (using the SPARQL protocol).
+pub(crate) fn send_query(endpoint: &str, query: &Query) -> Result<Response> {
+    let accept = match query {
+        Query::Select { .. } | Query::Ask { .. } => RESULTS_ACCEPT,
+        Query::Construct { .. } | Query::Describe { .. } => GRAPH_ACCEPT,
+    };
+    let (media_type, body) = http::post(
+        endpoint,
+        query.to_string().into_bytes(),
+        "application/sparql-query",
+        accept,
+    )?;
+    let media_type = media_type.unwrap_or_default();
+
+    if accept == GRAPH_ACCEPT {
+        let format = RdfFormat::from_media_type(&media_type).with_context(|| {
+            format!("Unsupported RDF format returned by {endpoint}: {media_type}")
+        })?;
+        return Ok(Response::Graph(format, Box::new(body)));
+    }
+
+    let format = QueryResultsFormat::from_media_type(&media_type).with_context(|| {
+        format!("Unsupported results format returned by {endpoint}: {media_type}")
+    })?;
+    let results = QueryResultsParser::from_format(format).for_reader(body)?;
+
+    Ok(Response::Results(results.into()))
+}
+
+/// Adds a SERVICE call to the endpoint as an alternative to the query pattern.
+///
+/// The alternative is added below any solution modifiers (such as aggregates, ordering and
+/// slicing), so that these apply to the combined local and remote solutions.
+pub(crate) fn with_service(query: Query, endpoint: &NamedNode) -> Query {
+    let add = |pattern| add_service(pattern, endpoint);
+    match query {
+        Query::Select {
+            dataset,
+            pattern,
+            base_iri,
+        } => Query::Select {
+            dataset,
+            pattern: add(pattern),
+            base_iri,
+        },
+        Query::Construct {
+            template,
+            dataset,
+            pattern,
+            base_iri,
+        } => Query::Construct {
+            template,
+            dataset,
+            pattern: add(pattern),
+            base_iri,
+        },
+        Query::Describe {
+            dataset,
+            pattern,
+            base_iri,
+        } => Query::Describe {
+            dataset,
+            pattern: add(pattern),
+            base_iri,
+        },
+        Query::Ask {
+            dataset,
+            pattern,
+            base_iri,
+        } => Query::Ask {
+            dataset,
+            pattern: add(pattern),
+            base_iri,
+        },
+    }
+}
+
+fn add_service(pattern: GraphPattern, endpoint: &NamedNode) -> GraphPattern {
+    let add = |inner: Box<GraphPattern>| Box::new(add_service(*inner, endpoint));
+    match pattern {
+        GraphPattern::Slice {
+            inner,
+            start,
+            length,
+        } => GraphPattern::Slice {
+            inner: add(inner),
+            start,
+            length,
+        },
+        GraphPattern::Distinct { inner } => GraphPattern::Distinct { inner: add(inner) },
+        GraphPattern::Reduced { inner } => GraphPattern::Reduced { inner: add(inner) },
+        GraphPattern::Project { inner, variables } => GraphPattern::Project {
+            inner: add(inner),
+            variables,
+        },
+        GraphPattern::OrderBy { inner, expression } => GraphPattern::OrderBy {
+            inner: add(inner),
+            expression,
+        },
+        // Filters and bindings on aggregates (i.e. HAVING and aggregate selections):
+        GraphPattern::Filter { expr, inner } if is_grouped(&inner) => GraphPattern::Filter {
+            expr,
+            inner: add(inner),
+        },
+        GraphPattern::Extend {
+            inner,
+            variable,
+            expression,
+        } if is_grouped(&inner) => GraphPattern::Extend {
+            inner: add(inner),
+            variable,
+            expression,
+        },
+        GraphPattern::Group {
+            inner,
+            variables,
+            aggregates,
+        } => GraphPattern::Group {
+            inner: Box::new(union_with_service(*inner, endpoint)),
+            variables,
+            aggregates,
+        },
+        pattern => union_with_service(pattern, endpoint),
+    }
+}
+
+fn is_grouped(pattern: &GraphPattern) -> bool {
+    match pattern {
+        GraphPattern::Group { .. } => true,
+        GraphPattern::Filter { inner, .. } | GraphPattern::Extend { inner, .. } => {
+            is_grouped(inner)
+        }
+        _ => false,
+    }
+}
+
+fn union_with_service(pattern: GraphPattern, endpoint: &NamedNode) -> GraphPattern {
+    GraphPattern::Union {
+        left: Box::new(pattern.clone()),
+        right: Box::new(GraphPattern::Service {
+            name: NamedNodePattern::NamedNode(endpoint.clone()),
+            inner: Box::new(pattern),
+            silent: false,
+        }),
+    }
+}
+
+/// Evaluates SERVICE calls to the endpoint (and only to that endpoint).
+pub(crate) struct EndpointService {
+    pub(crate) endpoint: String,
+}
+
+impl ServiceHandler for EndpointService {
+    type Error = QueryEvaluationError;
+
+    fn handle(
+        &self,
+        pattern: &GraphPattern,
+        base_iri: Option<&Iri<String>>,
+    ) -> Result<QuerySolutionIter<'static>, Self::Error> {
+        let query = Query::Select {
+            dataset: None,
+            pattern: pattern.clone(),
+            base_iri: base_iri.cloned(),
+        };
+        match send_query(&self.endpoint, &query) {
+            Ok(Response::Results(QueryResults::Solutions(solutions))) => Ok(solutions),
+            Ok(_) => Err(QueryEvaluationError::Service(
+                format!("No solutions returned by {}", self.endpoint).into(),
+            )),
+            Err(e) => Err(QueryEvaluationError::Service(e.into())),
+        }
+    }
+}
+
+/// Evaluates SERVICE calls over a local store (with the union of its graphs as default graph).
+pub(crate) struct LocalService {
+    pub(crate) store: Store,
+}
+
+impl ServiceHandler for LocalService {
+    type Error = QueryEvaluationError;
+
+    fn handle(
+        &self,
+        pattern: &GraphPattern,
+        base_iri: Option<&Iri<String>>,
+    ) -> Result<QuerySolutionIter<'static>, Self::Error> {
+        let query = Query::Select {
+            dataset: None,
+            pattern: pattern.clone(),
+            base_iri: base_iri.cloned(),
+        };
+        let mut query = SparqlEvaluator::new()
+            .without_default_http_service_handler()
+            .for_query(query);
+        query.dataset_mut().set_default_graph_as_union();
+        match query.on_store(&self.store).execute()? {
+            QueryResults::Solutions(solutions) => Ok(solutions),
+            _ => Err(QueryEvaluationError::Service(
+                "No solutions returned".into(),
+            )),
+        }
+    }
+}
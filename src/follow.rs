@@ -0,0 +1,46 @@
+//! Discovery of linked data documents to dereference, for crawling from the loaded data.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use oxigraph::model::{GraphNameRef, NamedNode, Term};
+use oxigraph::store::Store;
+
+/// Gives the URLs of the documents (without any fragment) of the HTTP(S) IRIs used as objects in
+/// the given graphs (or in all graphs if none are given), by any of the given predicates (or by
+/// any predicate if none are given), which are not yet loaded as graphs of their own.
+pub(crate) fn links(
+    store: &Store,
+    graphs: Option<&[NamedNode]>,
+    predicates: &[NamedNode],
+) -> Result<BTreeSet<String>> {
+    let graphs: Vec<Option<GraphNameRef>> = match graphs {
+        Some(graphs) => graphs.iter().map(|graph| Some(graph.into())).collect(),
+        None => vec![None],
+    };
+    let predicates: Vec<Option<&NamedNode>> = match predicates.is_empty() {
+        true => vec![None],
+        false => predicates.iter().map(Some).collect(),
+    };
+    let mut links = BTreeSet::new();
+    for graph in &graphs {
+        for predicate in &predicates {
+            let quads =
+                store.quads_for_pattern(None, predicate.map(NamedNode::as_ref), None, *graph);
+            for quad in quads {
+                let Term::NamedNode(iri) = quad?.object else {
+                    continue;
+                };
+                let iri = iri.as_str();
+                if !iri.starts_with("http://") && !iri.starts_with("https://") {
+                    continue;
+                }
+                let document = iri.split('#').next().unwrap_or(iri);
+                if !store.contains_named_graph(NamedNode::new_unchecked(document).as_ref())? {
+                    links.insert(document.to_owned());
+                }
+            }
+        }
+    }
+    Ok(links)
+}
@@ -0,0 +1,371 @@
+//! GeoSPARQL functions (`geof:distance`, `geof:sfWithin`, `geof:sfContains`, `geof:sfIntersects`
+//! and `geof:sfDisjoint`) over WKT literals, for filtering and joining geospatial data.
+//!
+//! Coordinates are longitude and latitude (in degrees, as in the default CRS84), unless given in
+//! EPSG:4326 (as latitude and longitude). Relations are computed as on a plane, and distances as
+//! on a sphere (between the closest points on the plane).
+
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{Literal, NamedNode, Term};
+
+/// The namespace of the GeoSPARQL functions.
+pub(crate) const FUNCTIONS: &str = "http://www.opengis.net/def/function/geosparql/";
+const WKT_LITERAL: &str = "http://www.opengis.net/ont/geosparql#wktLiteral";
+const UNITS: &str = "http://www.opengis.net/def/uom/OGC/1.0/";
+/// The mean radius of the Earth, in metres.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+type Point = [f64; 2];
+type Function = fn(&[Term]) -> Option<Term>;
+
+/// A geometry (or collection of them), as its points, lines and polygons (each a list of rings,
+/// the first being the exterior).
+#[derive(Debug, Default)]
+struct Geometry {
+    points: Vec<Point>,
+    lines: Vec<Vec<Point>>,
+    polygons: Vec<Vec<Vec<Point>>>,
+}
+
+/// Parsed WKT: a coordinate, or a parenthesized list.
+enum Node {
+    Coordinate(Point),
+    List(Vec<Node>),
+}
+
+/// The functions to register (by IRI).
+pub(crate) fn functions() -> Vec<(NamedNode, Function)> {
+    let functions: [(&str, Function); 5] = [
+        ("distance", distance),
+        ("sfWithin", |args| relate(args, within)),
+        ("sfContains", |args| relate(args, |a, b| within(b, a))),
+        ("sfIntersects", |args| relate(args, intersects)),
+        ("sfDisjoint", |args| relate(args, |a, b| !intersects(a, b))),
+    ];
+    functions
+        .into_iter()
+        .map(|(name, function)| {
+            (
+                NamedNode::new_unchecked(format!("{FUNCTIONS}{name}")),
+                function,
+            )
+        })
+        .collect()
+}
+
+fn relate(args: &[Term], relation: fn(&Geometry, &Geometry) -> bool) -> Option<Term> {
+    let [a, b] = args else {
+        return None;
+    };
+    Some(Literal::from(relation(&geometry(a)?, &geometry(b)?)).into())
+}
+
+/// Evaluates `geof:distance(a, b, units)` (in metres, kilometres, degrees or radians).
+fn distance(args: &[Term]) -> Option<Term> {
+    let [a, b, Term::NamedNode(units)] = args else {
+        return None;
+    };
+    let per_radian = match units.as_str().strip_prefix(UNITS)? {
+        "metre" | "meter" => EARTH_RADIUS,
+        "kilometre" | "kilometer" => EARTH_RADIUS / 1000.0,
+        "degree" => 180.0 / std::f64::consts::PI,
+        "radian" => 1.0,
+        _ => return None,
+    };
+    let (a, b) = (geometry(a)?, geometry(b)?);
+    let radians = if intersects(&a, &b) {
+        0.0
+    } else {
+        closest(&a, &b).min(closest(&b, &a))
+    };
+    Some(Literal::from(radians * per_radian).into())
+}
+
+/// Parses a WKT literal (or a plain string of WKT).
+fn geometry(term: &Term) -> Option<Geometry> {
+    let Term::Literal(literal) = term else {
+        return None;
+    };
+    if literal.datatype().as_str() != WKT_LITERAL && literal.datatype() != xsd::STRING {
+        return None;
+    }
+    let mut text = literal.value().trim();
+    let mut lat_long = false;
+    if let Some(rest) = text.strip_prefix('<') {
+        let (crs, rest) = rest.split_once('>')?;
+        lat_long = crs.ends_with("/EPSG/0/4326");
+        text = rest.trim_start();
+    }
+    let mut geometry = Geometry::default();
+    let mut rest = text;
+    parse_tagged(&mut rest, &mut geometry)?;
+    if !rest.trim().is_empty() {
+        return None;
+    }
+    if lat_long {
+        geometry.for_each_point(|point| point.swap(0, 1));
+    }
+    Some(geometry)
+}
+
+/// Parses a geometry type name and its coordinates (adding it to the geometry).
+fn parse_tagged(rest: &mut &str, geometry: &mut Geometry) -> Option<()> {
+    let text = rest.trim_start();
+    let end = text
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    let kind = text[..end].to_ascii_uppercase();
+    *rest = text[end..].trim_start();
+    // (Skipping any Z, M or ZM dimensions, whose extra coordinates are ignored.)
+    for dimensions in ["ZM", "Z", "M"] {
+        if rest.len() >= dimensions.len()
+            && rest[..dimensions.len()].eq_ignore_ascii_case(dimensions)
+        {
+            *rest = rest[dimensions.len()..].trim_start();
+            break;
+        }
+    }
+    if rest.len() >= 5 && rest[..5].eq_ignore_ascii_case("EMPTY") {
+        *rest = &rest[5..];
+        return Some(());
+    }
+    if kind == "GEOMETRYCOLLECTION" {
+        *rest = rest.strip_prefix('(')?;
+        loop {
+            parse_tagged(rest, geometry)?;
+            *rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                *rest = after;
+            } else {
+                *rest = rest.strip_prefix(')')?;
+                return Some(());
+            }
+        }
+    }
+    let node = parse_node(rest)?;
+    match (kind.as_str(), node) {
+        ("POINT", Node::List(items)) => geometry.points.push(single(&items)?),
+        ("LINESTRING", Node::List(items)) => geometry.lines.push(coordinates(&items)?),
+        ("POLYGON", Node::List(items)) => geometry.polygons.push(rings(&items)?),
+        ("MULTIPOINT", Node::List(items)) => {
+            for item in &items {
+                geometry.points.push(match item {
+                    Node::Coordinate(point) => *point,
+                    Node::List(items) => single(items)?,
+                });
+            }
+        }
+        ("MULTILINESTRING", Node::List(items)) => {
+            for item in &items {
+                let Node::List(items) = item else {
+                    return None;
+                };
+                geometry.lines.push(coordinates(items)?);
+            }
+        }
+        ("MULTIPOLYGON", Node::List(items)) => {
+            for item in &items {
+                let Node::List(items) = item else {
+                    return None;
+                };
+                geometry.polygons.push(rings(items)?);
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Parses a parenthesized list of coordinates or lists.
+fn parse_node(rest: &mut &str) -> Option<Node> {
+    *rest = rest.trim_start();
+    if let Some(after) = rest.strip_prefix('(') {
+        *rest = after;
+        let mut items = Vec::new();
+        loop {
+            items.push(parse_node(rest)?);
+            *rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                *rest = after;
+            } else {
+                *rest = rest.strip_prefix(')')?;
+                return Some(Node::List(items));
+            }
+        }
+    }
+    let end = rest.find([',', ')']).unwrap_or(rest.len());
+    let mut numbers = rest[..end].split_whitespace().map(str::parse::<f64>);
+    let point = [numbers.next()?.ok()?, numbers.next()?.ok()?];
+    if numbers.any(|number| number.is_err()) {
+        return None;
+    }
+    *rest = &rest[end..];
+    Some(Node::Coordinate(point))
+}
+
+fn single(items: &[Node]) -> Option<Point> {
+    match items {
+        [Node::Coordinate(point)] => Some(*point),
+        _ => None,
+    }
+}
+
+fn coordinates(items: &[Node]) -> Option<Vec<Point>> {
+    items
+        .iter()
+        .map(|item| match item {
+            Node::Coordinate(point) => Some(*point),
+            Node::List(_) => None,
+        })
+        .collect()
+}
+
+fn rings(items: &[Node]) -> Option<Vec<Vec<Point>>> {
+    items
+        .iter()
+        .map(|item| match item {
+            Node::List(items) => coordinates(items),
+            Node::Coordinate(_) => None,
+        })
+        .collect()
+}
+
+impl Geometry {
+    fn for_each_point(&mut self, mut f: impl FnMut(&mut Point)) {
+        self.points.iter_mut().for_each(&mut f);
+        self.lines.iter_mut().flatten().for_each(&mut f);
+        self.polygons
+            .iter_mut()
+            .flatten()
+            .flatten()
+            .for_each(&mut f);
+    }
+
+    fn vertices(&self) -> impl Iterator<Item = Point> + '_ {
+        let lines = self.lines.iter().flatten();
+        let rings = self.polygons.iter().flatten().flatten();
+        self.points.iter().chain(lines).chain(rings).copied()
+    }
+
+    /// The segments of the lines and of the rings of the polygons.
+    fn segments(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        let rings = self.polygons.iter().flatten();
+        self.lines
+            .iter()
+            .chain(rings)
+            .flat_map(|line| line.windows(2).map(|pair| (pair[0], pair[1])))
+    }
+
+    /// Tells whether a point is one of the points, on a segment, or inside a polygon.
+    fn covers(&self, point: Point) -> bool {
+        self.points.contains(&point)
+            || self.segments().any(|(a, b)| on_segment(point, a, b))
+            || self.polygons.iter().any(|rings| in_polygon(point, rings))
+    }
+}
+
+fn intersects(a: &Geometry, b: &Geometry) -> bool {
+    a.vertices().any(|point| b.covers(point))
+        || b.vertices().any(|point| a.covers(point))
+        || a.segments()
+            .any(|(p, q)| b.segments().any(|(r, s)| segments_cross(p, q, r, s)))
+}
+
+fn within(a: &Geometry, b: &Geometry) -> bool {
+    a.vertices().next().is_some()
+        && a.vertices().all(|point| b.covers(point))
+        && !a.segments().any(|(p, q)| {
+            b.segments()
+                .any(|(r, s)| segments_cross_properly(p, q, r, s))
+        })
+}
+
+/// Finds the distance (in radians) from the vertices of `a` to the closest points of `b`.
+fn closest(a: &Geometry, b: &Geometry) -> f64 {
+    let mut distance = f64::INFINITY;
+    for point in a.vertices() {
+        for other in &b.points {
+            distance = distance.min(haversine(point, *other));
+        }
+        for (p, q) in b.segments() {
+            distance = distance.min(haversine(point, closest_on_segment(point, p, q)));
+        }
+    }
+    distance
+}
+
+fn haversine(a: Point, b: Point) -> f64 {
+    let (lat1, lat2) = (a[1].to_radians(), b[1].to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b[0] - a[0]).to_radians();
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * h.sqrt().min(1.0).asin()
+}
+
+fn closest_on_segment(point: Point, a: Point, b: Point) -> Point {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length = dx * dx + dy * dy;
+    if length == 0.0 {
+        return a;
+    }
+    let t = (((point[0] - a[0]) * dx + (point[1] - a[1]) * dy) / length).clamp(0.0, 1.0);
+    [a[0] + t * dx, a[1] + t * dy]
+}
+
+/// Tells on which side of the line through `a` and `b` a point is (or 0 if on it).
+fn orientation(a: Point, b: Point, point: Point) -> f64 {
+    (b[0] - a[0]) * (point[1] - a[1]) - (b[1] - a[1]) * (point[0] - a[0])
+}
+
+fn on_segment(point: Point, a: Point, b: Point) -> bool {
+    orientation(a, b, point) == 0.0
+        && point[0] >= a[0].min(b[0])
+        && point[0] <= a[0].max(b[0])
+        && point[1] >= a[1].min(b[1])
+        && point[1] <= a[1].max(b[1])
+}
+
+fn segments_cross(p: Point, q: Point, r: Point, s: Point) -> bool {
+    segments_cross_properly(p, q, r, s)
+        || on_segment(r, p, q)
+        || on_segment(s, p, q)
+        || on_segment(p, r, s)
+        || on_segment(q, r, s)
+}
+
+/// Tells whether two segments cross at a point inside both.
+fn segments_cross_properly(p: Point, q: Point, r: Point, s: Point) -> bool {
+    let (d1, d2) = (orientation(r, s, p), orientation(r, s, q));
+    let (d3, d4) = (orientation(p, q, r), orientation(p, q, s));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+/// Tells whether a point is inside (or on the boundary of) a polygon, but not inside any of its
+/// holes.
+fn in_polygon(point: Point, rings: &[Vec<Point>]) -> bool {
+    let Some((exterior, holes)) = rings.split_first() else {
+        return false;
+    };
+    let on_boundary = |ring: &Vec<Point>| {
+        ring.windows(2)
+            .any(|pair| on_segment(point, pair[0], pair[1]))
+    };
+    (on_boundary(exterior) || in_ring(point, exterior))
+        && !holes
+            .iter()
+            .any(|hole| in_ring(point, hole) && !on_boundary(hole))
+}
+
+/// Tells whether a point is inside a ring (by counting the crossings of a ray from it).
+fn in_ring(point: Point, ring: &[Point]) -> bool {
+    let mut inside = false;
+    for pair in ring.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if (a[1] > point[1]) != (b[1] > point[1])
+            && point[0] < (b[0] - a[0]) * (point[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
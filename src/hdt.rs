@@ -0,0 +1,108 @@
+//! Reading and writing of HDT (Header Dictionary Triples) files.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use anyhow::{bail, Context, Result};
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term};
+
+const DEFAULT_DATASET_IRI: &str = "urn:x-oxrq:dataset";
+
+/// Reads all triples of an HDT file into the given graph.
+pub(crate) fn parse<R: BufRead>(reader: R, graph_name: &GraphName) -> Result<Vec<Quad>> {
+    let hdt = ::hdt::Hdt::read(reader).context("Invalid HDT data")?;
+
+    let mut blank_nodes = HashMap::new();
+    let mut quads = Vec::new();
+    for [s, p, o] in hdt.triples_all() {
+        let subject = match to_term(&s, &mut blank_nodes)? {
+            Term::NamedNode(node) => NamedOrBlankNode::from(node),
+            Term::BlankNode(node) => node.into(),
+            _ => continue,
+        };
+        let predicate = NamedNode::new(p.as_ref())?;
+        let object = to_term(&o, &mut blank_nodes)?;
+        quads.push(Quad::new(subject, predicate, object, graph_name.clone()));
+    }
+
+    Ok(quads)
+}
+
+/// Writes the triples of the given quads as an HDT file (described as a dataset by the base IRI).
+pub(crate) fn serialize<W: Write>(
+    quads: Vec<Quad>,
+    base_iri: Option<&str>,
+    mut writer: W,
+) -> Result<()> {
+    if let Some(Term::Triple(triple)) = quads
+        .iter()
+        .map(|quad| &quad.object)
+        .find(|object| matches!(object, Term::Triple(_)))
+    {
+        bail!("Triple terms cannot be written as HDT: <<( {triple} )>>");
+    }
+    let triples = quads.into_iter().map(|quad| {
+        [
+            from_term(quad.subject.into()),
+            quad.predicate.into_string(),
+            from_term(quad.object),
+        ]
+    });
+    let hdt = ::hdt::Hdt::from_triples(triples, base_iri.unwrap_or(DEFAULT_DATASET_IRI))?;
+    hdt.write(&mut writer).context("Unable to write HDT")?;
+
+    Ok(())
+}
+
+/// Converts a term to the HDT dictionary format.
+fn from_term(term: Term) -> String {
+    match term {
+        Term::NamedNode(node) => node.into_string(),
+        Term::BlankNode(node) => format!("_:{}", node.as_str()),
+        Term::Literal(literal) => {
+            let (value, datatype, lang, direction) = literal.destruct();
+            if let (Some(lang), Some(direction)) = (&lang, direction) {
+                format!("\"{value}\"@{lang}--{direction}")
+            } else if let Some(lang) = lang {
+                format!("\"{value}\"@{lang}")
+            } else if let Some(datatype) = datatype.filter(|dt| *dt != xsd::STRING) {
+                format!("\"{value}\"^^<{}>", datatype.as_str())
+            } else {
+                format!("\"{value}\"")
+            }
+        }
+        // (Rejected before converting.)
+        Term::Triple(triple) => format!("<<( {triple} )>>"),
+    }
+}
+
+/// Converts a term from the HDT dictionary format (IRIs without brackets, unescaped literals).
+fn to_term(value: &str, blank_nodes: &mut HashMap<String, BlankNode>) -> Result<Term> {
+    if let Some(label) = value.strip_prefix("_:") {
+        return Ok(blank_nodes
+            .entry(label.to_owned())
+            .or_default()
+            .clone()
+            .into());
+    }
+    let Some(literal) = value.strip_prefix('"') else {
+        return Ok(NamedNode::new(value)?.into());
+    };
+    let end = literal
+        .rfind('"')
+        .with_context(|| format!("Invalid HDT literal: {value}"))?;
+    let (lexical, suffix) = (&literal[..end], &literal[end + 1..]);
+
+    Ok(if let Some(lang) = suffix.strip_prefix('@') {
+        Literal::new_language_tagged_literal(lexical, lang)?
+    } else if let Some(datatype) = suffix
+        .strip_prefix("^^<")
+        .and_then(|dt| dt.strip_suffix('>'))
+    {
+        Literal::new_typed_literal(lexical, NamedNode::new(datatype)?)
+    } else {
+        Literal::new_simple_literal(lexical)
+    }
+    .into())
+}
@@ -0,0 +1,178 @@
+//! Syntax highlighting (by ANSI colors) of Turtle and TriG output, for reading on a terminal.
+
+use std::io::Write;
+
+use anyhow::Result;
+
+pub(crate) const BOLD: &str = "\x1b[1m";
+pub(crate) const DIM: &str = "\x1b[2m";
+pub(crate) const IRI_COLOR: &str = "\x1b[34m";
+pub(crate) const PREFIX_COLOR: &str = "\x1b[36m";
+pub(crate) const BLANK_NODE_COLOR: &str = "\x1b[35m";
+pub(crate) const LITERAL_COLOR: &str = "\x1b[32m";
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// Writes Turtle (or TriG) with IRIs, prefixes, literals, blank nodes, keywords and comments in
+/// distinct colors, line by line (or as is unless enabled).
+pub(crate) struct TurtleWriter<W: Write> {
+    writer: W,
+    enabled: bool,
+    /// Text of an unfinished line.
+    line: Vec<u8>,
+    /// The quotes of a long string continuing on the next line, if any.
+    long_quote: Option<&'static str>,
+}
+
+impl<W: Write> TurtleWriter<W> {
+    pub(crate) fn new(writer: W, enabled: bool) -> Self {
+        Self {
+            writer,
+            enabled,
+            line: Vec::new(),
+            long_quote: None,
+        }
+    }
+
+    /// Writes any unfinished line, and flushes the writer.
+    pub(crate) fn finish(mut self) -> Result<()> {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.write_line(&String::from_utf8_lossy(&line))?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let mut out = String::with_capacity(line.len() * 2);
+        let mut rest = line;
+        if let Some(quote) = self.long_quote {
+            let end = string_end(rest, quote);
+            self.long_quote = end.is_none().then_some(quote);
+            let (string, after) = rest.split_at(end.unwrap_or(rest.len()));
+            paint(&mut out, LITERAL_COLOR, string);
+            rest = after;
+        }
+        while let Some(c) = rest.chars().next() {
+            let len = match c {
+                '#' => {
+                    let comment = rest.trim_end_matches(['\r', '\n']);
+                    paint(&mut out, DIM, comment);
+                    comment.len()
+                }
+                '<' => {
+                    let len = rest.find('>').map_or(rest.len(), |end| end + 1);
+                    paint(&mut out, IRI_COLOR, &rest[..len]);
+                    len
+                }
+                '"' | '\'' => {
+                    let quote = if rest.starts_with("\"\"\"") {
+                        "\"\"\""
+                    } else if rest.starts_with("'''") {
+                        "'''"
+                    } else if c == '"' {
+                        "\""
+                    } else {
+                        "'"
+                    };
+                    let end = string_end(&rest[quote.len()..], quote);
+                    if end.is_none() && quote.len() == 3 {
+                        self.long_quote = Some(quote);
+                    }
+                    let mut len = end.map_or(rest.len(), |end| quote.len() + end);
+                    // (Along with any language tag.)
+                    if rest[len..].starts_with('@') {
+                        len += name_len(&rest[len..]);
+                    }
+                    let string = rest[..len].trim_end_matches(['\r', '\n']);
+                    paint(&mut out, LITERAL_COLOR, string);
+                    string.len()
+                }
+                '.' if !rest[1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                    out.push(c);
+                    1
+                }
+                c if c.is_whitespace() || "{}()[],;^".contains(c) => {
+                    out.push(c);
+                    c.len_utf8()
+                }
+                _ => {
+                    let len = name_len(rest).max(c.len_utf8());
+                    paint_name(&mut out, &rest[..len]);
+                    len
+                }
+            };
+            rest = &rest[len..];
+        }
+        self.writer.write_all(out.as_bytes())
+    }
+}
+
+impl<W: Write> Write for TurtleWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.enabled {
+            return self.writer.write(buf);
+        }
+        self.line.extend_from_slice(buf);
+        while let Some(newline) = self.line.iter().position(|b| *b == b'\n') {
+            let line = self.line.drain(..=newline).collect::<Vec<_>>();
+            self.write_line(&String::from_utf8_lossy(&line))?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Finds the end of a string (after its closing quotes), if on the same line.
+fn string_end(text: &str, quote: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if text[i..].starts_with(quote) {
+            return Some(i + quote.len());
+        }
+    }
+    None
+}
+
+/// Measures a name (a prefixed name, blank node label, keyword, number or language tag), which
+/// does not end with a dot.
+fn name_len(text: &str) -> usize {
+    let end = text
+        .find(|c: char| c.is_whitespace() || "<>\"'{}()[],;^#".contains(c))
+        .unwrap_or(text.len());
+    text[..end].trim_end_matches('.').len()
+}
+
+fn paint_name(out: &mut String, name: &str) {
+    let keyword = ["a", "@prefix", "@base"].contains(&name)
+        || ["PREFIX", "BASE", "GRAPH"].contains(&name.to_ascii_uppercase().as_str());
+    if ["true", "false"].contains(&name)
+        || name.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c))
+    {
+        paint(out, LITERAL_COLOR, name);
+    } else if keyword {
+        paint(out, BOLD, name);
+    } else if name.starts_with("_:") {
+        paint(out, BLANK_NODE_COLOR, name);
+    } else if let Some(colon) = name.find(':') {
+        paint(out, PREFIX_COLOR, &name[..colon + 1]);
+        paint(out, IRI_COLOR, &name[colon + 1..]);
+    } else {
+        out.push_str(name);
+    }
+}
+
+fn paint(out: &mut String, color: &str, text: &str) {
+    if !text.is_empty() {
+        out.push_str(color);
+        out.push_str(text);
+        out.push_str(RESET);
+    }
+}
@@ -0,0 +1,669 @@
+//! Extraction of RDF from HTML (RDFa, microdata and embedded JSON-LD).
+
+use std::collections::HashMap;
+
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term};
+use oxiri::Iri;
+use scraper::{ElementRef, Html};
+
+const RDFA_USES_VOCABULARY: &str = "http://www.w3.org/ns/rdfa#usesVocabulary";
+
+/// Prefixes of the RDFa initial context (a commonly used subset).
+const INITIAL_PREFIXES: &[(&str, &str)] = &[
+    ("as", "https://www.w3.org/ns/activitystreams#"),
+    ("cc", "http://creativecommons.org/ns#"),
+    ("dc", "http://purl.org/dc/terms/"),
+    ("dc11", "http://purl.org/dc/elements/1.1/"),
+    ("dcat", "http://www.w3.org/ns/dcat#"),
+    ("dcterms", "http://purl.org/dc/terms/"),
+    ("foaf", "http://xmlns.com/foaf/0.1/"),
+    ("gr", "http://purl.org/goodrelations/v1#"),
+    ("og", "http://ogp.me/ns#"),
+    ("org", "http://www.w3.org/ns/org#"),
+    ("owl", "http://www.w3.org/2002/07/owl#"),
+    ("prov", "http://www.w3.org/ns/prov#"),
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("rdfa", "http://www.w3.org/ns/rdfa#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("schema", "http://schema.org/"),
+    ("sioc", "http://rdfs.org/sioc/ns#"),
+    ("skos", "http://www.w3.org/2004/02/skos/core#"),
+    ("vcard", "http://www.w3.org/2006/vcard/ns#"),
+    ("void", "http://rdfs.org/ns/void#"),
+    ("xhv", "http://www.w3.org/1999/xhtml/vocab#"),
+    ("xml", "http://www.w3.org/XML/1998/namespace"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+];
+
+/// RDF data extracted from an HTML document.
+pub(crate) struct HtmlData {
+    pub(crate) quads: Vec<Quad>,
+    pub(crate) base_iri: Option<String>,
+    pub(crate) prefixes: Vec<(String, String)>,
+    /// Contents of embedded JSON-LD script elements (left to the JSON-LD parser).
+    pub(crate) json_ld: Vec<String>,
+}
+
+/// Extracts RDFa and microdata from an HTML document into the given graph.
+pub(crate) fn parse(html: &str, base_iri: Option<&str>, graph_name: &GraphName) -> HtmlData {
+    let document = Html::parse_document(html);
+
+    // A base element takes precedence over the given base IRI:
+    let base_element = document
+        .root_element()
+        .descendent_elements()
+        .find(|e| e.value().name() == "base")
+        .and_then(|e| e.attr("href"));
+    let base_iri = match (base_iri.map(Iri::parse), base_element) {
+        (Some(Ok(base_iri)), Some(href)) => base_iri.resolve(href).ok(),
+        (_, Some(href)) => Iri::parse(href.to_owned()).ok(),
+        (Some(Ok(base_iri)), None) => Some(base_iri.to_owned().into()),
+        _ => None,
+    }
+    .map(Iri::into_inner);
+
+    let mut processor = Processor {
+        base_iri: base_iri.clone(),
+        graph_name: graph_name.clone(),
+        quads: Vec::new(),
+        prefixes: Vec::new(),
+        blank_nodes: HashMap::new(),
+    };
+
+    let base_subject = base_iri
+        .as_deref()
+        .and_then(|iri| NamedNode::new(iri).ok())
+        .map(NamedOrBlankNode::from)
+        .unwrap_or_else(|| BlankNode::default().into());
+
+    let context = EvaluationContext {
+        parent_subject: base_subject.clone(),
+        parent_object: Some(base_subject),
+        incomplete_triples: Vec::new(),
+        language: None,
+        prefixes: INITIAL_PREFIXES
+            .iter()
+            .map(|(pfx, ns)| (pfx.to_string(), ns.to_string()))
+            .collect(),
+        vocab: None,
+    };
+    processor.process_rdfa(document.root_element(), &context, true);
+    processor.process_microdata(&document);
+
+    let json_ld = document
+        .root_element()
+        .descendent_elements()
+        .filter(|e| {
+            e.value().name() == "script"
+                && e.attr("type")
+                    .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/ld+json"))
+        })
+        .map(|e| e.text().collect())
+        .collect();
+
+    HtmlData {
+        quads: processor.quads,
+        base_iri,
+        prefixes: processor.prefixes,
+        json_ld,
+    }
+}
+
+#[derive(Clone)]
+enum Direction {
+    Forward,
+    Backward,
+}
+
+#[derive(Clone)]
+struct EvaluationContext {
+    parent_subject: NamedOrBlankNode,
+    parent_object: Option<NamedOrBlankNode>,
+    incomplete_triples: Vec<(NamedNode, Direction)>,
+    language: Option<String>,
+    prefixes: HashMap<String, String>,
+    vocab: Option<String>,
+}
+
+struct Processor {
+    base_iri: Option<String>,
+    graph_name: GraphName,
+    quads: Vec<Quad>,
+    prefixes: Vec<(String, String)>,
+    blank_nodes: HashMap<String, BlankNode>,
+}
+
+impl Processor {
+    fn process_rdfa(&mut self, element: ElementRef, parent: &EvaluationContext, is_root: bool) {
+        let name = element.value().name();
+        let attr = |name: &str| element.attr(name);
+        let mut context = parent.clone();
+        let mut skip = false;
+        let new_subject: Option<NamedOrBlankNode>;
+        let mut current_object: Option<NamedOrBlankNode> = None;
+        let mut typed_resource: Option<NamedOrBlankNode> = None;
+        let mut incomplete_triples = Vec::new();
+
+        // Vocabulary, prefixes and language:
+        if let Some(vocab) = attr("vocab") {
+            if vocab.trim().is_empty() {
+                context.vocab = None;
+            } else if let Some(vocab) = self.resolve(vocab.trim()) {
+                self.emit(
+                    self.base_node(),
+                    NamedNode::new_unchecked(RDFA_USES_VOCABULARY),
+                    vocab.clone().into(),
+                );
+                // Used as default namespace (like @vocab in JSON-LD):
+                self.declare_prefix("", vocab.as_str());
+                context.vocab = Some(vocab.into_string());
+            }
+        }
+        for (attr_name, value) in element.value().attrs() {
+            if let Some(pfx) = attr_name.strip_prefix("xmlns:") {
+                self.add_prefix(&mut context, pfx, value);
+            }
+        }
+        if let Some(prefix) = attr("prefix") {
+            let mut tokens = prefix.split_whitespace();
+            while let (Some(pfx), Some(ns)) = (tokens.next(), tokens.next()) {
+                if let Some(pfx) = pfx.strip_suffix(':') {
+                    self.add_prefix(&mut context, pfx, ns);
+                }
+            }
+        }
+        if let Some(lang) = attr("xml:lang").or(attr("lang")) {
+            context.language = Some(lang.to_owned()).filter(|lang| !lang.is_empty());
+        }
+
+        // Root elements (in HTML also head and body) are about the parent object (the document):
+        let is_root = is_root || name == "head" || name == "body";
+
+        let about = attr("about").and_then(|v| self.resource(&context, v));
+        let resource = attr("resource").and_then(|v| self.resource(&context, v));
+        let href = attr("href")
+            .or(attr("src"))
+            .and_then(|v| self.resolve(v))
+            .map(NamedOrBlankNode::from);
+        let rels = self.predicates(&context, attr("rel"));
+        let revs = self.predicates(&context, attr("rev"));
+        let properties = attr("property").map(|v| self.predicates(&context, Some(v)));
+        let types = attr("typeof").map(|v| self.predicates(&context, Some(v)));
+        let content = attr("content");
+        let datatype = attr("datatype");
+
+        if attr("rel").is_none() && attr("rev").is_none() {
+            if properties.is_some() && content.is_none() && datatype.is_none() {
+                new_subject = about.clone().or_else(|| context.parent_object.clone());
+                if types.is_some() {
+                    typed_resource = about.clone().or_else(|| {
+                        if is_root {
+                            context.parent_object.clone()
+                        } else {
+                            resource
+                                .clone()
+                                .or(href.clone())
+                                .or_else(|| Some(BlankNode::default().into()))
+                        }
+                    });
+                    current_object = typed_resource.clone();
+                }
+            } else {
+                new_subject = about
+                    .clone()
+                    .or(resource.clone())
+                    .or(href.clone())
+                    .or_else(|| {
+                        if is_root {
+                            context.parent_object.clone()
+                        } else if types.is_some() {
+                            Some(BlankNode::default().into())
+                        } else {
+                            skip = properties.is_none();
+                            context.parent_object.clone()
+                        }
+                    });
+                if types.is_some() {
+                    typed_resource = new_subject.clone();
+                }
+            }
+        } else {
+            if types.is_some() {
+                typed_resource = about.clone();
+            }
+            new_subject = about.clone().or_else(|| context.parent_object.clone());
+            current_object = resource.clone().or(href.clone());
+            if current_object.is_none() && types.is_some() && about.is_none() {
+                current_object = Some(BlankNode::default().into());
+            }
+            if types.is_some() && about.is_none() {
+                typed_resource = current_object.clone();
+            }
+        }
+
+        if let (Some(typed_resource), Some(types)) = (&typed_resource, &types) {
+            for t in types {
+                self.emit(typed_resource.clone(), rdf::TYPE.into(), t.clone().into());
+            }
+        }
+
+        let subject = new_subject.clone();
+        if let Some(subject) = &subject {
+            if let Some(object) = &current_object {
+                for rel in &rels {
+                    self.emit(subject.clone(), rel.clone(), object.clone().into());
+                }
+                for rev in &revs {
+                    self.emit(object.clone(), rev.clone(), subject.clone().into());
+                }
+            } else if !rels.is_empty() || !revs.is_empty() {
+                // Pending the discovery of an object:
+                current_object = Some(BlankNode::default().into());
+                for rel in &rels {
+                    incomplete_triples.push((rel.clone(), Direction::Forward));
+                }
+                for rev in &revs {
+                    incomplete_triples.push((rev.clone(), Direction::Backward));
+                }
+            }
+
+            if let Some(properties) = &properties {
+                let value = self.property_value(
+                    element,
+                    &context,
+                    content,
+                    datatype,
+                    &rels,
+                    &revs,
+                    resource.clone().or(href.clone()),
+                    typed_resource.clone().filter(|_| about.is_none()),
+                );
+                if let Some(value) = value {
+                    for property in properties {
+                        self.emit(subject.clone(), property.clone(), value.clone());
+                    }
+                }
+            }
+        }
+
+        // Complete incomplete triples of the parent:
+        if !skip {
+            if let Some(subject) = &new_subject {
+                for (predicate, direction) in &parent.incomplete_triples {
+                    match direction {
+                        Direction::Forward => self.emit(
+                            parent.parent_subject.clone(),
+                            predicate.clone(),
+                            subject.clone().into(),
+                        ),
+                        Direction::Backward => self.emit(
+                            subject.clone(),
+                            predicate.clone(),
+                            parent.parent_subject.clone().into(),
+                        ),
+                    }
+                }
+            }
+        }
+
+        let mut child_context = context.clone();
+        if !skip {
+            let subject = new_subject.unwrap_or(parent.parent_subject.clone());
+            child_context.parent_object = current_object.or(Some(subject.clone()));
+            child_context.parent_subject = subject;
+            child_context.incomplete_triples = incomplete_triples;
+        }
+
+        // Literal values do not apply to descendants:
+        if datatype.is_some_and(is_xml_datatype) && properties.is_some() {
+            return;
+        }
+        for child in element.child_elements() {
+            self.process_rdfa(child, &child_context, false);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn property_value(
+        &mut self,
+        element: ElementRef,
+        context: &EvaluationContext,
+        content: Option<&str>,
+        datatype: Option<&str>,
+        rels: &[NamedNode],
+        revs: &[NamedNode],
+        resource: Option<NamedOrBlankNode>,
+        typed_resource: Option<NamedOrBlankNode>,
+    ) -> Option<Term> {
+        let language = context.language.as_deref();
+        let datetime = element.attr("datetime");
+        let text = || {
+            content
+                .map(str::to_owned)
+                .unwrap_or_else(|| element.text().collect())
+        };
+        let literal = |value: String| match language {
+            Some(lang) => Literal::new_language_tagged_literal(value.clone(), lang)
+                .unwrap_or_else(|_| Literal::new_simple_literal(value)),
+            None => Literal::new_simple_literal(value),
+        };
+
+        if let Some(datatype) = datatype.map(str::trim).filter(|dt| !dt.is_empty()) {
+            let datatype = self
+                .predicates(context, Some(datatype))
+                .into_iter()
+                .next()?;
+            let value = if is_xml_datatype(datatype.as_str()) {
+                content
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| element.inner_html())
+            } else {
+                text()
+            };
+            return Some(Literal::new_typed_literal(value, datatype).into());
+        }
+        if datatype.is_some() {
+            return Some(literal(text()).into());
+        }
+        if let Some(content) = content {
+            return Some(literal(content.to_owned()).into());
+        }
+        if let Some(datetime) = datetime {
+            return Some(Literal::new_typed_literal(datetime, datetime_datatype(datetime)).into());
+        }
+        if rels.is_empty() && revs.is_empty() {
+            if let Some(resource) = resource {
+                return Some(resource.into());
+            }
+        }
+        if let Some(typed_resource) = typed_resource {
+            return Some(typed_resource.into());
+        }
+        Some(literal(text()).into())
+    }
+
+    /// Extracts all microdata items (top-level items being those not used as property values).
+    fn process_microdata(&mut self, document: &Html) {
+        let elements_by_id = document
+            .root_element()
+            .descendent_elements()
+            .filter_map(|e| Some((e.attr("id")?, e)))
+            .collect::<HashMap<_, _>>();
+        for element in document.root_element().descendent_elements() {
+            if element.attr("itemscope").is_some() && element.attr("itemprop").is_none() {
+                self.microdata_item(element, None, &elements_by_id, &mut Vec::new());
+            }
+        }
+    }
+
+    fn microdata_item<'a>(
+        &mut self,
+        item: ElementRef<'a>,
+        vocab: Option<&str>,
+        elements_by_id: &HashMap<&'a str, ElementRef<'a>>,
+        in_progress: &mut Vec<ElementRef<'a>>,
+    ) -> NamedOrBlankNode {
+        let subject = item
+            .attr("itemid")
+            .and_then(|id| self.resolve(id.trim()))
+            .map(NamedOrBlankNode::from)
+            .unwrap_or_else(|| BlankNode::default().into());
+
+        let types = item
+            .attr("itemtype")
+            .map(|v| v.split_whitespace().filter_map(|t| NamedNode::new(t).ok()))
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        for t in &types {
+            self.emit(subject.clone(), rdf::TYPE.into(), t.clone().into());
+        }
+
+        // The vocabulary is given by the first type (or inherited from the enclosing item):
+        let vocab = types
+            .first()
+            .map(|t| microdata_vocab(t.as_str()).to_owned())
+            .or(vocab.map(str::to_owned));
+        if let Some(vocab) = &vocab {
+            if let Some((pfx, ns)) = INITIAL_PREFIXES.iter().find(|(_, ns)| ns == vocab) {
+                self.declare_prefix(pfx, ns);
+            }
+        }
+
+        let mut property_elements = Vec::new();
+        collect_item_properties(item, &mut property_elements);
+        for id in item
+            .attr("itemref")
+            .into_iter()
+            .flat_map(str::split_whitespace)
+        {
+            if let Some(element) = elements_by_id.get(id) {
+                if element.attr("itemprop").is_some() {
+                    property_elements.push(*element);
+                } else if element.attr("itemscope").is_none() {
+                    collect_item_properties(*element, &mut property_elements);
+                }
+            }
+        }
+
+        in_progress.push(item);
+        for element in property_elements {
+            let value: Term = if element.attr("itemscope").is_some() {
+                // Skips cyclic item references:
+                if in_progress.contains(&element) {
+                    continue;
+                }
+                self.microdata_item(element, vocab.as_deref(), elements_by_id, in_progress)
+                    .into()
+            } else if let Some(value) = self.microdata_value(element) {
+                value
+            } else {
+                continue;
+            };
+
+            let names = element.attr("itemprop").unwrap_or_default();
+            for name in names.split_whitespace() {
+                let predicate = if name.contains(':') {
+                    NamedNode::new(name).ok()
+                } else {
+                    vocab
+                        .as_ref()
+                        .and_then(|vocab| NamedNode::new(format!("{vocab}{name}")).ok())
+                };
+                if let Some(predicate) = predicate {
+                    self.emit(subject.clone(), predicate, value.clone());
+                }
+            }
+        }
+        in_progress.pop();
+
+        subject
+    }
+
+    fn microdata_value(&self, element: ElementRef) -> Option<Term> {
+        let url = |name: &str| {
+            element
+                .attr(name)
+                .and_then(|v| self.resolve(v.trim()))
+                .map(Term::from)
+        };
+        let text = |value: &str| {
+            let literal = match language_of(element) {
+                Some(lang) => Literal::new_language_tagged_literal(value, lang).ok(),
+                None => None,
+            };
+            Term::from(literal.unwrap_or_else(|| Literal::new_simple_literal(value)))
+        };
+        match element.value().name() {
+            "meta" => element.attr("content").map(text),
+            "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => url("src"),
+            "a" | "area" | "link" => url("href"),
+            "object" => url("data"),
+            "data" | "meter" => element
+                .attr("value")
+                .map(|v| Literal::new_simple_literal(v).into()),
+            "time" => {
+                let value = element
+                    .attr("datetime")
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| element.text().collect());
+                let datatype = datetime_datatype(value.trim());
+                Some(if datatype == xsd::STRING {
+                    Literal::new_simple_literal(value).into()
+                } else {
+                    Literal::new_typed_literal(value.trim(), datatype).into()
+                })
+            }
+            _ => Some(text(&element.text().collect::<String>())),
+        }
+    }
+
+    fn add_prefix(&mut self, context: &mut EvaluationContext, pfx: &str, ns: &str) {
+        let pfx = pfx.to_lowercase();
+        self.declare_prefix(&pfx, ns);
+        context.prefixes.insert(pfx, ns.to_owned());
+    }
+
+    /// Records a prefix for the document (the first declaration wins).
+    fn declare_prefix(&mut self, pfx: &str, ns: &str) {
+        if !self.prefixes.iter().any(|(p, _)| p == pfx) {
+            self.prefixes.push((pfx.to_owned(), ns.to_owned()));
+        }
+    }
+
+    fn base_node(&self) -> NamedOrBlankNode {
+        self.base_iri
+            .as_deref()
+            .and_then(|iri| NamedNode::new(iri).ok())
+            .map(NamedOrBlankNode::from)
+            .unwrap_or_else(|| BlankNode::default().into())
+    }
+
+    fn emit(&mut self, subject: NamedOrBlankNode, predicate: NamedNode, object: Term) {
+        self.quads.push(Quad::new(
+            subject,
+            predicate,
+            object,
+            self.graph_name.clone(),
+        ));
+    }
+
+    fn resolve(&self, iri: &str) -> Option<NamedNode> {
+        let iri = match &self.base_iri {
+            Some(base_iri) => Iri::parse(base_iri.as_str())
+                .ok()?
+                .resolve(iri)
+                .ok()?
+                .into_inner(),
+            None => Iri::parse(iri.to_owned()).ok()?.into_inner(),
+        };
+        NamedNode::new(iri).ok()
+    }
+
+    fn blank_node(&mut self, label: &str) -> BlankNode {
+        self.blank_nodes
+            .entry(label.to_owned())
+            .or_default()
+            .clone()
+    }
+
+    fn expand_curie(
+        &mut self,
+        context: &EvaluationContext,
+        value: &str,
+    ) -> Option<NamedOrBlankNode> {
+        let (pfx, local) = value.split_once(':')?;
+        if pfx == "_" {
+            return Some(self.blank_node(local).into());
+        }
+        let ns = context.prefixes.get(&pfx.to_lowercase())?;
+        NamedNode::new(format!("{ns}{local}")).ok().map(Into::into)
+    }
+
+    /// Resolves a SafeCURIEorCURIEorIRI (as used in `@about` and `@resource`).
+    fn resource(&mut self, context: &EvaluationContext, value: &str) -> Option<NamedOrBlankNode> {
+        if let Some(safe_curie) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            return self.expand_curie(context, safe_curie);
+        }
+        self.expand_curie(context, value)
+            .or_else(|| self.resolve(value).map(Into::into))
+    }
+
+    /// Resolves TERMorCURIEorAbsIRIs (as used in `@property`, `@rel`, `@rev` and `@typeof`).
+    fn predicates(&mut self, context: &EvaluationContext, value: Option<&str>) -> Vec<NamedNode> {
+        let Some(value) = value else {
+            return Vec::new();
+        };
+        value
+            .split_whitespace()
+            .filter_map(|token| {
+                if token.contains(':') {
+                    match self.expand_curie(context, token) {
+                        Some(NamedOrBlankNode::NamedNode(node)) => Some(node),
+                        Some(NamedOrBlankNode::BlankNode(_)) => None,
+                        None => Iri::parse(token.to_owned())
+                            .ok()
+                            .map(|iri| NamedNode::new_unchecked(iri.into_inner())),
+                    }
+                } else {
+                    let vocab = context.vocab.as_deref()?;
+                    NamedNode::new(format!("{vocab}{token}")).ok()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Collects property elements of an item, without descending into nested items.
+fn collect_item_properties<'a>(element: ElementRef<'a>, properties: &mut Vec<ElementRef<'a>>) {
+    for child in element.child_elements() {
+        if child.attr("itemprop").is_some() {
+            properties.push(child);
+        }
+        if child.attr("itemscope").is_none() {
+            collect_item_properties(child, properties);
+        }
+    }
+}
+
+/// Derives the vocabulary of an item type (up to the last hash or slash).
+fn microdata_vocab(type_iri: &str) -> &str {
+    let end = type_iri
+        .rfind('#')
+        .or_else(|| type_iri.rfind('/'))
+        .map_or(type_iri.len(), |i| i + 1);
+    &type_iri[..end]
+}
+
+/// Finds the language of an element (as declared on it or on its nearest ancestor).
+fn language_of(element: ElementRef<'_>) -> Option<&str> {
+    std::iter::once(element)
+        .chain(element.ancestors().filter_map(ElementRef::wrap))
+        .find_map(|e| e.attr("xml:lang").or(e.attr("lang")))
+        .filter(|lang| !lang.is_empty())
+}
+
+fn is_xml_datatype(datatype: &str) -> bool {
+    datatype == rdf::XML_LITERAL.as_str() || datatype == rdf::HTML.as_str()
+}
+
+/// Guesses the datatype of an HTML `@datetime` value.
+fn datetime_datatype(value: &str) -> NamedNode {
+    let is_digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    let datatype = if value.starts_with('P') || value.starts_with("-P") {
+        xsd::DURATION
+    } else if value.contains('T') {
+        xsd::DATE_TIME
+    } else if value.contains(':') {
+        xsd::TIME
+    } else {
+        match value.split('-').collect::<Vec<_>>()[..] {
+            [y] if is_digits(y) => xsd::G_YEAR,
+            [y, m] if is_digits(y) && is_digits(m) => xsd::G_YEAR_MONTH,
+            [y, m, d] if is_digits(y) && is_digits(m) && is_digits(d) => xsd::DATE,
+            _ => xsd::STRING,
+        }
+    };
+    datatype.into_owned()
+}
@@ -0,0 +1,150 @@
+//! Fetching of data over HTTP(S), optionally through a cache of responses on disk.
+
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use oxhttp::model::header::{
+    ACCEPT, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use oxhttp::model::{Body, Method, Request, Response, StatusCode};
+use oxhttp::Client;
+use serde_json::json;
+
+use crate::HTTP_REDIRECTION_LIMIT;
+
+const USER_AGENT: &str = concat!("oxrq/", env!("CARGO_PKG_VERSION"));
+
+/// Gets a resource, returning its media type (if given) along with the response body.
+pub(crate) fn get(url: &str, accept: &str) -> Result<(Option<String>, Body)> {
+    let request = Request::builder()
+        .uri(url)
+        .header(ACCEPT, accept)
+        .body(Body::empty())?;
+
+    send(url, request)
+}
+
+/// Gets a resource as [`get`], but through a cache in the given directory (keyed by URL). A cached
+/// response is revalidated (by its `ETag` or `Last-Modified` date) and used if not modified (or if
+/// the server cannot be reached), and otherwise replaced by the new one.
+pub(crate) fn get_cached(
+    url: &str,
+    accept: &str,
+    cache_dir: &Path,
+) -> Result<(Option<String>, Box<dyn Read>)> {
+    let key = format!("{:016x}", fnv1a(url.as_bytes()));
+    let body_path = cache_dir.join(&key);
+    let meta_path = cache_dir.join(format!("{key}.json"));
+    let cached = fs::read(&meta_path)
+        .ok()
+        .and_then(|meta| serde_json::from_slice::<serde_json::Value>(&meta).ok())
+        .filter(|meta| meta["url"] == url && body_path.is_file());
+    let header = |name: &str| {
+        cached
+            .as_ref()
+            .and_then(|meta| meta[name].as_str().map(str::to_owned))
+    };
+    let cached_response = || -> Result<(Option<String>, Box<dyn Read>)> {
+        let body = fs::File::open(&body_path)?;
+        Ok((header("mediaType"), Box::new(body)))
+    };
+
+    let mut request = Request::builder().uri(url).header(ACCEPT, accept);
+    if let Some(etag) = header("etag") {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = header("lastModified") {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = match request_response(url, request.body(Body::empty())?) {
+        Ok(response) => response,
+        Err(_) if cached.is_some() => return cached_response(),
+        Err(e) => return Err(e),
+    };
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED && cached.is_some() {
+        return cached_response();
+    }
+    if !status.is_success() {
+        bail!("Error {status} returned by {url}");
+    }
+
+    let value = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    let media_type = media_type(&response);
+    let meta = json!({
+        "url": url,
+        "mediaType": media_type,
+        "etag": value(ETAG),
+        "lastModified": value(LAST_MODIFIED),
+    });
+    let mut body = Vec::new();
+    response.into_body().read_to_end(&mut body)?;
+    // (Failing to cache the response does not fail getting it.)
+    let _ = fs::create_dir_all(cache_dir)
+        .and_then(|()| fs::write(&body_path, &body))
+        .and_then(|()| fs::write(&meta_path, meta.to_string()));
+
+    Ok((media_type, Box::new(Cursor::new(body))))
+}
+
+/// Hashes bytes by FNV-1a (which, unlike the hasher of the standard library, is stable between
+/// builds, as needed for naming cached files).
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Posts a payload, returning the media type (if given) along with the response body.
+pub(crate) fn post(
+    url: &str,
+    payload: Vec<u8>,
+    content_type: &str,
+    accept: &str,
+) -> Result<(Option<String>, Body)> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header(ACCEPT, accept)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(payload))?;
+
+    send(url, request)
+}
+
+fn send(url: &str, request: Request<Body>) -> Result<(Option<String>, Body)> {
+    let response = request_response(url, request)?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Error {status} returned by {url}");
+    }
+
+    Ok((media_type(&response), response.into_body()))
+}
+
+fn request_response(url: &str, request: Request<Body>) -> Result<Response<Body>> {
+    let client = Client::new()
+        .with_redirection_limit(HTTP_REDIRECTION_LIMIT)
+        .with_user_agent(USER_AGENT)?;
+
+    client
+        .request(request)
+        .with_context(|| format!("Unable to fetch URL: {url}"))
+}
+
+fn media_type(response: &Response<Body>) -> Option<String> {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+}
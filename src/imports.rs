@@ -0,0 +1,71 @@
+//! Resolution of `owl:imports` of loaded ontologies: by an XML catalog (as used by Protégé, in
+//! `catalog-v001.xml` files) mapping ontology IRIs to local files, or else over HTTP.
+
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use oxigraph::model::vocab::rdf;
+use oxigraph::model::{NamedNode, NamedNodeRef, Term};
+use oxigraph::store::Store;
+
+use crate::xpath::{Document, Item, XPath};
+
+const IMPORTS: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#imports");
+const ONTOLOGY: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#Ontology");
+const VERSION_IRI: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#versionIRI");
+
+/// Reads the `uri` entries of an XML catalog, mapping the IRIs of their `name` to the paths of
+/// their `uri` (resolved against the directory of the catalog).
+pub(crate) fn read_catalog<R: BufRead>(reader: R, dir: &Path) -> Result<HashMap<String, PathBuf>> {
+    let document = Document::parse(reader)?;
+    let entries = XPath::parse("//uri")?;
+    let name = XPath::parse("@name")?;
+    let uri = XPath::parse("@uri")?;
+    let value = |path: &XPath, entry| {
+        let items = path.select(&document, entry);
+        (!items.is_empty()).then(|| document.string_value(items[0]))
+    };
+    let mut catalog = HashMap::new();
+    for entry in entries.select(&document, Item::Node(0)) {
+        if let (Some(name), Some(uri)) = (value(&name, entry), value(&uri, entry)) {
+            let path = uri.strip_prefix("file:").unwrap_or(&uri);
+            catalog.insert(name, dir.join(path));
+        }
+    }
+    Ok(catalog)
+}
+
+/// Gives the IRIs imported by the loaded data which are not yet loaded: neither tried before, nor
+/// loaded into a graph of their own, nor declared by a loaded ontology (as its IRI or version
+/// IRI).
+pub(crate) fn pending(store: &Store, tried: &HashSet<NamedNode>) -> Result<Vec<NamedNode>> {
+    let mut imports = Vec::new();
+    for quad in store.quads_for_pattern(None, Some(IMPORTS), None, None) {
+        let Term::NamedNode(iri) = quad?.object else {
+            continue;
+        };
+        let is_loaded = store.contains_named_graph(&iri)?
+            || store
+                .quads_for_pattern(
+                    Some(iri.as_ref().into()),
+                    Some(rdf::TYPE),
+                    Some(ONTOLOGY.into()),
+                    None,
+                )
+                .next()
+                .is_some()
+            || store
+                .quads_for_pattern(None, Some(VERSION_IRI), Some(iri.as_ref().into()), None)
+                .next()
+                .is_some();
+        if !is_loaded && !tried.contains(&iri) && !imports.contains(&iri) {
+            imports.push(iri);
+        }
+    }
+    Ok(imports)
+}
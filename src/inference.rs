@@ -0,0 +1,464 @@
+//! Materialization of entailed triples, by applying inference rules until no new triples follow.
+
+use oxigraph::model::vocab::{rdf, rdfs};
+use oxigraph::model::{Graph, NamedNode, NamedNodeRef, NamedOrBlankNode, Term, TermRef, Triple};
+
+use crate::InferenceProfile;
+
+mod owl {
+    use oxigraph::model::NamedNodeRef;
+
+    pub const ALL_VALUES_FROM: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#allValuesFrom");
+    pub const EQUIVALENT_CLASS: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#equivalentClass");
+    pub const EQUIVALENT_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#equivalentProperty");
+    pub const FUNCTIONAL_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#FunctionalProperty");
+    pub const HAS_VALUE: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#hasValue");
+    pub const INTERSECTION_OF: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#intersectionOf");
+    pub const INVERSE_FUNCTIONAL_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#InverseFunctionalProperty");
+    pub const INVERSE_OF: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#inverseOf");
+    pub const MAX_CARDINALITY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#maxCardinality");
+    pub const ONE_OF: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#oneOf");
+    pub const ON_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#onProperty");
+    pub const PROPERTY_CHAIN_AXIOM: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#propertyChainAxiom");
+    pub const SAME_AS: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#sameAs");
+    pub const SOME_VALUES_FROM: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#someValuesFrom");
+    pub const SYMMETRIC_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#SymmetricProperty");
+    pub const THING: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#Thing");
+    pub const TRANSITIVE_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#TransitiveProperty");
+    pub const UNION_OF: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#unionOf");
+}
+
+/// Infers the triples entailed by the graph under the given profile, adding them to the graph
+/// and returning them.
+pub(crate) fn materialize(graph: &mut Graph, profile: InferenceProfile) -> Vec<Triple> {
+    let mut inferred = Vec::new();
+    loop {
+        let mut candidates = Vec::new();
+        match profile {
+            InferenceProfile::Rdfs => apply_rdfs_rules(graph, &mut candidates),
+            InferenceProfile::OwlRl => {
+                apply_rdfs_rules(graph, &mut candidates);
+                apply_owl_rl_rules(graph, &mut candidates);
+            }
+        }
+        let mut is_new = false;
+        for triple in candidates {
+            if graph.insert(&triple) {
+                inferred.push(triple);
+                is_new = true;
+            }
+        }
+        if !is_new {
+            return inferred;
+        }
+    }
+}
+
+/// Applies the RDFS rules for subclasses, subproperties, domains and ranges (rdfs2, rdfs3,
+/// rdfs5, rdfs7, rdfs9 and rdfs11).
+fn apply_rdfs_rules(graph: &Graph, inferred: &mut Vec<Triple>) {
+    for (property, superproperty) in pairs(graph, rdfs::SUB_PROPERTY_OF) {
+        let Some(property) = as_predicate(&property) else {
+            continue;
+        };
+        for triple in graph.triples_for_predicate(&property) {
+            if let Some(superproperty) = as_predicate(&superproperty) {
+                inferred.push(Triple::new(
+                    triple.subject.into_owned(),
+                    superproperty,
+                    triple.object.into_owned(),
+                ));
+            }
+        }
+    }
+    transitive_closure(graph, rdfs::SUB_PROPERTY_OF, inferred);
+
+    for (property, class) in pairs(graph, rdfs::DOMAIN) {
+        let Some(property) = as_predicate(&property) else {
+            continue;
+        };
+        for triple in graph.triples_for_predicate(&property) {
+            inferred.push(Triple::new(
+                triple.subject.into_owned(),
+                rdf::TYPE,
+                class.clone(),
+            ));
+        }
+    }
+    for (property, class) in pairs(graph, rdfs::RANGE) {
+        let Some(property) = as_predicate(&property) else {
+            continue;
+        };
+        for triple in graph.triples_for_predicate(&property) {
+            if let Some(object) = as_subject(&triple.object.into_owned()) {
+                inferred.push(Triple::new(object, rdf::TYPE, class.clone()));
+            }
+        }
+    }
+
+    for (class, superclass) in pairs(graph, rdfs::SUB_CLASS_OF) {
+        for instance in graph.subjects_for_predicate_object(rdf::TYPE, &class) {
+            inferred.push(Triple::new(
+                instance.into_owned(),
+                rdf::TYPE,
+                superclass.clone(),
+            ));
+        }
+    }
+    transitive_closure(graph, rdfs::SUB_CLASS_OF, inferred);
+}
+
+/// Applies the OWL 2 RL rules for equality (`owl:sameAs`), property axioms (functional, inverse
+/// functional, symmetric, transitive and inverse properties, and property chains), class
+/// expressions (intersections, unions, enumerations, and `someValuesFrom`, `allValuesFrom`,
+/// `hasValue` and `maxCardinality` 1 restrictions) and equivalent classes and properties (the
+/// rules checking for inconsistencies are not applied).
+fn apply_owl_rl_rules(graph: &Graph, inferred: &mut Vec<Triple>) {
+    // Equality (eq-sym, eq-trans, eq-rep-s, eq-rep-p, eq-rep-o):
+    for (x, y) in pairs(graph, owl::SAME_AS) {
+        let (Some(x), Some(y_subject)) = (as_subject(&x), as_subject(&y)) else {
+            continue;
+        };
+        inferred.push(Triple::new(y_subject.clone(), owl::SAME_AS, x.clone()));
+        for triple in graph.triples_for_subject(&x) {
+            inferred.push(Triple::new(
+                y_subject.clone(),
+                triple.predicate.into_owned(),
+                triple.object.into_owned(),
+            ));
+        }
+        for triple in graph.triples_for_object(&Term::from(x.clone())) {
+            inferred.push(Triple::new(
+                triple.subject.into_owned(),
+                triple.predicate.into_owned(),
+                y.clone(),
+            ));
+        }
+        if let (NamedOrBlankNode::NamedNode(x), Some(y)) = (&x, as_predicate(&y)) {
+            for triple in graph.triples_for_predicate(x) {
+                inferred.push(Triple::new(
+                    triple.subject.into_owned(),
+                    y.clone(),
+                    triple.object.into_owned(),
+                ));
+            }
+        }
+    }
+    transitive_closure(graph, owl::SAME_AS, inferred);
+
+    // Property axioms (prp-fp, prp-ifp, prp-symp, prp-trp, prp-inv1, prp-inv2, prp-spo2):
+    for property in instances(graph, owl::FUNCTIONAL_PROPERTY) {
+        for (_, objects) in grouped(graph, &property, false) {
+            same_as_all(&objects, inferred);
+        }
+    }
+    for property in instances(graph, owl::INVERSE_FUNCTIONAL_PROPERTY) {
+        for (_, subjects) in grouped(graph, &property, true) {
+            same_as_all(&subjects, inferred);
+        }
+    }
+    for property in instances(graph, owl::SYMMETRIC_PROPERTY) {
+        for (subject, object) in pairs(graph, property.as_ref()) {
+            if let Some(object) = as_subject(&object) {
+                inferred.push(Triple::new(object, property.clone(), subject));
+            }
+        }
+    }
+    for property in instances(graph, owl::TRANSITIVE_PROPERTY) {
+        transitive_closure(graph, property.as_ref(), inferred);
+    }
+    for (p1, p2) in pairs(graph, owl::INVERSE_OF) {
+        let (Some(p1), Some(p2)) = (as_predicate(&p1), as_predicate(&p2)) else {
+            continue;
+        };
+        for (p, inverse) in [(&p1, &p2), (&p2, &p1)] {
+            for (subject, object) in pairs(graph, p.as_ref()) {
+                if let Some(object) = as_subject(&object) {
+                    inferred.push(Triple::new(object, inverse.clone(), subject));
+                }
+            }
+        }
+    }
+    for (property, chain) in pairs(graph, owl::PROPERTY_CHAIN_AXIOM) {
+        let Some(property) = as_predicate(&property) else {
+            continue;
+        };
+        let chain = list_items(graph, &chain)
+            .iter()
+            .map(as_predicate)
+            .collect::<Option<Vec<_>>>();
+        let Some((first, rest)) = chain.as_deref().and_then(<[_]>::split_first) else {
+            continue;
+        };
+        for (start, mut ends) in pairs(graph, first.as_ref())
+            .into_iter()
+            .map(|(start, end)| (start, vec![end]))
+        {
+            for link in rest {
+                ends = ends
+                    .iter()
+                    .filter_map(as_subject)
+                    .flat_map(|node| {
+                        graph
+                            .objects_for_subject_predicate(&node, link)
+                            .map(TermRef::into_owned)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+            }
+            if let Some(start) = as_subject(&start) {
+                for end in ends {
+                    inferred.push(Triple::new(start.clone(), property.clone(), end));
+                }
+            }
+        }
+    }
+
+    // Class expressions (cls-int1, cls-int2, cls-uni, cls-oo):
+    for (class, list) in pairs(graph, owl::INTERSECTION_OF) {
+        let members = list_items(graph, &list);
+        let mut instances_of_all: Option<Vec<Term>> = None;
+        for member in &members {
+            let member_instances = subjects(graph, rdf::TYPE, member);
+            instances_of_all = Some(match instances_of_all {
+                None => member_instances,
+                Some(instances) => instances
+                    .into_iter()
+                    .filter(|instance| member_instances.contains(instance))
+                    .collect(),
+            });
+        }
+        for instance in instances_of_all.unwrap_or_default() {
+            if let Some(instance) = as_subject(&instance) {
+                inferred.push(Triple::new(instance, rdf::TYPE, class.clone()));
+            }
+        }
+        for instance in subjects(graph, rdf::TYPE, &class) {
+            for member in &members {
+                if let Some(instance) = as_subject(&instance) {
+                    inferred.push(Triple::new(instance, rdf::TYPE, member.clone()));
+                }
+            }
+        }
+    }
+    for (class, list) in pairs(graph, owl::UNION_OF) {
+        for member in list_items(graph, &list) {
+            for instance in subjects(graph, rdf::TYPE, &member) {
+                if let Some(instance) = as_subject(&instance) {
+                    inferred.push(Triple::new(instance, rdf::TYPE, class.clone()));
+                }
+            }
+        }
+    }
+    for (class, list) in pairs(graph, owl::ONE_OF) {
+        for member in list_items(graph, &list) {
+            if let Some(member) = as_subject(&member) {
+                inferred.push(Triple::new(member, rdf::TYPE, class.clone()));
+            }
+        }
+    }
+
+    // Restrictions (cls-svf1, cls-svf2, cls-avf, cls-hv1, cls-hv2, cls-maxc2):
+    for (restriction, property, class) in restrictions(graph, owl::SOME_VALUES_FROM) {
+        for (subject, object) in pairs(graph, property.as_ref()) {
+            let is_instance = class == Term::from(owl::THING.into_owned())
+                || graph.contains(&Triple::new(
+                    match as_subject(&object) {
+                        Some(object) => object,
+                        None => continue,
+                    },
+                    rdf::TYPE,
+                    class.clone(),
+                ));
+            if let (true, Some(subject)) = (is_instance, as_subject(&subject)) {
+                inferred.push(Triple::new(subject, rdf::TYPE, restriction.clone()));
+            }
+        }
+    }
+    for (restriction, property, class) in restrictions(graph, owl::ALL_VALUES_FROM) {
+        for instance in subjects(graph, rdf::TYPE, &restriction) {
+            let Some(instance) = as_subject(&instance) else {
+                continue;
+            };
+            for value in graph.objects_for_subject_predicate(&instance, &property) {
+                if let Some(value) = as_subject(&value.into_owned()) {
+                    inferred.push(Triple::new(value, rdf::TYPE, class.clone()));
+                }
+            }
+        }
+    }
+    for (restriction, property, value) in restrictions(graph, owl::HAS_VALUE) {
+        for instance in subjects(graph, rdf::TYPE, &restriction) {
+            if let Some(instance) = as_subject(&instance) {
+                inferred.push(Triple::new(instance, property.clone(), value.clone()));
+            }
+        }
+        for subject in subjects(graph, property.as_ref(), &value) {
+            if let Some(subject) = as_subject(&subject) {
+                inferred.push(Triple::new(subject, rdf::TYPE, restriction.clone()));
+            }
+        }
+    }
+    for (restriction, property, max) in restrictions(graph, owl::MAX_CARDINALITY) {
+        let is_one = matches!(&max, Term::Literal(literal) if literal.value() == "1");
+        if !is_one {
+            continue;
+        }
+        for instance in subjects(graph, rdf::TYPE, &restriction) {
+            let Some(instance) = as_subject(&instance) else {
+                continue;
+            };
+            let values = graph
+                .objects_for_subject_predicate(&instance, &property)
+                .map(TermRef::into_owned)
+                .collect::<Vec<_>>();
+            same_as_all(&values, inferred);
+        }
+    }
+
+    // Equivalence (scm-eqc1, scm-eqp1), leaving the rest to the RDFS rules:
+    for (predicate, equivalence) in [
+        (rdfs::SUB_CLASS_OF, owl::EQUIVALENT_CLASS),
+        (rdfs::SUB_PROPERTY_OF, owl::EQUIVALENT_PROPERTY),
+    ] {
+        for (a, b) in pairs(graph, equivalence) {
+            if let (Some(a_subject), Some(b_subject)) = (as_subject(&a), as_subject(&b)) {
+                inferred.push(Triple::new(a_subject, predicate, b));
+                inferred.push(Triple::new(b_subject, predicate, a));
+            }
+        }
+    }
+}
+
+/// Infers `owl:sameAs` between each of the (non-literal) nodes.
+fn same_as_all(nodes: &[Term], inferred: &mut Vec<Triple>) {
+    for (i, a) in nodes.iter().enumerate() {
+        for b in &nodes[i + 1..] {
+            if let Some(a) = as_subject(a) {
+                inferred.push(Triple::new(a, owl::SAME_AS, b.clone()));
+            }
+        }
+    }
+}
+
+/// Collects the properties (or classes) typed with the class.
+fn instances(graph: &Graph, class: NamedNodeRef<'_>) -> Vec<NamedNode> {
+    graph
+        .subjects_for_predicate_object(rdf::TYPE, class)
+        .filter_map(|subject| as_predicate(&subject.into_owned().into()))
+        .collect()
+}
+
+/// Groups the objects of the property by subject (or, if inverse, the subjects by object).
+fn grouped(graph: &Graph, property: &NamedNode, inverse: bool) -> Vec<(Term, Vec<Term>)> {
+    let mut groups: Vec<(Term, Vec<Term>)> = Vec::new();
+    for (subject, object) in pairs(graph, property.as_ref()) {
+        let (key, value) = if inverse {
+            (object, subject)
+        } else {
+            (subject, object)
+        };
+        match groups.iter_mut().find(|(other, _)| *other == key) {
+            Some((_, values)) => values.push(value),
+            None => groups.push((key, vec![value])),
+        }
+    }
+    groups
+}
+
+/// Collects the restrictions with the predicate, with their `owl:onProperty` and the value of
+/// the predicate.
+fn restrictions(graph: &Graph, predicate: NamedNodeRef<'_>) -> Vec<(Term, NamedNode, Term)> {
+    pairs(graph, predicate)
+        .into_iter()
+        .filter_map(|(restriction, value)| {
+            let property = graph
+                .object_for_subject_predicate(&as_subject(&restriction)?, owl::ON_PROPERTY)?
+                .into_owned();
+            Some((restriction, as_predicate(&property)?, value))
+        })
+        .collect()
+}
+
+fn list_items(graph: &Graph, list: &Term) -> Vec<Term> {
+    let mut items = Vec::new();
+    let mut node = list.clone();
+    while let Some(subject) = as_subject(&node) {
+        let Some(item) = graph.object_for_subject_predicate(&subject, rdf::FIRST) else {
+            break;
+        };
+        items.push(item.into_owned());
+        match graph.object_for_subject_predicate(&subject, rdf::REST) {
+            Some(rest) => node = rest.into_owned(),
+            None => break,
+        }
+    }
+    items
+}
+
+fn subjects(graph: &Graph, predicate: NamedNodeRef<'_>, object: &Term) -> Vec<Term> {
+    graph
+        .subjects_for_predicate_object(predicate, object)
+        .map(|subject| subject.into_owned().into())
+        .collect()
+}
+
+/// Infers `a p c` from `a p b` and `b p c`.
+fn transitive_closure(graph: &Graph, predicate: NamedNodeRef<'_>, inferred: &mut Vec<Triple>) {
+    for (a, b) in pairs(graph, predicate) {
+        let Some(b) = as_subject(&b) else {
+            continue;
+        };
+        for c in graph.objects_for_subject_predicate(&b, predicate) {
+            if let Some(a) = as_subject(&a) {
+                inferred.push(Triple::new(a, predicate, c.into_owned()));
+            }
+        }
+    }
+}
+
+/// Collects the subjects and objects of the triples with the predicate.
+fn pairs(graph: &Graph, predicate: NamedNodeRef<'_>) -> Vec<(Term, Term)> {
+    graph
+        .triples_for_predicate(predicate)
+        .map(|triple| {
+            (
+                triple.subject.into_owned().into(),
+                triple.object.into_owned(),
+            )
+        })
+        .collect()
+}
+
+fn as_subject(term: &Term) -> Option<NamedOrBlankNode> {
+    match term {
+        Term::NamedNode(node) => Some(node.clone().into()),
+        Term::BlankNode(node) => Some(node.clone().into()),
+        Term::Literal(_) | Term::Triple(_) => None,
+    }
+}
+
+fn as_predicate(term: &Term) -> Option<NamedNode> {
+    match term {
+        Term::NamedNode(node) => Some(node.clone()),
+        _ => None,
+    }
+}
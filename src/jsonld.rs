@@ -0,0 +1,815 @@
+//! JSON-LD output, compacted using the collected prefixes as `@context`, or shaped by a frame.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{GraphName, NamedNode, NamedOrBlankNode, Quad, Term};
+use oxiri::Iri;
+use serde_json::{json, Map, Value};
+
+struct TermDefinition {
+    term: String,
+    iri: String,
+    type_coercion: Option<String>,
+    set: bool,
+}
+
+/// A (simple) JSON-LD context, used to compact IRIs and values.
+struct Context {
+    base_iri: Option<Iri<String>>,
+    vocab: Option<String>,
+    // Sorted by longest namespace first:
+    prefixes: Vec<(String, String)>,
+    terms: Vec<TermDefinition>,
+    source: Map<String, Value>,
+}
+
+impl Context {
+    fn from_prefixes(base_iri: Option<&str>, prefixes: &HashMap<String, String>) -> Result<Self> {
+        let base_iri = base_iri.map(|iri| Iri::parse(iri.to_owned())).transpose()?;
+
+        // The empty (Turtle default) prefix cannot be a JSON-LD term, so it becomes the vocabulary:
+        let vocab = prefixes.get("").cloned();
+
+        let mut prefixes: Vec<(String, String)> = prefixes
+            .iter()
+            .filter(|(pfx, _)| !pfx.is_empty())
+            .map(|(pfx, ns)| (pfx.to_owned(), ns.to_owned()))
+            .collect();
+
+        let mut source = Map::new();
+        if let Some(base_iri) = &base_iri {
+            source.insert("@base".into(), base_iri.as_str().into());
+        }
+        if let Some(vocab) = &vocab {
+            source.insert("@vocab".into(), vocab.as_str().into());
+        }
+        prefixes.sort();
+        for (pfx, ns) in &prefixes {
+            source.insert(pfx.to_owned(), ns.as_str().into());
+        }
+
+        Ok(Self::new(base_iri, vocab, prefixes, source))
+    }
+
+    /// Reads an embedded context (as given in a frame). Remote contexts are not supported.
+    fn from_json(context: &Value, base_iri: Option<&str>) -> Result<Self> {
+        let mut source = Map::new();
+        match context {
+            Value::Object(object) => source.extend(object.clone()),
+            Value::Array(contexts) => {
+                for context in contexts {
+                    let Value::Object(object) = context else {
+                        bail!("Only embedded JSON-LD contexts are supported, got: {context}");
+                    };
+                    source.extend(object.clone());
+                }
+            }
+            _ => bail!("Only embedded JSON-LD contexts are supported, got: {context}"),
+        }
+
+        let mut base_iri = base_iri.map(str::to_owned);
+        let mut vocab = None;
+        let mut prefixes = Vec::new();
+        for (key, value) in &source {
+            match (key.as_str(), value) {
+                ("@base", Value::String(iri)) => base_iri = Some(iri.to_owned()),
+                ("@vocab", Value::String(iri)) => vocab = Some(iri.to_owned()),
+                // Prefixes are simple terms ending with a generic delimiter:
+                (key, Value::String(iri))
+                    if !key.starts_with('@')
+                        && iri.ends_with(['/', '#', ':', '?', '[', ']', '@']) =>
+                {
+                    prefixes.push((key.to_owned(), iri.to_owned()));
+                }
+                _ => {}
+            }
+        }
+        let base_iri = base_iri.map(Iri::parse).transpose()?;
+
+        let mut context = Self::new(base_iri, vocab, prefixes, Map::new());
+
+        let mut terms = Vec::new();
+        for (key, value) in &source {
+            if key.starts_with('@') {
+                continue;
+            }
+            let (iri, type_coercion, container) = match value {
+                Value::String(iri) => (Some(iri.as_str()), None, None),
+                Value::Object(definition) => (
+                    definition.get("@id").and_then(Value::as_str),
+                    definition.get("@type").and_then(Value::as_str),
+                    definition.get("@container"),
+                ),
+                _ => continue,
+            };
+            let iri = context.expand_iri(iri.unwrap_or(key), true);
+            let type_coercion = type_coercion.map(|coercion| context.expand_iri(coercion, true));
+            let set = match container {
+                Some(Value::String(container)) => container == "@set",
+                Some(Value::Array(containers)) => containers.iter().any(|c| c == "@set"),
+                _ => false,
+            };
+            terms.push(TermDefinition {
+                term: key.to_owned(),
+                iri,
+                type_coercion,
+                set,
+            });
+        }
+
+        context.terms = terms;
+        context.source = source;
+        Ok(context)
+    }
+
+    fn new(
+        base_iri: Option<Iri<String>>,
+        vocab: Option<String>,
+        mut prefixes: Vec<(String, String)>,
+        source: Map<String, Value>,
+    ) -> Self {
+        prefixes.sort_by(|(apfx, ans), (bpfx, bns)| bns.len().cmp(&ans.len()).then(apfx.cmp(bpfx)));
+        Self {
+            base_iri,
+            vocab,
+            prefixes,
+            terms: Vec::new(),
+            source,
+        }
+    }
+
+    fn expand_iri(&self, value: &str, vocab: bool) -> String {
+        if value.starts_with('@') {
+            return value.to_owned();
+        }
+        if vocab {
+            if let Some(definition) = self.terms.iter().find(|def| def.term == value) {
+                return definition.iri.to_owned();
+            }
+        }
+        if let Some((pfx, local)) = value.split_once(':') {
+            if pfx == "_" || local.starts_with("//") {
+                return value.to_owned();
+            }
+            if let Some((_, ns)) = self.prefixes.iter().find(|(p, _)| p == pfx) {
+                return format!("{ns}{local}");
+            }
+            return value.to_owned();
+        }
+        if vocab {
+            if let Some(vocab) = &self.vocab {
+                return format!("{vocab}{value}");
+            }
+        }
+        if let Some(base_iri) = &self.base_iri {
+            if let Ok(iri) = base_iri.resolve(value) {
+                return iri.into_inner();
+            }
+        }
+        value.to_owned()
+    }
+
+    /// Compacts an IRI used as a property, type or datatype.
+    fn compact_vocab_iri(&self, iri: &str) -> String {
+        if let Some(definition) = self
+            .terms
+            .iter()
+            .find(|def| def.iri == iri && def.type_coercion.is_none())
+        {
+            return definition.term.to_owned();
+        }
+        if let Some(local) = self
+            .vocab
+            .as_deref()
+            .and_then(|vocab| iri.strip_prefix(vocab))
+        {
+            if is_plain_term(local) && !self.terms.iter().any(|def| def.term == local) {
+                return local.to_owned();
+            }
+        }
+        self.compact_iri(iri).unwrap_or_else(|| iri.to_owned())
+    }
+
+    /// Compacts an IRI used as a node identifier.
+    fn compact_id(&self, iri: &str) -> String {
+        if let Some(curie) = self.compact_iri(iri) {
+            return curie;
+        }
+        if let Some(base_iri) = &self.base_iri {
+            if let Ok(relative) = base_iri.relativize(&Iri::parse_unchecked(iri)) {
+                let relative = relative.into_inner();
+                // Avoid relative references which could be mistaken for compact IRIs:
+                if !relative.contains(':') || relative.starts_with('#') {
+                    return relative;
+                }
+            }
+        }
+        iri.to_owned()
+    }
+
+    fn compact_iri(&self, iri: &str) -> Option<String> {
+        self.prefixes.iter().find_map(|(pfx, ns)| {
+            iri.strip_prefix(ns.as_str())
+                .filter(|local| !local.is_empty() && !local.starts_with("//"))
+                .map(|local| format!("{pfx}:{local}"))
+        })
+    }
+
+    fn node_id(&self, node: &NamedOrBlankNode) -> String {
+        match node {
+            NamedOrBlankNode::NamedNode(node) => self.compact_id(node.as_str()),
+            NamedOrBlankNode::BlankNode(node) => format!("_:{}", node.as_str()),
+        }
+    }
+
+    /// Finds a term matching both the property and the value (given any type coercion).
+    fn term_for(&self, property: &NamedNode, value: &Term) -> Option<&TermDefinition> {
+        self.terms.iter().find(|def| {
+            def.iri == property.as_str()
+                && match (def.type_coercion.as_deref(), value) {
+                    (None, _) => true,
+                    (Some("@id" | "@vocab"), Term::NamedNode(_) | Term::BlankNode(_)) => true,
+                    (Some(datatype), Term::Literal(literal)) => {
+                        literal.language().is_none() && literal.datatype().as_str() == datatype
+                    }
+                    _ => false,
+                }
+        })
+    }
+
+    /// Returns the compacted key and value for a property value (and if it is a set).
+    fn compact_property(&self, property: &NamedNode, value: &Term) -> (String, Value, bool) {
+        if let Some(definition) = self.term_for(property, value) {
+            let compacted = match (definition.type_coercion.as_deref(), value) {
+                (Some("@id"), Term::NamedNode(node)) => self.compact_id(node.as_str()).into(),
+                (Some("@vocab"), Term::NamedNode(node)) => {
+                    self.compact_vocab_iri(node.as_str()).into()
+                }
+                (Some(_), Term::BlankNode(node)) => format!("_:{}", node.as_str()).into(),
+                (Some(_), Term::Literal(literal)) => literal.value().into(),
+                _ => self.value(value),
+            };
+            return (definition.term.to_owned(), compacted, definition.set);
+        }
+        let key = match self.compact_vocab_iri(property.as_str()) {
+            // Avoid using a term which is coerced to another type of value:
+            key if self.terms.iter().any(|def| def.term == key) => property.as_str().to_owned(),
+            key => key,
+        };
+        (key, self.value(value), false)
+    }
+
+    fn value(&self, object: &Term) -> Value {
+        match object {
+            Term::NamedNode(node) => json!({ "@id": self.compact_id(node.as_str()) }),
+            Term::BlankNode(node) => json!({ "@id": format!("_:{}", node.as_str()) }),
+            Term::Literal(literal) => {
+                if let (Some(language), Some(direction)) = (literal.language(), literal.direction())
+                {
+                    json!({
+                        "@value": literal.value(),
+                        "@language": language,
+                        "@direction": direction.to_string(),
+                    })
+                } else if let Some(language) = literal.language() {
+                    json!({ "@value": literal.value(), "@language": language })
+                } else if literal.datatype() == xsd::STRING {
+                    literal.value().into()
+                } else {
+                    json!({
+                        "@value": literal.value(),
+                        "@type": self.compact_vocab_iri(literal.datatype().as_str()),
+                    })
+                }
+            }
+            Term::Triple(_) => unreachable!("triple terms are rejected when added"),
+        }
+    }
+}
+
+/// Fails for triple terms (as objects), which cannot be represented in JSON-LD.
+fn check_object(quad: &Quad) -> Result<()> {
+    if let Term::Triple(triple) = &quad.object {
+        bail!("Triple terms cannot be written as JSON-LD: <<( {triple} )>>");
+    }
+    Ok(())
+}
+
+fn is_plain_term(local: &str) -> bool {
+    !local.is_empty() && !local.starts_with('@') && !local.contains(':') && !local.contains('/')
+}
+
+struct Node {
+    id: NamedOrBlankNode,
+    types: Vec<NamedOrBlankNode>,
+    properties: Vec<(NamedNode, Vec<Term>)>,
+}
+
+/// Nodes of a graph, in order of appearance.
+#[derive(Default)]
+struct NodeMap {
+    nodes: Vec<Node>,
+    index: HashMap<NamedOrBlankNode, usize>,
+}
+
+impl NodeMap {
+    fn add(&mut self, quad: Quad) {
+        let idx = *self.index.entry(quad.subject.clone()).or_insert_with(|| {
+            self.nodes.push(Node {
+                id: quad.subject,
+                types: Vec::new(),
+                properties: Vec::new(),
+            });
+            self.nodes.len() - 1
+        });
+        let node = &mut self.nodes[idx];
+
+        match quad.object {
+            Term::NamedNode(object) if quad.predicate == rdf::TYPE => {
+                node.types.push(object.into());
+            }
+            Term::BlankNode(object) if quad.predicate == rdf::TYPE => {
+                node.types.push(object.into());
+            }
+            object => {
+                if let Some((_, values)) = node
+                    .properties
+                    .iter_mut()
+                    .find(|(p, _)| *p == quad.predicate)
+                {
+                    if !values.contains(&object) {
+                        values.push(object);
+                    }
+                } else {
+                    node.properties.push((quad.predicate, vec![object]));
+                }
+            }
+        }
+    }
+
+    fn get(&self, id: &NamedOrBlankNode) -> Option<&Node> {
+        self.index.get(id).map(|idx| &self.nodes[*idx])
+    }
+}
+
+fn compact_array(mut values: Vec<Value>, set: bool) -> Value {
+    if values.len() == 1 && !set {
+        values.remove(0)
+    } else {
+        Value::Array(values)
+    }
+}
+
+/// Inserts compacted property values into a node object (grouping values by key).
+fn insert_values(object: &mut Map<String, Value>, values: Vec<(String, Value, bool)>) {
+    let mut grouped: Vec<(String, Vec<Value>, bool)> = Vec::new();
+    for (key, value, set) in values {
+        if let Some((_, values, _)) = grouped.iter_mut().find(|(k, _, _)| *k == key) {
+            values.push(value);
+        } else {
+            grouped.push((key, vec![value], set));
+        }
+    }
+    for (key, values, set) in grouped {
+        object.insert(key, compact_array(values, set));
+    }
+}
+
+fn insert_types(context: &Context, object: &mut Map<String, Value>, types: &[NamedOrBlankNode]) {
+    if types.is_empty() {
+        return;
+    }
+    let types = types
+        .iter()
+        .map(|t| match t {
+            NamedOrBlankNode::NamedNode(t) => context.compact_vocab_iri(t.as_str()).into(),
+            NamedOrBlankNode::BlankNode(t) => format!("_:{}", t.as_str()).into(),
+        })
+        .collect();
+    object.insert("@type".into(), compact_array(types, false));
+}
+
+fn node_object(context: &Context, node: &Node) -> Map<String, Value> {
+    let mut object = Map::new();
+    object.insert("@id".into(), context.node_id(&node.id).into());
+    insert_types(context, &mut object, &node.types);
+    let values = node
+        .properties
+        .iter()
+        .flat_map(|(p, values)| values.iter().map(|v| context.compact_property(p, v)))
+        .collect();
+    insert_values(&mut object, values);
+    object
+}
+
+/// Wraps the node objects in a document with the context (unwrapping a single node).
+fn to_document(context: &Context, mut top_nodes: Vec<Map<String, Value>>) -> Value {
+    let mut document = Map::new();
+    if !context.source.is_empty() {
+        document.insert("@context".into(), Value::Object(context.source.clone()));
+    }
+    if top_nodes.len() == 1 {
+        document.extend(top_nodes.remove(0));
+    } else {
+        document.insert(
+            "@graph".into(),
+            top_nodes.into_iter().map(Value::Object).collect(),
+        );
+    }
+    Value::Object(document)
+}
+
+/// Serializes quads as compacted JSON-LD.
+pub(crate) fn serialize<W: Write>(
+    quads: impl IntoIterator<Item = Quad>,
+    base_iri: Option<&str>,
+    prefixes: &HashMap<String, String>,
+    mut writer: W,
+) -> Result<()> {
+    let context = Context::from_prefixes(base_iri, prefixes)?;
+
+    let mut graphs: Vec<(GraphName, NodeMap)> = vec![(GraphName::DefaultGraph, NodeMap::default())];
+    for quad in quads {
+        check_object(&quad)?;
+        let graph_name = quad.graph_name.clone();
+        if let Some((_, nodes)) = graphs.iter_mut().find(|(g, _)| *g == graph_name) {
+            nodes.add(quad);
+        } else {
+            let mut nodes = NodeMap::default();
+            nodes.add(quad);
+            graphs.push((graph_name, nodes));
+        }
+    }
+
+    let mut graphs = graphs.into_iter();
+    let mut top_nodes: Vec<Map<String, Value>> = graphs
+        .next()
+        .map(|(_, nodes)| {
+            nodes
+                .nodes
+                .iter()
+                .map(|n| node_object(&context, n))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for (graph_name, nodes) in graphs {
+        let id = match graph_name {
+            GraphName::NamedNode(node) => context.compact_id(node.as_str()),
+            GraphName::BlankNode(node) => format!("_:{}", node.as_str()),
+            GraphName::DefaultGraph => continue,
+        };
+        let graph: Value = nodes
+            .nodes
+            .iter()
+            .map(|n| Value::Object(node_object(&context, n)))
+            .collect();
+        if let Some(node) = top_nodes.iter_mut().find(|node| node["@id"] == id.as_str()) {
+            node.insert("@graph".into(), graph);
+        } else {
+            let mut node = Map::new();
+            node.insert("@id".into(), id.into());
+            node.insert("@graph".into(), graph);
+            top_nodes.push(node);
+        }
+    }
+
+    serde_json::to_writer_pretty(&mut writer, &to_document(&context, top_nodes))?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Embed {
+    Once,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy)]
+struct Flags {
+    embed: Embed,
+    explicit: bool,
+    require_all: bool,
+}
+
+impl Default for Flags {
+    fn default() -> Self {
+        Self {
+            embed: Embed::Once,
+            explicit: false,
+            require_all: false,
+        }
+    }
+}
+
+/// A frame, with keys and values expanded using the frame context.
+struct Frame {
+    ids: Option<Vec<String>>,
+    types: Option<Vec<String>>,
+    // Property IRI, subframe (none if matching nothing) and default value:
+    properties: Vec<(String, Option<Frame>, Option<Value>)>,
+    flags: Flags,
+}
+
+impl Frame {
+    fn parse(context: &Context, frame: &Value, parent_flags: Flags) -> Result<Self> {
+        let frame = match frame {
+            Value::Object(frame) => frame,
+            Value::Array(frames) if frames.len() == 1 => {
+                return Self::parse(context, &frames[0], parent_flags);
+            }
+            _ => bail!("Expected a JSON-LD frame object, got: {frame}"),
+        };
+
+        let mut flags = parent_flags;
+        let mut ids = None;
+        let mut types = None;
+        let mut properties = Vec::new();
+
+        for (key, value) in frame {
+            match key.as_str() {
+                "@id" => ids = Some(strings(value, |id| context.expand_iri(id, false))),
+                "@type" => {
+                    types = Some(if value.as_object().is_some_and(Map::is_empty) {
+                        vec!["@wildcard".into()]
+                    } else {
+                        strings(value, |t| context.expand_iri(t, true))
+                    })
+                }
+                "@embed" => {
+                    flags.embed = match value {
+                        Value::String(s) if s == "@always" => Embed::Always,
+                        Value::String(s) if s == "@never" => Embed::Never,
+                        Value::Bool(false) => Embed::Never,
+                        _ => Embed::Once,
+                    }
+                }
+                "@explicit" => flags.explicit = value.as_bool().unwrap_or(false),
+                "@requireAll" => flags.require_all = value.as_bool().unwrap_or(false),
+                key if key.starts_with('@') => {}
+                key => {
+                    let property = context.expand_iri(key, true);
+                    let subframe_value = match value {
+                        Value::Array(values) => values.first(),
+                        value => Some(value),
+                    };
+                    let default = subframe_value.and_then(|v| v.get("@default")).cloned();
+                    let subframe = subframe_value
+                        .map(|v| Self::parse(context, v, flags))
+                        .transpose()?;
+                    properties.push((property, subframe, default));
+                }
+            }
+        }
+
+        Ok(Self {
+            ids,
+            types,
+            properties,
+            flags,
+        })
+    }
+
+    fn implicit(flags: Flags) -> Self {
+        Self {
+            ids: None,
+            types: None,
+            properties: Vec::new(),
+            flags,
+        }
+    }
+
+    fn matches(&self, node: &Node) -> bool {
+        if let Some(ids) = &self.ids {
+            let id = match &node.id {
+                NamedOrBlankNode::NamedNode(n) => n.as_str().to_owned(),
+                NamedOrBlankNode::BlankNode(n) => format!("_:{}", n.as_str()),
+            };
+            if !ids.contains(&id) {
+                return false;
+            }
+        }
+
+        if let Some(types) = &self.types {
+            let matched = if types.first().is_some_and(|t| t == "@wildcard") {
+                !node.types.is_empty()
+            } else if types.is_empty() {
+                node.types.is_empty()
+            } else {
+                node.types.iter().any(|t| match t {
+                    NamedOrBlankNode::NamedNode(t) => types.iter().any(|ft| ft == t.as_str()),
+                    NamedOrBlankNode::BlankNode(_) => false,
+                })
+            };
+            if !matched {
+                return false;
+            }
+        }
+
+        let mut checks = self.properties.iter().map(|(property, subframe, default)| {
+            let has_property = node.properties.iter().any(|(p, _)| p.as_str() == property);
+            match subframe {
+                // Match none (given as empty array):
+                None => !has_property,
+                Some(_) => has_property || default.is_some(),
+            }
+        });
+
+        if self.flags.require_all {
+            checks.all(|check| check)
+        } else if self.ids.is_none() && self.types.is_none() && !self.properties.is_empty() {
+            // Match by "duck typing":
+            checks.any(|check| check)
+        } else {
+            true
+        }
+    }
+}
+
+fn strings(value: &Value, expand: impl Fn(&str) -> String) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![expand(s)],
+        Value::Array(values) => values
+            .iter()
+            .filter_map(Value::as_str)
+            .map(expand)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+struct Framer<'a> {
+    context: &'a Context,
+    nodes: &'a NodeMap,
+    embedded: HashSet<NamedOrBlankNode>,
+}
+
+impl Framer<'_> {
+    fn frame_node(
+        &mut self,
+        node: &Node,
+        frame: &Frame,
+        path: &mut Vec<NamedOrBlankNode>,
+    ) -> Map<String, Value> {
+        let context = self.context;
+        self.embedded.insert(node.id.clone());
+        path.push(node.id.clone());
+
+        let mut object = Map::new();
+        object.insert("@id".into(), context.node_id(&node.id).into());
+        insert_types(context, &mut object, &node.types);
+
+        let mut values = Vec::new();
+        for (property, property_values) in &node.properties {
+            let framed = frame
+                .properties
+                .iter()
+                .find(|(p, _, _)| p == property.as_str());
+            let implicit;
+            let subframe = match framed {
+                Some((_, Some(subframe), _)) => subframe,
+                Some((_, None, _)) => continue,
+                None if frame.flags.explicit => continue,
+                None => {
+                    implicit = Frame::implicit(frame.flags);
+                    &implicit
+                }
+            };
+            for value in property_values {
+                if let Some(value) = self.frame_value(property, value, subframe, path) {
+                    values.push(value);
+                }
+            }
+        }
+
+        // Add defaults for framed properties missing from the node:
+        for (property, _, default) in &frame.properties {
+            if let Some(default) = default {
+                if !node.properties.iter().any(|(p, _)| p.as_str() == property) {
+                    let key = context.compact_vocab_iri(property);
+                    let default = if default == "@null" {
+                        Value::Null
+                    } else {
+                        default.clone()
+                    };
+                    values.push((key, default, false));
+                }
+            }
+        }
+
+        insert_values(&mut object, values);
+        path.pop();
+        object
+    }
+
+    fn frame_value(
+        &mut self,
+        property: &NamedNode,
+        value: &Term,
+        subframe: &Frame,
+        path: &mut Vec<NamedOrBlankNode>,
+    ) -> Option<(String, Value, bool)> {
+        let context = self.context;
+        let id: NamedOrBlankNode = match value {
+            Term::NamedNode(n) => n.clone().into(),
+            Term::BlankNode(n) => n.clone().into(),
+            Term::Literal(_) | Term::Triple(_) => {
+                return Some(context.compact_property(property, value))
+            }
+        };
+        let Some(node) = self.nodes.get(&id) else {
+            return Some(context.compact_property(property, value));
+        };
+        if !subframe.matches(node) {
+            return None;
+        }
+        let embed = match subframe.flags.embed {
+            Embed::Never => false,
+            Embed::Once => !self.embedded.contains(&id),
+            Embed::Always => !path.contains(&id),
+        };
+        let (key, compacted, set) = context.compact_property(property, value);
+        if !embed {
+            return Some((key, compacted, set));
+        }
+        let object = self.frame_node(node, subframe, path);
+        Some((key, Value::Object(object), set))
+    }
+}
+
+/// Removes identifiers of blank nodes which are only used once.
+fn prune_blank_node_ids(document: &mut Value) {
+    fn count(value: &Value, counts: &mut HashMap<String, usize>) {
+        match value {
+            Value::String(s) if s.starts_with("_:") => {
+                *counts.entry(s.to_owned()).or_default() += 1
+            }
+            Value::Array(values) => values.iter().for_each(|v| count(v, counts)),
+            Value::Object(object) => object.values().for_each(|v| count(v, counts)),
+            _ => {}
+        }
+    }
+    fn prune(value: &mut Value, counts: &HashMap<String, usize>) {
+        match value {
+            Value::Array(values) => values.iter_mut().for_each(|v| prune(v, counts)),
+            Value::Object(object) => {
+                let once = object
+                    .get("@id")
+                    .and_then(Value::as_str)
+                    .is_some_and(|id| counts.get(id) == Some(&1));
+                if once && object.len() > 1 {
+                    object.shift_remove("@id");
+                }
+                object.values_mut().for_each(|v| prune(v, counts));
+            }
+            _ => {}
+        }
+    }
+    let mut counts = HashMap::new();
+    count(document, &mut counts);
+    prune(document, &counts);
+}
+
+/// Serializes quads as JSON-LD shaped by the given frame (merging all graphs).
+pub(crate) fn serialize_framed<W: Write>(
+    quads: impl IntoIterator<Item = Quad>,
+    frame: &Value,
+    base_iri: Option<&str>,
+    prefixes: &HashMap<String, String>,
+    mut writer: W,
+) -> Result<()> {
+    let context = if let Some(frame_context) = frame.get("@context") {
+        Context::from_json(frame_context, base_iri)?
+    } else {
+        Context::from_prefixes(base_iri, prefixes)?
+    };
+    let frame = Frame::parse(&context, frame, Flags::default())?;
+
+    let mut nodes = NodeMap::default();
+    for quad in quads {
+        check_object(&quad)?;
+        nodes.add(quad);
+    }
+
+    let mut framer = Framer {
+        context: &context,
+        nodes: &nodes,
+        embedded: HashSet::new(),
+    };
+    let mut top_nodes = Vec::new();
+    for node in &nodes.nodes {
+        if frame.matches(node) {
+            top_nodes.push(framer.frame_node(node, &frame, &mut Vec::new()));
+        }
+    }
+
+    let mut document = to_document(&context, top_nodes);
+    prune_blank_node_ids(&mut document);
+    serde_json::to_writer_pretty(&mut writer, &document)?;
+    writeln!(writer)?;
+    Ok(())
+}
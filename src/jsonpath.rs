@@ -0,0 +1,205 @@
+//! Selection of values in JSON by JSONPath (the common subset of it: `$`, `.name`, `['name']`,
+//! `[0]`, `[-1]`, `[0:2]`, `[*]`, `.*`, `..name` and unions like `['a','b']`, but no filters).
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+enum Step {
+    Member(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+    Union(Vec<Step>),
+    /// Any descendants (or the value itself) to apply the next step to.
+    Descendants,
+}
+
+/// A parsed path.
+pub(crate) struct JsonPath {
+    steps: Vec<Step>,
+}
+
+impl JsonPath {
+    /// Parses a path (relative paths, such as `address.city`, start from the given value as
+    /// `$` does).
+    pub(crate) fn parse(path: &str) -> Result<Self> {
+        if path.contains("?(") {
+            bail!("Filters in JSONPath are not supported: {path}");
+        }
+        let invalid = || anyhow::anyhow!("Invalid or unsupported JSONPath: {path}");
+        let mut rest = path.trim();
+        rest = rest.strip_prefix('$').unwrap_or(rest);
+        if !rest.is_empty() && !rest.starts_with(['.', '[']) {
+            // (A relative path begins with a member name.)
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let name = &rest[..end];
+            rest = &rest[end..];
+            let mut steps = vec![member_step(name)];
+            steps.extend(Self::parse_steps(rest).ok_or_else(invalid)?);
+            return Ok(Self { steps });
+        }
+        let steps = Self::parse_steps(rest).ok_or_else(invalid)?;
+        Ok(Self { steps })
+    }
+
+    fn parse_steps(mut rest: &str) -> Option<Vec<Step>> {
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("..") {
+                steps.push(Step::Descendants);
+                rest = after;
+                if rest.starts_with('[') {
+                    continue;
+                }
+            } else if let Some(after) = rest.strip_prefix('.') {
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = bracket_end(after)?;
+                steps.push(parse_bracket(after[..end].trim())?);
+                rest = &after[end + 1..];
+                continue;
+            } else {
+                return None;
+            }
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return None;
+            }
+            steps.push(member_step(&rest[..end]));
+            rest = &rest[end..];
+        }
+        Some(steps)
+    }
+
+    /// Selects the values at the path (in document order).
+    pub(crate) fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut values = vec![value];
+        for step in &self.steps {
+            let mut selected = Vec::new();
+            for value in values {
+                apply(step, value, &mut selected);
+            }
+            values = selected;
+        }
+        values
+    }
+}
+
+fn member_step(name: &str) -> Step {
+    match name {
+        "*" => Step::Wildcard,
+        name => Step::Member(name.to_owned()),
+    }
+}
+
+/// Finds the closing bracket (outside of quotes).
+fn bracket_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ']') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_bracket(content: &str) -> Option<Step> {
+    let parts = split_union(content);
+    if parts.len() > 1 {
+        return parts
+            .iter()
+            .map(|part| parse_selector(part))
+            .collect::<Option<_>>()
+            .map(Step::Union);
+    }
+    parse_selector(content)
+}
+
+fn split_union(content: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in content.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ',') => {
+                parts.push(content[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(content[start..].trim());
+    parts
+}
+
+fn parse_selector(selector: &str) -> Option<Step> {
+    if selector == "*" {
+        return Some(Step::Wildcard);
+    }
+    for quote in ['\'', '"'] {
+        if let Some(name) = selector
+            .strip_prefix(quote)
+            .and_then(|name| name.strip_suffix(quote))
+        {
+            return Some(Step::Member(name.to_owned()));
+        }
+    }
+    if let Some((start, end)) = selector.split_once(':') {
+        let bound = |text: &str| match text.trim() {
+            "" => Some(None),
+            text => text.parse().ok().map(Some),
+        };
+        // (Any step of the slice is not supported.)
+        if end.contains(':') {
+            return None;
+        }
+        return Some(Step::Slice(bound(start)?, bound(end)?));
+    }
+    selector.parse().ok().map(Step::Index)
+}
+
+fn apply<'a>(step: &Step, value: &'a Value, selected: &mut Vec<&'a Value>) {
+    match (step, value) {
+        (Step::Member(name), Value::Object(members)) => selected.extend(members.get(name)),
+        (Step::Index(i), Value::Array(items)) => {
+            let i = if *i < 0 { items.len() as i64 + i } else { *i };
+            selected.extend(usize::try_from(i).ok().and_then(|i| items.get(i)));
+        }
+        (Step::Slice(start, end), Value::Array(items)) => {
+            let len = items.len() as i64;
+            let bound = |bound: i64| (if bound < 0 { len + bound } else { bound }).clamp(0, len);
+            let start = bound(start.unwrap_or(0)) as usize;
+            let end = bound(end.unwrap_or(len)) as usize;
+            selected.extend(items.get(start..end.max(start)).into_iter().flatten());
+        }
+        (Step::Wildcard, Value::Array(items)) => selected.extend(items),
+        (Step::Wildcard, Value::Object(members)) => selected.extend(members.values()),
+        (Step::Union(steps), value) => {
+            for step in steps {
+                apply(step, value, selected);
+            }
+        }
+        (Step::Descendants, value) => {
+            selected.push(value);
+            match value {
+                Value::Array(items) => {
+                    for item in items {
+                        apply(step, item, selected);
+                    }
+                }
+                Value::Object(members) => {
+                    for member in members.values() {
+                        apply(step, member, selected);
+                    }
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,3519 @@
+//! Run SPARQL queries over RDF data, as done by the `oxrq` command-line tool.
+//!
+//! The [`Runner`] collects input data into an in-memory store (keeping track of prefixes and base
+//! IRI found in the data), executes a query or update against it, and serializes the result.
+//!
+//! ```
+//! use oxrq::Runner;
+//!
+//! let mut runner = Runner::new()?;
+//! let data = "PREFIX : <http://example.org/ns#> <http://example.org/item/1> a :Item .";
+//! runner.load_reader(data.as_bytes(), None)?;
+//!
+//! let query = runner.prepare_query("select ?item { ?item a :Item }");
+//! let mut out = Vec::new();
+//! runner.run(&query, None, &mut out)?;
+//! assert_eq!(String::from_utf8(out)?, "?item\n<http://example.org/item/1>\n");
+//! # anyhow::Ok(())
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+use oxigraph::io::{
+    JsonLdProfile, JsonLdProfileSet, LoadedDocument, RdfFormat, RdfParser, RdfSerializer,
+};
+use oxigraph::model::dataset::CanonicalizationAlgorithm;
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{
+    Dataset, Graph, GraphName, GraphNameRef, Literal, NamedNode, NamedOrBlankNode, Quad, QuadRef,
+    Term, Triple, Variable,
+};
+use oxigraph::sparql::results::{QueryResultsFormat, QueryResultsSerializer};
+use oxigraph::sparql::{
+    AggregateFunctionAccumulator, CancellationToken, PreparedSparqlQuery, PreparedSparqlUpdate,
+    QueryResults, QuerySolution, QuerySolutionIter, QueryTripleIter, SparqlEvaluator,
+};
+use oxigraph::store::{StorageError, Store};
+use oxiri::Iri;
+use progress::Progress;
+use regex::Regex;
+use spargebra::algebra::{GraphPattern, GraphTarget};
+use spargebra::term::{GraphNamePattern, GroundTerm};
+use spargebra::{GraphUpdateOperation, SparqlParser};
+
+mod archive;
+mod compression;
+mod config;
+mod csv;
+mod csvw;
+mod diff;
+mod endpoint;
+mod follow;
+mod geo;
+mod hdt;
+mod highlight;
+mod html;
+mod http;
+mod imports;
+mod inference;
+mod jsonld;
+mod jsonpath;
+mod lint;
+mod n3;
+mod namespaces;
+mod patch;
+mod prefixes;
+mod pretty;
+mod progress;
+mod r2rml;
+mod rdfjson;
+mod render;
+mod results;
+mod rules;
+mod search;
+mod server;
+mod shacl;
+mod shex;
+mod skolem;
+mod sniff;
+mod split;
+mod sqlite;
+mod summary;
+mod table;
+mod template;
+mod void;
+mod wasm;
+mod xlsx;
+mod xpath;
+
+pub use archive::{archive_entry_iri, is_archive, read_archive};
+pub use config::{Config, ConfigOption, OptionSource, OptionValue};
+pub use csv::{CsvDialect, Quoting};
+pub use diff::SetOperation;
+pub use split::{Partition, SplitBy};
+
+/// A supported input format: an RDF syntax, RDF/JSON, HTML (with embedded RDF), HDT or CSV
+/// (converted to RDF).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    Rdf(RdfFormat),
+    RdfJson,
+    Html,
+    Hdt,
+    Csv,
+    Sqlite,
+}
+
+impl InputFormat {
+    /// Looks up an input format from a file extension (or format name).
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            "hdt" => Some(Self::Hdt),
+            "rj" => Some(Self::RdfJson),
+            "csv" => Some(Self::Csv),
+            "sqlite" | "sqlite3" | "db" => Some(Self::Sqlite),
+            _ => RdfFormat::from_extension(extension).map(Self::Rdf),
+        }
+    }
+
+    /// Looks up an input format from a media type (without parameters).
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "text/html" | "application/xhtml+xml" => Some(Self::Html),
+            "application/vnd.hdt" => Some(Self::Hdt),
+            "application/rdf+json" => Some(Self::RdfJson),
+            "text/csv" => Some(Self::Csv),
+            "application/vnd.sqlite3" => Some(Self::Sqlite),
+            _ => RdfFormat::from_media_type(media_type).map(Self::Rdf),
+        }
+    }
+
+    /// Guesses the input format from the beginning of the data (such as an XML declaration, a
+    /// JSON object, or the shape of the first statement).
+    pub fn from_content(content: &[u8]) -> Option<Self> {
+        sniff::sniff(content)
+    }
+}
+
+/// A supported patch format: RDF Patch or LD Patch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchFormat {
+    RdfPatch,
+    LdPatch,
+}
+
+impl PatchFormat {
+    /// Looks up a patch format from a file extension (`rdp` or `ldpatch`).
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "rdp" => Some(Self::RdfPatch),
+            "ldpatch" => Some(Self::LdPatch),
+            _ => None,
+        }
+    }
+}
+
+/// A supported inference profile: the entailment rules applied when materializing triples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InferenceProfile {
+    Rdfs,
+    OwlRl,
+}
+
+impl InferenceProfile {
+    /// Looks up an inference profile by name (`rdfs` or `owl-rl`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rdfs" => Some(Self::Rdfs),
+            "owl-rl" => Some(Self::OwlRl),
+            _ => None,
+        }
+    }
+}
+
+const HTTP_REDIRECTION_LIMIT: usize = 10;
+
+const STORE_PREFIXES_FILE: &str = "prefixes.ttl";
+
+/// The namespace of the names of virtual endpoints (of data loaded apart).
+const SERVICE_NAMESPACE: &str = "urn:oxrq:";
+
+/// Well-known prefixes, available in queries (and used in output) unless declared otherwise.
+const DEFAULT_PREFIXES: &[(&str, &str)] = &[
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("owl", "http://www.w3.org/2002/07/owl#"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+    ("skos", "http://www.w3.org/2004/02/skos/core#"),
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("dct", "http://purl.org/dc/terms/"),
+    ("foaf", "http://xmlns.com/foaf/0.1/"),
+    ("schema", "http://schema.org/"),
+    ("sh", "http://www.w3.org/ns/shacl#"),
+    ("prov", "http://www.w3.org/ns/prov#"),
+    ("dcat", "http://www.w3.org/ns/dcat#"),
+    ("void", "http://rdfs.org/ns/void#"),
+    ("time", "http://www.w3.org/2006/time#"),
+    ("geo", "http://www.opengis.net/ont/geosparql#"),
+    ("geof", geo::FUNCTIONS),
+];
+
+/// Number of quads loaded at a time (checking the memory cap in between).
+const LOAD_BATCH_SIZE: usize = 100_000;
+
+/// Estimated memory used per quad in the in-memory store, besides the terms themselves (for the
+/// indexes over them).
+const QUAD_MEMORY_OVERHEAD: usize = 200;
+
+/// Accepted media types when fetching data over HTTP (preferring formats carrying prefixes).
+const RDF_ACCEPT: &str = "text/turtle, application/trig, application/n-triples, \
+    application/n-quads, application/rdf+xml;q=0.9, application/ld+json;q=0.9, \
+    text/n3;q=0.8, application/rdf+json;q=0.5, application/vnd.hdt;q=0.5, text/html;q=0.5, */*;q=0.1";
+
+/// A custom SPARQL function (giving no value for unsupported arguments).
+type CustomFunction = Arc<dyn Fn(&[Term]) -> Option<Term> + Send + Sync>;
+/// A custom SPARQL aggregate function (making an accumulator for each group).
+type CustomAggregate =
+    Arc<dyn Fn() -> Box<dyn AggregateFunctionAccumulator + Send + Sync> + Send + Sync>;
+
+/// Holds the loaded data along with prefixes and base IRI collected from it.
+pub struct Runner {
+    store: Store,
+    base_iri: Option<String>,
+    prefixes: HashMap<String, String>,
+    graph_namespaces: HashMap<GraphName, Namespaces>,
+    frame: Option<serde_json::Value>,
+    mappings: Mappings,
+    federation: bool,
+    endpoint: Option<String>,
+    store_path: Option<PathBuf>,
+    bindings: Vec<(String, String)>,
+    params: HashMap<String, String>,
+    default_graph_as_union: bool,
+    default_prefixes: bool,
+    skolem_base: Option<String>,
+    count: bool,
+    offset: usize,
+    limit: Option<usize>,
+    ask_result: Option<bool>,
+    cancellation_token: Option<CancellationToken>,
+    max_memory: Option<usize>,
+    loaded_size: usize,
+    progress: bool,
+    pretty: bool,
+    prune_prefixes: bool,
+    sort: bool,
+    output_graph: Option<NamedNode>,
+    merge_graphs: bool,
+    shared_blank_nodes: bool,
+    http_cache: Option<PathBuf>,
+    csv_dialect: Option<CsvDialect>,
+    results_template: Option<render::Template>,
+    table: bool,
+    color: bool,
+    text_search: bool,
+    functions: Vec<(NamedNode, CustomFunction)>,
+    aggregates: Vec<(NamedNode, CustomAggregate)>,
+    services: Vec<(NamedNode, Store)>,
+    stats: Stats,
+    #[cfg(feature = "rocksdb")]
+    spill_dir: Option<TempDir>,
+}
+
+/// Quads parsed from some input, along with the base IRI and prefixes declared in it.
+struct ParsedData {
+    quads: Vec<Quad>,
+    base_iri: Option<String>,
+    prefixes: Vec<(String, String)>,
+}
+
+/// Parts of a file parsed in a thread of its own, as sent to be added to the store.
+enum Parsed {
+    /// A batch of quads.
+    Quads(Vec<Quad>),
+    /// The end of a document, with the base IRI and prefixes declared in it.
+    End(Option<String>, Vec<(String, String)>),
+    Error(anyhow::Error),
+}
+
+/// A temporary directory (for a store spilled to disk), removed when dropped.
+#[cfg(feature = "rocksdb")]
+struct TempDir(PathBuf);
+
+#[cfg(feature = "rocksdb")]
+impl TempDir {
+    fn new() -> Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("oxrq-{}-{count}", std::process::id()));
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Unable to create directory: {}", path.display()))?;
+        Ok(Self(path))
+    }
+
+    fn open_store(&self) -> Result<Store> {
+        Store::open(&self.0).with_context(|| format!("Unable to open store: {}", self.0.display()))
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Counts and timings collected while loading and serializing data.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// The number of quads loaded (in total).
+    pub loaded_quads: usize,
+    /// The time spent adding parsed quads to the store.
+    pub load_time: Duration,
+    /// The time spent serializing the resulting data when running queries (but not query
+    /// results, which are written as they are evaluated).
+    pub serialize_time: Duration,
+}
+
+/// How input which is not RDF is converted to RDF.
+#[derive(Clone, Default)]
+struct Mappings {
+    csv: csvw::Mapping,
+    r2rml: Option<r2rml::Mapping>,
+    rml: Option<r2rml::Mapping>,
+    xml: Option<r2rml::Mapping>,
+}
+
+impl Mappings {
+    /// Converts the tables of a SQLite database, by R2RML if a mapping is given, or else by
+    /// the Direct Mapping (with IRIs made from the base IRI, as a directory).
+    fn convert_sqlite<R: Read>(
+        &self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: &GraphName,
+    ) -> Result<Vec<Quad>> {
+        let database = sqlite::Database::read(reader)?;
+        let Some(base_iri) = base_iri else {
+            bail!("A base IRI is needed to map a SQLite database to RDF (such as by --base-iri)");
+        };
+        let base_iri = match base_iri.ends_with(['/', '#']) {
+            true => base_iri.to_owned(),
+            false => format!("{base_iri}/"),
+        };
+        let Some(mapping) = &self.r2rml else {
+            return sqlite::direct_mapping(&database, &base_iri, graph_name);
+        };
+        let rows = |source: &r2rml::LogicalSource, _: &[String]| match source {
+            r2rml::LogicalSource::Table(table) => database.named_rows(table),
+            _ => Ok(Vec::new()),
+        };
+        mapping.apply(rows, Some(&base_iri), graph_name)
+    }
+
+    /// Converts JSON by the RML mapping.
+    fn convert_json<R: Read>(
+        &self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: &GraphName,
+    ) -> Result<Vec<Quad>> {
+        let Some(mapping) = &self.rml else {
+            bail!("No RML mapping to convert JSON by");
+        };
+        let value: serde_json::Value =
+            serde_json::from_reader(BufReader::new(reader)).context("Invalid JSON")?;
+        apply_to_document(
+            mapping,
+            base_iri,
+            graph_name,
+            |source, references| match source {
+                r2rml::LogicalSource::Json { iterator, .. } => {
+                    r2rml::json_rows(&value, iterator, references)
+                }
+                _ => Ok(Vec::new()),
+            },
+        )
+    }
+
+    /// Converts XML by the XML mapping.
+    fn convert_xml<R: Read>(
+        &self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: &GraphName,
+    ) -> Result<Vec<Quad>> {
+        let Some(mapping) = &self.xml else {
+            bail!("No mapping to convert XML by");
+        };
+        let document = xpath::Document::parse(BufReader::new(reader))?;
+        apply_to_document(
+            mapping,
+            base_iri,
+            graph_name,
+            |source, references| match source {
+                r2rml::LogicalSource::Xml { iterator, .. } => {
+                    r2rml::xml_rows(&document, iterator, references)
+                }
+                _ => Ok(Vec::new()),
+            },
+        )
+    }
+}
+
+/// Applies an RML mapping to the rows of an input document, by the triples maps whose
+/// `rml:source` has the file name of the input (or by all of them, if none has).
+fn apply_to_document(
+    mapping: &r2rml::Mapping,
+    base_iri: Option<&str>,
+    graph_name: &GraphName,
+    rows: impl Fn(&r2rml::LogicalSource, &[String]) -> Result<Vec<r2rml::Row>>,
+) -> Result<Vec<Quad>> {
+    let file_name = |iri: &str| iri.rsplit('/').next().unwrap_or_default().to_owned();
+    let input_name = base_iri.map(file_name);
+    let is_input = |source: &r2rml::LogicalSource| {
+        input_name.is_some() && source.source_name().map(file_name) == input_name
+    };
+    let sources_input = mapping.sources().any(is_input);
+    let rows = |source: &r2rml::LogicalSource, references: &[String]| match !sources_input
+        || is_input(source)
+    {
+        true => rows(source, references),
+        false => Ok(Vec::new()),
+    };
+    mapping.apply(rows, base_iri, graph_name)
+}
+
+/// The prefixes and base IRI parsed from the data loaded into a graph.
+#[derive(Default)]
+struct Namespaces {
+    base_iri: Option<String>,
+    prefixes: HashMap<String, String>,
+    /// Whether other graphs were loaded along with it (from a dataset).
+    with_other_graphs: bool,
+}
+
+/// The graphs the quads of an input were added to, for removing these again on an error: for each,
+/// whether it was empty before (and if so, whether it existed as a named graph). (Quads added to
+/// graphs with other data are not kept track of, to not hold them all in memory.)
+#[derive(Default)]
+struct Added {
+    graphs: HashMap<GraphName, Option<bool>>,
+}
+
+impl Added {
+    /// Notes the graphs the quads are about to be added to.
+    fn note(&mut self, store: &Store, quads: &[Quad]) -> Result<()> {
+        for quad in quads {
+            if self.graphs.contains_key(&quad.graph_name) {
+                continue;
+            }
+            let is_empty = store
+                .quads_for_pattern(None, None, None, Some(quad.graph_name.as_ref()))
+                .next()
+                .is_none();
+            let exists = match &quad.graph_name {
+                GraphName::NamedNode(node) => store.contains_named_graph(node)?,
+                GraphName::BlankNode(node) => store.contains_named_graph(node)?,
+                GraphName::DefaultGraph => true,
+            };
+            self.graphs
+                .insert(quad.graph_name.clone(), is_empty.then_some(exists));
+        }
+        Ok(())
+    }
+
+    /// Removes the graphs which were empty before from the store (or empties them, if they
+    /// existed), and tells whether quads were added to any other graphs (and are thus kept).
+    fn remove_from(&self, store: &Store) -> Result<bool> {
+        let mut kept = false;
+        for (graph_name, was_empty) in &self.graphs {
+            match (graph_name, was_empty) {
+                (GraphName::NamedNode(node), Some(false)) => store.remove_named_graph(node)?,
+                (GraphName::BlankNode(node), Some(false)) => store.remove_named_graph(node)?,
+                (_, Some(_)) => store.clear_graph(graph_name)?,
+                (_, None) => kept = true,
+            }
+        }
+        Ok(kept)
+    }
+}
+
+impl Runner {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            store: Store::new()?,
+            base_iri: None,
+            prefixes: HashMap::new(),
+            graph_namespaces: HashMap::new(),
+            frame: None,
+            mappings: Mappings::default(),
+            federation: false,
+            endpoint: None,
+            store_path: None,
+            bindings: Vec::new(),
+            params: HashMap::new(),
+            default_graph_as_union: true,
+            default_prefixes: true,
+            skolem_base: None,
+            count: false,
+            offset: 0,
+            limit: None,
+            ask_result: None,
+            cancellation_token: None,
+            max_memory: None,
+            loaded_size: 0,
+            progress: false,
+            pretty: false,
+            prune_prefixes: false,
+            sort: false,
+            output_graph: None,
+            merge_graphs: false,
+            shared_blank_nodes: false,
+            http_cache: None,
+            csv_dialect: None,
+            results_template: None,
+            table: false,
+            color: false,
+            text_search: false,
+            functions: Vec::new(),
+            aggregates: Vec::new(),
+            services: Vec::new(),
+            stats: Stats::default(),
+            #[cfg(feature = "rocksdb")]
+            spill_dir: None,
+        })
+    }
+
+    /// Uses a persistent (RocksDB) store at the given path (created if missing) instead of an
+    /// in-memory store.
+    ///
+    /// Prefixes and base IRI collected from loaded data are kept in a `prefixes.ttl` file in the
+    /// store directory, and used when the store is opened again.
+    #[cfg(feature = "rocksdb")]
+    pub fn with_store(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        self.store = Store::open(path)
+            .with_context(|| format!("Unable to open store: {}", path.display()))?;
+
+        let prefixes_path = path.join(STORE_PREFIXES_FILE);
+        if prefixes_path.exists() {
+            let file = File::open(&prefixes_path)?;
+            let parser = RdfParser::from_format(RdfFormat::Turtle);
+            self.load_data(parser, &GraphName::DefaultGraph, file, None)?;
+        }
+        self.store_path = Some(path.to_owned());
+
+        Ok(self)
+    }
+
+    /// Switches from the in-memory store to a temporary on-disk (RocksDB) store when the
+    /// (estimated) memory used by the loaded data exceeds the given number of bytes. (The
+    /// temporary store is removed when the runner is dropped.)
+    #[cfg(feature = "rocksdb")]
+    pub fn with_max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    /// Adds prefixes from a prefix file, for use in queries and output (taking precedence over
+    /// prefixes declared in data loaded later). The file is either Turtle or SPARQL prefix
+    /// declarations (one per line), or a JSON object of prefixes and namespaces (or a JSON-LD
+    /// document with such a context).
+    pub fn with_prefixes_file(mut self, text: &str) -> Result<Self> {
+        for (pfx, ns) in prefixes::parse(text)? {
+            self.prefixes.insert(pfx, ns);
+        }
+        Ok(self)
+    }
+
+    /// Adds a prefix for use in queries and output (taking precedence over prefixes declared in
+    /// data loaded later).
+    pub fn with_prefix(mut self, pfx: &str, ns: &str) -> Result<Self> {
+        Iri::parse(ns).with_context(|| format!("Invalid namespace of {pfx}: {ns}"))?;
+        self.prefixes.insert(pfx.to_owned(), ns.to_owned());
+        Ok(self)
+    }
+
+    /// Writes Turtle and TriG output pretty-printed: with triples grouped by subject, blank nodes
+    /// nested where possible, lists abbreviated and only used prefixes declared (sorted).
+    pub fn with_pretty(mut self) -> Self {
+        self.pretty = true;
+        self.prune_prefixes = true;
+        self
+    }
+
+    /// Declares only the prefixes used in the output data (instead of all prefixes collected from
+    /// the input).
+    pub fn with_pruned_prefixes(mut self) -> Self {
+        self.prune_prefixes = true;
+        self
+    }
+
+    /// Writes only the given named graph (as triples) when serializing the resulting data. (By
+    /// default, the default graph is written as triples unless empty, and otherwise the named
+    /// graphs merged, in formats that cannot serialize datasets.)
+    pub fn with_output_graph(mut self, graph_iri: &str) -> Result<Self> {
+        let graph_name =
+            NamedNode::new(graph_iri).with_context(|| format!("Invalid graph IRI: {graph_iri}"))?;
+        self.output_graph = Some(graph_name);
+        Ok(self)
+    }
+
+    /// Writes the triples of all graphs (the default graph and the named graphs) merged, without
+    /// duplicates, as triples when serializing the resulting data (also in dataset formats).
+    pub fn with_merged_graphs(mut self) -> Self {
+        self.merge_graphs = true;
+        self
+    }
+
+    /// Keeps blank node labels as given when loading data, so that blank nodes with the same label
+    /// in different files (or inputs) are the same node (rather than these being scoped to each).
+    pub fn with_shared_blank_nodes(mut self) -> Self {
+        self.shared_blank_nodes = true;
+        self
+    }
+
+    /// Caches responses when fetching data over HTTP(S) (from URLs given as files, by following
+    /// links, or of imported ontologies) in the given directory, revalidating them when reused.
+    pub fn with_http_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.http_cache = Some(dir.into());
+        self
+    }
+
+    /// Writes CSV and TSV results in the given dialect (of delimiter, quoting, header row and line
+    /// terminator) instead of the standard one.
+    pub fn with_csv_dialect(mut self, dialect: CsvDialect) -> Self {
+        self.csv_dialect = Some(dialect);
+        self
+    }
+
+    /// Writes query results rendered through a template (in a subset of the Handlebars syntax,
+    /// once per solution or, with an `{{#each}}` block, once for all of them) instead of in a
+    /// results format. (Graph results of `CONSTRUCT` and `DESCRIBE` then fail with an error.)
+    pub fn with_results_template(mut self, template: &str) -> Result<Self> {
+        self.results_template = Some(render::Template::parse(template)?);
+        Ok(self)
+    }
+
+    /// Writes `SELECT` results as an aligned table when no output format is given (instead of as
+    /// TSV), for reading on a terminal.
+    pub fn with_table(mut self) -> Self {
+        self.table = true;
+        self
+    }
+
+    /// Uses color in output for terminals (for `SELECT` results as a table, and syntax
+    /// highlighting of Turtle and TriG).
+    pub fn with_color(mut self) -> Self {
+        self.color = true;
+        self
+    }
+
+    /// Makes the string literals of the data searchable by keywords in queries, with the
+    /// `oxrq:matches(TEXT, KEYWORDS)` and (for ranking, by BM25) `oxrq:score(TEXT, KEYWORDS)`
+    /// functions. (The index is built when first searched in a query.)
+    pub fn with_text_search(mut self) -> Self {
+        self.text_search = true;
+        self
+    }
+
+    /// Loads a WebAssembly module and makes its exported functions available in queries (as
+    /// `oxrq:NAME`). See the `wasm` module for how arguments and results are passed.
+    pub fn with_plugin(mut self, path: &Path) -> Result<Self> {
+        let plugin = Arc::new(wasm::Plugin::load(path)?);
+        for name in plugin.functions() {
+            let iri = format!("{}{name}", search::NAMESPACE);
+            let (plugin, name) = (plugin.clone(), name.to_owned());
+            self.register_function(&iri, move |args| plugin.call(&name, args))
+                .with_context(|| format!("Invalid function name in {}", path.display()))?;
+        }
+        Ok(self)
+    }
+
+    /// Registers a custom SPARQL function, called by its IRI in queries with the values of its
+    /// arguments (and giving no value, i.e. an error, for unsupported ones). A function registered
+    /// later under the same IRI replaces an earlier one.
+    ///
+    /// ```
+    /// use oxigraph::model::{Literal, Term};
+    /// use oxrq::Runner;
+    ///
+    /// let mut runner = Runner::new()?;
+    /// runner.register_function("http://example.org/fn#double", |args| {
+    ///     let [Term::Literal(value)] = args else {
+    ///         return None;
+    ///     };
+    ///     Some(Literal::from(2 * value.value().parse::<i64>().ok()?).into())
+    /// })?;
+    /// let query = runner.prepare_query("select (<http://example.org/fn#double>(21) as ?n) {}");
+    /// let mut out = Vec::new();
+    /// runner.run(&query, Some("tsv"), &mut out)?;
+    /// assert_eq!(String::from_utf8(out)?, "?n\n42\n");
+    /// # anyhow::Ok(())
+    /// ```
+    pub fn register_function(
+        &mut self,
+        iri: &str,
+        function: impl Fn(&[Term]) -> Option<Term> + Send + Sync + 'static,
+    ) -> Result<()> {
+        let name = NamedNode::new(iri).with_context(|| format!("Invalid function IRI: {iri}"))?;
+        self.functions.retain(|(other, _)| *other != name);
+        self.functions.push((name, Arc::new(function)));
+        Ok(())
+    }
+
+    /// Registers a custom SPARQL aggregate function, called by its IRI in queries (like e.g.
+    /// `SUM`). For each group of solutions, an accumulator is made (by the given function), fed
+    /// the values of the expression and then asked for the result.
+    pub fn register_aggregate(
+        &mut self,
+        iri: &str,
+        accumulator: impl Fn() -> Box<dyn AggregateFunctionAccumulator + Send + Sync>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Result<()> {
+        let name = NamedNode::new(iri).with_context(|| format!("Invalid aggregate IRI: {iri}"))?;
+        self.aggregates.retain(|(other, _)| *other != name);
+        self.aggregates.push((name, Arc::new(accumulator)));
+        Ok(())
+    }
+
+    /// Writes output data in a stable order (sorted by graph, subject, predicate and object, with
+    /// canonical blank node labels), so that the same data always gives the same output.
+    pub fn with_sort(mut self) -> Self {
+        self.sort = true;
+        self
+    }
+
+    /// Reports progress (as a count of quads, on stderr) while loading and serializing data.
+    pub fn with_progress(mut self) -> Self {
+        self.progress = true;
+        self
+    }
+
+    /// Sets the base IRI used when parsing (unless one is already set).
+    pub fn with_base_iri(mut self, base_iri: impl Into<String>) -> Self {
+        self.base_iri.get_or_insert(base_iri.into());
+        self
+    }
+
+    /// Sets a JSON-LD frame used to shape graph output (implying JSON-LD output).
+    pub fn with_frame(mut self, frame: serde_json::Value) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    /// Sets how CSV input is converted to RDF: by CSVW metadata (of a table, or the first table of
+    /// a table group), or by a minimal mapping of column names to properties (as a JSON object
+    /// shaped like a JSON-LD context, with `@id` as template for the IRIs of the rows and `@type`
+    /// as their class). Prefixed names are expanded by the prefixes known so far.
+    pub fn with_csv_mapping(mut self, mapping: &serde_json::Value) -> Result<Self> {
+        self.mappings.csv = csvw::Mapping::from_json(mapping, &self.query_prefixes())?;
+        Ok(self)
+    }
+
+    /// Sets how SQLite databases are converted to RDF: by an R2RML mapping (in Turtle) of their
+    /// tables, instead of by the W3C Direct Mapping.
+    pub fn with_r2rml<R: Read>(mut self, reader: R, base_iri: &str) -> Result<Self> {
+        self.mappings.r2rml = Some(r2rml::Mapping::parse(reader, base_iri)?);
+        Ok(self)
+    }
+
+    /// Sets how JSON input is converted to RDF: by an RML mapping (in Turtle) of values selected
+    /// by JSONPath, instead of being read as JSON-LD. Triples maps with an `rml:source` of the
+    /// same file name as the input are used (or all of them if none is).
+    pub fn with_rml<R: Read>(mut self, reader: R, base_iri: &str) -> Result<Self> {
+        self.mappings.rml = Some(r2rml::Mapping::parse(reader, base_iri)?);
+        Ok(self)
+    }
+
+    /// Sets how XML input is converted to RDF: by an RML mapping (in Turtle) of nodes selected
+    /// by XPath (as records, each made into a subject with properties of relative paths),
+    /// instead of being read as RDF/XML. Triples maps are picked by `rml:source` as for JSON.
+    pub fn with_xml_mapping<R: Read>(mut self, reader: R, base_iri: &str) -> Result<Self> {
+        self.mappings.xml = Some(r2rml::Mapping::parse(reader, base_iri)?);
+        Ok(self)
+    }
+
+    /// Enables SERVICE calls to remote SPARQL endpoints (over HTTP) in queries.
+    pub fn with_federation(mut self) -> Self {
+        self.federation = true;
+        self
+    }
+
+    /// Makes the data loaded by another runner queryable as a virtual endpoint, by SERVICE calls
+    /// to `urn:oxrq:NAME` in queries (over the union of its graphs), keeping it apart from the
+    /// data of this runner.
+    pub fn with_service(mut self, name: &str, other: Runner) -> Result<Self> {
+        let iri = format!("{SERVICE_NAMESPACE}{name}");
+        let node = NamedNode::new(&iri).with_context(|| format!("Invalid service name: {name}"))?;
+        self.services.retain(|(other, _)| *other != node);
+        self.services.push((node, other.store));
+        Ok(self)
+    }
+
+    /// Queries the default graph as is, instead of as the union of all graphs (which is the
+    /// default, to make data loaded into named graphs queryable without `GRAPH` patterns).
+    pub fn without_default_graph_as_union(mut self) -> Self {
+        self.default_graph_as_union = false;
+        self
+    }
+
+    /// Leaves out the built-in well-known prefixes (such as `rdf:`, `xsd:` and `schema:`), which
+    /// are otherwise available in queries and used in output unless declared otherwise.
+    pub fn without_default_prefixes(mut self) -> Self {
+        self.default_prefixes = false;
+        self
+    }
+
+    /// Sets a remote SPARQL endpoint to query, instead of (or, if any data is loaded, along with)
+    /// the loaded data.
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Binds a query variable to a value (given as an IRI in angle brackets, a prefixed name or a
+    /// literal in N-Triples syntax; other values are used as integers or plain strings).
+    ///
+    /// Values are resolved against the prefixes and base IRI of the loaded data when queries are
+    /// run, and bound within the `WHERE` clauses of queries and updates alike.
+    pub fn with_binding(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.bindings.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets a value for `{{name}}` placeholders in queries (which are otherwise filled from
+    /// environment variables of the same name).
+    ///
+    /// Values are escaped as IRIs within angle brackets (e.g. `<{{id}}>`), as strings within
+    /// quotes, and are otherwise filled in as string literals.
+    pub fn with_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.insert(name.into(), value.into());
+        self
+    }
+
+    /// Replaces blank nodes in the output with IRIs made from the given base IRI (such as
+    /// `urn:bnode:`) and the canonical labels of the blank nodes (which are the same across runs
+    /// over the same data).
+    pub fn with_skolemization(mut self, base: impl Into<String>) -> Self {
+        self.skolem_base = Some(base.into());
+        self
+    }
+
+    /// Writes just the number of results (solutions, or triples for graph results and data)
+    /// instead of serializing them.
+    pub fn with_count(mut self) -> Self {
+        self.count = true;
+        self
+    }
+
+    /// Limits the number of solutions (or triples) of the query results written (as a `LIMIT`
+    /// applied on top of the query).
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skips solutions (or triples) of the query results written (as an `OFFSET` applied on top
+    /// of the query).
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Aborts queries and updates (with an error) once the given token is cancelled (e.g. from
+    /// another thread after a timeout).
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Counts and timings collected so far.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// The result of the last `ASK` query run (if the last query run was one).
+    pub fn ask_result(&self) -> Option<bool> {
+        self.ask_result
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn base_iri(&self) -> Option<&str> {
+        self.base_iri.as_deref()
+    }
+
+    pub fn prefixes(&self) -> &HashMap<String, String> {
+        &self.prefixes
+    }
+
+    /// Loads a file into a named graph named by the file IRI (format detected by suffix).
+    ///
+    /// HTTP(S) URLs are fetched, into a named graph named by the URL (format detected by the
+    /// response media type).
+    pub fn load_file(&mut self, fpath: &str) -> Result<()> {
+        if is_url(fpath) {
+            let (format, reader) = open_url(fpath, self.http_cache.as_deref())?;
+            return self.load_graph(reader, format, fpath);
+        }
+        let (format, reader) = open_file(fpath)?;
+        self.load_graph(reader, format, &file_graph_iri(fpath))
+    }
+
+    /// Loads the ontologies imported (by `owl:imports`) by the loaded data, and those imported by
+    /// them in turn, each into a named graph named by its IRI. The files of ontologies are looked
+    /// up in the given XML catalog (if any), and otherwise fetched over HTTP(S). Returns the IRIs
+    /// of the loaded ontologies.
+    pub fn load_imports(&mut self, catalog: Option<&Path>) -> Result<Vec<String>> {
+        let catalog = match catalog {
+            Some(fpath) => {
+                let file = File::open(fpath)
+                    .with_context(|| format!("Unable to open catalog: {}", fpath.display()))?;
+                let dir = fpath.parent().unwrap_or(Path::new(""));
+                imports::read_catalog(BufReader::new(file), dir)
+                    .with_context(|| format!("Error in catalog '{}'", fpath.display()))?
+            }
+            None => HashMap::new(),
+        };
+        let mut tried = HashSet::new();
+        let mut loaded = Vec::new();
+        loop {
+            let pending = imports::pending(&self.store, &tried)?;
+            if pending.is_empty() {
+                break;
+            }
+            for iri in pending {
+                let iri_str = iri.as_str();
+                let result = match catalog.get(iri_str) {
+                    Some(fpath) => open_file(&fpath.to_string_lossy())
+                        .and_then(|(format, reader)| self.load_graph(reader, format, iri_str)),
+                    None if is_url(iri_str) => open_url(iri_str, self.http_cache.as_deref())
+                        .and_then(|(format, reader)| self.load_graph(reader, format, iri_str)),
+                    None => Err(anyhow::anyhow!("No file of it in a catalog")),
+                };
+                result.with_context(|| format!("Unable to load imported ontology: {iri}"))?;
+                loaded.push(iri.as_str().to_owned());
+                tried.insert(iri);
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Follows links in the loaded data: fetches the documents of the HTTP(S) IRIs used as objects
+    /// (by any of the given predicates, as for bindings, or by any predicate if none are given),
+    /// each into a named graph named by its URL, and then those linked to from these in turn, up
+    /// to the given number of hops. Returns each URL fetched, with the number of quads loaded from
+    /// it (or the error fetching or parsing it).
+    pub fn follow_links(
+        &mut self,
+        hops: usize,
+        predicates: &[String],
+    ) -> Result<Vec<(String, Result<usize>)>> {
+        let predicates = predicates
+            .iter()
+            .map(|value| match value.as_str() {
+                "a" => Ok(rdf::TYPE.into_owned()),
+                value => self.parse_iri_value(value),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut fetched = Vec::new();
+        let mut tried = HashSet::new();
+        let mut graphs: Option<Vec<NamedNode>> = None;
+        for _ in 0..hops {
+            let mut links = follow::links(&self.store, graphs.as_deref(), &predicates)?;
+            // (Not retrying failed URLs.)
+            links.retain(|url| tried.insert(url.clone()));
+            if links.is_empty() {
+                break;
+            }
+            let mut loaded = Vec::new();
+            for url in links {
+                let loaded_quads = self.stats.loaded_quads;
+                let result = open_url(&url, self.http_cache.as_deref())
+                    .and_then(|(format, reader)| self.load_graph(reader, format, &url))
+                    .map(|()| self.stats.loaded_quads - loaded_quads);
+                if result.is_ok() {
+                    loaded.push(NamedNode::new(&url)?);
+                }
+                fetched.push((url, result));
+            }
+            graphs = Some(loaded);
+        }
+        Ok(fetched)
+    }
+
+    /// Loads data into the given named graph (also used as base IRI unless one is set).
+    pub fn load_graph<R: Read>(
+        &mut self,
+        reader: R,
+        format: InputFormat,
+        graph_iri: &str,
+    ) -> Result<()> {
+        self.load_graph_with_base(reader, format, Some(graph_iri), graph_iri)
+    }
+
+    /// Loads data into the given named graph (or the default graph if none is given), using the
+    /// given base IRI (unless one is set).
+    ///
+    /// HTML is read as RDFa and microdata, along with any embedded JSON-LD.
+    pub fn load_graph_with_base<R: Read>(
+        &mut self,
+        reader: R,
+        format: InputFormat,
+        graph_iri: Option<&str>,
+        base_iri: &str,
+    ) -> Result<()> {
+        let graph_name = match graph_iri {
+            Some(graph_iri) => NamedNode::new(graph_iri)?.into(),
+            None => GraphName::DefaultGraph,
+        };
+        let base_iri = self.base_iri.clone().unwrap_or_else(|| base_iri.to_owned());
+        let base_iri = base_iri.as_str();
+
+        let format = match format {
+            InputFormat::Rdf(RdfFormat::N3) => {
+                return self.load_n3(reader, Some(base_iri), graph_name);
+            }
+            InputFormat::Rdf(RdfFormat::JsonLd { .. }) if self.mappings.rml.is_some() => {
+                return self.load_json(reader, Some(base_iri), graph_name);
+            }
+            InputFormat::Rdf(RdfFormat::RdfXml) if self.mappings.xml.is_some() => {
+                return self.load_xml(reader, Some(base_iri), graph_name);
+            }
+            InputFormat::Rdf(format) => format,
+            InputFormat::Html => {
+                return self.load_html(reader, Some(base_iri), graph_name);
+            }
+            InputFormat::RdfJson => return self.load_rdf_json(reader, graph_name),
+            InputFormat::Csv => return self.load_csv(reader, Some(base_iri), graph_name),
+            InputFormat::Sqlite => return self.load_sqlite(reader, Some(base_iri), graph_name),
+            InputFormat::Hdt => return self.load_hdt(reader, graph_name),
+        };
+        let parser = RdfParser::from_format(format)
+            .with_default_graph(graph_name.clone())
+            .with_base_iri(base_iri)?;
+
+        self.load_data(parser, &graph_name, reader, Some(base_iri))
+    }
+
+    /// Loads files (as [`load_graph_with_base`](Self::load_graph_with_base), each into the given
+    /// named graph or the default graph, with the file IRI as base IRI), parsing them in
+    /// parallel (on a thread per available core). The data is added in the order given (so that
+    /// prefixes are collected as when loading one file at a time), a batch of quads at a time as
+    /// it is parsed (so that at most a batch per thread is held in memory apart from the store).
+    ///
+    /// Fails if any file cannot be opened, otherwise returns the format and number of quads of
+    /// each loaded file (or the error parsing it).
+    pub fn load_files(
+        &mut self,
+        files: &[(String, Option<String>)],
+    ) -> Result<Vec<Result<(InputFormat, usize)>>> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if files.len() < 2 || threads < 2 {
+            let mut results = Vec::with_capacity(files.len());
+            for (fpath, graph_iri) in files {
+                let (format, reader) = open_file(fpath)?;
+                let loaded_quads = self.stats.loaded_quads;
+                let result = self.load_graph_with_base(
+                    reader,
+                    format,
+                    graph_iri.as_deref(),
+                    &file_graph_iri(fpath),
+                );
+                results.push(result.map(|_| (format, self.stats.loaded_quads - loaded_quads)));
+            }
+            return Ok(results);
+        }
+
+        let next = AtomicUsize::new(0);
+        let base_iri = self.base_iri.clone();
+        let mappings = self.mappings.clone();
+        let shared_blank_nodes = self.shared_blank_nodes;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::scope(|scope| {
+            for _ in 0..threads.min(files.len()) {
+                let (next, base_iri, sender) = (&next, &base_iri, sender.clone());
+                let mappings = &mappings;
+                scope.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((fpath, graph_iri)) = files.get(i) else {
+                        break;
+                    };
+                    // (A batch at a time, for each file to be added as soon as it is parsed.)
+                    let (parts, received) = mpsc::sync_channel(1);
+                    let opened = open_file(fpath).and_then(|(format, reader)| {
+                        let graph_name = match graph_iri {
+                            Some(graph_iri) => NamedNode::new(graph_iri)?.into(),
+                            None => GraphName::DefaultGraph,
+                        };
+                        Ok((format, graph_name, reader))
+                    });
+                    let (format, graph_name, reader) = match opened {
+                        Ok(opened) => opened,
+                        Err(err) => match sender.send((i, Err(err))) {
+                            Ok(()) => continue,
+                            Err(_) => break,
+                        },
+                    };
+                    if sender
+                        .send((i, Ok((format, graph_name.clone(), received))))
+                        .is_err()
+                    {
+                        break;
+                    }
+                    let file_iri = file_graph_iri(fpath);
+                    let base_iri = base_iri.as_deref().unwrap_or(&file_iri);
+                    parse_file(
+                        reader,
+                        format,
+                        &graph_name,
+                        base_iri,
+                        mappings,
+                        shared_blank_nodes,
+                        &parts,
+                    );
+                });
+            }
+            drop(sender);
+
+            // Add the data of each file in order, as it is parsed:
+            let mut opened_files = HashMap::new();
+            let mut results = Vec::with_capacity(files.len());
+            for (i, opened) in receiver {
+                opened_files.insert(i, opened);
+                while let Some(opened) = opened_files.remove(&results.len()) {
+                    let (format, graph_name, parts) = opened?;
+                    let given_base_iri = match &base_iri {
+                        Some(base_iri) => base_iri.clone(),
+                        None => file_graph_iri(&files[results.len()].0),
+                    };
+                    let loaded_quads = self.stats.loaded_quads;
+                    let result = self.add_received(parts, &graph_name, &given_base_iri);
+                    results.push(result.map(|_| (format, self.stats.loaded_quads - loaded_quads)));
+                }
+            }
+            Ok(results)
+        })
+    }
+
+    /// Loads data into the default graph (detecting the format by content unless an input format
+    /// is given).
+    pub fn load_reader<R: Read>(&mut self, reader: R, input_format: Option<&str>) -> Result<()> {
+        let (format, reader) = if let Some(fmt) = input_format {
+            let format = InputFormat::from_extension(fmt)
+                .with_context(|| format!("Unknown input format: {fmt}"))?;
+            (format, compression::decompress(reader)?)
+        } else {
+            sniff_format(reader)?
+        };
+
+        let format = match format {
+            InputFormat::Rdf(RdfFormat::N3) => {
+                let base_iri = self.base_iri.clone();
+                return self.load_n3(reader, base_iri.as_deref(), GraphName::DefaultGraph);
+            }
+            InputFormat::Rdf(RdfFormat::JsonLd { .. }) if self.mappings.rml.is_some() => {
+                let base_iri = self.base_iri.clone();
+                return self.load_json(reader, base_iri.as_deref(), GraphName::DefaultGraph);
+            }
+            InputFormat::Rdf(RdfFormat::RdfXml) if self.mappings.xml.is_some() => {
+                let base_iri = self.base_iri.clone();
+                return self.load_xml(reader, base_iri.as_deref(), GraphName::DefaultGraph);
+            }
+            InputFormat::Rdf(format) => format,
+            InputFormat::Html => {
+                let base_iri = self.base_iri.clone();
+                return self.load_html(reader, base_iri.as_deref(), GraphName::DefaultGraph);
+            }
+            InputFormat::RdfJson => return self.load_rdf_json(reader, GraphName::DefaultGraph),
+            InputFormat::Csv => {
+                let base_iri = self.base_iri.clone();
+                return self.load_csv(reader, base_iri.as_deref(), GraphName::DefaultGraph);
+            }
+            InputFormat::Sqlite => {
+                let base_iri = self.base_iri.clone();
+                return self.load_sqlite(reader, base_iri.as_deref(), GraphName::DefaultGraph);
+            }
+            InputFormat::Hdt => return self.load_hdt(reader, GraphName::DefaultGraph),
+        };
+
+        let mut parser = RdfParser::from_format(format);
+        let base_iri = self.base_iri.clone();
+        if let Some(value) = &base_iri {
+            parser = parser.with_base_iri(value.to_owned())?;
+        }
+
+        self.load_data(
+            parser,
+            &GraphName::DefaultGraph,
+            reader,
+            base_iri.as_deref(),
+        )
+    }
+
+    fn load_data<R: Read>(
+        &mut self,
+        parser: RdfParser,
+        graph_name: &GraphName,
+        reader: R,
+        given_base_iri: Option<&str>,
+    ) -> Result<()> {
+        let parser = match self.shared_blank_nodes {
+            true => parser,
+            false => parser.rename_blank_nodes(),
+        };
+        let mut parser_reader = parser
+            .for_reader(BufReader::new(reader))
+            .with_document_loader(load_document);
+        let quads = parser_reader.by_ref().map(|quad| Ok(quad?));
+        let with_other_graphs = self.add_quads(quads, graph_name)?;
+
+        // (Available once all quads are parsed:)
+        let (base_iri, prefixes) = (parser_reader.base_iri(), parser_reader.prefixes());
+        self.add_namespaces(
+            graph_name,
+            with_other_graphs,
+            base_iri,
+            given_base_iri,
+            prefixes,
+        )
+    }
+
+    fn load_html<R: Read>(
+        &mut self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: GraphName,
+    ) -> Result<()> {
+        for data in parse_html(reader, base_iri, &graph_name)? {
+            self.add_parsed(data, &graph_name, base_iri)?;
+        }
+        Ok(())
+    }
+
+    fn load_n3<R: Read>(
+        &mut self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: GraphName,
+    ) -> Result<()> {
+        let data = n3::parse(BufReader::new(reader), base_iri, &graph_name)?;
+        self.add_parsed(data, &graph_name, base_iri)
+    }
+
+    fn load_rdf_json<R: Read>(&mut self, reader: R, graph_name: GraphName) -> Result<()> {
+        let quads = rdfjson::parse(reader, &graph_name)?;
+        self.add_data(quads, &graph_name, None, None, [])
+    }
+
+    fn load_csv<R: Read>(
+        &mut self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: GraphName,
+    ) -> Result<()> {
+        let quads = self.mappings.csv.convert(reader, base_iri, &graph_name)?;
+        self.add_data(quads, &graph_name, None, None, [])
+    }
+
+    fn load_json<R: Read>(
+        &mut self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: GraphName,
+    ) -> Result<()> {
+        let quads = self.mappings.convert_json(reader, base_iri, &graph_name)?;
+        self.add_data(quads, &graph_name, None, None, [])
+    }
+
+    fn load_xml<R: Read>(
+        &mut self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: GraphName,
+    ) -> Result<()> {
+        let quads = self.mappings.convert_xml(reader, base_iri, &graph_name)?;
+        self.add_data(quads, &graph_name, None, None, [])
+    }
+
+    fn load_sqlite<R: Read>(
+        &mut self,
+        reader: R,
+        base_iri: Option<&str>,
+        graph_name: GraphName,
+    ) -> Result<()> {
+        let quads = self
+            .mappings
+            .convert_sqlite(reader, base_iri, &graph_name)?;
+        self.add_data(quads, &graph_name, None, None, [])
+    }
+
+    fn load_hdt<R: Read>(&mut self, reader: R, graph_name: GraphName) -> Result<()> {
+        let quads = hdt::parse(BufReader::new(reader), &graph_name)?;
+        self.add_data(quads, &graph_name, None, None, [])
+    }
+
+    fn add_parsed(
+        &mut self,
+        data: ParsedData,
+        graph_name: &GraphName,
+        given_base_iri: Option<&str>,
+    ) -> Result<()> {
+        let prefixes = data
+            .prefixes
+            .iter()
+            .map(|(pfx, ns)| (pfx.as_str(), ns.as_str()));
+        let base_iri = data.base_iri.as_deref();
+        self.add_data(data.quads, graph_name, base_iri, given_base_iri, prefixes)
+    }
+
+    /// Adds the data of a file parsed in another thread (with the given base IRI), as it is
+    /// received.
+    fn add_received(
+        &mut self,
+        parts: Receiver<Parsed>,
+        graph_name: &GraphName,
+        given_base_iri: &str,
+    ) -> Result<()> {
+        let mut parts = parts.into_iter().peekable();
+        while parts.peek().is_some() {
+            let mut end = None;
+            let quads = parts
+                .by_ref()
+                .map_while(|part| match part {
+                    Parsed::Quads(quads) => Some(Ok(quads)),
+                    Parsed::Error(err) => Some(Err(err)),
+                    Parsed::End(base_iri, prefixes) => {
+                        end = Some((base_iri, prefixes));
+                        None
+                    }
+                })
+                .flat_map(|batch| {
+                    let (quads, err) = match batch {
+                        Ok(quads) => (quads, None),
+                        Err(err) => (Vec::new(), Some(Err(err))),
+                    };
+                    quads.into_iter().map(Ok).chain(err)
+                });
+            let with_other_graphs = self.add_quads(quads, graph_name)?;
+            if let Some((base_iri, prefixes)) = end {
+                let prefixes = prefixes.iter().map(|(pfx, ns)| (pfx.as_str(), ns.as_str()));
+                self.add_namespaces(
+                    graph_name,
+                    with_other_graphs,
+                    base_iri.as_deref(),
+                    Some(given_base_iri),
+                    prefixes,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds parsed quads to the store, and collects the prefixes and base IRI found (both in
+    /// total and for the graph loaded into).
+    fn add_data<'a>(
+        &mut self,
+        quads: Vec<Quad>,
+        graph_name: &GraphName,
+        base_iri: Option<&str>,
+        given_base_iri: Option<&str>,
+        prefixes: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<()> {
+        let with_other_graphs = self.add_quads(quads.into_iter().map(Ok), graph_name)?;
+        self.add_namespaces(
+            graph_name,
+            with_other_graphs,
+            base_iri,
+            given_base_iri,
+            prefixes,
+        )
+    }
+
+    /// Adds quads to the store as they are parsed (in batches, to not hold them all in memory at
+    /// once). Returns whether any quads are in other graphs than the one loaded into. On an error
+    /// (such as invalid syntax late in a file), the quads already added are removed again, except
+    /// from graphs which already had data (such as the default graph with `-d`), where the batches
+    /// added before the error are kept.
+    fn add_quads(
+        &mut self,
+        quads: impl Iterator<Item = Result<Quad>>,
+        graph_name: &GraphName,
+    ) -> Result<bool> {
+        let mut added = Added::default();
+        let loaded_quads = self.stats.loaded_quads;
+        self.add_quad_batches(quads, graph_name, &mut added)
+            .or_else(|err| {
+                self.stats.loaded_quads = loaded_quads;
+                if added.remove_from(&self.store)? {
+                    eprintln!("Quads added before the error to graphs with other data are kept");
+                }
+                Err(err)
+            })
+    }
+
+    fn add_quad_batches(
+        &mut self,
+        quads: impl Iterator<Item = Result<Quad>>,
+        graph_name: &GraphName,
+        added: &mut Added,
+    ) -> Result<bool> {
+        let mut with_other_graphs = false;
+        let mut progress = self.progress.then(|| Progress::new("Loading", None));
+        let mut quads = quads
+            .inspect(|_| {
+                if let Some(progress) = &mut progress {
+                    progress.inc();
+                }
+            })
+            .peekable();
+        while quads.peek().is_some() {
+            let batch = quads
+                .by_ref()
+                .take(LOAD_BATCH_SIZE)
+                .collect::<Result<Vec<_>>>()?;
+            with_other_graphs |= batch.iter().any(|quad| &quad.graph_name != graph_name);
+            added.note(&self.store, &batch)?;
+            self.check_memory(&batch)?;
+            let start = Instant::now();
+            self.stats.loaded_quads += batch.len();
+            let mut loader = self.store.bulk_loader();
+            loader.load_quads(batch)?;
+            loader.commit()?;
+            self.stats.load_time += start.elapsed();
+        }
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+        Ok(with_other_graphs)
+    }
+
+    /// Collects the prefixes and base IRI found in loaded data (both in total and for the graph
+    /// loaded into). The base IRI of the graph is only kept if declared in the data (unlike the
+    /// one given to parse it with), to not add one when writing it back.
+    fn add_namespaces<'a>(
+        &mut self,
+        graph_name: &GraphName,
+        with_other_graphs: bool,
+        base_iri: Option<&str>,
+        given_base_iri: Option<&str>,
+        prefixes: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<()> {
+        let namespaces = self.graph_namespaces.entry(graph_name.clone()).or_default();
+        namespaces.with_other_graphs |= with_other_graphs;
+
+        if let Some(value) = base_iri {
+            self.base_iri.get_or_insert(value.to_owned());
+            if base_iri != given_base_iri {
+                namespaces.base_iri.get_or_insert(value.to_owned());
+            }
+        }
+
+        for (pfx, ns) in prefixes {
+            if !self.prefixes.contains_key(pfx) {
+                self.prefixes.insert(pfx.to_owned(), ns.to_owned());
+            }
+            if !namespaces.prefixes.contains_key(pfx) {
+                namespaces.prefixes.insert(pfx.to_owned(), ns.to_owned());
+            }
+        }
+
+        if let Some(path) = &self.store_path {
+            self.save_prefixes(&path.join(STORE_PREFIXES_FILE))?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the estimated memory used by quads about to be loaded to the total, and moves the
+    /// data to disk if that exceeds the memory cap.
+    fn check_memory(&mut self, quads: &[Quad]) -> Result<()> {
+        let Some(max_memory) = self.max_memory else {
+            return Ok(());
+        };
+        self.loaded_size += quads
+            .iter()
+            .map(|quad| quad.to_string().len() + QUAD_MEMORY_OVERHEAD)
+            .sum::<usize>();
+        if self.loaded_size > max_memory {
+            self.max_memory = None;
+            self.spill_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the store with a new one holding the given quads (as they are read from the old
+    /// one), kept on disk if the data has been moved there.
+    fn replace_store(
+        &mut self,
+        quads: impl IntoIterator<Item = Result<Quad, StorageError>>,
+    ) -> Result<()> {
+        #[cfg(feature = "rocksdb")]
+        let dir = self
+            .spill_dir
+            .as_ref()
+            .map(|_| TempDir::new())
+            .transpose()?;
+        #[cfg(feature = "rocksdb")]
+        let store = match &dir {
+            Some(dir) => dir.open_store()?,
+            None => Store::new()?,
+        };
+        #[cfg(not(feature = "rocksdb"))]
+        let store = Store::new()?;
+        let mut loader = store.bulk_loader();
+        loader.load_ok_quads::<_, anyhow::Error>(quads)?;
+        loader.commit()?;
+        self.store = store;
+        #[cfg(feature = "rocksdb")]
+        {
+            self.spill_dir = dir;
+        }
+        Ok(())
+    }
+
+    /// Replaces the in-memory store with a temporary on-disk store holding the same data.
+    #[cfg(feature = "rocksdb")]
+    fn spill_to_disk(&mut self) -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = dir.open_store()?;
+        let mut loader = store.bulk_loader();
+        loader.load_ok_quads::<_, anyhow::Error>(self.store.iter())?;
+        loader.commit()?;
+        self.store = store;
+        self.spill_dir = Some(dir);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "rocksdb"))]
+    fn spill_to_disk(&mut self) -> Result<()> {
+        bail!("Spilling to disk requires oxrq to be built with the 'rocksdb' feature")
+    }
+
+    /// Writes the collected prefixes and base IRI as an (otherwise empty) Turtle file.
+    fn save_prefixes(&self, fpath: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(fpath)?);
+        if let Some(value) = &self.base_iri {
+            writeln!(writer, "@base <{value}> .")?;
+        }
+        let mut prefixes = self.prefixes.iter().collect::<Vec<_>>();
+        prefixes.sort();
+        for (pfx, ns) in prefixes {
+            writeln!(writer, "@prefix {pfx}: <{ns}> .")?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Materializes the triples entailed by the loaded data (in all graphs) under the inference
+    /// profile, adding those not already present to the default graph. Returns the number of
+    /// triples added.
+    pub fn materialize(&mut self, profile: InferenceProfile) -> Result<usize> {
+        let mut graph = self
+            .store
+            .iter()
+            .map(|quad| quad.map(Triple::from))
+            .collect::<Result<Graph, _>>()?;
+        let inferred = inference::materialize(&mut graph, profile);
+
+        let mut loader = self.store.bulk_loader();
+        loader.load_quads(
+            inferred
+                .iter()
+                .map(|triple| triple.clone().in_graph(GraphName::DefaultGraph)),
+        )?;
+        loader.commit()?;
+
+        Ok(inferred.len())
+    }
+
+    /// Applies the rules of the files to the loaded data (in all graphs) until no new triples
+    /// follow, adding those derived to the default graph. Returns the number of triples added.
+    ///
+    /// Rules are read from N3 (`{ ... } => { ... }`, with any other statements of the file added
+    /// to the data), or from SPARQL files (`.rq`) as a CONSTRUCT query each.
+    pub fn apply_rules(&mut self, files: &[PathBuf]) -> Result<usize> {
+        let mut rules = Vec::new();
+        for file in files {
+            let fpath = file.to_string_lossy();
+            let file =
+                File::open(file).with_context(|| format!("Unable to open rules file: {fpath}"))?;
+            let mut reader = compression::decompress(file)?;
+            let context = || format!("Error in rules file '{fpath}'");
+            if format_suffix(&fpath) == Some("rq") {
+                let mut text = String::new();
+                reader.read_to_string(&mut text)?;
+                let query = self
+                    .parse_query_algebra(&self.prepare_query(&text))
+                    .with_context(context)?;
+                rules.push(rules::Rule::from_query(query).with_context(context)?);
+            } else {
+                let base_iri = file_graph_iri(&fpath);
+                let (parsed, data) =
+                    n3::parse_rules(reader, Some(&base_iri)).with_context(context)?;
+                rules.extend(parsed);
+                self.add_parsed(data, &GraphName::DefaultGraph, Some(&base_iri))?;
+            }
+        }
+        rules::apply(&self.store, &self.evaluator()?, &rules)
+    }
+
+    /// Applies a patch to the loaded data (as a whole, or not at all if it fails), with triples
+    /// applied to the given named graph (or the default graph if none is given). IRIs are
+    /// resolved against the given base IRI (unless one is set).
+    ///
+    /// LD Patch is limited to the `Add`, `AddNew`, `Delete` and `DeleteExisting` operations.
+    pub fn apply_patch<R: Read>(
+        &mut self,
+        mut reader: R,
+        format: PatchFormat,
+        graph_iri: Option<&str>,
+        base_iri: &str,
+    ) -> Result<()> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let graph_name = match graph_iri {
+            Some(graph_iri) => NamedNode::new(graph_iri)?.into(),
+            None => GraphName::DefaultGraph,
+        };
+        let base_iri = self.base_iri.as_deref().unwrap_or(base_iri);
+
+        let changes = match format {
+            PatchFormat::RdfPatch => patch::parse_rdf_patch(&text, &graph_name)?,
+            PatchFormat::LdPatch => patch::parse_ld_patch(&text, base_iri, &graph_name)?,
+        };
+        let mut transaction = self.store.start_transaction()?;
+        for change in &changes {
+            match change {
+                patch::Change::Add(quad) => transaction.insert(quad),
+                patch::Change::Delete(quad) => transaction.remove(quad),
+                patch::Change::AddNew(quad) => {
+                    if transaction.contains(quad)? {
+                        bail!("Triple to add already exists: {quad}");
+                    }
+                    transaction.insert(quad);
+                }
+                patch::Change::DeleteExisting(quad) => {
+                    if !transaction.contains(quad)? {
+                        bail!("Triple to delete does not exist: {quad}");
+                    }
+                    transaction.remove(quad);
+                }
+            }
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Prepends found prefixes (and any default prefixes not declared otherwise) to the query
+    /// body. A body which is neither a query nor an update, but a graph pattern (like
+    /// `?s a foaf:Person`), is wrapped in `SELECT * WHERE { ... }`. (So is a body starting like a
+    /// triple pattern which is then invalid, to give the error of it as a pattern.)
+    pub fn prepare_query(&self, query_body: &str) -> String {
+        let prefixes = self.prefix_declarations();
+        let query_str = format!("{prefixes}{query_body}");
+        if !self.is_query_or_update(&query_str) {
+            // (After any declarations of the body.)
+            let prologue =
+                Regex::new(r"(?i)^(\s+|#[^\n]*|prefix\s+[^\s:]*:\s*<[^>]*>|base\s*<[^>]*>)*")
+                    .expect("valid regex");
+            let (declarations, pattern) =
+                query_body.split_at(prologue.find(query_body).map_or(0, |m| m.end()));
+            let wrapped = format!("{prefixes}{declarations}SELECT * WHERE {{ {pattern}\n}}");
+            let triple_pattern = Regex::new(r"^\s*([?$<:]|[A-Za-z][\w.-]*:)").expect("valid regex");
+            if self.parse_query_algebra(&wrapped).is_ok() || triple_pattern.is_match(pattern) {
+                return wrapped;
+            }
+        }
+        query_str
+    }
+
+    /// Gives the declarations of the prefixes added to queries by [`Runner::prepare_query`].
+    fn prefix_declarations(&self) -> String {
+        let mut prefixes = String::new();
+        for (pfx, ns) in self.query_prefixes().iter() {
+            prefixes.push_str(&format!("PREFIX {pfx}: <{ns}>\n"));
+        }
+        prefixes
+    }
+
+    /// Gives the syntax error of a query or update, with the line of the error as in the query
+    /// before any prefixes were added by [`Runner::prepare_query`].
+    fn query_error(&self, query_str: &str, err: impl std::fmt::Display) -> anyhow::Error {
+        let message = err.to_string();
+        let added = self.prefix_declarations();
+        if added.is_empty() || !query_str.starts_with(&added) {
+            return anyhow::anyhow!(message);
+        }
+        let added_lines = added.lines().count();
+        let location = Regex::new(r"^error at (\d+):").expect("valid regex");
+        let message = location.replace(&message, |caps: &regex::Captures| {
+            let line = caps[1].parse::<usize>().unwrap_or(0);
+            format!("error at {}:", line.saturating_sub(added_lines))
+        });
+        anyhow::anyhow!(message.into_owned())
+    }
+
+    /// Looks up prefixes used in the queries but declared neither in them nor in the loaded data
+    /// (nor otherwise given) on prefix.cc, and adds those found (also to the queries, if prepared
+    /// by [`Runner::prepare_query`]). Prefixes are first looked up in the cache file (a JSON
+    /// object of prefixes and namespaces), if given, where those found are also kept.
+    pub fn lookup_prefixes(
+        &mut self,
+        query_strs: &mut [String],
+        cache_file: Option<&Path>,
+    ) -> Result<()> {
+        let known = self.query_prefixes();
+        let mut undeclared = BTreeSet::new();
+        for query_str in query_strs.iter() {
+            let prefixes = prefixes::undeclared_prefixes(query_str);
+            undeclared.extend(prefixes.into_iter().filter(|pfx| !known.contains_key(pfx)));
+        }
+        if undeclared.is_empty() {
+            return Ok(());
+        }
+
+        let mut cache: serde_json::Map<String, serde_json::Value> = match cache_file {
+            Some(fpath) if fpath.exists() => serde_json::from_str(&fs::read_to_string(fpath)?)
+                .with_context(|| format!("Invalid prefix cache: {}", fpath.display()))?,
+            _ => serde_json::Map::new(),
+        };
+        let added = self.prefix_declarations();
+        let mut cache_changed = false;
+        for pfx in undeclared {
+            if let Some(ns) = cache.get(&pfx).and_then(|ns| ns.as_str()) {
+                self.prefixes.insert(pfx, ns.to_owned());
+                continue;
+            }
+            match prefixes::lookup(&pfx) {
+                Ok(Some(ns)) => {
+                    cache.insert(pfx.clone(), ns.clone().into());
+                    cache_changed = true;
+                    self.prefixes.insert(pfx, ns);
+                }
+                Ok(None) => eprintln!("Prefix not found on prefix.cc: {pfx}"),
+                Err(e) => eprintln!("Unable to look up prefix {pfx}: {e:#}"),
+            }
+        }
+        if let Some(fpath) = cache_file.filter(|_| cache_changed) {
+            if let Some(dir) = fpath.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let json = serde_json::to_string_pretty(&serde_json::Value::Object(cache))?;
+            fs::write(fpath, json + "\n")
+                .with_context(|| format!("Unable to write prefix cache: {}", fpath.display()))?;
+        }
+        for query_str in query_strs {
+            if let Some(query_body) = query_str.strip_prefix(&added) {
+                *query_str = self.prepare_query(query_body);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a query or update (as given to [`Runner::run`]) without running it, failing with the
+    /// syntax error of the query if it is neither. The line of the error is given as in the
+    /// query before any prefixes were added by [`Runner::prepare_query`].
+    pub fn check_query(&self, query_str: &str) -> Result<()> {
+        let query_str = self.fill_template(query_str)?;
+        let Err(err) = self.evaluator()?.parse_query(&query_str) else {
+            return Ok(());
+        };
+        if self.evaluator()?.parse_update(&query_str).is_ok() {
+            return Ok(());
+        }
+        Err(self.query_error(&query_str, err))
+    }
+
+    /// Runs a query or update and writes the results, or the resulting data, to the writer.
+    ///
+    /// Results of `SELECT` and `ASK` are serialized directly. Results of `CONSTRUCT` and `DESCRIBE`
+    /// replace the loaded data, and updates modify it, before it is serialized.
+    pub fn run<W: Write>(
+        &mut self,
+        query_str: &str,
+        output_format: Option<&str>,
+        writer: W,
+    ) -> Result<()> {
+        if output_format == Some(diff::PATCH_FORMAT) {
+            return self.run_as_patch(&[query_str], writer);
+        }
+        let mut writer = BufWriter::new(writer);
+
+        if !self.execute(query_str, output_format, Some(&mut writer))? {
+            if self.count {
+                writeln!(writer, "{}", self.store.len()?)?;
+                writer.flush()?;
+                return Ok(());
+            }
+            if let Some(base) = &self.skolem_base {
+                let skolemizer = skolem::Skolemizer::new(base, &self.store)?;
+                let store = Store::new()?;
+                let mut loader = store.bulk_loader();
+                loader.load_quads(
+                    self.store
+                        .iter()
+                        .map(|quad| quad.map(|quad| skolemizer.quad(quad)))
+                        .collect::<Result<Vec<_>, _>>()?,
+                )?;
+                loader.commit()?;
+                self.store = store;
+            }
+            let start = Instant::now();
+            self.serialize(output_format, &mut writer)?;
+            writer.flush()?;
+            self.stats.serialize_time += start.elapsed();
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Runs queries or updates in sequence, each over the data resulting from the previous one,
+    /// and writes the results of the last one (as [`Runner::run`] does).
+    ///
+    /// All but the last query must be `CONSTRUCT` or `DESCRIBE` queries or updates. (If no query
+    /// is given, the loaded data is written.)
+    ///
+    /// With the `rdp` output format, all queries must be of this kind, and the changes made to the
+    /// data by them are written as an RDF Patch (instead of the resulting data).
+    pub fn run_pipeline<W: Write>(
+        &mut self,
+        query_strs: &[String],
+        output_format: Option<&str>,
+        writer: W,
+    ) -> Result<()> {
+        if output_format == Some(diff::PATCH_FORMAT) {
+            let query_strs = query_strs.iter().map(String::as_str).collect::<Vec<_>>();
+            return self.run_as_patch(&query_strs, writer);
+        }
+        let Some((last, steps)) = query_strs.split_last() else {
+            return self.run("", output_format, writer);
+        };
+        for query_str in steps {
+            self.execute(query_str, output_format, None)?;
+        }
+
+        self.run(last, output_format, writer)
+    }
+
+    /// Runs queries and updates in sequence over the same data (as changed by the updates), and
+    /// writes the results of the last one (as [`Runner::run`] does). The results of the other
+    /// queries are left out (but any errors in them reported).
+    pub fn run_sequence<W: Write>(
+        &mut self,
+        query_strs: &[String],
+        output_format: Option<&str>,
+        writer: W,
+    ) -> Result<()> {
+        if output_format == Some(diff::PATCH_FORMAT) {
+            return self.run_pipeline(query_strs, output_format, writer);
+        }
+        let Some((last, steps)) = query_strs.split_last() else {
+            return self.run("", output_format, writer);
+        };
+        // (Only the results of the last query are rendered through any template.)
+        let template = self.results_template.take();
+        for query_str in steps {
+            // (Keeping the data, which graph results would otherwise replace, unless updated.)
+            let store = self.store.clone();
+            self.execute(query_str, None, Some(&mut std::io::sink()))?;
+            self.store = store;
+        }
+        self.results_template = template;
+
+        self.run(last, output_format, writer)
+    }
+
+    /// Writes the optimized query plan of the last query (after running any previous queries and
+    /// updates as [`Runner::run_pipeline`] does) instead of its results, as an indented tree.
+    ///
+    /// With statistics, the query is evaluated (without writing the results), and the number of
+    /// results and time taken are added to each node of the plan.
+    pub fn explain<W: Write>(
+        &mut self,
+        query_strs: &[String],
+        with_stats: bool,
+        mut writer: W,
+    ) -> Result<()> {
+        let Some((last, steps)) = query_strs.split_last() else {
+            bail!("No query to explain");
+        };
+        if self.endpoint.is_some() {
+            bail!("Queries sent to an endpoint cannot be explained");
+        }
+        for query_str in steps {
+            self.execute(query_str, None, None)?;
+        }
+
+        let query_str = self.fill_template(last)?;
+        let query = self
+            .parser()?
+            .parse_query(&query_str)
+            .map_err(|err| self.query_error(&query_str, err))
+            .context("Only queries can be explained")?;
+        let mut query = self
+            .prepare(self.evaluator()?, query)?
+            .on_store(&self.store);
+        if with_stats {
+            query = query.compute_statistics();
+        }
+        let (results, explanation) = query.explain();
+        if with_stats {
+            // (Statistics are collected as the results are read:)
+            match results.context("Query failed")? {
+                QueryResults::Solutions(solutions) => {
+                    for solution in solutions {
+                        solution?;
+                    }
+                }
+                QueryResults::Graph(triples) => {
+                    for triple in triples {
+                        triple?;
+                    }
+                }
+                QueryResults::Boolean(_) => {}
+            }
+        }
+
+        let mut json = Vec::new();
+        explanation.write_in_json(&mut json)?;
+        let explanation: serde_json::Value = serde_json::from_slice(&json)?;
+        let planning_duration = explanation["planning duration in seconds"].as_f64();
+        if let Some(seconds) = planning_duration.filter(|_| with_stats) {
+            writeln!(writer, "Planning: {seconds}s")?;
+        }
+        write_plan(&explanation["plan"], 0, &mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Validates the loaded data (in all graphs) against the SHACL shapes loaded into another
+    /// runner, and replaces the data with the validation report (to be serialized). Returns
+    /// whether the data conforms.
+    pub fn validate_shacl(&mut self, shapes: &Runner) -> Result<bool> {
+        let to_graph = |store: &Store| {
+            store
+                .iter()
+                .map(|quad| quad.map(Triple::from))
+                .collect::<Result<Graph, _>>()
+        };
+        let (conforms, report) =
+            shacl::validate(&to_graph(&shapes.store)?, &to_graph(&self.store)?)?;
+
+        self.store = Store::new()?;
+        let mut loader = self.store.bulk_loader();
+        loader.load_quads(
+            report
+                .into_iter()
+                .map(|triple| triple.in_graph(GraphName::DefaultGraph)),
+        )?;
+        loader.commit()?;
+        self.prefixes
+            .entry("sh".to_owned())
+            .or_insert_with(|| "http://www.w3.org/ns/shacl#".to_owned());
+
+        Ok(conforms)
+    }
+
+    /// Replaces the loaded data with a VoID description of it (of the triples in all graphs), to
+    /// be serialized.
+    pub fn describe_as_void(&mut self) -> Result<()> {
+        let quads = self.store.iter().collect::<Result<Vec<_>, _>>()?;
+        let description = void::describe(&quads);
+
+        self.store = Store::new()?;
+        let mut loader = self.store.bulk_loader();
+        loader.load_quads(
+            description
+                .into_iter()
+                .map(|triple| triple.in_graph(GraphName::DefaultGraph)),
+        )?;
+        loader.commit()?;
+        self.prefixes
+            .entry("void".to_owned())
+            .or_insert_with(|| "http://rdfs.org/ns/void#".to_owned());
+
+        Ok(())
+    }
+
+    /// Keeps only the loaded quads matching a pattern of the given terms (any term matching where
+    /// none is given), looked up directly in the store without running a query. Terms are given
+    /// as for bindings (such as `<IRI>`, `pfx:name`, `"literal"` or a number), or `a` for the
+    /// predicate.
+    pub fn retain_matching(
+        &mut self,
+        subject: Option<&str>,
+        predicate: Option<&str>,
+        object: Option<&str>,
+        graph: Option<&str>,
+    ) -> Result<()> {
+        let subject = subject
+            .map(|value| self.parse_iri_value(value))
+            .transpose()?;
+        let predicate = predicate
+            .map(|value| match value {
+                "a" => Ok(rdf::TYPE.into_owned()),
+                value => self.parse_iri_value(value),
+            })
+            .transpose()?;
+        let object = object
+            .map(|value| {
+                self.parse_value(value)
+                    .with_context(|| format!("Invalid object to match: {value}"))
+            })
+            .transpose()?;
+        let graph = graph.map(|value| self.parse_iri_value(value)).transpose()?;
+
+        let store = self.store.clone();
+        self.replace_store(store.quads_for_pattern(
+            subject.as_ref().map(|node| node.as_ref().into()),
+            predicate.as_ref().map(NamedNode::as_ref),
+            object.as_ref().map(Term::as_ref),
+            graph.as_ref().map(|node| node.as_ref().into()),
+        ))
+    }
+
+    /// Keeps only the loaded quads with an IRI (as subject, predicate or object) or a literal
+    /// value matching the regular expression, and with context, also all other quads about the
+    /// subjects of those.
+    pub fn retain_grep(&mut self, pattern: &str, with_context: bool) -> Result<()> {
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid regular expression: {pattern}"))?;
+        let is_match = |quad: &Quad| {
+            let subject = match &quad.subject {
+                NamedOrBlankNode::NamedNode(node) => Some(node.as_str()),
+                _ => None,
+            };
+            let object = match &quad.object {
+                Term::NamedNode(node) => Some(node.as_str()),
+                Term::Literal(literal) => Some(literal.value()),
+                _ => None,
+            };
+            [subject, Some(quad.predicate.as_str()), object]
+                .into_iter()
+                .flatten()
+                .any(|text| regex.is_match(text))
+        };
+        let store = self.store.clone();
+        if !with_context {
+            return self.replace_store(
+                store
+                    .iter()
+                    .filter(|quad| quad.as_ref().map_or(true, is_match)),
+            );
+        }
+        let mut subjects = HashSet::new();
+        for quad in store.iter() {
+            let quad = quad?;
+            if is_match(&quad) {
+                subjects.insert(quad.subject);
+            }
+        }
+        self.replace_store(
+            subjects.iter().flat_map(|subject| {
+                store.quads_for_pattern(Some(subject.as_ref()), None, None, None)
+            }),
+        )
+    }
+
+    fn parse_iri_value(&self, value: &str) -> Result<NamedNode> {
+        match self.parse_value(value) {
+            Ok(Term::NamedNode(node)) => Ok(node),
+            _ => bail!("Not an IRI (or a prefixed name): {value}"),
+        }
+    }
+
+    /// Partitions the loaded data (the triples of all graphs) into descriptions of resources, by
+    /// subject IRI or by class, named by those (see [`SplitBy`]). The parts are read from the
+    /// data as it is loaded now, even if it is replaced afterwards.
+    pub fn partition(&self, by: SplitBy) -> Result<Partition> {
+        split::partition(&self.store, by)
+    }
+
+    /// Replaces the loaded data with the given triples (in the default graph), as when writing
+    /// one part of it at a time.
+    pub fn replace_data(&mut self, triples: Vec<Triple>) -> Result<()> {
+        self.store = Store::new()?;
+        let mut loader = self.store.bulk_loader();
+        loader.load_quads(
+            triples
+                .into_iter()
+                .map(|triple| triple.in_graph(GraphName::DefaultGraph)),
+        )?;
+        loader.commit()?;
+        Ok(())
+    }
+
+    /// Validates the loaded data (in all graphs) against a ShEx schema (in ShExC, resolving
+    /// relative IRIs against the given base IRI), for the nodes and shapes associated by a shape
+    /// map (using the prefixes of the schema). Writes the result shape map, and returns whether
+    /// all nodes conform.
+    pub fn validate_shex<W: Write>(
+        &self,
+        schema: &str,
+        base_iri: Option<&str>,
+        shape_map: &str,
+        writer: W,
+    ) -> Result<bool> {
+        let data = self
+            .store
+            .iter()
+            .map(|quad| quad.map(Triple::from))
+            .collect::<Result<Graph, _>>()?;
+        shex::validate(schema, base_iri, shape_map, &data, writer)
+    }
+
+    /// Checks the loaded data for common problems (malformed literals and language tags, IRIs
+    /// with spaces or looking like prefixed names with undefined prefixes, and resources without
+    /// a type), writing a warning for each (prefixed by the graph it is in, i.e. the file loaded).
+    /// Returns the number of warnings.
+    pub fn lint<W: Write>(&self, writer: W) -> Result<usize> {
+        lint::lint(&self.store, writer)
+    }
+
+    /// Writes a summary of the loaded data for reading: the number of quads in each graph,
+    /// instances of each class, usages of each predicate, and literals of each datatype and
+    /// language (with IRIs compacted by the prefixes).
+    pub fn write_summary<W: Write>(&self, writer: W) -> Result<()> {
+        let terms = pretty::Terms::new(None, &self.query_prefixes())?;
+        summary::write(self.store.iter(), &terms, writer)
+    }
+
+    /// Writes a report of the namespaces of IRIs in the loaded data, with the number of uses of
+    /// each, the prefix declared for it (if any), and a note if it looks mistyped.
+    pub fn write_namespaces<W: Write>(&self, writer: W) -> Result<()> {
+        namespaces::write_report(self.store.iter(), &self.prefixes, writer)
+    }
+
+    /// Replaces the loaded data with its combination with the data of other runners by a set
+    /// operation (with blank nodes matched by the structures they are part of, as in diffs), also
+    /// using the prefixes of the others (unless declared otherwise).
+    pub fn combine(&mut self, others: &[Runner], operation: SetOperation) -> Result<()> {
+        let quads = self.store.iter().collect::<Result<Vec<_>, _>>()?;
+        let other_quads = others
+            .iter()
+            .map(|other| other.store.iter().collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let combined = diff::combine(quads, other_quads, operation);
+
+        self.store = Store::new()?;
+        let mut loader = self.store.bulk_loader();
+        loader.load_quads(combined)?;
+        loader.commit()?;
+        for other in others {
+            for (pfx, ns) in &other.prefixes {
+                if !self.prefixes.values().any(|value| value == ns) {
+                    self.prefixes
+                        .entry(pfx.clone())
+                        .or_insert_with(|| ns.clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether the loaded data is isomorphic to that of another runner (i.e. equal, apart
+    /// from blank node labels), and if not, writes a short report of the mismatch (naming the
+    /// inputs as given). Returns whether they differ.
+    pub fn write_mismatch<W: Write>(
+        &self,
+        other: &Runner,
+        name: &str,
+        other_name: &str,
+        mut writer: W,
+    ) -> Result<bool> {
+        let old = self.store.iter().collect::<Result<Vec<_>, _>>()?;
+        let new = other.store.iter().collect::<Result<Vec<_>, _>>()?;
+        diff::write_mismatch(old, new, (name, other_name), &mut writer)
+    }
+
+    /// Runs updates (or graph queries) and writes the changes made by them as an RDF Patch.
+    fn run_as_patch<W: Write>(&mut self, query_strs: &[&str], writer: W) -> Result<()> {
+        let quads_before = self.store.iter().collect::<Result<HashSet<_>, _>>()?;
+        for query_str in query_strs {
+            self.execute(query_str, None, None)?;
+        }
+        let quads_after = self.store.iter().collect::<Result<HashSet<_>, _>>()?;
+
+        let mut removed = quads_before.difference(&quads_after).collect::<Vec<_>>();
+        removed.sort_by_cached_key(|quad| quad.to_string());
+        let mut added = quads_after.difference(&quads_before).collect::<Vec<_>>();
+        added.sort_by_cached_key(|quad| quad.to_string());
+
+        let mut writer = BufWriter::new(writer);
+        diff::write_patch(removed, added, &mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Executes a query or update, returning whether results were written (or else the data has
+    /// been replaced or modified). Without a writer, only graph results and updates are allowed.
+    fn execute(
+        &mut self,
+        query_str: &str,
+        output_format: Option<&str>,
+        writer: Option<&mut dyn Write>,
+    ) -> Result<bool> {
+        let query_str = &self.fill_template(query_str)?;
+
+        match self.parser()?.parse_query(query_str) {
+            Ok(query) => {
+                let results = match &self.endpoint {
+                    // Send the query to the endpoint instead (when there is no local data):
+                    Some(_) if self.store.is_empty()? && !self.bindings.is_empty() => {
+                        bail!("Bound variables cannot be sent to an endpoint");
+                    }
+                    Some(endpoint) if self.store.is_empty()? => {
+                        self.send_to_endpoint(&endpoint.clone(), query_str)?
+                    }
+                    Some(endpoint) => Some(self.query_with_endpoint(endpoint, query_str)?),
+                    None => Some(self.execute_query(self.prepare(self.evaluator()?, query)?)?),
+                };
+                let results = match &writer {
+                    Some(_) => results.map(|results| slice(results, self.offset, self.limit)),
+                    None => results,
+                };
+                if writer.is_some()
+                    && self.results_template.is_some()
+                    && matches!(results, Some(QueryResults::Graph(_)))
+                {
+                    bail!("Templates apply only to SELECT and ASK results");
+                }
+                let ask_result = match &results {
+                    Some(QueryResults::Boolean(value)) => Some(*value),
+                    _ => None,
+                };
+                let skolemizer = match (&self.skolem_base, &writer) {
+                    (Some(base), Some(_)) => Some(skolem::Skolemizer::new(base, &self.store)?),
+                    _ => None,
+                };
+                let count = self.count;
+                let prefixes = self.query_prefixes();
+                let style = ResultsStyle {
+                    csv_dialect: self.csv_dialect.as_ref(),
+                    template: self.results_template.as_ref(),
+                    table: self.table,
+                    color: self.color,
+                };
+                let serialized = results
+                    .map(|results| match writer {
+                        Some(writer) if count => write_count(results, writer).map(|_| None),
+                        Some(writer) => results_to_new_store_or_serialize(
+                            results,
+                            output_format,
+                            skolemizer.as_ref(),
+                            &style,
+                            &prefixes,
+                            writer,
+                        ),
+                        None if matches!(results, QueryResults::Graph(_)) => {
+                            results_to_new_store_or_serialize(
+                                results,
+                                None,
+                                None,
+                                &ResultsStyle::default(),
+                                &HashMap::new(),
+                                std::io::sink(),
+                            )
+                        }
+                        None => bail!("Only graph results can be passed on to another query (or written as a patch)"),
+                    })
+                    .transpose()?;
+                self.ask_result = ask_result;
+                match serialized {
+                    Some(Some(new_store)) => {
+                        self.store = new_store;
+                    }
+                    Some(None) => return Ok(true),
+                    // Graph results have already replaced the data:
+                    None => {}
+                }
+            }
+            Err(query_parse_err) => {
+                // Maybe an update query:
+                if let Ok(update) = self.parser()?.parse_update(query_str) {
+                    if self.endpoint.is_some() {
+                        bail!("Updates cannot be sent to an endpoint");
+                    }
+                    // Insert or Delete:
+                    self.prepare_update(update)?
+                        .on_store(&self.store)
+                        .execute()
+                        .context("Update failed")?;
+                } else {
+                    // Bail for query error (assumed more likely than update attempt; maybe report both?):
+                    return Err(self.query_error(query_str, query_parse_err));
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Runs updates in sequence, returning the graphs modified by them (for writing these back to
+    /// where they were loaded from).
+    pub fn run_updates(&mut self, update_strs: &[String]) -> Result<Vec<GraphName>> {
+        if self.endpoint.is_some() {
+            bail!("Updates cannot be sent to an endpoint");
+        }
+        let digests_before = self.graph_digests()?;
+        for update_str in update_strs {
+            let update_str = self.fill_template(update_str)?;
+            let update = self
+                .parser()?
+                .parse_update(&update_str)
+                .map_err(|err| self.query_error(&update_str, err))
+                .context("Not an update")?;
+            self.prepare_update(update)?
+                .on_store(&self.store)
+                .execute()
+                .context("Update failed")?;
+        }
+        let digests_after = self.graph_digests()?;
+
+        let mut modified = digests_before
+            .keys()
+            .filter(|graph_name| !digests_after.contains_key(graph_name))
+            .chain(
+                digests_after
+                    .iter()
+                    .filter(|(graph_name, digest)| digests_before.get(graph_name) != Some(digest))
+                    .map(|(graph_name, _)| graph_name),
+            )
+            .cloned()
+            .collect::<Vec<_>>();
+        modified.sort_by_key(GraphName::to_string);
+
+        Ok(modified)
+    }
+
+    /// Tells whether any of the updates would modify the default graph (such as by `INSERT` or
+    /// `DELETE` templates without `GRAPH`), whether or not they do given the data.
+    pub fn updates_default_graph(&self, update_strs: &[String]) -> Result<bool> {
+        for update_str in update_strs {
+            let update_str = self.fill_template(update_str)?;
+            let update = self
+                .parser()?
+                .parse_update(&update_str)
+                .context("Not an update")?;
+            let in_default_graph = |graph_name: &GraphNamePattern| {
+                matches!(graph_name, GraphNamePattern::DefaultGraph)
+            };
+            let updates = update.operations.iter().any(|operation| match operation {
+                GraphUpdateOperation::InsertData { data } => data
+                    .iter()
+                    .any(|quad| quad.graph_name == spargebra::term::GraphName::DefaultGraph),
+                GraphUpdateOperation::DeleteData { data } => data
+                    .iter()
+                    .any(|quad| quad.graph_name == spargebra::term::GraphName::DefaultGraph),
+                GraphUpdateOperation::DeleteInsert { delete, insert, .. } => {
+                    delete.iter().any(|quad| in_default_graph(&quad.graph_name))
+                        || insert.iter().any(|quad| in_default_graph(&quad.graph_name))
+                }
+                GraphUpdateOperation::Load { destination, .. } => {
+                    *destination == spargebra::term::GraphName::DefaultGraph
+                }
+                GraphUpdateOperation::Clear { graph, .. }
+                | GraphUpdateOperation::Drop { graph, .. } => {
+                    matches!(graph, GraphTarget::DefaultGraph | GraphTarget::AllGraphs)
+                }
+                GraphUpdateOperation::Create { .. } => false,
+            });
+            if updates {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Sums up the hashes of the quads in each graph (regardless of order), to tell whether a graph
+    /// has been modified.
+    fn graph_digests(&self) -> Result<HashMap<GraphName, (usize, u64)>> {
+        let mut digests = HashMap::new();
+        for quad in self.store.iter() {
+            let quad = quad?;
+            let mut hasher = DefaultHasher::new();
+            quad.hash(&mut hasher);
+            let (count, sum) = digests.entry(quad.graph_name).or_insert((0, 0));
+            *count += 1;
+            *sum = hasher.finish().wrapping_add(*sum);
+        }
+        Ok(digests)
+    }
+
+    fn fill_template(&self, query_str: &str) -> Result<String> {
+        template::fill(query_str, |name| {
+            self.params
+                .get(name)
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+        })
+    }
+
+    /// Writes the differences between the loaded data and that of another runner (removed and
+    /// added quads), returning whether there were any.
+    ///
+    /// Blank nodes are matched by the structures they are part of (quads connected by blank
+    /// nodes are compared as a whole). Changes are written as lines of N-Quads prefixed with `-`
+    /// or `+` (optionally in color), or as SPARQL results if an output format is given.
+    pub fn write_diff<W: Write>(
+        &self,
+        other: &Runner,
+        output_format: Option<&str>,
+        color: bool,
+        writer: W,
+    ) -> Result<bool> {
+        let old = self.store.iter().collect::<Result<Vec<_>, _>>()?;
+        let new = other.store.iter().collect::<Result<Vec<_>, _>>()?;
+        let mut writer = BufWriter::new(writer);
+        let changed = diff::write_diff(old, new, output_format, color, &mut writer)?;
+        writer.flush()?;
+
+        Ok(changed)
+    }
+
+    /// Serves the loaded data as a SPARQL endpoint (using the SPARQL protocol) on the given
+    /// address, until terminated.
+    ///
+    /// Queries are answered in the formats requested by the clients (JSON results and Turtle
+    /// unless otherwise accepted). Updates are not supported.
+    pub fn serve(self, addr: &str) -> Result<()> {
+        server::serve(self, addr)
+    }
+
+    /// Sends a query to the endpoint, returning any results (or replacing the data with a graph).
+    fn send_to_endpoint(
+        &mut self,
+        endpoint: &str,
+        query_str: &str,
+    ) -> Result<Option<QueryResults<'static>>> {
+        let query = self.parse_query_algebra(query_str)?;
+        match endpoint::send_query(endpoint, &query)? {
+            endpoint::Response::Results(results) => Ok(Some(results)),
+            endpoint::Response::Graph(format, body) => {
+                self.store = Store::new()?;
+                let parser = RdfParser::from_format(format);
+                self.load_data(parser, &GraphName::DefaultGraph, body, None)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Queries the loaded data along with the data of the endpoint.
+    fn query_with_endpoint(&self, endpoint: &str, query_str: &str) -> Result<QueryResults<'_>> {
+        let endpoint_node = NamedNode::new(endpoint)?;
+        let query = endpoint::with_service(self.parse_query_algebra(query_str)?, &endpoint_node);
+        let service = endpoint::EndpointService {
+            endpoint: endpoint.to_owned(),
+        };
+        let evaluator = self
+            .evaluator()?
+            .with_service_handler(endpoint_node, service);
+
+        self.execute_query(self.prepare(evaluator, query)?)
+    }
+
+    /// Executes a prepared query over the store.
+    fn execute_query(&self, query: PreparedSparqlQuery) -> Result<QueryResults<'_>> {
+        query
+            .on_store(&self.store)
+            .execute()
+            .context("Query failed")
+    }
+
+    /// Prepares a query for the evaluator, with the bound variables bound (as by a `VALUES`
+    /// clause in its `WHERE` clause).
+    fn prepare(
+        &self,
+        evaluator: SparqlEvaluator,
+        mut query: spargebra::Query,
+    ) -> Result<PreparedSparqlQuery> {
+        if let Some(values) = self.bound_values()? {
+            let (spargebra::Query::Select { pattern, .. }
+            | spargebra::Query::Construct { pattern, .. }
+            | spargebra::Query::Describe { pattern, .. }
+            | spargebra::Query::Ask { pattern, .. }) = &mut query;
+            join_values(pattern, &values);
+        }
+        let mut query = evaluator.for_query(query);
+        if self.default_graph_as_union {
+            query.dataset_mut().set_default_graph_as_union();
+        }
+        Ok(query)
+    }
+
+    /// Prepares an update, with the bound variables bound in the `WHERE` clauses of its
+    /// operations.
+    fn prepare_update(&self, mut update: spargebra::Update) -> Result<PreparedSparqlUpdate> {
+        if let Some(values) = self.bound_values()? {
+            for operation in &mut update.operations {
+                if let GraphUpdateOperation::DeleteInsert { pattern, .. } = operation {
+                    join_values(pattern, &values);
+                }
+            }
+        }
+        Ok(self.evaluator()?.for_update(update))
+    }
+
+    /// Gives the bound variables as a `VALUES` pattern of one solution (if any are bound).
+    fn bound_values(&self) -> Result<Option<GraphPattern>> {
+        if self.bindings.is_empty() {
+            return Ok(None);
+        }
+        let mut variables = Vec::new();
+        let mut values = Vec::new();
+        for (name, value) in &self.bindings {
+            let variable = Variable::new(name.trim_start_matches(['?', '$']))
+                .with_context(|| format!("Invalid variable name: {name}"))?;
+            let term = self
+                .parse_value(value)
+                .ok()
+                .and_then(|term| GroundTerm::try_from(term).ok())
+                .with_context(|| format!("Invalid value of {name}: {value}"))?;
+            // (A variable bound again takes the last value.)
+            match variables.iter().position(|bound| *bound == variable) {
+                Some(i) => values[i] = Some(term),
+                None => {
+                    variables.push(variable);
+                    values.push(Some(term));
+                }
+            }
+        }
+        Ok(Some(GraphPattern::Values {
+            variables,
+            bindings: vec![values],
+        }))
+    }
+
+    fn parse_value(&self, value: &str) -> Result<Term> {
+        if let Some(iri) = value.strip_prefix('<').and_then(|v| v.strip_suffix('>')) {
+            let iri = match &self.base_iri {
+                Some(base_iri) => Iri::parse(base_iri.as_str())?.resolve(iri)?.into_inner(),
+                None => iri.to_owned(),
+            };
+            return Ok(NamedNode::new(iri)?.into());
+        }
+        if value.starts_with('"') {
+            return Ok(Term::from_str(value)?);
+        }
+        if let Some((pfx, local)) = value.split_once(':') {
+            if let Some(ns) = self.query_prefixes().get(pfx) {
+                return Ok(NamedNode::new(format!("{ns}{local}"))?.into());
+            }
+        }
+        if let Ok(number) = value.parse::<i64>() {
+            return Ok(Literal::from(number).into());
+        }
+        Ok(Literal::new_simple_literal(value).into())
+    }
+
+    /// The prefixes to use in queries: the collected ones, along with the default prefixes not
+    /// declared otherwise (unless left out).
+    fn query_prefixes(&self) -> HashMap<String, String> {
+        let mut prefixes = self.prefixes.clone();
+        let mut names = self.functions.iter().map(|(name, _)| name);
+        let mut aggregate_names = self.aggregates.iter().map(|(name, _)| name);
+        let in_namespace = |name: &NamedNode| name.as_str().starts_with(search::NAMESPACE);
+        if self.text_search || names.any(in_namespace) || aggregate_names.any(in_namespace) {
+            prefixes
+                .entry("oxrq".to_owned())
+                .or_insert_with(|| search::NAMESPACE.to_owned());
+        }
+        if self.default_prefixes {
+            for (pfx, ns) in DEFAULT_PREFIXES {
+                prefixes
+                    .entry(pfx.to_string())
+                    .or_insert_with(|| ns.to_string());
+            }
+        }
+        prefixes
+    }
+
+    /// The prefixes to declare in output: the collected ones, along with the default prefixes (for
+    /// prefixes and namespaces not declared otherwise) used in the data. If pruning prefixes, only
+    /// those used in the output data are kept.
+    fn output_prefixes(&self, as_triples: bool) -> Result<HashMap<String, String>> {
+        let mut unused: Vec<(String, String)> = DEFAULT_PREFIXES
+            .iter()
+            .filter(|(pfx, ns)| {
+                self.default_prefixes
+                    && !self.prefixes.contains_key(*pfx)
+                    && !self.prefixes.values().any(|value| value == ns)
+            })
+            .map(|(pfx, ns)| (pfx.to_string(), ns.to_string()))
+            .collect();
+        let mut prefixes = HashMap::new();
+        if self.prune_prefixes {
+            unused.extend(self.prefixes.clone());
+        } else {
+            prefixes = self.prefixes.clone();
+        }
+        if unused.is_empty() {
+            return Ok(prefixes);
+        }
+
+        let graph_name = match as_triples {
+            true => self.triples_graph()?,
+            false => None,
+        };
+        let quads = match &graph_name {
+            Some(graph_name) => {
+                self.store
+                    .quads_for_pattern(None, None, None, Some(graph_name.as_ref()))
+            }
+            None => self.store.iter(),
+        };
+        for quad in quads {
+            let quad = quad?;
+            // (Datatypes of plain and language-tagged strings, and of numbers and booleans, are
+            // not written in Turtle.)
+            let datatype = match &quad.object {
+                Term::Literal(literal)
+                    if literal.datatype() != xsd::STRING
+                        && literal.language().is_none()
+                        && !csv::is_turtle_shorthand(literal) =>
+                {
+                    Some(literal.datatype().into_owned())
+                }
+                _ => None,
+            };
+            let iris = [
+                match &quad.subject {
+                    NamedOrBlankNode::NamedNode(node) => Some(node),
+                    _ => None,
+                },
+                // (As `a` in Turtle.)
+                (quad.predicate != rdf::TYPE).then_some(&quad.predicate),
+                match &quad.object {
+                    Term::NamedNode(node) => Some(node),
+                    _ => None,
+                },
+                datatype.as_ref(),
+                match &quad.graph_name {
+                    GraphName::NamedNode(node) if !as_triples => Some(node),
+                    _ => None,
+                },
+            ];
+            for iri in iris.into_iter().flatten() {
+                while let Some(i) = unused
+                    .iter()
+                    .position(|(_, ns)| iri.as_str().starts_with(ns.as_str()))
+                {
+                    let (pfx, ns) = unused.swap_remove(i);
+                    prefixes.insert(pfx, ns);
+                }
+            }
+            if unused.is_empty() {
+                break;
+            }
+        }
+        Ok(prefixes)
+    }
+
+    fn parse_query_algebra(&self, query_str: &str) -> Result<spargebra::Query> {
+        self.parser()?
+            .parse_query(query_str)
+            .map_err(|err| self.query_error(query_str, err))
+    }
+
+    fn is_query_or_update(&self, query_str: &str) -> bool {
+        self.parser().is_ok_and(|parser| {
+            parser.clone().parse_query(query_str).is_ok() || parser.parse_update(query_str).is_ok()
+        })
+    }
+
+    fn parser(&self) -> Result<SparqlParser> {
+        let mut parser = SparqlParser::new();
+        if let Some(value) = &self.base_iri {
+            parser = parser.with_base_iri(value)?;
+        }
+        for (name, _) in &self.aggregates {
+            parser = parser.with_custom_aggregate_function(name.clone());
+        }
+        Ok(parser)
+    }
+
+    fn evaluator(&self) -> Result<SparqlEvaluator> {
+        let mut evaluator = if self.federation {
+            SparqlEvaluator::new().with_http_redirection_limit(HTTP_REDIRECTION_LIMIT)
+        } else {
+            SparqlEvaluator::new().without_default_http_service_handler()
+        };
+        if let Some(value) = &self.base_iri {
+            evaluator = evaluator.with_base_iri(value)?;
+        }
+        if let Some(token) = &self.cancellation_token {
+            evaluator = evaluator.with_cancellation_token(token.clone());
+        }
+        for (name, function) in geo::functions() {
+            evaluator = evaluator.with_custom_function(name, function);
+        }
+        if self.text_search {
+            let index = Arc::new(search::LazyIndex::new(self.store.clone()));
+            let matches = index.clone();
+            evaluator = evaluator
+                .with_custom_function(NamedNode::new_unchecked(search::MATCHES), move |args| {
+                    matches.matches(args)
+                })
+                .with_custom_function(NamedNode::new_unchecked(search::SCORE), move |args| {
+                    index.score(args)
+                });
+        }
+        for (name, store) in &self.services {
+            let service = endpoint::LocalService {
+                store: store.clone(),
+            };
+            evaluator = evaluator.with_service_handler(name.clone(), service);
+        }
+        for (name, function) in &self.functions {
+            let function = function.clone();
+            evaluator = evaluator.with_custom_function(name.clone(), move |args| function(args));
+        }
+        for (name, accumulator) in &self.aggregates {
+            let accumulator = accumulator.clone();
+            evaluator =
+                evaluator.with_custom_aggregate_function(name.clone(), move || accumulator());
+        }
+        Ok(evaluator)
+    }
+
+    /// Serializes the loaded data (TriG unless another output format is given).
+    ///
+    /// HDT output (`hdt`) is written as a binary file, and RDF/JSON output (`rj`) as a JSON
+    /// object, of one graph (like other formats that cannot serialize datasets).
+    pub fn serialize<W: Write>(&self, output_format: Option<&str>, writer: W) -> Result<()> {
+        if matches!(output_format, Some("hdt" | "rj")) {
+            if self.frame.is_some() {
+                bail!("A frame can only be applied to JSON-LD output");
+            }
+            let quads = self.output_quads(true)?.collect::<Result<Vec<_>>>()?;
+            if output_format == Some("rj") {
+                return rdfjson::serialize(quads, writer);
+            }
+            return hdt::serialize(quads, self.base_iri.as_deref(), writer);
+        }
+
+        let format = if let Some(fmt) = output_format {
+            RdfFormat::from_extension(fmt)
+                .with_context(|| format!("Unknown output format: {fmt}"))?
+        } else if self.frame.is_some() {
+            RdfFormat::JsonLd {
+                profile: JsonLdProfile::Framed.into(),
+            }
+        } else {
+            RdfFormat::TriG
+        };
+
+        if let RdfFormat::JsonLd { .. } = format {
+            let as_triples = self.output_graph.is_some() || self.merge_graphs;
+            let quads = self.output_quads(as_triples)?.collect::<Result<Vec<_>>>()?;
+            let prefixes = &self.output_prefixes(as_triples)?;
+            let base_iri = self.base_iri.as_deref();
+            if let Some(frame) = &self.frame {
+                return jsonld::serialize_framed(quads, frame, base_iri, prefixes, writer);
+            }
+            return jsonld::serialize(quads, base_iri, prefixes, writer);
+        } else if self.frame.is_some() {
+            bail!("A frame can only be applied to JSON-LD output");
+        }
+        if self.pretty && !matches!(format, RdfFormat::Turtle | RdfFormat::TriG) {
+            bail!("Only Turtle and TriG output can be pretty-printed");
+        }
+
+        let mut serializer = RdfSerializer::from_format(format);
+        if let Some(value) = &self.base_iri {
+            serializer = serializer.with_base_iri(value)?;
+        }
+        let as_triples =
+            !format.supports_datasets() || self.output_graph.is_some() || self.merge_graphs;
+        let output_prefixes = match format {
+            RdfFormat::NTriples | RdfFormat::NQuads => HashMap::new(),
+            _ => self.output_prefixes(as_triples)?,
+        };
+        let mut prefixes = output_prefixes.iter().collect::<Vec<_>>();
+        prefixes.sort();
+        for (pfx, ns) in prefixes {
+            serializer = serializer.with_prefix(pfx, ns)?;
+        }
+
+        let quads = self.output_quads(as_triples)?;
+
+        let mut writer = highlight::TurtleWriter::new(
+            writer,
+            self.color && matches!(format, RdfFormat::Turtle | RdfFormat::TriG),
+        );
+        if self.pretty {
+            let quads = quads.collect::<Result<Vec<_>>>()?;
+            let base_iri = self.base_iri.as_deref();
+            pretty::serialize(quads, base_iri, &output_prefixes, &mut writer)?;
+            return writer.finish();
+        }
+
+        let mut progress = self.progress.then(|| {
+            // (The total is unknown when merging graphs, which may share triples.)
+            let total = match as_triples {
+                false => self.store.len().ok(),
+                true => self.triples_graph().ok().flatten().map(|graph_name| {
+                    let quads =
+                        self.store
+                            .quads_for_pattern(None, None, None, Some(graph_name.as_ref()));
+                    quads.count()
+                }),
+            };
+            Progress::new("Serializing", total)
+        });
+        let mut serializer = serializer.for_writer(&mut writer);
+        for quad in quads {
+            let quad = quad?;
+            if as_triples {
+                serializer.serialize_triple(quad.as_ref())?;
+            } else {
+                serializer.serialize_quad(&quad)?;
+            }
+            if let Some(progress) = &mut progress {
+                progress.inc();
+            }
+        }
+        serializer.finish()?;
+        writer.finish()?;
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+
+        Ok(())
+    }
+
+    /// Iterates over the quads to output: all of them, or (as triples, in the default graph) those
+    /// of the graph picked by [`Runner::triples_graph`], or else the distinct triples of all graphs
+    /// merged.
+    ///
+    /// When sorting, blank nodes are first given canonical labels, and quads are sorted by graph,
+    /// subject, predicate and object (so that the same data is always written the same way).
+    fn output_quads(
+        &self,
+        as_triples: bool,
+    ) -> Result<Box<dyn Iterator<Item = Result<Quad>> + '_>> {
+        let in_default_graph = |quad: Quad| Quad {
+            graph_name: GraphName::DefaultGraph,
+            ..quad
+        };
+        let quads: Box<dyn Iterator<Item = Result<Quad>>> =
+            if !as_triples {
+                Box::new(self.store.iter().map(|quad| quad.map_err(Into::into)))
+            } else if let Some(graph_name) = self.triples_graph()? {
+                let quads =
+                    self.store
+                        .quads_for_pattern(None, None, None, Some(graph_name.as_ref()));
+                Box::new(quads.map(move |quad| Ok(in_default_graph(quad?))))
+            } else {
+                let mut seen = HashSet::new();
+                let quads = self
+                    .store
+                    .iter()
+                    .map(move |quad| Ok(in_default_graph(quad?)));
+                Box::new(quads.filter(move |quad| {
+                    quad.as_ref().map_or(true, |quad| seen.insert(quad.clone()))
+                }))
+            };
+        if !self.sort {
+            return Ok(Box::new(quads));
+        }
+        let mut dataset = quads.collect::<Result<Dataset>>()?;
+        dataset.canonicalize(CanonicalizationAlgorithm::Unstable);
+        let mut quads = dataset.iter().map(QuadRef::into_owned).collect::<Vec<_>>();
+        quads.sort_by_cached_key(|quad| {
+            (
+                !quad.graph_name.is_default_graph(),
+                quad.graph_name.to_string(),
+                quad.subject.to_string(),
+                quad.predicate.to_string(),
+                quad.object.to_string(),
+            )
+        });
+        Ok(Box::new(quads.into_iter().map(Ok)))
+    }
+
+    /// Serializes one graph in the given input format, using the prefixes and base IRI parsed from
+    /// the data loaded into it (as when writing it back to its source file).
+    pub fn serialize_graph<W: Write>(
+        &self,
+        graph_name: &GraphName,
+        format: InputFormat,
+        writer: W,
+    ) -> Result<()> {
+        let default_namespaces = Namespaces::default();
+        let namespaces = self
+            .graph_namespaces
+            .get(graph_name)
+            .unwrap_or(&default_namespaces);
+        if namespaces.with_other_graphs {
+            bail!(
+                "Graph {graph_name} was loaded along with other graphs and cannot be written alone"
+            );
+        }
+        let base_iri = namespaces.base_iri.as_deref();
+        let quads = || {
+            self.store
+                .quads_for_pattern(None, None, None, Some(graph_name.as_ref()))
+                .map(|quad| {
+                    quad.map(|quad| Quad {
+                        graph_name: GraphName::DefaultGraph,
+                        ..quad
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let format = match format {
+            InputFormat::Rdf(RdfFormat::JsonLd { .. }) => {
+                return jsonld::serialize(quads()?, base_iri, &namespaces.prefixes, writer);
+            }
+            InputFormat::Rdf(format) => format,
+            InputFormat::RdfJson => return rdfjson::serialize(quads()?, writer),
+            InputFormat::Hdt => return hdt::serialize(quads()?, base_iri, writer),
+            InputFormat::Html => bail!("HTML cannot be written"),
+            InputFormat::Csv => bail!("CSV cannot be written"),
+            InputFormat::Sqlite => bail!("SQLite databases cannot be written"),
+        };
+        let mut serializer = RdfSerializer::from_format(format);
+        if let Some(value) = base_iri {
+            serializer = serializer.with_base_iri(value)?;
+        }
+        for (pfx, ns) in &namespaces.prefixes {
+            serializer = serializer.with_prefix(pfx, ns)?;
+        }
+        self.store
+            .dump_graph_to_writer(graph_name.as_ref(), serializer, writer)?;
+
+        Ok(())
+    }
+
+    /// Picks the graph to serialize as triples (in formats that cannot serialize datasets, or when
+    /// a graph to output is given): the given graph, or else the default graph unless empty (or
+    /// all graphs are to be merged). If `None`, all graphs are merged.
+    fn triples_graph(&self) -> Result<Option<GraphName>> {
+        let store = &self.store;
+        if let Some(graph_name) = &self.output_graph {
+            if !store.contains_named_graph(graph_name)? {
+                bail!("No graph to output named: {graph_name}");
+            }
+            return Ok(Some(graph_name.clone().into()));
+        }
+        if self.merge_graphs {
+            return Ok(None);
+        }
+        if store
+            .quads_for_pattern(None, None, None, Some(GraphNameRef::DefaultGraph))
+            .next()
+            .is_some()
+        {
+            return Ok(Some(GraphName::DefaultGraph));
+        }
+        Ok(None)
+    }
+}
+
+/// Opens a file for reading, detecting its input format by suffix (or else by content).
+///
+/// Compressed files (using gzip, bzip2, xz or zstd) are decompressed, with the input format
+/// detected by the suffix preceding the compression suffix (as in `data.ttl.gz`).
+pub fn open_file(fpath: &str) -> Result<(InputFormat, Box<dyn BufRead>)> {
+    let file = File::open(fpath).with_context(|| format!("Unable to open file: {fpath}"))?;
+    let mut reader = compression::decompress(file)?;
+
+    let format = match format_from_path(fpath) {
+        Some(format) => format,
+        None => InputFormat::from_content(reader.fill_buf()?)
+            .with_context(|| format!("Unable to detect input format of file: {fpath}"))?,
+    };
+
+    Ok((format, reader))
+}
+
+/// Detects the input format of data by its first bytes (falling back to Turtle), decompressing
+/// it if compressed.
+pub fn sniff_format<'a, R: Read + 'a>(reader: R) -> Result<(InputFormat, Box<dyn BufRead + 'a>)> {
+    let mut reader = compression::decompress(reader)?;
+    let format = InputFormat::from_content(reader.fill_buf()?)
+        .unwrap_or(InputFormat::Rdf(RdfFormat::Turtle));
+
+    Ok((format, reader))
+}
+
+/// Fetches data from an HTTP(S) URL, detecting its input format by media type (or suffix, or
+/// else by content). Responses are cached in the given directory (if any).
+pub fn open_url(url: &str, cache_dir: Option<&Path>) -> Result<(InputFormat, impl Read)> {
+    let (media_type, body): (_, Box<dyn Read>) = match cache_dir {
+        Some(cache_dir) => http::get_cached(url, RDF_ACCEPT, cache_dir)?,
+        None => {
+            let (media_type, body) = http::get(url, RDF_ACCEPT)?;
+            (media_type, Box::new(body))
+        }
+    };
+    let mut reader = compression::decompress(body)?;
+
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let format = match media_type
+        .as_deref()
+        .and_then(InputFormat::from_media_type)
+        .or_else(|| format_from_path(path))
+    {
+        Some(format) => format,
+        None => InputFormat::from_content(reader.fill_buf()?).with_context(|| {
+            let media_type = media_type.as_deref().unwrap_or("unknown");
+            format!("No RDF format found for URL {url} (of type {media_type})")
+        })?,
+    };
+
+    Ok((format, reader))
+}
+
+/// Looks up the input format by the suffix of a path (skipping any compression suffix).
+pub fn format_from_path(fpath: &str) -> Option<InputFormat> {
+    format_suffix(fpath).and_then(InputFormat::from_extension)
+}
+
+/// Checks if a file has a distinctly RDF suffix (i.e. not a generic one like `.txt`, `.xml`,
+/// `.json`, `.csv` or `.db`), for picking out files in directories and archives.
+pub fn is_rdf_file(fpath: &str) -> bool {
+    format_suffix(fpath).is_some_and(|ext| {
+        !["txt", "xml", "json", "csv", "sqlite", "sqlite3", "db"].contains(&ext)
+            && InputFormat::from_extension(ext).is_some()
+    })
+}
+
+/// Checks if a file has a compression suffix (such as `.gz`).
+pub fn is_compressed_file(fpath: &str) -> bool {
+    Path::new(fpath)
+        .extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(compression::is_compression_extension)
+}
+
+fn format_suffix(fpath: &str) -> Option<&str> {
+    let path = Path::new(fpath);
+    let ext = path.extension()?.to_str()?;
+    if !compression::is_compression_extension(ext) {
+        return Some(ext);
+    }
+    Path::new(path.file_stem()?).extension()?.to_str()
+}
+
+/// Checks if a file argument is an HTTP(S) URL.
+pub fn is_url(fpath: &str) -> bool {
+    fpath.starts_with("http://") || fpath.starts_with("https://")
+}
+
+/// Loads documents referenced from the data (i.e. JSON-LD contexts) from local files or URLs.
+fn parse_data<R: Read>(
+    parser: RdfParser,
+    reader: R,
+    shared_blank_nodes: bool,
+) -> Result<ParsedData> {
+    let parser = match shared_blank_nodes {
+        true => parser,
+        false => parser.rename_blank_nodes(),
+    };
+    let mut parser_reader = parser
+        .for_reader(BufReader::new(reader))
+        .with_document_loader(load_document);
+    let quads = parser_reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+    Ok(ParsedData {
+        quads,
+        base_iri: parser_reader.base_iri().map(str::to_owned),
+        prefixes: parser_reader
+            .prefixes()
+            .map(|(pfx, ns)| (pfx.to_owned(), ns.to_owned()))
+            .collect(),
+    })
+}
+
+/// Parses RDFa and microdata from HTML, along with any embedded JSON-LD.
+fn parse_html<R: Read>(
+    mut reader: R,
+    base_iri: Option<&str>,
+    graph_name: &GraphName,
+) -> Result<Vec<ParsedData>> {
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
+    let data = html::parse(&content, base_iri, graph_name);
+
+    let mut parsed = vec![ParsedData {
+        quads: data.quads,
+        base_iri: data.base_iri,
+        prefixes: data.prefixes,
+    }];
+
+    // Embedded JSON-LD (resolved against the base IRI of the document):
+    for json_ld in &data.json_ld {
+        let mut parser = RdfParser::from_format(RdfFormat::JsonLd {
+            profile: JsonLdProfileSet::empty(),
+        })
+        .with_default_graph(graph_name.clone());
+        if let Some(value) = parsed[0].base_iri.as_deref().or(base_iri) {
+            parser = parser.with_base_iri(value)?;
+        }
+        parsed.push(parse_data(parser, json_ld.as_bytes(), false)?);
+    }
+
+    Ok(parsed)
+}
+
+/// Parses an opened file (into the given named graph or the default graph), sending the parsed
+/// data to be added a batch of quads at a time (until the receiver is gone).
+fn parse_file(
+    reader: Box<dyn BufRead>,
+    format: InputFormat,
+    graph_name: &GraphName,
+    base_iri: &str,
+    mappings: &Mappings,
+    shared_blank_nodes: bool,
+    sender: &SyncSender<Parsed>,
+) {
+    let parsed = match format {
+        InputFormat::Rdf(RdfFormat::N3) => {
+            n3::parse(BufReader::new(reader), Some(base_iri), graph_name).map(|data| vec![data])
+        }
+        InputFormat::Rdf(RdfFormat::JsonLd { .. }) if mappings.rml.is_some() => mappings
+            .convert_json(reader, Some(base_iri), graph_name)
+            .map(|quads| {
+                vec![ParsedData {
+                    quads,
+                    base_iri: None,
+                    prefixes: Vec::new(),
+                }]
+            }),
+        InputFormat::Rdf(RdfFormat::RdfXml) if mappings.xml.is_some() => mappings
+            .convert_xml(reader, Some(base_iri), graph_name)
+            .map(|quads| {
+                vec![ParsedData {
+                    quads,
+                    base_iri: None,
+                    prefixes: Vec::new(),
+                }]
+            }),
+        InputFormat::Rdf(rdf_format) => RdfParser::from_format(rdf_format)
+            .with_default_graph(graph_name.clone())
+            .with_base_iri(base_iri)
+            .map_err(anyhow::Error::from)
+            .and_then(|parser| send_data(parser, reader, shared_blank_nodes, sender))
+            .map(|()| Vec::new()),
+        InputFormat::Html => parse_html(reader, Some(base_iri), graph_name),
+        InputFormat::Csv => mappings
+            .csv
+            .convert(reader, Some(base_iri), graph_name)
+            .map(|quads| {
+                vec![ParsedData {
+                    quads,
+                    base_iri: None,
+                    prefixes: Vec::new(),
+                }]
+            }),
+        InputFormat::Sqlite => mappings
+            .convert_sqlite(reader, Some(base_iri), graph_name)
+            .map(|quads| {
+                vec![ParsedData {
+                    quads,
+                    base_iri: None,
+                    prefixes: Vec::new(),
+                }]
+            }),
+        InputFormat::RdfJson => rdfjson::parse(reader, graph_name).map(|quads| {
+            vec![ParsedData {
+                quads,
+                base_iri: None,
+                prefixes: Vec::new(),
+            }]
+        }),
+        InputFormat::Hdt => hdt::parse(BufReader::new(reader), graph_name).map(|quads| {
+            vec![ParsedData {
+                quads,
+                base_iri: None,
+                prefixes: Vec::new(),
+            }]
+        }),
+    };
+    let sent = parsed.map(|parsed| {
+        parsed.into_iter().all(|data| {
+            send_quads(data.quads.into_iter().map(Ok), sender).unwrap_or(false)
+                && sender
+                    .send(Parsed::End(data.base_iri, data.prefixes))
+                    .is_ok()
+        })
+    });
+    if let Err(err) = sent {
+        let _ = sender.send(Parsed::Error(err));
+    }
+}
+
+/// Parses data, sending it a batch of quads at a time (and lastly the base IRI and prefixes
+/// declared in it), until the receiver is gone.
+fn send_data<R: Read>(
+    parser: RdfParser,
+    reader: R,
+    shared_blank_nodes: bool,
+    sender: &SyncSender<Parsed>,
+) -> Result<()> {
+    let parser = match shared_blank_nodes {
+        true => parser,
+        false => parser.rename_blank_nodes(),
+    };
+    let mut parser_reader = parser
+        .for_reader(BufReader::new(reader))
+        .with_document_loader(load_document);
+    if send_quads(parser_reader.by_ref().map(|quad| Ok(quad?)), sender)? {
+        let base_iri = parser_reader.base_iri().map(str::to_owned);
+        let prefixes = parser_reader
+            .prefixes()
+            .map(|(pfx, ns)| (pfx.to_owned(), ns.to_owned()))
+            .collect();
+        let _ = sender.send(Parsed::End(base_iri, prefixes));
+    }
+    Ok(())
+}
+
+/// Sends quads a batch at a time, returning whether all were received (or failing on the first
+/// error, after sending the batches before it).
+fn send_quads(
+    mut quads: impl Iterator<Item = Result<Quad>>,
+    sender: &SyncSender<Parsed>,
+) -> Result<bool> {
+    loop {
+        let batch = quads
+            .by_ref()
+            .take(LOAD_BATCH_SIZE)
+            .collect::<Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            return Ok(true);
+        }
+        if sender.send(Parsed::Quads(batch)).is_err() {
+            return Ok(false);
+        }
+    }
+}
+
+fn load_document(url: &str) -> Result<LoadedDocument, Box<dyn Error + Send + Sync>> {
+    let content = if is_url(url) {
+        let (_, mut body) = http::get(url, "application/ld+json, application/json;q=0.9")?;
+        let mut content = Vec::new();
+        body.read_to_end(&mut content)?;
+        content
+    } else {
+        let Some(fpath) = url.strip_prefix("file://").or(url.strip_prefix("file:")) else {
+            return Err(format!("Unable to load document: {url}").into());
+        };
+        fs::read(fpath.replace("%20", " "))
+            .map_err(|e| format!("Unable to load document {url}: {e}"))?
+    };
+
+    Ok(LoadedDocument {
+        url: url.to_owned(),
+        content,
+        format: RdfFormat::JsonLd {
+            profile: JsonLdProfile::Context.into(),
+        },
+    })
+}
+
+/// Uses file path as named graph IRI.
+pub fn file_graph_iri(fpath: &str) -> String {
+    if fpath.starts_with('/') {
+        format!("file://{fpath}")
+    } else {
+        format!("file:{fpath}")
+    }
+    .replace(' ', "%20")
+}
+
+/// How query results are written (besides in which format).
+#[derive(Default)]
+struct ResultsStyle<'a> {
+    csv_dialect: Option<&'a CsvDialect>,
+    template: Option<&'a render::Template>,
+    /// Whether solutions are written as a table when no output format is given.
+    table: bool,
+    color: bool,
+}
+
+fn results_to_new_store_or_serialize<W: Write>(
+    results: QueryResults,
+    output_format: Option<&str>,
+    skolemizer: Option<&skolem::Skolemizer>,
+    style: &ResultsStyle,
+    prefixes: &HashMap<String, String>,
+    writer: W,
+) -> Result<Option<Store>> {
+    match results {
+        // Select:
+        QueryResults::Solutions(solutions) => {
+            let values = |solution: QuerySolution| {
+                let values = solution.values().iter().map(|value| {
+                    value.as_ref().map(|term| match skolemizer {
+                        Some(skolemizer) => skolemizer.term(term.clone()),
+                        None => term.clone(),
+                    })
+                });
+                values.collect::<Vec<_>>()
+            };
+            if let Some(template) = style.template {
+                let variables = solutions.variables().to_vec();
+                let solutions = solutions.map(|solution| Ok(values(solution?)));
+                template.write_solutions(&variables, solutions, writer)?;
+                return Ok(None);
+            }
+            let output_format = match output_format {
+                None if style.table && style.csv_dialect.is_none() => Some("table"),
+                output_format => output_format,
+            };
+            if let Some(format) = output_format.and_then(results::ResultsFormat::from_extension) {
+                let variables = solutions.variables().to_vec();
+                let solutions = solutions.map(|solution| Ok(values(solution?)));
+                results::write_solutions(
+                    format,
+                    &variables,
+                    solutions,
+                    prefixes,
+                    style.color,
+                    writer,
+                )?;
+                return Ok(None);
+            }
+            let format = get_queryresults_format(output_format)?;
+            if let Some(dialect) = style.csv_dialect {
+                let tsv = match format {
+                    QueryResultsFormat::Csv => false,
+                    QueryResultsFormat::Tsv => true,
+                    _ => bail!("A CSV dialect can only be used for CSV and TSV results"),
+                };
+                let mut writer =
+                    csv::SolutionsWriter::new(writer, dialect, tsv, solutions.variables())?;
+                for solution in solutions {
+                    writer.write(&values(solution?))?;
+                }
+                writer.finish()?;
+                return Ok(None);
+            }
+            let mut serializer = QueryResultsSerializer::from_format(format)
+                .serialize_solutions_to_writer(writer, solutions.variables().to_vec())?;
+            for solution in solutions {
+                let solution = solution?;
+                match skolemizer {
+                    Some(skolemizer) => {
+                        let values = solution
+                            .iter()
+                            .map(|(variable, value)| (variable, skolemizer.term(value.clone())))
+                            .collect::<Vec<_>>();
+                        serializer
+                            .serialize(values.iter().map(|(variable, value)| (*variable, value)))?
+                    }
+                    None => serializer.serialize(&solution)?,
+                }
+            }
+            serializer.finish()?;
+            // Done serializing:
+            Ok(None)
+        }
+
+        // Ask:
+        QueryResults::Boolean(result) => {
+            if let Some(template) = style.template {
+                // (Rendered as one solution binding `boolean`.)
+                let variables = [Variable::new_unchecked("boolean")];
+                let value = Some(Literal::from(result).into());
+                template.write_solutions(&variables, std::iter::once(Ok(vec![value])), writer)?;
+                return Ok(None);
+            }
+            if let Some(format) = output_format.and_then(results::ResultsFormat::from_extension) {
+                results::write_boolean(format, result, writer)?;
+                return Ok(None);
+            }
+            let format = get_queryresults_format(output_format)?;
+            QueryResultsSerializer::from_format(format)
+                .serialize_boolean_to_writer(writer, result)?;
+            // Done serializing:
+            Ok(None)
+        }
+
+        // Construct or Describe:
+        QueryResults::Graph(triples) => {
+            let store = Store::new()?;
+            for triple in triples {
+                store.insert(triple?.in_graph(GraphName::DefaultGraph).as_ref())?;
+            }
+            Ok(Some(store))
+        }
+    }
+}
+
+/// Joins a pattern with `VALUES`, within any solution modifiers, projection and grouping of it (so
+/// that variables which are not selected can be bound too, and aggregates are computed over the
+/// bound solutions only).
+fn join_values(pattern: &mut GraphPattern, values: &GraphPattern) {
+    let is_bound = |variable| matches!(values, GraphPattern::Values { variables, .. } if variables.contains(variable));
+    match pattern {
+        GraphPattern::Slice { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Project { inner, .. }
+        | GraphPattern::OrderBy { inner, .. }
+        | GraphPattern::Group { inner, .. }
+        | GraphPattern::Filter { inner, .. } => join_values(inner, values),
+        // (Unless it binds a bound variable, such as an aggregate selected by a name.)
+        GraphPattern::Extend {
+            inner, variable, ..
+        } if !is_bound(variable) => join_values(inner, values),
+        _ => {
+            let inner = std::mem::replace(pattern, values.clone());
+            *pattern = GraphPattern::Join {
+                left: Box::new(values.clone()),
+                right: Box::new(inner),
+            };
+        }
+    }
+}
+
+/// Skips and limits the solutions (or distinct triples) of query results.
+fn slice(results: QueryResults<'_>, offset: usize, limit: Option<usize>) -> QueryResults<'_> {
+    if offset == 0 && limit.is_none() {
+        return results;
+    }
+    let limit = limit.unwrap_or(usize::MAX);
+    match results {
+        QueryResults::Solutions(solutions) => {
+            let variables = solutions.variables().into();
+            QueryResults::Solutions(QuerySolutionIter::new(
+                variables,
+                solutions.skip(offset).take(limit),
+            ))
+        }
+        QueryResults::Graph(triples) => {
+            let mut seen = HashSet::new();
+            let triples = triples.filter(move |triple| {
+                triple
+                    .as_ref()
+                    .map_or(true, |triple| seen.insert(triple.clone()))
+            });
+            QueryResults::Graph(QueryTripleIter::new(triples.skip(offset).take(limit)))
+        }
+        results => results,
+    }
+}
+
+/// Writes the number of solutions (or distinct triples, or 1 if true and 0 if false) of query
+/// results.
+fn write_count<W: Write>(results: QueryResults, mut writer: W) -> Result<()> {
+    let count = match results {
+        QueryResults::Solutions(solutions) => {
+            let mut count = 0;
+            for solution in solutions {
+                solution?;
+                count += 1;
+            }
+            count
+        }
+        QueryResults::Boolean(result) => usize::from(result),
+        QueryResults::Graph(triples) => triples.collect::<Result<HashSet<_>, _>>()?.len(),
+    };
+    writeln!(writer, "{count}")?;
+    Ok(())
+}
+
+/// Writes a node of a query plan (as given in JSON by oxigraph), and its children indented below
+/// it.
+fn write_plan<W: Write>(node: &serde_json::Value, depth: usize, writer: &mut W) -> Result<()> {
+    write!(
+        writer,
+        "{}{}",
+        "  ".repeat(depth),
+        node["name"].as_str().unwrap_or("")
+    )?;
+    if let Some(count) = node["number of results"].as_u64() {
+        write!(writer, " [{count} results")?;
+        if let Some(seconds) = node["duration in seconds"].as_f64() {
+            write!(writer, ", {seconds}s")?;
+        }
+        write!(writer, "]")?;
+    }
+    writeln!(writer)?;
+    for child in node["children"].as_array().into_iter().flatten() {
+        write_plan(child, depth + 1, writer)?;
+    }
+    Ok(())
+}
+
+fn get_queryresults_format(output_format: Option<&str>) -> Result<QueryResultsFormat> {
+    if let Some(fmt) = output_format {
+        QueryResultsFormat::from_extension(fmt)
+            .with_context(|| format!("Unknown query results format: {fmt}"))
+    } else {
+        Ok(QueryResultsFormat::Tsv)
+    }
+}
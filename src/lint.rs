@@ -0,0 +1,115 @@
+//! Checks of loaded data for common problems (that parsers accept, but that are likely mistakes).
+
+use std::collections::{BTreeSet, HashSet};
+use std::io::Write;
+
+use anyhow::Result;
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{GraphName, Literal, NamedNodeRef, NamedOrBlankNode, Term};
+use oxigraph::store::Store;
+use regex::Regex;
+
+use crate::shacl::is_well_formed;
+
+/// IRI schemes in common use (other schemes are more likely to be undeclared prefixes).
+const KNOWN_SCHEMES: &[&str] = &[
+    "http", "https", "urn", "file", "mailto", "tag", "data", "did", "doi", "ftp", "geo", "info",
+    "isbn", "jar", "ldap", "news", "tel", "uuid", "ws", "wss", "about", "blob",
+];
+
+/// Writes a warning (prefixed by the source graph) for each problem found in the store, and
+/// returns the number of warnings.
+pub(crate) fn lint<W: Write>(store: &Store, mut writer: W) -> Result<usize> {
+    let checker = Checker::new();
+
+    let mut typed = HashSet::new();
+    for quad in store.quads_for_pattern(None, Some(rdf::TYPE), None, None) {
+        typed.insert(quad?.subject);
+    }
+
+    // Sorted by source and message, without duplicates:
+    let mut warnings = BTreeSet::new();
+    for quad in store.iter() {
+        let quad = quad?;
+        let source = match &quad.graph_name {
+            GraphName::NamedNode(node) => node.as_str().to_owned(),
+            _ => "(default graph)".to_owned(),
+        };
+        let mut warn = |message: String| {
+            warnings.insert((source.clone(), message));
+        };
+
+        if let NamedOrBlankNode::NamedNode(subject) = &quad.subject {
+            checker.check_iri(subject.as_ref(), &mut warn);
+            if !typed.contains(&quad.subject) {
+                warn(format!("Untyped resource {subject} (has no rdf:type)"));
+            }
+        }
+        checker.check_iri(quad.predicate.as_ref(), &mut warn);
+        match &quad.object {
+            Term::NamedNode(object) => checker.check_iri(object.as_ref(), &mut warn),
+            Term::Literal(literal) => checker.check_literal(literal, &mut warn),
+            _ => {}
+        }
+    }
+
+    for (source, message) in &warnings {
+        writeln!(writer, "{source}: {message}")?;
+    }
+    Ok(warnings.len())
+}
+
+struct Checker {
+    language_tag: Regex,
+    date: Regex,
+    date_time: Regex,
+}
+
+impl Checker {
+    fn new() -> Self {
+        let date = r"-?\d{4,}-\d{2}-\d{2}";
+        let zone = r"(Z|[+-]\d{2}:\d{2})?";
+        Self {
+            language_tag: Regex::new(r"^[a-zA-Z]{1,8}(-[a-zA-Z0-9]{1,8})*$").expect("valid regex"),
+            date: Regex::new(&format!("^{date}{zone}$")).expect("valid regex"),
+            date_time: Regex::new(&format!(r"^{date}T\d{{2}}:\d{{2}}:\d{{2}}(\.\d+)?{zone}$"))
+                .expect("valid regex"),
+        }
+    }
+
+    fn check_iri(&self, iri: NamedNodeRef<'_>, warn: &mut impl FnMut(String)) {
+        let value = iri.as_str();
+        if value.contains(char::is_whitespace) {
+            warn(format!("IRI with spaces: {iri}"));
+        }
+        if let Some((scheme, rest)) = value.split_once(':') {
+            if !KNOWN_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str())
+                && !rest.starts_with("//")
+            {
+                warn(format!(
+                    "IRI {iri} looks like a prefixed name (with undefined prefix '{scheme}:')"
+                ));
+            }
+        }
+    }
+
+    fn check_literal(&self, literal: &Literal, warn: &mut impl FnMut(String)) {
+        if let Some(language) = literal.language() {
+            if !self.language_tag.is_match(language) {
+                warn(format!("Invalid language tag in {literal}"));
+            }
+            return;
+        }
+        let datatype = literal.datatype();
+        let well_formed = if datatype == xsd::DATE {
+            self.date.is_match(literal.value())
+        } else if datatype == xsd::DATE_TIME {
+            self.date_time.is_match(literal.value())
+        } else {
+            is_well_formed(literal)
+        };
+        if !well_formed {
+            warn(format!("Malformed literal {literal}"));
+        }
+    }
+}
@@ -1,323 +1,1902 @@
-use std::collections::HashMap;
-use std::ffi::OsStr;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::collections::BTreeSet;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-use anyhow::{bail, Context, Result};
-use clap::Parser as CliParser;
+use anyhow::{Context, Result};
+use clap::builder::{PossibleValue, StringValueParser, TypedValueParser};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, CommandFactory, Parser as CliParser, ValueHint};
+use clap_complete::Shell;
 
-use oxigraph::io::{RdfFormat, RdfParser, RdfSerializer};
-use oxigraph::model::{GraphName, GraphNameRef, NamedNode};
-use oxigraph::sparql::results::{QueryResultsFormat, QueryResultsSerializer};
-use oxigraph::sparql::{Query, QueryResults, SparqlSyntaxError, Update};
-use oxigraph::store::{BulkLoader, Store};
+use oxigraph::model::{GraphName, GraphNameRef, NamedOrBlankNode};
+use oxigraph::sparql::CancellationToken;
+use oxiri::Iri;
+use oxrq::{
+    archive_entry_iri, file_graph_iri, is_archive, is_compressed_file, is_rdf_file, is_url,
+    open_file, open_url, read_archive, sniff_format, Config, CsvDialect, InferenceProfile,
+    InputFormat, OptionValue, PatchFormat, Quoting, Runner, SetOperation, SplitBy,
+};
+
+const INPUT_FORMATS: &[&str] = &[
+    "ttl", "trig", "rdf", "nt", "nq", "n3", "jsonld", "rj", "html", "hdt", "csv", "sqlite",
+];
+
+const OUTPUT_FORMATS: &[&str] = &[
+    "ttl", "trig", "rdf", "nt", "nq", "jsonld", "rj", "hdt", "tsv", "csv", "json", "xml", "md",
+    "html", "xlsx", "sqlite", "jsonl", "ndjson", "yaml", "table", "rdp",
+];
+
+/// The output formats for query results only.
+const RESULTS_FORMATS: &[&str] = &[
+    "tsv", "csv", "json", "xml", "md", "html", "xlsx", "sqlite", "jsonl", "ndjson", "yaml", "table",
+];
 
 #[derive(CliParser)]
 #[command(version, about, long_about = None)]
 struct CliArgs {
-    /// Input RDF format (ttl, rdf, nt, nq)
-    #[arg(short, long)]
+    /// Input RDF format (ttl, rdf, nt, nq, jsonld, rj, html, hdt), or CSV (csv) or SQLite database (sqlite), converted to RDF
+    #[arg(short, long, value_parser = FormatNames(INPUT_FORMATS), hide_possible_values = true)]
     input_format: Option<String>,
 
-    /// Output RDF format (ttl, rdf, nt, nq) or SPARQL results format (tsv, csv, json, xml)
-    #[arg(short, long)]
+    /// Convert CSV input to RDF by CSVW metadata, or by a JSON object of column names and properties (instead of properties made from the column names)
+    #[arg(long, value_name = "FILE")]
+    csv_mapping: Option<String>,
+
+    /// Convert SQLite databases to RDF by an R2RML mapping (in Turtle), instead of by the W3C Direct Mapping
+    #[arg(long, value_name = "FILE")]
+    r2rml: Option<String>,
+
+    /// Convert JSON input to RDF by an RML mapping (in Turtle, of values selected by JSONPath), instead of reading it as JSON-LD
+    #[arg(long, value_name = "FILE")]
+    rml: Option<String>,
+
+    /// Convert XML input to RDF by an RML mapping (in Turtle, of nodes selected by XPath), instead of reading it as RDF/XML
+    #[arg(long, value_name = "FILE")]
+    xml_mapping: Option<String>,
+
+    /// Output RDF format (ttl, rdf, nt, nq, jsonld, rj, hdt), SPARQL results format (tsv, csv, json, xml), Markdown table (md), HTML page (html), Excel workbook (xlsx), SQLite database (sqlite), JSON Lines (jsonl), YAML (yaml), aligned table (table) or RDF Patch of changes (rdp)
+    #[arg(short, long, value_parser = FormatNames(OUTPUT_FORMATS), hide_possible_values = true)]
     output_format: Option<String>,
 
+    /// Write output to a file (replaced once complete), in the format given by its suffix unless '-o' is used
+    #[arg(short = 'O', long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    output: Option<String>,
+
     /// Base IRI used when parsing
     #[arg(short, long)]
     base_iri: Option<String>,
 
+    /// File of prefixes to use in queries and output (Turtle or SPARQL prefix declarations, or a JSON object of prefixes and namespaces), taking precedence over prefixes declared in input data
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    prefixes: Option<String>,
+
+    /// Do not use the built-in well-known prefixes (rdf, rdfs, owl, xsd, skos, dc, dct, foaf, schema, sh, prov, dcat, void, time, geo, geof) in queries and output
+    #[arg(long)]
+    no_default_prefixes: bool,
+
+    /// Look up prefixes used but not declared in queries on prefix.cc (caching them in the user cache directory)
+    #[arg(long)]
+    lookup_prefixes: bool,
+
+    /// Directory to cache data fetched over HTTP(S) in (revalidating it when reused), instead of 'oxrq/http' in the user cache directory
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with = "no_cache")]
+    cache_dir: Option<PathBuf>,
+
+    /// Do not cache data fetched over HTTP(S)
+    #[arg(long)]
+    no_cache: bool,
+
     /// Provide query via file (with '.rq' suffix)
     #[arg(short, long)]
     file_query: bool,
 
+    /// Pretty-print Turtle and TriG output (grouping triples by subject, nesting blank nodes and abbreviating lists, and declaring only used prefixes)
+    #[arg(long)]
+    pretty: bool,
+
+    /// Declare only the prefixes used in output data (instead of all prefixes from the input)
+    #[arg(long)]
+    prune_prefixes: bool,
+
+    /// Named graph to output (as triples; an IRI, or the path of a file loaded into its own graph)
+    #[arg(long, value_name = "IRI")]
+    output_graph: Option<String>,
+
+    /// Output the triples of all graphs merged into the default graph (without duplicates), also in dataset formats
+    #[arg(long, conflicts_with = "output_graph")]
+    merge_graphs: bool,
+
+    /// Write each named graph (after any updates) to its own file in a directory (named by the graph IRI, in the format given by '-o', or else Turtle) instead of output
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with_all = ["output", "output_graph", "merge_graphs", "in_place", "serve", "explain", "stats", "namespaces"])]
+    split_graphs: Option<PathBuf>,
+
+    /// Write the description of each subject IRI, or of the instances of each class, to its own file in the directory given by '-O' (named by the IRI, in the format given by '-o', or else Turtle) instead of output
+    #[arg(long, value_name = "MODE", value_parser = ["subject", "class"], requires = "output", conflicts_with_all = ["split_graphs", "output_graph", "merge_graphs", "in_place", "serve", "explain", "stats", "namespaces"])]
+    split_by: Option<String>,
+
+    /// How blank nodes of different input files are merged: kept apart (standard), or the same for the same label (shared)
+    #[arg(long, value_name = "MODE", value_parser = ["standard", "shared"], default_value = "standard")]
+    bnode_merge: String,
+
+    /// Write output data sorted (by graph, subject, predicate and object, with canonical blank node labels), to give the same output for the same data
+    #[arg(long)]
+    sort: bool,
+
+    /// Field delimiter of CSV and TSV results (a character, or 'tab')
+    #[arg(long, value_name = "CHAR", value_parser = parse_delimiter)]
+    delimiter: Option<char>,
+
+    /// Quoting of fields in CSV and TSV results
+    #[arg(long, value_name = "STYLE", value_parser = ["minimal", "always", "never"])]
+    quoting: Option<String>,
+
+    /// Leave out the header row of CSV and TSV results
+    #[arg(long)]
+    no_header: bool,
+
+    /// Line terminator of CSV and TSV results
+    #[arg(long, value_name = "EOL", value_parser = ["lf", "crlf"])]
+    line_terminator: Option<String>,
+
+    /// Template to render query results through (in a subset of the Handlebars syntax), once per solution or, with an {{#each}} block, once for all solutions
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with = "output_format")]
+    template: Option<String>,
+
+    /// JSON-LD frame used to shape graph output (implies JSON-LD output)
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    frame: Option<String>,
+
+    /// Named graph to load input data into (instead of one per file; use 'FILE=IRI' for one file)
+    #[arg(short, long, value_name = "IRI")]
+    graph: Option<String>,
+
+    /// Load all input data into the default graph (instead of one named graph per file)
+    #[arg(short, long, conflicts_with = "graph")]
+    default_graph: bool,
+
+    /// Query the default graph as is (instead of as the union of all graphs)
+    #[arg(long)]
+    no_union: bool,
+
+    /// Persistent store directory to load data into and query (implies '--no-stdin')
+    #[arg(short, long, value_hint = ValueHint::DirPath)]
+    store: Option<String>,
+
+    /// Move the loaded data into a temporary on-disk store when it would use more memory than SIZE (in bytes, or with a K, M, G or T suffix)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with = "store")]
+    max_memory: Option<usize>,
+
+    /// Remote SPARQL endpoint to query (along with any given data; implies '--no-stdin')
+    #[arg(short, long)]
+    endpoint: Option<String>,
+
+    /// Allow SERVICE calls to remote SPARQL endpoints
+    #[arg(long)]
+    federation: bool,
+
+    /// Load data apart, for SERVICE calls to <urn:oxrq:NAME> in queries (as a virtual endpoint)
+    #[arg(long, value_name = "NAME=FILE_OR_DIR")]
+    service: Vec<String>,
+
+    /// Index the string literals of the data for keyword search in queries, with the functions oxrq:matches(TEXT, KEYWORDS) and oxrq:score(TEXT, KEYWORDS) (for ranking)
+    #[arg(long)]
+    text_search: bool,
+
+    /// Load a WebAssembly module whose exported functions are then available in queries (as oxrq:NAME)
+    #[arg(long, value_name = "FILE.wasm")]
+    plugin: Vec<PathBuf>,
+
+    /// Serve the data as a SPARQL endpoint on the given address (e.g. 'localhost:8000')
+    #[arg(long, value_name = "ADDR")]
+    serve: Option<String>,
+
+    /// Run the query and all given query files ('.rq') in order, each over the result of the previous
+    #[arg(short, long)]
+    pipeline: bool,
+
+    /// Run an update before the query (repeatable, run in order, followed by any '.ru' files)
+    #[arg(short, long, value_name = "UPDATE")]
+    update: Vec<String>,
+
+    /// Load the ontologies imported (by owl:imports) by the loaded data, recursively, each into a named graph named by its IRI (from a file given by '--catalog', or else over HTTP)
+    #[arg(long, conflicts_with = "in_place")]
+    imports: bool,
+
+    /// XML catalog (like the catalog-v001.xml of Protégé) mapping the IRIs of imported ontologies to local files
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, requires = "imports")]
+    catalog: Option<PathBuf>,
+
+    /// Fetch the documents of HTTP(S) IRIs used as objects in the loaded data (by any of the given predicates, or else by any), each into a named graph named by its URL, and those linked to from them in turn, up to N hops
+    #[arg(long, value_name = "N[:PREDICATE,...]", value_parser = parse_follow, conflicts_with = "in_place")]
+    follow: Option<(usize, Vec<String>)>,
+
+    /// Materialize the triples entailed under an inference profile (rdfs or owl-rl) into the default graph before running the query
+    #[arg(long, value_name = "PROFILE", value_parser = ["rdfs", "owl-rl"], conflicts_with = "in_place")]
+    inference: Option<String>,
+
+    /// Apply rules (N3 rules, or SPARQL CONSTRUCT queries in '.rq' files) until no new triples follow, adding those derived to the default graph before running the query (repeatable)
+    #[arg(long, value_name = "FILE", conflicts_with = "in_place")]
+    rules: Vec<PathBuf>,
+
+    /// Replace blank nodes in the output with skolem IRIs (made from BASE, by default 'urn:bnode:', and the canonical labels of the blank nodes)
+    #[arg(long, value_name = "BASE", num_args = 0..=1, require_equals = true, default_missing_value = "urn:bnode:")]
+    skolemize: Option<String>,
+
+    /// Bind a query variable to a value (an IRI like '<...>', a prefixed name, a literal like '"..."@en', or a plain value)
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_name_value)]
+    bind: Vec<(String, String)>,
+
+    /// Fill '{{NAME}}' placeholders in queries with a value (otherwise taken from environment variables)
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_name_value)]
+    param: Vec<(String, String)>,
+
+    /// Run updates and write each modified graph back to the file it was loaded from (instead of output)
+    #[arg(long, conflicts_with_all = ["graph", "default_graph", "output", "serve"])]
+    in_place: bool,
+
+    /// Compare two inputs (the first two arguments), writing removed and added triples (or results with '-o')
+    #[arg(long, conflicts_with_all = ["in_place", "serve", "output"])]
+    diff: bool,
+
+    /// Check whether two inputs (the first two arguments) are isomorphic, reporting any mismatch
+    #[arg(long, conflicts_with_all = ["diff", "in_place", "serve", "output"])]
+    isomorphic: bool,
+
+    /// Output the union of the triples of the inputs (the arguments), comparing blank nodes by the structures they are part of
+    #[arg(long, conflicts_with_all = ["diff", "isomorphic", "in_place", "serve"])]
+    union: bool,
+
+    /// Output the triples of the first input that are in all of the others (comparing blank nodes by the structures they are part of)
+    #[arg(long, conflicts_with_all = ["diff", "isomorphic", "in_place", "serve", "union"])]
+    intersect: bool,
+
+    /// Output the triples of the first input that are in none of the others (comparing blank nodes by the structures they are part of)
+    #[arg(long, conflicts_with_all = ["diff", "isomorphic", "in_place", "serve", "union", "intersect"])]
+    subtract: bool,
+
+    /// Validate the data against SHACL shapes (after any updates), outputting the validation report
+    #[arg(long, value_name = "SHAPES", value_hint = ValueHint::FilePath)]
+    shacl: Option<String>,
+
+    /// Validate the data against a ShEx schema (after any updates), for the nodes and shapes associated by '--shape-map', outputting the result shape map
+    #[arg(long, value_name = "SCHEMA", value_hint = ValueHint::FilePath, requires = "shape_map", conflicts_with = "shacl")]
+    shex: Option<String>,
+
+    /// Shape map (or file containing one) associating nodes (or triple patterns selecting them, like '{FOCUS a :Item}') with ShEx shapes
+    #[arg(long, value_name = "MAP", requires = "shex")]
+    shape_map: Option<String>,
+
+    /// Output just the number of solutions (or triples for CONSTRUCT or DESCRIBE, or of the data if no query is given)
+    #[arg(short, long, conflicts_with_all = ["frame", "quiet"])]
+    count: bool,
+
+    /// Output at most N solutions (or triples) of the query results (as if the query had a LIMIT)
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Skip the first N solutions (or triples) of the query results (as if the query had an OFFSET)
+    #[arg(long, value_name = "N")]
+    offset: Option<usize>,
+
+    /// Abort queries and updates still running after this many seconds (exiting with status 124)
+    #[arg(long, value_name = "SECONDS", conflicts_with = "serve")]
+    timeout: Option<u64>,
+
+    /// Write the optimized plan of the (last) query instead of its results (or, with '--explain=stats', evaluate it and add the number of results and time taken for each step)
+    #[arg(long, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "plan", value_parser = ["plan", "stats"], conflicts_with_all = ["in_place", "serve", "count"])]
+    explain: Option<String>,
+
+    /// Output a VoID description of the loaded data (after any updates) instead of the data: the numbers of triples, entities, distinct subjects and objects, a partition for each class and property, and the vocabularies used
+    #[arg(long, conflicts_with_all = ["in_place", "serve", "explain", "shacl", "shex"])]
+    void: bool,
+
+    /// Output a summary of the loaded data (after any updates) instead of the data: the number of quads in each graph, instances of each class, usages of each predicate, and literals of each datatype and language
+    #[arg(long, conflicts_with_all = ["in_place", "serve", "explain", "shacl", "shex", "void", "output_format"])]
+    stats: bool,
+
+    /// Output the namespaces of IRIs in the loaded data (after any updates) instead of the data, with the number of uses of each, any prefix declared for it, and a note if it looks mistyped (or like another one)
+    #[arg(long, conflicts_with_all = ["in_place", "serve", "explain", "shacl", "shex", "void", "stats", "output_format"])]
+    namespaces: bool,
+
+    /// Check the loaded data for common problems (like malformed literals, undefined-looking prefixes and untyped resources), writing a warning for each (and exiting with status 1 if there are any)
+    #[arg(long, conflicts_with_all = ["in_place", "serve", "explain", "check"])]
+    lint: bool,
+
+    /// Only parse the input data and queries (or updates), reporting any syntax errors (and exiting with status 1 if there are any)
+    #[arg(long, conflicts_with_all = ["in_place", "serve", "explain"])]
+    check: bool,
+
+    /// Output only the quads with this subject (as '--subject', but keeping their graphs, and looked up directly instead of by a query)
+    #[arg(long, value_name = "TERM", conflicts_with = "in_place")]
+    match_subject: Option<String>,
+
+    /// Output only the quads with this predicate (as '--predicate', but keeping their graphs, and looked up directly instead of by a query)
+    #[arg(long, value_name = "TERM", conflicts_with = "in_place")]
+    match_predicate: Option<String>,
+
+    /// Output only the quads with this object (as '--object', but keeping their graphs, and looked up directly instead of by a query)
+    #[arg(long, value_name = "TERM", conflicts_with = "in_place")]
+    match_object: Option<String>,
+
+    /// Output only the quads in this named graph (an IRI, or the path of a file loaded into its own graph), looked up directly instead of by a query
+    #[arg(long, value_name = "IRI", conflicts_with = "in_place")]
+    match_graph: Option<String>,
+
+    /// Output only the quads with an IRI or a literal value matching this regular expression, without running any query
+    #[arg(long, value_name = "REGEX", conflicts_with = "in_place")]
+    grep: Option<String>,
+
+    /// With '--grep', also output all other quads about the subjects of matching quads
+    #[arg(long, requires = "grep")]
+    grep_context: bool,
+
+    /// Write timings of each phase and the final store size (and, if repeated, the number of quads loaded from each input) to stderr
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Report progress (as a count of quads, on stderr) while loading and serializing data
+    #[arg(long)]
+    progress: bool,
+
+    /// When to use color in output (on a terminal unless NO_COLOR is set, by default)
+    #[arg(long, value_name = "WHEN", value_parser = ["auto", "always", "never"], default_value = "auto")]
+    color: String,
+
+    /// Do not write any output (e.g. when only checking the exit status of an ASK query)
+    #[arg(short, long, conflicts_with = "output")]
+    quiet: bool,
+
     /// Do not read from stdin (unless '-' is given as file)
     #[arg(short, long)]
     no_stdin: bool,
 
-    /// Query string (unless '--file-query' is used)
+    /// Query for the triples with this subject (an IRI, with or without angle brackets, or a prefixed name), reading all arguments as input
+    #[arg(long, value_name = "TERM", conflicts_with_all = ["file_query", "saved"])]
+    subject: Option<String>,
+
+    /// Query for the triples with this predicate (an IRI, a prefixed name or 'a'), reading all arguments as input
+    #[arg(long, value_name = "TERM", conflicts_with_all = ["file_query", "saved"])]
+    predicate: Option<String>,
+
+    /// Query for the triples with this object (an IRI, a prefixed name, a literal like '"..."@en', or a plain value), reading all arguments as input
+    #[arg(long, value_name = "TERM", conflicts_with_all = ["file_query", "saved"])]
+    object: Option<String>,
+
+    /// Describe a resource (an IRI, with or without angle brackets, or a prefixed name), reading all arguments as input
+    #[arg(long, value_name = "IRI", conflicts_with_all = ["file_query", "saved", "subject", "predicate", "object"])]
+    describe: Option<String>,
+
+    /// Run a saved query (NAME.rq in the 'queries' directory of the user configuration directory, or NAME in [queries] of a config file), reading all arguments as input
+    #[arg(long, value_name = "NAME", conflicts_with = "file_query")]
+    saved: Option<String>,
+
+    /// List the names of the saved queries
+    #[arg(long, exclusive = true)]
+    list_saved: bool,
+
+    /// Print shell completions (for bash, zsh, fish, elvish or powershell)
+    #[arg(long, value_name = "SHELL", exclusive = true)]
+    completions: Option<Shell>,
+
+    /// Query string (unless '--file-query' is used), or '-' to read the query from stdin
+    #[arg(value_hint = ValueHint::FilePath)]
     query: Option<String>,
 
-    /// RDF file(s)
+    /// RDF file(s), directories, glob patterns or HTTP(S) URL(s)
+    #[arg(value_hint = ValueHint::FilePath)]
     file: Vec<String>,
 }
 
+fn parse_name_value(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{value}'"))
+}
+
+fn parse_follow(value: &str) -> Result<(usize, Vec<String>), String> {
+    let (hops, predicates) = value.split_once(':').unwrap_or((value, ""));
+    let hops = hops
+        .parse()
+        .map_err(|_| format!("expected N[:PREDICATE,...], got '{value}'"))?;
+    let predicates = predicates
+        .split(',')
+        .filter(|predicate| !predicate.is_empty())
+        .map(|predicate| pattern_term(predicate.trim()))
+        .collect();
+    Ok((hops, predicates))
+}
+
+fn parse_size(value: &str) -> Result<usize, String> {
+    let digits = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let factor: usize = match value[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("unknown size unit in '{value}'")),
+    };
+    let size = digits
+        .parse::<f64>()
+        .map_err(|_| format!("expected a size (like 512M or 4G), got '{value}'"))?;
+    Ok((size * factor as f64) as usize)
+}
+
+fn parse_delimiter(value: &str) -> Result<char, String> {
+    let mut chars = value.chars();
+    match (value, chars.next(), chars.next()) {
+        ("tab" | "\\t", _, _) => Ok('\t'),
+        (_, Some(c), None) => Ok(c),
+        _ => Err(format!(
+            "expected a single character (or 'tab'), got '{value}'"
+        )),
+    }
+}
+
+/// Accepts any format name, while suggesting the known ones (for shell completion).
+#[derive(Clone)]
+struct FormatNames(&'static [&'static str]);
+
+impl TypedValueParser for FormatNames {
+    type Value = String;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<String, clap::Error> {
+        StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(self.0.iter().copied().map(PossibleValue::new)))
+    }
+}
+
+/// A file loaded into a named graph (kept for writing the graph back to it).
+struct Source {
+    graph_iri: String,
+    fpath: String,
+    format: InputFormat,
+}
+
 fn collect_input(
     args: &mut CliArgs,
-    store: &Store,
-    query_str: &mut String,
-    base_iri: &mut Option<String>,
-    prefixes: &mut HashMap<String, String>,
+    runner: &mut Runner,
+    queries: &mut Vec<String>,
+    sources: &mut Vec<Source>,
 ) -> Result<()> {
-    if let Some(value) = &args.base_iri {
-        base_iri.get_or_insert(value.to_owned());
-    }
+    let mut script_files: Vec<&str> = Vec::new();
+    let mut patch_files: Vec<(&str, PatchFormat, Option<&str>)> = Vec::new();
 
-    let mut query_file: Option<&str> = None;
+    // Read query from stdin (instead of data):
+    let query_from_stdin = args.query.as_deref() == Some("-") && args.serve.is_none();
+    if query_from_stdin {
+        args.query = None;
+        if args.file.iter().any(|arg| split_graph_iri(arg).0 == "-") {
+            anyhow::bail!("Stdin cannot be read for both query and data");
+        }
+    }
 
-    // Use query as file:
-    if args.file_query {
-        if let Some(actually_fpath) = &args.query {
-            args.file.push(actually_fpath.to_owned());
-            args.query = None;
+    // Use query as (first) file (no query is given when serving, validating, linting or describing
+    // the data, and may be left out to just run updates, or to convert the input):
+    let is_input_query = args
+        .query
+        .as_deref()
+        .is_some_and(|arg| is_input(split_graph_iri(arg).0));
+    let is_validating = args.shacl.is_some() || args.shex.is_some() || args.lint;
+    let is_describing = args.void || args.stats || args.namespaces;
+    if args.file_query || args.serve.is_some() || is_validating || is_describing || is_input_query {
+        if let Some(actually_fpath) = args.query.take() {
+            args.file.insert(0, actually_fpath);
         }
     }
 
-    let loader = store.bulk_loader();
+    let mut use_stdin =
+        !args.no_stdin && !query_from_stdin && args.endpoint.is_none() && args.store.is_none();
 
-    let mut use_stdin = !args.no_stdin;
+    // Read data from files (parsing consecutive local files in parallel):
+    let mut files = Vec::new();
+    for arg in &args.file {
+        let (fpath, graph_iri) = split_graph_iri(arg);
+        let graph_iri = graph_iri.or(args.graph.as_deref());
 
-    // Read data from files:
-    for fpath in &args.file {
         if fpath == "-" {
-            load_from_stdin(&loader, &args.input_format, base_iri, prefixes)?;
+            load_files(runner, &mut files, sources, args.verbose)?;
+            let loaded_quads = runner.stats().loaded_quads;
+            load_from_stdin(runner, &args.input_format, graph_iri)?;
+            report_loaded(runner, loaded_quads, "stdin", args.verbose);
             use_stdin = false;
             continue;
         }
 
-        let path = Path::new(fpath);
-        let ext = path
+        if Path::new(fpath)
             .extension()
-            .and_then(OsStr::to_str)
-            .with_context(|| format!("Needs file extensions to detect input format"))?;
+            .is_some_and(|ext| ext == "rq" || ext == "ru")
+        {
+            script_files.push(fpath);
+            continue;
+        }
 
-        if ext == "rq" {
-            query_file = Some(fpath);
+        if let Some(format) = patch_format(fpath) {
+            patch_files.push((fpath, format, graph_iri));
             continue;
         }
 
         use_stdin = false;
 
-        let format = RdfFormat::from_extension(ext)
-            .with_context(|| format!("No RDF format found for extension {ext}"))?;
+        if is_url(fpath) {
+            load_files(runner, &mut files, sources, args.verbose)?;
+            let (format, reader) = open_url(fpath, http_cache_dir(args).as_deref())?;
+            let graph_iri = graph_iri.or((!args.default_graph).then_some(fpath));
+            let loaded_quads = runner.stats().loaded_quads;
+            if let Err(e) = runner.load_graph_with_base(reader, format, graph_iri, fpath) {
+                report_file_error(fpath, &e);
+            } else {
+                report_loaded(runner, loaded_quads, fpath, args.verbose);
+            }
+            continue;
+        }
+
+        for fpath in expand_path(fpath)? {
+            if is_archive(&fpath) {
+                load_files(runner, &mut files, sources, args.verbose)?;
+                let archive_iri = file_graph_iri(&fpath);
+                read_archive(&fpath, |name, format, reader| {
+                    let entry_iri = archive_entry_iri(&archive_iri, name);
+                    let graph_iri = graph_iri.or((!args.default_graph).then_some(&entry_iri));
+                    let loaded_quads = runner.stats().loaded_quads;
+                    if let Err(e) =
+                        runner.load_graph_with_base(reader, format, graph_iri, &entry_iri)
+                    {
+                        report_file_error(&format!("{fpath}!/{name}"), &e);
+                    } else {
+                        let source = format!("{fpath}!/{name}");
+                        report_loaded(runner, loaded_quads, &source, args.verbose);
+                    }
+                    Ok(())
+                })?;
+                continue;
+            }
+            let file_iri = file_graph_iri(&fpath);
+            let graph_iri = graph_iri.or((!args.default_graph).then_some(&file_iri));
+            let graph_iri = graph_iri.map(str::to_owned);
+            files.push((fpath, graph_iri));
+        }
+    }
+    load_files(runner, &mut files, sources, args.verbose)?;
 
-        let file = File::open(path).with_context(|| format!("Unable to open file: {fpath}"))?;
-        let reader = BufReader::new(file);
+    // Read data from stdin:
+    if use_stdin {
+        let loaded_quads = runner.stats().loaded_quads;
+        load_from_stdin(runner, &args.input_format, args.graph.as_deref())?;
+        report_loaded(runner, loaded_quads, "stdin", args.verbose);
+    }
 
-        // Use file path as named graph IRI
-        let graph_iri = if fpath.starts_with("/") {
-            format!("file://{fpath}")
-        } else {
-            format!("file:{fpath}")
+    // Apply patches to the loaded data:
+    for (fpath, format, graph_iri) in patch_files {
+        let file = File::open(fpath).with_context(|| format!("Unable to open patch: {fpath}"))?;
+        runner
+            .apply_patch(file, format, graph_iri, &file_graph_iri(fpath))
+            .with_context(|| format!("Error in patch '{fpath}'"))?;
+    }
+
+    // Get updates, run in order before the query and any query and update files (which are run
+    // in the order given, after the query if followed by any query files):
+    for update_body in &args.update {
+        queries.push(runner.prepare_query(update_body));
+    }
+    let query_body = match &args.query {
+        Some(query_body) => Some(query_body.clone()),
+        None if query_from_stdin => {
+            let mut query_body = String::new();
+            std::io::stdin().read_to_string(&mut query_body)?;
+            Some(query_body)
+        }
+        None => None,
+    };
+    let mut query_body = query_body.map(|query_body| runner.prepare_query(&query_body));
+    for fpath in script_files {
+        if fpath.ends_with(".rq") {
+            queries.extend(query_body.take());
         }
-        .replace(" ", "%20");
+        queries.push(read_query_file(fpath)?);
+    }
+    queries.extend(query_body);
 
-        let parser = RdfParser::from_format(format)
-            .with_default_graph(NamedNode::new(&graph_iri)?)
-            .with_base_iri(base_iri.as_ref().unwrap_or(&graph_iri))?;
+    Ok(())
+}
 
-        if let Err(e) = load_data(&loader, parser, reader, base_iri, prefixes) {
-            eprintln!("Error in file '{fpath}': {e}");
+/// Loads the data of a virtual endpoint (from files, or all files in a directory), into a graph
+/// per file (unless loading into the default graph), reporting any errors in them.
+fn load_service(fpath: &str, default_graph: bool) -> Result<Runner> {
+    let mut runner = Runner::new()?;
+    let mut files = Vec::new();
+    for fpath in expand_path(fpath)? {
+        if is_archive(&fpath) {
+            let archive_iri = file_graph_iri(&fpath);
+            read_archive(&fpath, |name, format, reader| {
+                let entry_iri = archive_entry_iri(&archive_iri, name);
+                let graph_iri = (!default_graph).then_some(entry_iri.as_str());
+                if let Err(e) = runner.load_graph_with_base(reader, format, graph_iri, &entry_iri) {
+                    report_file_error(&format!("{fpath}!/{name}"), &e);
+                }
+                Ok(())
+            })?;
             continue;
         }
+        let graph_iri = (!default_graph).then(|| file_graph_iri(&fpath));
+        files.push((fpath, graph_iri));
     }
-
-    // Read data from stdin:
-    if use_stdin {
-        load_from_stdin(&loader, &args.input_format, base_iri, prefixes)?;
+    for ((fpath, _), result) in files.iter().zip(runner.load_files(&files)?) {
+        if let Err(e) = result {
+            report_file_error(fpath, &e);
+        }
     }
+    Ok(runner)
+}
 
-    // Get query:
-    if let Some(fpath) = query_file {
-        let path = Path::new(&fpath);
-        let mut file =
-            File::open(path).with_context(|| format!("Unable to open query file: {fpath}"))?;
-        file.read_to_string(query_str)?;
-    } else if let Some(query_body) = &args.query {
-        // Prepend found prefixes to query:
-        for (pfx, ns) in prefixes.iter() {
-            query_str.push_str(&format!("PREFIX {pfx}: <{ns}>\n"));
+/// Loads the collected files (in parallel), reporting any errors in them.
+fn load_files(
+    runner: &mut Runner,
+    files: &mut Vec<(String, Option<String>)>,
+    sources: &mut Vec<Source>,
+    verbose: u8,
+) -> Result<()> {
+    let results = runner.load_files(files)?;
+    for ((fpath, graph_iri), result) in files.drain(..).zip(results) {
+        let format = match result {
+            Ok((format, loaded_quads)) => {
+                if verbose >= 2 {
+                    eprintln!("Loaded {loaded_quads} quads from {fpath}");
+                }
+                format
+            }
+            Err(e) => {
+                report_file_error(&fpath, &e);
+                continue;
+            }
+        };
+        if let Some(graph_iri) = graph_iri {
+            sources.push(Source {
+                graph_iri,
+                fpath,
+                format,
+            });
         }
-        // Get query body:
-        query_str.push_str(&format!("{}", query_body));
     }
+    Ok(())
+}
+
+/// Reports an error in an input file (which is then skipped, with none of its data kept, unless
+/// loaded into a graph with other data).
+fn report_file_error(fpath: &str, err: &anyhow::Error) {
+    eprintln!("Error in file '{fpath}': {err}");
+    FILE_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Reports the number of quads loaded from an input since the given total (with '-vv').
+fn report_loaded(runner: &Runner, loaded_quads: usize, source: &str, verbose: u8) {
+    if verbose >= 2 {
+        let loaded_quads = runner.stats().loaded_quads - loaded_quads;
+        eprintln!("Loaded {loaded_quads} quads from {source}");
+    }
+}
 
+/// Wall-clock times of the phases run (for '--verbose').
+#[derive(Default)]
+struct Timings {
+    reading: Duration,
+    inference: Option<Duration>,
+    running: Duration,
+}
+
+/// Writes the time spent in each phase and the final store size to stderr (with '-v').
+fn report_stats(runner: &Runner, timings: &Timings, verbose: u8) -> Result<()> {
+    if verbose == 0 {
+        return Ok(());
+    }
+    let stats = runner.stats();
+    eprintln!(
+        "Parsing: {:.1?}",
+        timings.reading.saturating_sub(stats.load_time)
+    );
+    eprintln!(
+        "Loading: {:.1?} ({} quads)",
+        stats.load_time, stats.loaded_quads
+    );
+    if let Some(inference) = timings.inference {
+        eprintln!("Inference: {inference:.1?}");
+    }
+    let query_time = timings.running.saturating_sub(stats.serialize_time);
+    eprintln!("Query: {query_time:.1?}");
+    eprintln!("Serializing: {:.1?}", stats.serialize_time);
+    eprintln!("Store size: {} quads", runner.store().len()?);
     Ok(())
 }
 
+fn read_query_file(fpath: &str) -> Result<String> {
+    let mut file =
+        File::open(fpath).with_context(|| format!("Unable to open query file: {fpath}"))?;
+    let mut query_str = String::new();
+    file.read_to_string(&mut query_str)?;
+    Ok(query_str)
+}
+
+fn patch_format(fpath: &str) -> Option<PatchFormat> {
+    PatchFormat::from_extension(Path::new(fpath).extension()?.to_str()?)
+}
+
 fn load_from_stdin(
-    loader: &BulkLoader,
+    runner: &mut Runner,
     input_format: &Option<String>,
-    base_iri: &mut Option<String>,
-    prefixes: &mut HashMap<String, String>,
+    graph_iri: Option<&str>,
 ) -> Result<()> {
-    let format = if let Some(fmt) = input_format {
-        RdfFormat::from_extension(&fmt).with_context(|| format!("Unknown input format: {fmt}"))?
-    } else {
-        RdfFormat::Turtle
-    };
     let stdin = std::io::stdin();
-    let reader = BufReader::new(stdin.lock());
+    let Some(graph_iri) = graph_iri else {
+        return runner.load_reader(stdin.lock(), input_format.as_deref());
+    };
+    let (mut format, reader) = sniff_format(stdin.lock())?;
+    if let Some(fmt) = input_format {
+        format = InputFormat::from_extension(fmt)
+            .with_context(|| format!("Unknown input format: {fmt}"))?;
+    }
+    runner.load_graph(reader, format, graph_iri)
+}
 
-    let mut parser = RdfParser::from_format(format);
-    if let Some(value) = base_iri {
-        parser = parser.with_base_iri(value.to_owned())?;
+/// Expands a directory (recursively) or a glob pattern into the RDF files within it.
+fn expand_path(fpath: &str) -> Result<Vec<String>> {
+    let path = Path::new(fpath);
+    if path.is_dir() {
+        let mut fpaths = Vec::new();
+        collect_dir_files(path, &mut fpaths)?;
+        return Ok(fpaths);
+    }
+    if path.exists() || !fpath.contains(['*', '?', '[']) {
+        return Ok(vec![fpath.to_owned()]);
     }
 
-    load_data(&loader, parser, reader, base_iri, prefixes)?;
+    let mut fpaths = Vec::new();
+    for entry in glob::glob(fpath).with_context(|| format!("Invalid pattern: {fpath}"))? {
+        let path = entry?;
+        if path.is_dir() {
+            collect_dir_files(&path, &mut fpaths)?;
+        } else {
+            fpaths.push(path.to_string_lossy().into_owned());
+        }
+    }
+    if fpaths.is_empty() {
+        anyhow::bail!("No files found matching: {fpath}");
+    }
 
-    return Ok(());
+    Ok(fpaths)
 }
 
-fn load_data<R: Read>(
-    loader: &BulkLoader,
-    parser: RdfParser,
-    reader: BufReader<R>,
-    base_iri: &mut Option<String>,
-    prefixes: &mut HashMap<String, String>,
-) -> Result<()> {
-    let mut parser_reader = parser.rename_blank_nodes().for_reader(reader);
-    let quads = parser_reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+fn collect_dir_files(dir: &Path, fpaths: &mut Vec<String>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Unable to read directory: {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
 
-    loader.load_quads(quads)?;
+    for path in entries {
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            collect_dir_files(&path, fpaths)?;
+        } else {
+            let fpath = path.to_string_lossy().into_owned();
+            if is_rdf_file(&fpath) || is_archive(&fpath) {
+                fpaths.push(fpath);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks if an argument names an existing file (or directory) or a URL.
+fn is_input(fpath: &str) -> bool {
+    is_url(fpath) || Path::new(fpath).exists()
+}
 
-    if let Some(value) = parser_reader.base_iri() {
-        base_iri.get_or_insert(value.to_owned());
+/// Splits a 'FILE=IRI' argument into the file and the named graph to load it into.
+fn split_graph_iri(arg: &str) -> (&str, Option<&str>) {
+    if let Some((fpath, iri)) = arg.rsplit_once('=') {
+        if (fpath == "-" || is_input(fpath)) && Iri::parse(iri).is_ok() {
+            return (fpath, Some(iri));
+        }
     }
+    (arg, None)
+}
+
+#[cfg(feature = "rocksdb")]
+fn with_store(runner: Runner, path: &str) -> Result<Runner> {
+    runner.with_store(path)
+}
+
+#[cfg(feature = "rocksdb")]
+fn with_max_memory(runner: Runner, max_memory: usize) -> Result<Runner> {
+    Ok(runner.with_max_memory(max_memory))
+}
+
+#[cfg(not(feature = "rocksdb"))]
+fn with_max_memory(_runner: Runner, _max_memory: usize) -> Result<Runner> {
+    anyhow::bail!("Spilling to disk requires oxrq to be built with the 'rocksdb' feature")
+}
+
+#[cfg(not(feature = "rocksdb"))]
+fn with_store(_runner: Runner, _path: &str) -> Result<Runner> {
+    anyhow::bail!("Persistent stores require oxrq to be built with the 'rocksdb' feature")
+}
+
+/// Set (to the number of seconds) once '--timeout' has cancelled running queries and updates.
+static TIMED_OUT: OnceLock<u64> = OnceLock::new();
+
+/// The number of input files skipped due to errors in them.
+static FILE_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Time given to cancelled queries and updates to stop before exiting anyway (as evaluation of
+/// e.g. joins of already computed solutions does not check for cancellation).
+const TIMEOUT_GRACE_PERIOD: Duration = Duration::from_secs(1);
 
-    for (pfx, ns) in parser_reader.prefixes() {
-        if !prefixes.contains_key(pfx) {
-            prefixes.insert(pfx.to_owned(), ns.to_owned());
+fn main() {
+    // Exit with status 2 on errors (as status 1 means false, different or not conforming):
+    if let Err(err) = run() {
+        if let Some(seconds) = TIMED_OUT.get() {
+            exit_timed_out(*seconds);
         }
+        eprintln!("Error: {err:?}");
+        std::process::exit(2);
     }
+}
 
-    Ok(())
+/// Exits with status 124 (like `timeout`), noting that any output is partial.
+fn exit_timed_out(seconds: u64) -> ! {
+    eprintln!("Error: Timed out after {seconds}s (any output written is incomplete)");
+    std::process::exit(124);
 }
 
-fn query_to_new_store_or_serialize<W: Write>(
-    store: &Store,
-    mut query: Query,
-    output_format: &Option<String>,
-    writer: W,
-) -> Result<Option<Store>> {
-    query.dataset_mut().set_default_graph_as_union();
-    let results = store.query(query).context("Query failed")?;
-    match results {
-        // Select:
-        QueryResults::Solutions(solutions) => {
-            let format = get_queryresults_format(output_format)?;
-            let mut serializer = QueryResultsSerializer::from_format(format)
-                .serialize_solutions_to_writer(writer, solutions.variables().to_vec())?;
-            for solution in solutions {
-                serializer.serialize(&solution?)?;
+fn run() -> Result<()> {
+    let mut config = Config::load(&config_files())?;
+    config.read_env();
+    let mut args = parse_args(&config)?;
+
+    if let Some(shell) = args.completions {
+        clap_complete::generate(
+            shell,
+            &mut CliArgs::command(),
+            "oxrq",
+            &mut std::io::stdout(),
+        );
+        return Ok(());
+    }
+
+    // Refuse to write binary output to a terminal (as it would garble it):
+    let is_binary_output = matches!(
+        args.output_format.as_deref(),
+        Some("xlsx" | "sqlite" | "hdt")
+    );
+    if is_binary_output && args.output.is_none() && !args.quiet && std::io::stdout().is_terminal() {
+        anyhow::bail!(
+            "Not writing binary {} output to a terminal (use --output FILE, or redirect it)",
+            args.output_format.as_deref().unwrap_or_default()
+        );
+    }
+
+    if args.diff || args.isomorphic {
+        return compare_inputs(&args);
+    }
+    let set_operation = match (args.union, args.intersect, args.subtract) {
+        (true, _, _) => Some(SetOperation::Union),
+        (_, true, _) => Some(SetOperation::Intersection),
+        (_, _, true) => Some(SetOperation::Difference),
+        _ => None,
+    };
+    if let Some(operation) = set_operation {
+        return combine_inputs(&args, operation);
+    }
+
+    if args.list_saved {
+        for name in saved_query_names(&config)? {
+            println!("{name}");
+        }
+        return Ok(());
+    }
+
+    if let Some(resource) = &args.describe {
+        args.file.splice(0..0, args.query.take());
+        args.query = Some(format!("DESCRIBE {}", pattern_term(resource)));
+    } else if args.subject.is_some() || args.predicate.is_some() || args.object.is_some() {
+        args.file.splice(0..0, args.query.take());
+        args.query = Some(pattern_query(&args));
+    } else if let Some(name) = &args.saved {
+        args.file.splice(0..0, args.query.take());
+        args.query = Some(saved_query(&config, name)?);
+    } else if let Some(name) = args
+        .query
+        .as_deref()
+        .and_then(|query| query.strip_prefix('@'))
+    {
+        if !args.file_query {
+            args.query = Some(saved_query(&config, name)?);
+        }
+    }
+    if let Some(url) = args
+        .endpoint
+        .as_ref()
+        .and_then(|name| config.endpoints.get(name))
+    {
+        args.endpoint = Some(url.clone());
+    }
+
+    let mut runner = Runner::new()?;
+    if let Some(value) = &args.base_iri {
+        runner = runner.with_base_iri(value);
+    }
+    if args.no_default_prefixes {
+        runner = runner.without_default_prefixes();
+    }
+    for (pfx, ns) in &config.prefixes {
+        runner = runner
+            .with_prefix(pfx, ns)
+            .context("Error in [prefixes] of a config file")?;
+    }
+    if let Some(fpath) = &args.prefixes {
+        let text = fs::read_to_string(fpath)
+            .with_context(|| format!("Unable to read prefixes: {fpath}"))?;
+        runner = runner
+            .with_prefixes_file(&text)
+            .with_context(|| format!("Error in prefixes file '{fpath}'"))?;
+    }
+    if let Some(path) = &args.store {
+        runner = with_store(runner, path)?;
+    }
+    if let Some(max_memory) = args.max_memory {
+        runner = with_max_memory(runner, max_memory)?;
+    }
+    if let Some(value) = &args.endpoint {
+        runner = runner.with_endpoint(value);
+    }
+    if args.federation {
+        runner = runner.with_federation();
+    }
+    for service in &args.service {
+        let Some((name, fpath)) = service.split_once('=') else {
+            anyhow::bail!("Expected NAME=FILE_OR_DIR for --service: {service}");
+        };
+        runner = runner.with_service(name, load_service(fpath, args.default_graph)?)?;
+    }
+    if args.text_search {
+        runner = runner.with_text_search();
+    }
+    for path in &args.plugin {
+        runner = runner.with_plugin(path)?;
+    }
+    if args.no_union {
+        runner = runner.without_default_graph_as_union();
+    }
+    if args.pretty {
+        runner = runner.with_pretty();
+    }
+    if args.prune_prefixes {
+        runner = runner.with_pruned_prefixes();
+    }
+    if args.sort {
+        runner = runner.with_sort();
+    }
+    if args.delimiter.is_some()
+        || args.quoting.is_some()
+        || args.no_header
+        || args.line_terminator.is_some()
+    {
+        runner = runner.with_csv_dialect(CsvDialect {
+            delimiter: args.delimiter,
+            quoting: args.quoting.as_deref().and_then(Quoting::from_name),
+            no_header: args.no_header,
+            line_terminator: args.line_terminator.as_deref().map(|eol| match eol {
+                "crlf" => "\r\n".to_owned(),
+                _ => "\n".to_owned(),
+            }),
+        });
+    }
+    if let Some(fpath) = &args.template {
+        let template = fs::read_to_string(fpath)
+            .with_context(|| format!("Unable to read template: {fpath}"))?;
+        runner = runner.with_results_template(&template)?;
+    }
+    if let Some(graph) = &args.output_graph {
+        let graph_iri = match Path::new(graph).exists() {
+            true => file_graph_iri(graph),
+            false => graph.to_owned(),
+        };
+        runner = runner.with_output_graph(&graph_iri)?;
+    }
+    if args.merge_graphs {
+        runner = runner.with_merged_graphs();
+    }
+    if args.bnode_merge == "shared" {
+        runner = runner.with_shared_blank_nodes();
+    }
+    if let Some(dir) = http_cache_dir(&args) {
+        runner = runner.with_http_cache(dir);
+    }
+    if args.progress {
+        runner = runner.with_progress();
+    }
+    if args.count {
+        runner = runner.with_count();
+    }
+    if let Some(limit) = args.limit {
+        runner = runner.with_limit(limit);
+    }
+    if let Some(offset) = args.offset {
+        runner = runner.with_offset(offset);
+    }
+    if let Some(base) = &args.skolemize {
+        Iri::parse(base.as_str()).with_context(|| format!("Invalid skolem base IRI: {base}"))?;
+        runner = runner.with_skolemization(base);
+    }
+    let cancellation_token = CancellationToken::new();
+    if args.timeout.is_some() {
+        runner = runner.with_cancellation_token(cancellation_token.clone());
+    }
+    for (name, value) in &args.bind {
+        runner = runner.with_binding(name, value);
+    }
+    for (name, value) in &args.param {
+        runner = runner.with_param(name, value);
+    }
+    if let Some(fpath) = &args.frame {
+        let file = File::open(fpath).with_context(|| format!("Unable to open frame: {fpath}"))?;
+        let frame = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Invalid JSON in frame: {fpath}"))?;
+        runner = runner.with_frame(frame);
+    }
+    if let Some(fpath) = &args.csv_mapping {
+        let file =
+            File::open(fpath).with_context(|| format!("Unable to open CSV mapping: {fpath}"))?;
+        let mapping: serde_json::Value = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Invalid JSON in CSV mapping: {fpath}"))?;
+        runner = runner
+            .with_csv_mapping(&mapping)
+            .with_context(|| format!("Error in CSV mapping '{fpath}'"))?;
+    }
+    if let Some(fpath) = &args.r2rml {
+        let file =
+            File::open(fpath).with_context(|| format!("Unable to open R2RML mapping: {fpath}"))?;
+        runner = runner
+            .with_r2rml(BufReader::new(file), &file_graph_iri(fpath))
+            .with_context(|| format!("Error in R2RML mapping '{fpath}'"))?;
+    }
+    if let Some(fpath) = &args.rml {
+        let file =
+            File::open(fpath).with_context(|| format!("Unable to open RML mapping: {fpath}"))?;
+        runner = runner
+            .with_rml(BufReader::new(file), &file_graph_iri(fpath))
+            .with_context(|| format!("Error in RML mapping '{fpath}'"))?;
+    }
+    if let Some(fpath) = &args.xml_mapping {
+        let file =
+            File::open(fpath).with_context(|| format!("Unable to open XML mapping: {fpath}"))?;
+        runner = runner
+            .with_xml_mapping(BufReader::new(file), &file_graph_iri(fpath))
+            .with_context(|| format!("Error in XML mapping '{fpath}'"))?;
+    }
+
+    let mut queries = Vec::new();
+    let mut sources = Vec::new();
+
+    let mut timings = Timings::default();
+    let start = Instant::now();
+    collect_input(&mut args, &mut runner, &mut queries, &mut sources)?;
+    timings.reading = start.elapsed();
+
+    // Run any configured default query when no query (or update) is given, instead of converting
+    // the input:
+    let is_validating = args.shacl.is_some() || args.shex.is_some() || args.lint;
+    let is_describing = args.void || args.stats || args.namespaces;
+    if queries.is_empty() && args.serve.is_none() && !is_validating && !is_describing {
+        if let Some(query) = config.default_query() {
+            queries.push(runner.prepare_query(query));
+        }
+    }
+
+    if args.imports {
+        let start = Instant::now();
+        for iri in runner.load_imports(args.catalog.as_deref())? {
+            if args.verbose >= 2 {
+                eprintln!("Loaded imported ontology {iri}");
             }
-            // Done serializing:
-            return Ok(None);
         }
+        timings.reading += start.elapsed();
+    }
 
-        // Ask:
-        QueryResults::Boolean(result) => {
-            let format = get_queryresults_format(output_format)?;
-            QueryResultsSerializer::from_format(format)
-                .serialize_boolean_to_writer(writer, result)?;
-            // Done serializing:
-            return Ok(None);
+    if let Some((hops, predicates)) = &args.follow {
+        let start = Instant::now();
+        for (url, result) in runner.follow_links(*hops, predicates)? {
+            match result {
+                Ok(loaded_quads) if args.verbose >= 2 => {
+                    eprintln!("Loaded {loaded_quads} quads from {url}");
+                }
+                Ok(_) => {}
+                Err(e) => report_file_error(&url, &e),
+            }
         }
+        timings.reading += start.elapsed();
+    }
+
+    if args.lookup_prefixes {
+        runner.lookup_prefixes(&mut queries, prefix_cache_file().as_deref())?;
+    }
 
-        // Construct or Describe:
-        QueryResults::Graph(triples) => {
-            let store = Store::new()?;
-            for triple in triples {
-                store.insert(triple?.in_graph(GraphName::DefaultGraph).as_ref())?;
+    if args.check {
+        let mut errors = FILE_ERRORS.load(Ordering::Relaxed);
+        for query_str in &queries {
+            if let Err(e) = runner.check_query(query_str) {
+                eprintln!("Error in query: {e}");
+                errors += 1;
             }
-            return Ok(Some(store));
         }
+        if errors > 0 {
+            drop(runner); // (removing any temporary store)
+            std::process::exit(1);
+        }
+        return Ok(());
     }
-}
 
-fn get_queryresults_format(output_format: &Option<String>) -> Result<QueryResultsFormat> {
-    if let Some(fmt) = output_format {
-        QueryResultsFormat::from_extension(&fmt)
-            .with_context(|| format!("Unknown query results format: {fmt}"))
-    } else {
-        Ok(QueryResultsFormat::Tsv)
+    if args.lint {
+        let warnings = runner.lint(std::io::stdout().lock())?;
+        let errors = FILE_ERRORS.load(Ordering::Relaxed);
+        if warnings + errors > 0 {
+            eprintln!("{warnings} warnings ({errors} files with errors)");
+            drop(runner); // (removing any temporary store)
+            std::process::exit(1);
+        }
+        return Ok(());
     }
-}
 
-fn main() -> Result<()> {
-    let mut store = Store::new()?;
-    let mut query_str = String::new();
-    let mut prefixes: HashMap<String, String> = HashMap::new();
-    let mut base_iri: Option<String> = None;
+    if let Some(name) = &args.inference {
+        let profile = InferenceProfile::from_name(name)
+            .with_context(|| format!("Unknown inference profile: {name}"))?;
+        let start = Instant::now();
+        runner.materialize(profile)?;
+        timings.inference = Some(start.elapsed());
+    }
+    if !args.rules.is_empty() {
+        let start = Instant::now();
+        runner.apply_rules(&args.rules)?;
+        timings.inference = Some(timings.inference.unwrap_or_default() + start.elapsed());
+    }
 
-    let mut args = CliArgs::parse();
+    // Filter the data by a quad pattern, or a regular expression (without running a query):
+    if args.match_subject.is_some()
+        || args.match_predicate.is_some()
+        || args.match_object.is_some()
+        || args.match_graph.is_some()
+    {
+        let term = |value: &Option<String>| value.as_deref().map(pattern_term);
+        let graph = args.match_graph.as_deref().map(|graph| {
+            if Path::new(graph).exists() {
+                format!("<{}>", file_graph_iri(graph))
+            } else if graph.starts_with("file:") {
+                format!("<{graph}>")
+            } else {
+                pattern_term(graph)
+            }
+        });
+        runner.retain_matching(
+            term(&args.match_subject).as_deref(),
+            term(&args.match_predicate).as_deref(),
+            term(&args.match_object).as_deref(),
+            graph.as_deref(),
+        )?;
+    }
 
-    collect_input(
-        &mut args,
-        &store,
-        &mut query_str,
-        &mut base_iri,
-        &mut prefixes,
-    )?;
+    if let Some(pattern) = &args.grep {
+        runner.retain_grep(pattern, args.grep_context)?;
+    }
 
-    // Output:
-    let stdout = std::io::stdout();
+    // Start timing once the data is loaded:
+    if let Some(seconds) = args.timeout {
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs(seconds));
+            TIMED_OUT.get_or_init(|| seconds);
+            cancellation_token.cancel();
+            std::thread::sleep(TIMEOUT_GRACE_PERIOD);
+            exit_timed_out(seconds);
+        });
+    }
 
-    let mut query_parse_err: Option<SparqlSyntaxError> = None;
+    let start = Instant::now();
+    if args.in_place {
+        update_in_place(&mut runner, &queries, &sources)?;
+        timings.running = start.elapsed();
+        return report_stats(&runner, &timings, args.verbose);
+    }
 
-    // Run query:
-    match Query::parse(&query_str, base_iri.as_deref()) {
-        Ok(query) => {
-            let writer = BufWriter::new(stdout.lock());
-            match query_to_new_store_or_serialize(&store, query, &args.output_format, writer)? {
-                Some(new_store) => {
-                    store = new_store;
-                }
-                None => {
-                    return Ok(());
-                }
+    if let Some(addr) = &args.serve {
+        return runner.serve(addr);
+    }
+
+    // Validate (after any updates), replacing the data with the validation report:
+    let mut conforms = true;
+    if let Some(fpath) = &args.shacl {
+        runner.run_updates(&queries)?;
+        queries.clear();
+        let mut shapes = Runner::new()?;
+        load_into_default_graph(&mut shapes, fpath, &args)?;
+        conforms = runner.validate_shacl(&shapes)?;
+    }
+
+    // Describe the data (after any updates), replacing it with the description:
+    if args.void {
+        runner.run_updates(&queries)?;
+        queries.clear();
+        runner.describe_as_void()?;
+    }
+
+    // Summarize the data, or its namespaces (after any updates):
+    if args.stats || args.namespaces {
+        runner.run_updates(&queries)?;
+        let write = |writer: &mut dyn Write| match args.stats {
+            true => runner.write_summary(writer),
+            false => runner.write_namespaces(writer),
+        };
+        match &args.output {
+            Some(fpath) => write_atomically(fpath, |mut file| write(&mut file))?,
+            None => write(&mut std::io::stdout().lock())?,
+        }
+        timings.running = start.elapsed();
+        return report_stats(&runner, &timings, args.verbose);
+    }
+
+    // Write each named graph, or resource, to its own file (after any updates):
+    if let Some(dir) = &args.split_graphs {
+        runner.run_updates(&queries)?;
+        runner = split_graphs(runner, dir, args.output_format.as_deref().unwrap_or("ttl"))?;
+        timings.running = start.elapsed();
+        return report_stats(&runner, &timings, args.verbose);
+    }
+    if let (Some(name), Some(dir)) = (&args.split_by, &args.output) {
+        runner.run_updates(&queries)?;
+        let by = SplitBy::from_name(name).with_context(|| format!("Unknown split mode: {name}"))?;
+        let output_format = args.output_format.as_deref().unwrap_or("ttl");
+        split_resources(&mut runner, Path::new(dir), by, output_format)?;
+        timings.running = start.elapsed();
+        return report_stats(&runner, &timings, args.verbose);
+    }
+
+    // Validate (after any updates), outputting the result shape map:
+    if let (Some(fpath), Some(shape_map)) = (&args.shex, &args.shape_map) {
+        runner.run_updates(&queries)?;
+        let schema =
+            fs::read_to_string(fpath).with_context(|| format!("Unable to read schema: {fpath}"))?;
+        let shape_map = if Path::new(shape_map).is_file() {
+            fs::read_to_string(shape_map)
+                .with_context(|| format!("Unable to read shape map: {shape_map}"))?
+        } else {
+            shape_map.clone()
+        };
+        let base_iri = file_graph_iri(fpath);
+        let validate = |writer: &mut dyn Write| {
+            runner.validate_shex(&schema, Some(&base_iri), &shape_map, writer)
+        };
+        let conforms = match &args.output {
+            Some(output) => {
+                let mut conforms = false;
+                write_atomically(output, |file| {
+                    conforms = validate(file)?;
+                    Ok(())
+                })?;
+                conforms
             }
+            None if args.quiet => validate(&mut std::io::sink())?,
+            None => validate(&mut std::io::stdout().lock())?,
+        };
+        timings.running = start.elapsed();
+        report_stats(&runner, &timings, args.verbose)?;
+        if !conforms {
+            drop(runner); // (removing any temporary store)
+            std::process::exit(1);
         }
-        Err(err) => {
-            query_parse_err = Some(err);
+        return Ok(());
+    }
+
+    if let Some(mode) = &args.explain {
+        let with_stats = mode == "stats";
+        return match &args.output {
+            Some(fpath) => {
+                write_atomically(fpath, |file| runner.explain(&queries, with_stats, file))
+            }
+            None => runner.explain(&queries, with_stats, std::io::stdout().lock()),
+        };
+    }
+
+    if let Some(fpath) = &args.output {
+        // Output to file:
+        let output_format = output_file_format(&args, fpath);
+        write_atomically(fpath, |file| match args.pipeline {
+            true => runner.run_pipeline(&queries, output_format.as_deref(), file),
+            false => runner.run_sequence(&queries, output_format.as_deref(), file),
+        })?;
+    } else {
+        // Output (with SELECT results as a table if to a terminal):
+        let stdout = std::io::stdout();
+        if stdout.is_terminal() {
+            runner = runner.with_table();
+        }
+        if use_color(&args, &stdout) {
+            runner = runner.with_color();
+        }
+        let writer: Box<dyn Write> = if args.quiet {
+            Box::new(std::io::sink())
+        } else {
+            Box::new(stdout.lock())
+        };
+
+        // Run query:
+        match args.pipeline {
+            true => runner.run_pipeline(&queries, args.output_format.as_deref(), writer)?,
+            false => runner.run_sequence(&queries, args.output_format.as_deref(), writer)?,
         }
     }
+    timings.running = start.elapsed();
+    report_stats(&runner, &timings, args.verbose)?;
+
+    // Exit with status 1 for false (like `test`):
+    if !conforms || runner.ask_result() == Some(false) {
+        drop(runner); // (removing any temporary store)
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Gives the output format to write a file in: the one given, or else the one of its suffix (if
+/// known).
+fn output_file_format(args: &CliArgs, fpath: &str) -> Option<String> {
+    args.output_format.clone().or_else(|| {
+        let ext = Path::new(fpath).extension()?.to_str()?.to_ascii_lowercase();
+        let ext = match ext.as_str() {
+            "sqlite3" | "db" => "sqlite".to_owned(),
+            _ => ext,
+        };
+        OUTPUT_FORMATS.contains(&ext.as_str()).then_some(ext)
+    })
+}
+
+/// Writes each named graph to its own file in the directory (as triples, in the given format),
+/// giving back the runner (which is set to output each graph in turn).
+fn split_graphs(mut runner: Runner, dir: &Path, output_format: &str) -> Result<Runner> {
+    if RESULTS_FORMATS.contains(&output_format) || output_format == "rdp" {
+        anyhow::bail!("Graphs cannot be written as {output_format}");
+    }
+    let mut graphs = runner
+        .store()
+        .named_graphs()
+        .filter_map(|graph_name| match graph_name {
+            Ok(NamedOrBlankNode::NamedNode(node)) => Some(Ok(node.into_string())),
+            Ok(NamedOrBlankNode::BlankNode(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    graphs.sort();
+    let store = runner.store();
+    if store
+        .quads_for_pattern(None, None, None, Some(GraphNameRef::DefaultGraph))
+        .next()
+        .is_some()
+    {
+        eprintln!("Warning: The default graph is not written (only named graphs are)");
+    }
+    let fpaths = iri_file_paths(dir, &graphs, output_format)?;
+    for (graph_iri, fpath) in graphs.iter().zip(fpaths) {
+        runner = runner.with_output_graph(graph_iri)?;
+        write_atomically(&fpath.to_string_lossy(), |file| {
+            runner.serialize(Some(output_format), BufWriter::new(file))
+        })?;
+    }
+    Ok(runner)
+}
 
-    if let Some(query_parse_err) = query_parse_err {
-        // Maybe an update query:
-        if let Ok(update) = Update::parse(&query_str, base_iri.as_deref()) {
-            // Insert or Delete:
-            store.update(update).context("Update failed")?;
+/// Writes the description of each resource (by subject or class, named by that) to its own file
+/// in the directory (in the given format), replacing the data of the runner with each in turn.
+fn split_resources(
+    runner: &mut Runner,
+    dir: &Path,
+    by: SplitBy,
+    output_format: &str,
+) -> Result<()> {
+    if RESULTS_FORMATS.contains(&output_format) || output_format == "rdp" {
+        anyhow::bail!("Resources cannot be written as {output_format}");
+    }
+    let partition = runner.partition(by)?;
+    let iris = partition
+        .iris()
+        .map(|iri| iri.as_str().to_owned())
+        .collect::<Vec<_>>();
+    let fpaths = iri_file_paths(dir, &iris, output_format)?;
+    for (part, fpath) in partition.parts().zip(fpaths) {
+        let (_, triples) = part?;
+        runner.replace_data(triples)?;
+        write_atomically(&fpath.to_string_lossy(), |file| {
+            runner.serialize(Some(output_format), BufWriter::new(file))
+        })?;
+    }
+    if partition.left_out() > 0 {
+        let about = match by {
+            SplitBy::Subject => "blank nodes not referred to from a subject IRI",
+            SplitBy::Class => "resources of no class",
+        };
+        let count = partition.left_out();
+        let triples = if count == 1 {
+            "triple is"
         } else {
-            // Bail for query error (assumed more likely than update attempt; maybe report both?):
-            bail!(query_parse_err);
+            "triples are"
+        };
+        eprintln!("Warning: {count} {triples} not written (being about {about})");
+    }
+    Ok(())
+}
+
+/// Gives the paths of the files (in the directory) to write data named by the IRIs to (see
+/// [`iri_file_path`]), creating the directories of them. Fails if any two would be the same.
+fn iri_file_paths(dir: &Path, iris: &[String], suffix: &str) -> Result<Vec<PathBuf>> {
+    let fpaths = iris
+        .iter()
+        .map(|iri| dir.join(iri_file_path(iri, suffix)))
+        .collect::<Vec<_>>();
+    let mut unique = BTreeSet::new();
+    for fpath in &fpaths {
+        if !unique.insert(fpath) {
+            anyhow::bail!("More than one IRI would be written to {}", fpath.display());
+        }
+    }
+    for parent in fpaths.iter().filter_map(|fpath| fpath.parent()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create directory: {}", parent.display()))?;
+    }
+    Ok(fpaths)
+}
+
+/// Gives the relative path of the file to write data named by an IRI to: for a file IRI (such as
+/// of a graph loaded from a file, or from an entry of an archive file), its path (with any suffix
+/// replaced by that of the format), and otherwise the path segments of the IRI (after the scheme,
+/// with unsafe characters replaced), as directories and a file name (or `index`, if the IRI ends
+/// with a slash) with the suffix of the format added.
+fn iri_file_path(iri: &str, suffix: &str) -> PathBuf {
+    let file_iri = iri.strip_prefix("jar:").unwrap_or(iri);
+    let (path, is_file) = match file_iri.strip_prefix("file:") {
+        Some(path) => (path.replace("%20", " ").replace("!/", "/"), true),
+        None => match iri.split_once("://") {
+            Some((_, rest)) => (rest.to_owned(), false),
+            None => (
+                iri.split_once(':').map_or(iri, |(_, rest)| rest).to_owned(),
+                false,
+            ),
+        },
+    };
+    let mut segments: Vec<String> = path
+        .split(['/', '?', '#'])
+        .filter(|segment| !matches!(*segment, "" | "." | ".."))
+        .map(|segment| {
+            segment
+                .chars()
+                .map(|c| match c.is_alphanumeric() || "-_.~ ".contains(c) {
+                    true => c,
+                    false => '_',
+                })
+                .collect()
+        })
+        .collect();
+    if path.ends_with('/') || segments.is_empty() {
+        segments.push("index".to_owned());
+    }
+    let mut fpath = segments.into_iter().collect::<PathBuf>();
+    if is_file {
+        if is_compressed_file(&fpath.to_string_lossy()) {
+            fpath.set_extension("");
+        }
+        fpath.set_extension(suffix);
+    } else {
+        let name = fpath.file_name().unwrap_or_default().to_string_lossy();
+        fpath.set_file_name(format!("{name}.{suffix}"));
+    }
+    fpath
+}
+
+/// Combines the inputs (each loaded into the default graph of its own store) by a set operation,
+/// writing the resulting data.
+fn combine_inputs(args: &CliArgs, operation: SetOperation) -> Result<()> {
+    let inputs = args.query.iter().chain(&args.file).collect::<Vec<_>>();
+    if inputs.len() < 2 {
+        anyhow::bail!(
+            "Two or more inputs are needed to combine (got {})",
+            inputs.len()
+        );
+    }
+
+    let mut runners = Vec::new();
+    for input in inputs {
+        let mut runner = Runner::new()?;
+        if let Some(value) = &args.base_iri {
+            runner = runner.with_base_iri(value);
+        }
+        if args.pretty {
+            runner = runner.with_pretty();
+        }
+        if args.sort {
+            runner = runner.with_sort();
+        }
+        load_into_default_graph(&mut runner, input, args)?;
+        runners.push(runner);
+    }
+    let (runner, others) = runners.split_first_mut().expect("two or more runners");
+    runner.combine(others, operation)?;
+
+    match &args.output {
+        Some(fpath) => {
+            let output_format = output_file_format(args, fpath);
+            write_atomically(fpath, |file| {
+                runner.run_sequence(&[], output_format.as_deref(), file)
+            })
         }
+        None => runner.run_sequence(&[], args.output_format.as_deref(), std::io::stdout().lock()),
     }
+}
+
+/// Compares two inputs (each loaded into the default graph of its own store), writing their
+/// differences (or, if checking for isomorphism, a short report), and exiting with status 1 if
+/// they differ (like `diff`).
+fn compare_inputs(args: &CliArgs) -> Result<()> {
+    let inputs = args.query.iter().chain(&args.file).collect::<Vec<_>>();
+    let [old_input, new_input] = inputs[..] else {
+        anyhow::bail!("Two inputs are needed to compare (got {})", inputs.len());
+    };
+
+    let mut runners = Vec::new();
+    for input in [old_input, new_input] {
+        let mut runner = Runner::new()?;
+        if let Some(value) = &args.base_iri {
+            runner = runner.with_base_iri(value);
+        }
+        load_into_default_graph(&mut runner, input, args)?;
+        runners.push(runner);
+    }
+
+    let stdout = std::io::stdout();
+    let changed = if args.isomorphic {
+        let name = |input: &str| if input == "-" { "stdin" } else { input }.to_owned();
+        let (old_name, new_name) = (name(old_input), name(new_input));
+        runners[0].write_mismatch(&runners[1], &old_name, &new_name, stdout.lock())?
+    } else {
+        let color = use_color(args, &stdout);
+        runners[0].write_diff(
+            &runners[1],
+            args.output_format.as_deref(),
+            color,
+            stdout.lock(),
+        )?
+    };
+    if changed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
 
-    let format = if let Some(fmt) = &args.output_format {
-        RdfFormat::from_extension(&fmt).with_context(|| format!("Unknown output format: {fmt}"))?
+/// Builds a query for the triples matching the pattern of the given subject, predicate and
+/// object: a `CONSTRUCT` query, or a `SELECT` query of the other positions if output is in a
+/// results format (or through a template).
+fn pattern_query(args: &CliArgs) -> String {
+    let positions = [
+        ("?s", &args.subject),
+        ("?p", &args.predicate),
+        ("?o", &args.object),
+    ];
+    let pattern = positions
+        .iter()
+        .map(|(variable, value)| value.as_deref().map_or(variable.to_string(), pattern_term))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let is_results_output = args.template.is_some()
+        || args
+            .output_format
+            .as_deref()
+            .is_some_and(|format| RESULTS_FORMATS.contains(&format));
+    if !is_results_output {
+        return format!("CONSTRUCT {{ {pattern} }} WHERE {{ {pattern} }}");
+    }
+    let variables: Vec<&str> = positions
+        .iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(variable, _)| *variable)
+        .collect();
+    match variables.is_empty() {
+        true => format!("ASK {{ {pattern} }}"),
+        false => format!("SELECT {} WHERE {{ {pattern} }}", variables.join(" ")),
+    }
+}
+
+/// Writes a value given for a pattern as a SPARQL term: IRIs in angle brackets, prefixed names,
+/// literals in quotes, numbers and `a` as is, other IRIs (with `://` or `urn:`) in angle brackets,
+/// and other values as strings.
+fn pattern_term(value: &str) -> String {
+    let is_prefixed_name = value
+        .split_once(':')
+        .is_some_and(|(pfx, local)| !pfx.contains(char::is_whitespace) && !local.contains(' '));
+    if value.starts_with(['<', '"']) || value == "a" || value.parse::<f64>().is_ok() {
+        value.to_owned()
+    } else if value.contains("://") || value.starts_with("urn:") {
+        format!("<{value}>")
+    } else if is_prefixed_name {
+        value.to_owned()
     } else {
-        RdfFormat::TriG
+        oxigraph::model::Literal::new_simple_literal(value).to_string()
+    }
+}
+
+/// Locates the user configuration directory of oxrq (in `$XDG_CONFIG_HOME` or `~/.config`).
+fn user_config_dir() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
     };
+    Some(config_dir.join("oxrq"))
+}
 
-    // Serialize resulting store:
-    let mut serializer = RdfSerializer::from_format(format);
-    if let Some(value) = base_iri {
-        serializer = serializer.with_base_iri(value)?;
+/// Looks up a saved query: `NAME.rq` in the `queries` directory of the user configuration
+/// directory, or else `NAME` in `[queries]` of a config file.
+fn saved_query(config: &Config, name: &str) -> Result<String> {
+    if let Some(fpath) = user_config_dir()
+        .map(|dir| dir.join("queries").join(format!("{name}.rq")))
+        .filter(|fpath| fpath.is_file())
+    {
+        return fs::read_to_string(&fpath)
+            .with_context(|| format!("Unable to read query file: {}", fpath.display()));
     }
-    for (pfx, ns) in prefixes {
-        serializer = serializer.with_prefix(pfx, ns)?;
+    match config.queries.get(name) {
+        Some(query) => Ok(query.clone()),
+        None => anyhow::bail!("Unknown saved query: {name}"),
     }
+}
 
-    let writer = BufWriter::new(stdout.lock());
-    if !format.supports_datasets() {
-        if store
-            .quads_for_pattern(None, None, None, Some(GraphNameRef::DefaultGraph))
-            .peekable()
-            .peek()
-            .is_some()
+/// Lists the names of the saved queries (in the queries directory and config files), sorted.
+fn saved_query_names(config: &Config) -> Result<BTreeSet<String>> {
+    let mut names: BTreeSet<String> = config.queries.keys().cloned().collect();
+    let Some(dir) = user_config_dir().map(|dir| dir.join("queries")) else {
+        return Ok(names);
+    };
+    if dir.is_dir() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "rq") {
+                names.extend(path.file_stem().and_then(OsStr::to_str).map(str::to_owned));
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Locates the configuration files: `oxrq/config.toml` in the user configuration directory (in
+/// `$XDG_CONFIG_HOME` or `~/.config`), then `.oxrq.toml` in the current directory or the nearest
+/// parent directory having one (taking precedence).
+fn config_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(dir) = user_config_dir() {
+        files.push(dir.join("config.toml"));
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        if let Some(file) = cwd
+            .ancestors()
+            .map(|dir| dir.join(".oxrq.toml"))
+            .find(|file| file.is_file())
         {
-            store.dump_graph_to_writer(GraphNameRef::DefaultGraph, serializer, writer)?;
-        } else {
-            // Picks one named graph at random (i.e. only predictable for one input file):
-            for graph_name in store.named_graphs() {
-                store.dump_graph_to_writer(graph_name.unwrap().as_ref(), serializer, writer)?;
-                break;
+            files.push(file);
+        }
+    }
+    files
+}
+
+/// Parses the command-line arguments, preceded by the options set in config files and environment
+/// variables (except those given, or conflicting with those given, on the command line).
+fn parse_args(config: &Config) -> Result<CliArgs> {
+    let given: Vec<OsString> = std::env::args_os().collect();
+    if config.options.is_empty() {
+        return Ok(CliArgs::parse_from(given));
+    }
+    let mut command = CliArgs::command();
+    command.build();
+    let matches = command.clone().get_matches_from(&given);
+    let is_given =
+        |arg: &Arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine);
+
+    // (Options used on their own, like '--completions', take no others.)
+    if command
+        .get_arguments()
+        .any(|arg| arg.is_exclusive_set() && is_given(arg))
+    {
+        return Ok(CliArgs::parse_from(given));
+    }
+
+    let mut args = given[..1].to_vec();
+    for option in &config.options {
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == option.name.as_str() && !arg.is_positional())
+        else {
+            anyhow::bail!("Unknown option in {}: {}", option.source, option.name);
+        };
+        let conflicts = command.get_arguments().filter(|other| {
+            command.get_arg_conflicts_with(arg).contains(other)
+                || command.get_arg_conflicts_with(other).contains(&arg)
+        });
+        if is_given(arg) || conflicts.into_iter().any(is_given) {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap_or(arg.get_id().as_str()));
+        match &option.value {
+            OptionValue::Flag => args.push(flag.into()),
+            OptionValue::Values(values) if !arg.get_action().takes_values() => {
+                let count = match arg.get_action() {
+                    ArgAction::Count => values[0].parse::<usize>().ok(),
+                    _ => None,
+                };
+                let Some(count) = count else {
+                    anyhow::bail!(
+                        "Expected true or false for {} in {}",
+                        option.name,
+                        option.source
+                    );
+                };
+                args.extend(std::iter::repeat_n(OsString::from(&flag), count));
+            }
+            OptionValue::Values(values) => {
+                for value in values {
+                    let mut flag_value = OsString::from(format!("{flag}="));
+                    match arg.get_value_hint() {
+                        ValueHint::FilePath | ValueHint::DirPath | ValueHint::AnyPath => {
+                            flag_value.push(option.source.dir().join(value));
+                        }
+                        _ => flag_value.push(value),
+                    }
+                    args.push(flag_value);
+                }
             }
         }
-    } else {
-        store.dump_to_writer(serializer, writer)?;
+    }
+    args.extend(given.into_iter().skip(1));
+    Ok(CliArgs::parse_from(args))
+}
+
+/// Locates the cache of prefixes looked up (in `$XDG_CACHE_HOME` or `~/.cache`).
+fn prefix_cache_file() -> Option<PathBuf> {
+    Some(user_cache_dir()?.join("prefixes.json"))
+}
+
+/// Gives the directory to cache data fetched over HTTP(S) in (unless disabled).
+fn http_cache_dir(args: &CliArgs) -> Option<PathBuf> {
+    match (&args.cache_dir, args.no_cache) {
+        (_, true) => None,
+        (Some(dir), _) => Some(dir.clone()),
+        (None, _) => Some(user_cache_dir()?.join("http")),
+    }
+}
+
+/// Locates the user cache directory of oxrq (in `$XDG_CACHE_HOME` or `~/.cache`).
+fn user_cache_dir() -> Option<PathBuf> {
+    let cache_dir = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache_dir.join("oxrq"))
+}
+
+/// Tells whether to use color in output to stdout.
+fn use_color(args: &CliArgs, stdout: &std::io::Stdout) -> bool {
+    match args.color.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => stdout.is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    }
+}
+
+/// Loads an input (a file, directory, glob pattern, URL or '-' for stdin) into the default graph.
+fn load_into_default_graph(runner: &mut Runner, input: &str, args: &CliArgs) -> Result<()> {
+    if input == "-" {
+        return load_from_stdin(runner, &args.input_format, None);
+    }
+    if is_url(input) {
+        let (format, reader) = open_url(input, http_cache_dir(args).as_deref())?;
+        return runner.load_graph_with_base(reader, format, None, input);
+    }
+    for fpath in expand_path(input)? {
+        let (format, reader) = open_file(&fpath)?;
+        runner
+            .load_graph_with_base(reader, format, None, &file_graph_iri(&fpath))
+            .with_context(|| format!("Error in file '{fpath}'"))?;
+    }
+
+    Ok(())
+}
+
+/// Runs updates, and writes each modified graph back to the file it was loaded from (in the same
+/// format).
+fn update_in_place(runner: &mut Runner, queries: &[String], sources: &[Source]) -> Result<()> {
+    let modified = runner.run_updates(queries)?;
+    if !modified.contains(&GraphName::DefaultGraph) && runner.updates_default_graph(queries)? {
+        eprintln!(
+            "Updates of the default graph modified nothing (use GRAPH in updates to modify files)"
+        );
+    }
+    for graph_name in modified {
+        let GraphName::NamedNode(node) = &graph_name else {
+            eprintln!(
+                "Modified default graph left unwritten (use GRAPH in updates to modify files)"
+            );
+            continue;
+        };
+        let mut graph_sources = sources
+            .iter()
+            .filter(|source| source.graph_iri == node.as_str());
+        let (Some(source), None) = (graph_sources.next(), graph_sources.next()) else {
+            eprintln!("Modified graph not loaded from one file (left unwritten): {graph_name}");
+            continue;
+        };
+        let fpath = &source.fpath;
+        if is_compressed_file(fpath) {
+            eprintln!("Modified graph loaded from a compressed file (left unwritten): {fpath}");
+            continue;
+        }
+        write_atomically(fpath, |file| {
+            let mut writer = BufWriter::new(file);
+            runner
+                .serialize_graph(&graph_name, source.format, &mut writer)
+                .with_context(|| format!("Unable to update file: {fpath}"))?;
+            Ok(writer.flush()?)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Writes to a temporary file next to the given one, which is then renamed to replace it (so that
+/// a failure leaves any existing file untouched).
+fn write_atomically(fpath: &str, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let path = Path::new(fpath);
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid output file: {fpath}"))?;
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let mut file = File::create(&tmp_path)
+        .with_context(|| format!("Unable to create output file: {fpath}"))?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    let result = write(&mut file).and_then(|()| Ok(file.sync_all()?));
+    drop(file);
+    if let Err(e) = result.and_then(|()| {
+        fs::rename(&tmp_path, path).with_context(|| format!("Unable to write output file: {fpath}"))
+    }) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
     }
 
     Ok(())
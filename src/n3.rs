@@ -0,0 +1,216 @@
+//! Reading of Notation3 (N3) input, keeping the statements which are plain RDF (as is most N3 in
+//! the wild), and skipping those using formulas (`{ ... }`, as in rules given by `=>`) or
+//! variables, which RDF cannot represent. Rules can also be read, to be applied to the data.
+
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+
+use anyhow::{bail, Result};
+use oxigraph::model::{BlankNode, GraphName, NamedOrBlankNode, Quad, Term, Variable};
+use oxttl::n3::{N3Parser, N3Quad, N3Term};
+use spargebra::algebra::GraphPattern;
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+
+use crate::rules::Rule;
+use crate::ParsedData;
+
+/// The predicate of rules (`=>`, and `<=` in reverse).
+const LOG_IMPLIES: &str = "http://www.w3.org/2000/10/swap/log#implies";
+
+/// The statements of an N3 document, along with the base IRI and prefixes declared in it.
+struct Document {
+    statements: Vec<N3Quad>,
+    base_iri: Option<String>,
+    prefixes: Vec<(String, String)>,
+}
+
+/// Parses N3 into the given graph, writing a warning about any statements skipped (as not RDF).
+pub(crate) fn parse<R: BufRead>(
+    reader: R,
+    base_iri: Option<&str>,
+    graph_name: &GraphName,
+) -> Result<ParsedData> {
+    let document = read(reader, base_iri)?;
+    let data = document.data(graph_name);
+    warn_skipped(document.statements.len() - data.quads.len(), base_iri);
+    Ok(data)
+}
+
+/// Parses the rules of N3 (`{ ... } => { ... }`, with the variables and blank nodes of the first
+/// formula matching any terms), along with the other statements (as by [`parse`], into the
+/// default graph).
+pub(crate) fn parse_rules<R: BufRead>(
+    reader: R,
+    base_iri: Option<&str>,
+) -> Result<(Vec<Rule>, ParsedData)> {
+    let document = read(reader, base_iri)?;
+    let mut formulas: HashMap<&BlankNode, Vec<&N3Quad>> = HashMap::new();
+    for statement in &document.statements {
+        if let GraphName::BlankNode(node) = &statement.graph_name {
+            formulas.entry(node).or_default().push(statement);
+        }
+    }
+
+    let mut rules = Vec::new();
+    let mut in_rules = 0;
+    for statement in &document.statements {
+        let is_rule = statement.graph_name == GraphName::DefaultGraph
+            && matches!(&statement.predicate, N3Term::NamedNode(p) if p.as_str() == LOG_IMPLIES);
+        let (true, N3Term::BlankNode(body), N3Term::BlankNode(head)) =
+            (is_rule, &statement.subject, &statement.object)
+        else {
+            continue;
+        };
+        let (Some(body), Some(head)) = (formulas.get(body), formulas.get(head)) else {
+            // (An empty formula as body holds for nothing to match, and as head derives nothing.)
+            continue;
+        };
+        let patterns = body
+            .iter()
+            .map(|statement| to_pattern(statement, true))
+            .collect::<Result<_>>()?;
+        let template = head
+            .iter()
+            .map(|statement| to_pattern(statement, false))
+            .collect::<Result<_>>()?;
+        rules.push(Rule {
+            pattern: GraphPattern::Bgp { patterns },
+            template,
+        });
+        in_rules += 1 + body.len() + head.len();
+    }
+
+    let data = document.data(&GraphName::DefaultGraph);
+    warn_skipped(
+        document.statements.len() - data.quads.len() - in_rules,
+        base_iri,
+    );
+    Ok((rules, data))
+}
+
+fn read<R: BufRead>(reader: R, base_iri: Option<&str>) -> Result<Document> {
+    let mut parser = N3Parser::new();
+    if let Some(base_iri) = base_iri {
+        parser = parser.with_base_iri(base_iri)?;
+    }
+    let mut parser = parser.for_reader(reader);
+    let statements = parser.by_ref().collect::<Result<Vec<_>, _>>()?;
+    Ok(Document {
+        statements,
+        base_iri: parser.base_iri().map(str::to_owned),
+        prefixes: parser
+            .prefixes()
+            .map(|(pfx, ns)| (pfx.to_owned(), ns.to_owned()))
+            .collect(),
+    })
+}
+
+fn warn_skipped(skipped: usize, base_iri: Option<&str>) {
+    if skipped > 0 {
+        let source = base_iri.map_or(String::new(), |base_iri| format!(" in <{base_iri}>"));
+        eprintln!(
+            "Skipped {skipped} N3 statements{source} using formulas or variables (such as rules)"
+        );
+    }
+}
+
+impl Document {
+    /// Gives the statements which are RDF, in the given graph.
+    fn data(&self, graph_name: &GraphName) -> ParsedData {
+        // (Formulas are given as blank nodes, naming the graphs of their statements.)
+        let formulas: HashSet<&BlankNode> = self
+            .statements
+            .iter()
+            .filter_map(|statement| match &statement.graph_name {
+                GraphName::BlankNode(node) => Some(node),
+                _ => None,
+            })
+            .collect();
+        let mut blank_nodes = HashMap::new();
+        let quads = self
+            .statements
+            .iter()
+            .filter_map(|statement| to_quad(statement, &formulas, &mut blank_nodes, graph_name))
+            .collect();
+        ParsedData {
+            quads,
+            base_iri: self.base_iri.clone(),
+            prefixes: self.prefixes.clone(),
+        }
+    }
+}
+
+/// Converts a statement to a quad in the given graph, unless it is in or about a formula, or
+/// contains variables.
+fn to_quad(
+    statement: &N3Quad,
+    formulas: &HashSet<&BlankNode>,
+    blank_nodes: &mut HashMap<BlankNode, BlankNode>,
+    graph_name: &GraphName,
+) -> Option<Quad> {
+    if statement.graph_name != GraphName::DefaultGraph {
+        return None;
+    }
+    let N3Term::NamedNode(predicate) = &statement.predicate else {
+        return None;
+    };
+    if predicate.as_str() == LOG_IMPLIES {
+        return None;
+    }
+    let subject = match to_term(&statement.subject, formulas, blank_nodes)? {
+        Term::NamedNode(node) => NamedOrBlankNode::from(node),
+        Term::BlankNode(node) => node.into(),
+        _ => return None,
+    };
+    let object = to_term(&statement.object, formulas, blank_nodes)?;
+    Some(Quad::new(
+        subject,
+        predicate.clone(),
+        object,
+        graph_name.clone(),
+    ))
+}
+
+/// Converts a term (giving blank nodes new labels, to not clash with those of other data).
+fn to_term(
+    term: &N3Term,
+    formulas: &HashSet<&BlankNode>,
+    blank_nodes: &mut HashMap<BlankNode, BlankNode>,
+) -> Option<Term> {
+    Some(match term {
+        N3Term::NamedNode(node) => node.clone().into(),
+        N3Term::BlankNode(node) if formulas.contains(node) => return None,
+        N3Term::BlankNode(node) => blank_nodes.entry(node.clone()).or_default().clone().into(),
+        N3Term::Literal(literal) => literal.clone().into(),
+        N3Term::Triple(triple) => triple.as_ref().clone().into(),
+        N3Term::Variable(_) => return None,
+    })
+}
+
+/// Converts a statement of a rule to a triple pattern (with the blank nodes of the body as
+/// variables, and those of the head made anew for each match).
+fn to_pattern(statement: &N3Quad, in_body: bool) -> Result<TriplePattern> {
+    let subject = to_term_pattern(&statement.subject, in_body)?;
+    let predicate = match to_term_pattern(&statement.predicate, in_body)? {
+        TermPattern::NamedNode(node) => NamedNodePattern::NamedNode(node),
+        TermPattern::Variable(variable) => NamedNodePattern::Variable(variable),
+        _ => bail!("Invalid predicate in rule: {}", statement.predicate),
+    };
+    let object = to_term_pattern(&statement.object, in_body)?;
+    Ok(TriplePattern {
+        subject,
+        predicate,
+        object,
+    })
+}
+
+fn to_term_pattern(term: &N3Term, in_body: bool) -> Result<TermPattern> {
+    Ok(match term {
+        N3Term::NamedNode(node) => node.clone().into(),
+        N3Term::BlankNode(node) if in_body => Variable::new(format!("_{}", node.as_str()))?.into(),
+        N3Term::BlankNode(node) => node.clone().into(),
+        N3Term::Literal(literal) => literal.clone().into(),
+        N3Term::Variable(variable) => variable.clone().into(),
+        N3Term::Triple(_) => bail!("Triple terms in rules are not supported: {term}"),
+    })
+}
@@ -0,0 +1,98 @@
+//! A report of the namespaces of IRIs in loaded data, for spotting mistyped ones.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use anyhow::Result;
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{NamedOrBlankNode, Quad, Term};
+use oxigraph::store::StorageError;
+
+/// Gives the namespace of an IRI (up to the last hash or slash).
+pub(crate) fn namespace(iri: &str) -> &str {
+    let end = iri
+        .rfind('#')
+        .or_else(|| iri.rfind('/'))
+        .map_or(iri.len(), |i| i + 1);
+    &iri[..end]
+}
+
+/// Writes a line for each namespace of the IRIs in the quads (but not of the graph names, which
+/// are mostly those of the files loaded) and each declared one (even if unused), with the number
+/// of times IRIs in it are used (as terms or datatypes), the prefix declared for it (if any), and
+/// a note if it looks mistyped (such as `http//`) or like another one (differing only by the
+/// scheme, letter case or a trailing `#` or `/`). Sorted by count (largest first).
+pub(crate) fn write_report<W: Write>(
+    quads: impl IntoIterator<Item = Result<Quad, StorageError>>,
+    prefixes: &HashMap<String, String>,
+    mut writer: W,
+) -> Result<()> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut count = |iri: &str| match counts.get_mut(namespace(iri)) {
+        Some(count) => *count += 1,
+        None => {
+            counts.insert(namespace(iri).to_owned(), 1);
+        }
+    };
+    for quad in quads {
+        let quad = quad?;
+        if let NamedOrBlankNode::NamedNode(node) = &quad.subject {
+            count(node.as_str());
+        }
+        count(quad.predicate.as_str());
+        match &quad.object {
+            Term::NamedNode(node) => count(node.as_str()),
+            Term::Literal(literal)
+                if literal.datatype() != xsd::STRING && literal.datatype() != rdf::LANG_STRING =>
+            {
+                count(literal.datatype().as_str());
+            }
+            _ => {}
+        }
+    }
+    let declared: HashMap<&str, &str> = prefixes
+        .iter()
+        .map(|(pfx, ns)| (ns.as_str(), pfx.as_str()))
+        .collect();
+    for ns in declared.keys() {
+        counts.entry((*ns).to_owned()).or_default();
+    }
+
+    let mut lines: Vec<_> = counts.iter().collect();
+    lines.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    let width = lines
+        .first()
+        .map_or(1, |(_, count)| count.to_string().len());
+    let prefix_width = declared
+        .values()
+        .map(|pfx| pfx.len() + 1)
+        .max()
+        .unwrap_or(0);
+    for (ns, count) in lines {
+        let prefix = declared
+            .get(ns.as_str())
+            .map_or(String::new(), |pfx| format!("{pfx}:"));
+        let mut line = format!("{count:>width$}  {prefix:prefix_width$}  {ns}");
+        if ns.contains("http//") || ns.contains("https//") {
+            line.push_str("  (missing ':' after the scheme)");
+        } else if let Some(other) = counts
+            .keys()
+            .find(|other| *other != ns && normalized(other) == normalized(ns))
+        {
+            line.push_str(&format!("  (like {other})"));
+        }
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Normalizes a namespace for comparison with others (ignoring the scheme, letter case and any
+/// trailing hash or slash).
+fn normalized(ns: &str) -> String {
+    let ns = ns
+        .strip_prefix("https:")
+        .or(ns.strip_prefix("http:"))
+        .unwrap_or(ns);
+    ns.trim_end_matches(['#', '/']).to_lowercase()
+}
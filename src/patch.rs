@@ -0,0 +1,294 @@
+//! Parsing of RDF Patch and LD Patch documents into changes to the data.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use oxigraph::io::{RdfFormat, RdfParser};
+use oxigraph::model::{GraphName, NamedNode, NamedOrBlankNode, Quad, Term};
+
+use crate::sniff::find_closing_quote;
+
+/// A change to apply to the data.
+pub(crate) enum Change {
+    Add(Quad),
+    Delete(Quad),
+    /// Adds a quad which must not already be in the data.
+    AddNew(Quad),
+    /// Deletes a quad which must be in the data.
+    DeleteExisting(Quad),
+}
+
+/// Parses an [RDF Patch](https://afs.github.io/rdf-patch/), with triples put in the given graph.
+///
+/// Changes within aborted transactions (`TA`) are left out.
+pub(crate) fn parse_rdf_patch(text: &str, graph_name: &GraphName) -> Result<Vec<Change>> {
+    let mut prefixes = HashMap::new();
+    let mut changes = Vec::new();
+    let mut transaction_start = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (code, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let rest = rest.strip_suffix('.').unwrap_or(rest).trim_end();
+        let context = || format!("Invalid RDF Patch row at line {}: {line}", i + 1);
+
+        match code {
+            "H" => {}
+            "TX" => transaction_start = Some(changes.len()),
+            "TC" => transaction_start = None,
+            "TA" => {
+                if let Some(start) = transaction_start.take() {
+                    changes.truncate(start);
+                }
+            }
+            "PA" => {
+                let (pfx, ns) = rest.split_once(char::is_whitespace).with_context(context)?;
+                let ns = ns.trim();
+                let ns = ns
+                    .strip_prefix('<')
+                    .and_then(|ns| ns.strip_suffix('>'))
+                    .with_context(context)?;
+                prefixes.insert(pfx.trim_end_matches(':').to_owned(), ns.to_owned());
+            }
+            "PD" => {
+                let pfx = rest.split_whitespace().next().with_context(context)?;
+                prefixes.remove(pfx.trim_end_matches(':'));
+            }
+            "A" | "D" => {
+                let quad = parse_quad(rest, &prefixes, graph_name).with_context(context)?;
+                changes.push(if code == "A" {
+                    Change::Add(quad)
+                } else {
+                    Change::Delete(quad)
+                });
+            }
+            _ => bail!("Unknown RDF Patch row at line {}: {line}", i + 1),
+        }
+    }
+
+    Ok(changes)
+}
+
+fn parse_quad(
+    text: &str,
+    prefixes: &HashMap<String, String>,
+    graph_name: &GraphName,
+) -> Result<Quad> {
+    let terms = split_terms(text)?
+        .into_iter()
+        .map(|token| parse_term(token, prefixes))
+        .collect::<Result<Vec<_>>>()?;
+    let (subject, predicate, object, graph) = match <[Term; 3]>::try_from(terms) {
+        Ok([subject, predicate, object]) => (subject, predicate, object, None),
+        Err(terms) => match <[Term; 4]>::try_from(terms) {
+            Ok([subject, predicate, object, graph]) => (subject, predicate, object, Some(graph)),
+            Err(_) => bail!("Expected 3 or 4 terms"),
+        },
+    };
+
+    let subject = NamedOrBlankNode::try_from(subject.clone())
+        .map_err(|_| anyhow!("Invalid subject: {subject}"))?;
+    let Term::NamedNode(predicate) = predicate else {
+        bail!("Invalid predicate: {predicate}");
+    };
+    let graph_name = match graph {
+        None => graph_name.clone(),
+        Some(Term::NamedNode(node)) => node.into(),
+        Some(Term::BlankNode(bnode)) => bnode.into(),
+        Some(term) => bail!("Invalid graph name: {term}"),
+    };
+
+    Ok(Quad::new(subject, predicate, object, graph_name))
+}
+
+/// Splits a row into the IRIs, blank nodes, literals and prefixed names in it.
+fn split_terms(text: &str) -> Result<Vec<&str>> {
+    let mut terms = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let end = if rest.starts_with('<') {
+            rest.find('>').context("Unterminated IRI")? + 1
+        } else if let Some(literal) = rest.strip_prefix('"') {
+            let close = find_closing_quote(literal).context("Unterminated literal")? + 2;
+            close
+                + rest[close..]
+                    .find(char::is_whitespace)
+                    .unwrap_or(rest.len() - close)
+        } else {
+            rest.find(char::is_whitespace).unwrap_or(rest.len())
+        };
+        terms.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Ok(terms)
+}
+
+fn parse_term(token: &str, prefixes: &HashMap<String, String>) -> Result<Term> {
+    if token.starts_with('"') {
+        if let Some((literal, datatype)) = token.rsplit_once("^^") {
+            if literal.ends_with('"') && !datatype.starts_with('<') {
+                let datatype = expand_prefixed_name(datatype, prefixes)?;
+                return Ok(Term::from_str(&format!("{literal}^^{datatype}"))?);
+            }
+        }
+    }
+    if token.starts_with(['<', '"']) || token.starts_with("_:") {
+        return Ok(Term::from_str(token)?);
+    }
+    Ok(expand_prefixed_name(token, prefixes)?.into())
+}
+
+fn expand_prefixed_name(name: &str, prefixes: &HashMap<String, String>) -> Result<NamedNode> {
+    let (pfx, local) = name
+        .split_once(':')
+        .with_context(|| format!("Invalid term: {name}"))?;
+    let ns = prefixes
+        .get(pfx)
+        .with_context(|| format!("Unknown prefix: {pfx}"))?;
+    Ok(NamedNode::new(format!("{ns}{local}"))?)
+}
+
+/// Parses the `Add`, `AddNew`, `Delete` and `DeleteExisting` operations of an
+/// [LD Patch](https://www.w3.org/TR/ldpatch/) (along with prefix and base declarations), with
+/// triples put in the given graph.
+pub(crate) fn parse_ld_patch(
+    text: &str,
+    base_iri: &str,
+    graph_name: &GraphName,
+) -> Result<Vec<Change>> {
+    let mut declarations = String::new();
+    let mut changes = Vec::new();
+    let mut rest = skip_space(text);
+
+    while !rest.is_empty() {
+        let keyword_end = rest
+            .find(|c: char| c.is_whitespace() || c == '{')
+            .unwrap_or(rest.len());
+        let keyword = &rest[..keyword_end];
+
+        match keyword {
+            "@prefix" | "@base" | "PREFIX" | "BASE" | "prefix" | "base" => {
+                let end = rest.find('>').context("Invalid declaration in LD Patch")? + 1;
+                declarations.push_str(&rest[..end]);
+                rest = &rest[end..];
+                if keyword.starts_with('@') {
+                    rest = rest
+                        .trim_start()
+                        .strip_prefix('.')
+                        .with_context(|| format!("Expected '.' after {keyword} in LD Patch"))?;
+                    declarations.push_str(" .");
+                }
+                declarations.push('\n');
+            }
+            "Add" | "A" | "AddNew" | "AN" | "Delete" | "D" | "DeleteExisting" | "DE" => {
+                let block = rest[keyword_end..]
+                    .trim_start()
+                    .strip_prefix('{')
+                    .with_context(|| format!("Expected '{{' after {keyword} in LD Patch"))?;
+                let end = find_block_end(block).context("Unterminated block in LD Patch")?;
+                let quads = parse_block(&declarations, &block[..end], base_iri, graph_name)?;
+                rest = block[end + 1..]
+                    .trim_start()
+                    .strip_prefix('.')
+                    .with_context(|| format!("Expected '.' after {keyword} block in LD Patch"))?;
+
+                let is_delete = keyword.starts_with('D');
+                if is_delete && quads.iter().any(has_blank_node) {
+                    bail!("Blank nodes cannot be deleted by LD Patch");
+                }
+                changes.extend(quads.into_iter().map(|quad| match keyword {
+                    "Add" | "A" => Change::Add(quad),
+                    "AddNew" | "AN" => Change::AddNew(quad),
+                    "Delete" | "D" => Change::Delete(quad),
+                    _ => Change::DeleteExisting(quad),
+                }));
+            }
+            "Bind" | "B" | "Cut" | "C" | "UpdateList" | "UL" => {
+                bail!("Unsupported LD Patch operation: {keyword}");
+            }
+            _ => bail!(
+                "Invalid LD Patch at: {}",
+                rest.lines().next().unwrap_or(rest)
+            ),
+        }
+        rest = skip_space(rest);
+    }
+
+    Ok(changes)
+}
+
+fn skip_space(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start();
+        if !text.starts_with('#') {
+            return text;
+        }
+        text = text.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+}
+
+/// Finds the end of a block of Turtle (skipping IRIs, strings and comments).
+fn find_block_end(block: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut in_iri = false;
+    let mut in_comment = false;
+    for (i, c) in block.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\n' if in_comment => in_comment = false,
+            _ if in_comment => {}
+            '>' if in_iri => in_iri = false,
+            _ if in_iri => {}
+            '"' | '\'' => quote = Some(c),
+            '<' => in_iri = true,
+            '#' => in_comment = true,
+            '}' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_block(
+    declarations: &str,
+    block: &str,
+    base_iri: &str,
+    graph_name: &GraphName,
+) -> Result<Vec<Quad>> {
+    let block = block.trim();
+    if block.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut document = format!("{declarations}{block}");
+    if !block.ends_with('.') {
+        document.push_str(" .");
+    }
+
+    RdfParser::from_format(RdfFormat::Turtle)
+        .with_base_iri(base_iri)?
+        .with_default_graph(graph_name.clone())
+        .rename_blank_nodes()
+        .for_reader(document.as_bytes())
+        .map(|quad| Ok(quad?))
+        .collect()
+}
+
+fn has_blank_node(quad: &Quad) -> bool {
+    matches!(quad.subject, NamedOrBlankNode::BlankNode(_))
+        || matches!(quad.object, Term::BlankNode(_))
+}
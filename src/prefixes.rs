@@ -0,0 +1,148 @@
+//! Parsing of prefix mappings given in a file (as Turtle or SPARQL declarations, or as JSON), and
+//! lookup of prefixes used but not declared in queries.
+
+use std::collections::{BTreeSet, HashSet};
+use std::io::Read;
+
+use anyhow::{bail, Context, Result};
+use oxiri::Iri;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::http;
+
+/// The service to look up undeclared prefixes with.
+const LOOKUP_SERVICE: &str = "https://prefix.cc/";
+
+/// Parses prefix mappings from `@prefix` (Turtle) or `PREFIX` (SPARQL) declarations, or from a
+/// JSON object of prefixes and namespaces (optionally as the `@context` of a JSON-LD document,
+/// skipping other terms than prefixes).
+pub(crate) fn parse(text: &str) -> Result<Vec<(String, String)>> {
+    let mappings = if text.trim_start().starts_with('{') {
+        parse_json(text)?
+    } else {
+        parse_declarations(text)?
+    };
+    for (pfx, ns) in &mappings {
+        Iri::parse(ns.as_str()).with_context(|| format!("Invalid namespace of {pfx}: {ns}"))?;
+    }
+    Ok(mappings)
+}
+
+fn parse_declarations(text: &str) -> Result<Vec<(String, String)>> {
+    let declaration =
+        Regex::new(r"(?i)^\s*@?prefix\s+([^\s:]*):\s*<([^>]*)>\s*\.?\s*$").expect("valid regex");
+    let mut mappings = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some(caps) = declaration.captures(line) else {
+            bail!("Expected a prefix declaration on line {}: {line}", i + 1);
+        };
+        mappings.push((caps[1].to_owned(), caps[2].to_owned()));
+    }
+    Ok(mappings)
+}
+
+fn parse_json(text: &str) -> Result<Vec<(String, String)>> {
+    let value: Value = serde_json::from_str(text).context("Invalid JSON prefixes")?;
+    let (object, in_context) = match value.get("@context") {
+        Some(context) => (context, true),
+        None => (&value, false),
+    };
+    let Some(object) = object.as_object() else {
+        bail!("Expected a JSON object of prefixes");
+    };
+    let mappings = object
+        .iter()
+        .filter(|(pfx, _)| !pfx.starts_with('@'))
+        .filter_map(|(pfx, ns)| {
+            let ns = ns.as_str().or_else(|| ns.get("@id")?.as_str())?;
+            // (Namespaces end with a delimiter, unlike other terms of a JSON-LD context.)
+            let is_namespace = !in_context || ns.ends_with(['/', '#', ':', '?', '[', ']', '@']);
+            is_namespace.then(|| (pfx.to_owned(), ns.to_owned()))
+        })
+        .collect();
+    Ok(mappings)
+}
+
+/// Finds the prefixes used in prefixed names in a query but not declared in it (skipping IRIs,
+/// strings, comments, variables, blank nodes and language tags).
+pub(crate) fn undeclared_prefixes(query: &str) -> BTreeSet<String> {
+    let iri = Regex::new(r#"^<[^<>"{}|^`\\\s]*>"#).expect("valid regex");
+    let declaration = Regex::new(r"(?i)\bprefix\s+([^\s:]*):").expect("valid regex");
+    let declared: HashSet<&str> = declaration
+        .captures_iter(query)
+        .map(|caps| caps.get(1).map_or("", |m| m.as_str()))
+        .collect();
+
+    let mut used = BTreeSet::new();
+    let mut rest = query;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '<' => iri.find(rest).map_or(1, |m| m.end()),
+            '"' | '\'' => {
+                let quote = match rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+                    true => &rest[..3],
+                    false => &rest[..1],
+                };
+                let string = &rest[quote.len()..];
+                let mut end = string.len();
+                let mut escaped = false;
+                for (i, c) in string.char_indices() {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if string[i..].starts_with(quote) {
+                        end = i + quote.len();
+                        break;
+                    }
+                }
+                quote.len() + end
+            }
+            '#' => rest.find('\n').unwrap_or(rest.len()),
+            '?' | '$' | '@' | '_' => 1 + name_len(&rest[1..]),
+            c if c.is_alphabetic() => {
+                let len = name_len(rest);
+                if rest[len..].starts_with(':') {
+                    used.insert(rest[..len].to_owned());
+                    // (Skipping the local name.)
+                    len + 1 + name_len(&rest[len + 1..])
+                } else {
+                    len
+                }
+            }
+            c => c.len_utf8(),
+        };
+        rest = &rest[len..];
+    }
+    used.retain(|pfx| !declared.contains(pfx.as_str()));
+    used
+}
+
+/// Measures a name (of letters, digits, `_`, `-` and `.`, not ending with `.`).
+fn name_len(text: &str) -> usize {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || "_-.".contains(c)))
+        .unwrap_or(text.len());
+    text[..end].trim_end_matches('.').len()
+}
+
+/// Looks up the namespace of a prefix on prefix.cc.
+pub(crate) fn lookup(pfx: &str) -> Result<Option<String>> {
+    let url = format!("{LOOKUP_SERVICE}{pfx}.file.json");
+    let (_, mut body) = match http::get(&url, "application/json") {
+        Ok(response) => response,
+        // (Unknown prefixes are not found.)
+        Err(e) if e.to_string().starts_with("Error 404") => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut content = String::new();
+    body.read_to_string(&mut content)?;
+    let value: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid JSON returned by {url}"))?;
+    Ok(value.get(pfx).and_then(Value::as_str).map(str::to_owned))
+}
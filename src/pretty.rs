@@ -0,0 +1,498 @@
+//! Pretty-printed Turtle and TriG output, with triples grouped by subject and predicate, blank
+//! nodes nested where possible and lists abbreviated.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::Result;
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term};
+use oxiri::Iri;
+use regex::Regex;
+
+const INDENT: &str = "    ";
+
+/// The predicates and objects of a subject (in a graph).
+type Description = Vec<(NamedNode, Vec<Term>)>;
+
+/// Writes the quads as Turtle (if all are in the default graph) or TriG, using the given base IRI
+/// and prefixes (declaring those used, in sorted order).
+pub(crate) fn serialize<W: Write>(
+    quads: Vec<Quad>,
+    base_iri: Option<&str>,
+    prefixes: &HashMap<String, String>,
+    mut writer: W,
+) -> Result<()> {
+    let terms = Terms::new(base_iri, prefixes)?;
+
+    // (Written after the declarations of the prefixes used in it.)
+    let mut body = Vec::new();
+    write_graphs(quads, &terms, &mut body)?;
+
+    if let Some(base_iri) = base_iri {
+        writeln!(writer, "@base <{base_iri}> .")?;
+    }
+    let used = terms.used_prefixes();
+    let mut sorted_prefixes: Vec<_> = prefixes
+        .iter()
+        .filter(|(pfx, _)| used.contains(*pfx))
+        .collect();
+    sorted_prefixes.sort();
+    for (pfx, ns) in &sorted_prefixes {
+        writeln!(writer, "@prefix {pfx}: <{ns}> .")?;
+    }
+    if (base_iri.is_some() || !sorted_prefixes.is_empty()) && !body.is_empty() {
+        writeln!(writer)?;
+    }
+    writer.write_all(&body)?;
+
+    Ok(())
+}
+
+fn write_graphs<W: Write>(quads: Vec<Quad>, terms: &Terms, mut writer: W) -> Result<()> {
+    let mut separate = false;
+
+    let uses = blank_node_uses(&quads);
+
+    let mut graphs: HashMap<GraphName, HashMap<NamedOrBlankNode, Description>> = HashMap::new();
+    for quad in quads {
+        let description = graphs
+            .entry(quad.graph_name)
+            .or_default()
+            .entry(quad.subject)
+            .or_default();
+        match description.iter_mut().find(|(p, _)| *p == quad.predicate) {
+            Some((_, objects)) => objects.push(quad.object),
+            None => description.push((quad.predicate, vec![quad.object])),
+        }
+    }
+
+    let mut graph_names: Vec<_> = graphs.keys().cloned().collect();
+    graph_names.sort_by_key(|graph_name| match graph_name {
+        GraphName::DefaultGraph => (0, String::new()),
+        GraphName::NamedNode(node) => (1, node.as_str().to_owned()),
+        GraphName::BlankNode(node) => (2, node.as_str().to_owned()),
+    });
+
+    for graph_name in graph_names {
+        let mut descriptions = graphs.remove(&graph_name).unwrap_or_default();
+        for description in descriptions.values_mut() {
+            sort_description(description);
+        }
+        let mut graph = Graph {
+            terms,
+            descriptions,
+            uses: &uses,
+            written: HashSet::new(),
+        };
+
+        if separate {
+            writeln!(writer)?;
+        }
+        separate = true;
+        let indent = match &graph_name {
+            GraphName::DefaultGraph => "",
+            GraphName::NamedNode(node) => {
+                writeln!(writer, "{} {{", terms.iri(node))?;
+                INDENT
+            }
+            GraphName::BlankNode(node) => {
+                writeln!(writer, "_:{} {{", node.as_str())?;
+                INDENT
+            }
+        };
+        graph.write(&mut writer, indent)?;
+        if !indent.is_empty() {
+            writeln!(writer, "}}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts the uses as objects of the blank nodes which are only used in one graph (and not as a
+/// graph name). Those used once can be written nested in place, and those not used as objects
+/// need no label.
+fn blank_node_uses(quads: &[Quad]) -> HashMap<BlankNode, usize> {
+    let mut object_uses: HashMap<&BlankNode, usize> = HashMap::new();
+    let mut graphs: HashMap<&BlankNode, &GraphName> = HashMap::new();
+    let mut excluded = HashSet::new();
+    let mut note_graph = |bnode, graph_name| {
+        if *graphs.entry(bnode).or_insert(graph_name) != graph_name {
+            excluded.insert(bnode);
+        }
+    };
+    for quad in quads {
+        if let NamedOrBlankNode::BlankNode(subject) = &quad.subject {
+            note_graph(subject, &quad.graph_name);
+            object_uses.entry(subject).or_default();
+        }
+        if let Term::BlankNode(object) = &quad.object {
+            note_graph(object, &quad.graph_name);
+            *object_uses.entry(object).or_default() += 1;
+        }
+    }
+    for quad in quads {
+        if let GraphName::BlankNode(graph_name) = &quad.graph_name {
+            excluded.insert(graph_name);
+        }
+        // (Blank nodes in triple terms are referred to by label.)
+        let mut object = &quad.object;
+        while let Term::Triple(triple) = object {
+            if let NamedOrBlankNode::BlankNode(subject) = &triple.subject {
+                excluded.insert(subject);
+            }
+            if let Term::BlankNode(bnode) = &triple.object {
+                excluded.insert(bnode);
+            }
+            object = &triple.object;
+        }
+    }
+    object_uses
+        .into_iter()
+        .filter(|(bnode, _)| !excluded.contains(bnode))
+        .map(|(bnode, uses)| (bnode.clone(), uses))
+        .collect()
+}
+
+/// Sorts predicates (with `rdf:type` first) and their objects.
+fn sort_description(description: &mut Description) {
+    description.sort_by_key(|(predicate, _)| {
+        (
+            predicate.as_ref() != rdf::TYPE,
+            predicate.as_str().to_owned(),
+        )
+    });
+    for (_, objects) in description {
+        objects.sort_by_key(term_sort_key);
+        objects.dedup();
+    }
+}
+
+fn term_sort_key(term: &Term) -> (u8, String, String) {
+    match term {
+        Term::NamedNode(node) => (0, node.as_str().to_owned(), String::new()),
+        Term::BlankNode(node) => (1, node.as_str().to_owned(), String::new()),
+        Term::Literal(literal) => (
+            2,
+            literal.value().to_owned(),
+            literal
+                .language()
+                .unwrap_or(literal.datatype().as_str())
+                .to_owned(),
+        ),
+        Term::Triple(triple) => (3, triple.to_string(), String::new()),
+    }
+}
+
+/// The descriptions of the subjects in one graph, written in subject order with any nestable
+/// blank nodes written in place.
+struct Graph<'a> {
+    terms: &'a Terms,
+    descriptions: HashMap<NamedOrBlankNode, Description>,
+    uses: &'a HashMap<BlankNode, usize>,
+    written: HashSet<BlankNode>,
+}
+
+impl Graph<'_> {
+    fn write<W: Write>(&mut self, writer: &mut W, indent: &str) -> Result<()> {
+        let mut subjects: Vec<_> = self.descriptions.keys().cloned().collect();
+        subjects.sort_by_key(|subject| match subject {
+            NamedOrBlankNode::NamedNode(node) => (0, node.as_str().to_owned()),
+            NamedOrBlankNode::BlankNode(node) => (1, node.as_str().to_owned()),
+        });
+        let (unnested, nested): (Vec<_>, Vec<_>) =
+            subjects.into_iter().partition(|subject| match subject {
+                NamedOrBlankNode::BlankNode(bnode) => !self.is_nestable(bnode),
+                _ => true,
+            });
+
+        let mut first = true;
+        // Any nestable blank nodes left after the others are part of cycles, and get labels:
+        for subject in unnested.into_iter().chain(nested) {
+            if let NamedOrBlankNode::BlankNode(bnode) = &subject {
+                if self.written.contains(bnode) {
+                    continue;
+                }
+            }
+            if !first {
+                writeln!(writer)?;
+            }
+            first = false;
+            self.write_subject(writer, &subject, indent)?;
+        }
+        Ok(())
+    }
+
+    fn write_subject<W: Write>(
+        &mut self,
+        writer: &mut W,
+        subject: &NamedOrBlankNode,
+        indent: &str,
+    ) -> Result<()> {
+        let description = self.descriptions.get(subject).cloned().unwrap_or_default();
+        match subject {
+            NamedOrBlankNode::NamedNode(node) => {
+                write!(writer, "{indent}{}", self.terms.iri(node))?
+            }
+            NamedOrBlankNode::BlankNode(bnode) => {
+                self.written.insert(bnode.clone());
+                // Blank nodes which are not used as objects need no label:
+                if self.uses.get(bnode) == Some(&0) {
+                    write!(writer, "{indent}")?;
+                    self.write_nested(writer, &description, indent)?;
+                    writeln!(writer, " .")?;
+                    return Ok(());
+                }
+                write!(writer, "{indent}_:{}", bnode.as_str())?;
+            }
+        }
+        write!(writer, " ")?;
+        self.write_description(writer, &description, indent)?;
+        writeln!(writer, " .")?;
+        Ok(())
+    }
+
+    fn is_nestable(&self, bnode: &BlankNode) -> bool {
+        self.uses.get(bnode) == Some(&1)
+    }
+
+    fn is_nested(&self, object: &Term) -> bool {
+        matches!(object, Term::BlankNode(bnode) if self.is_nestable(bnode) && !self.written.contains(bnode))
+    }
+
+    /// Writes the predicates and objects of a subject (continuing on indented lines).
+    fn write_description<W: Write>(
+        &mut self,
+        writer: &mut W,
+        description: &Description,
+        indent: &str,
+    ) -> Result<()> {
+        for (i, (predicate, objects)) in description.iter().enumerate() {
+            if i > 0 {
+                write!(writer, " ;\n{indent}{INDENT}")?;
+            }
+            if predicate.as_ref() == rdf::TYPE {
+                write!(writer, "a")?;
+            } else {
+                write!(writer, "{}", self.terms.iri(predicate))?;
+            }
+            for (j, object) in objects.iter().enumerate() {
+                write!(writer, "{}", if j == 0 { " " } else { ", " })?;
+                self.write_object(writer, object, &format!("{indent}{INDENT}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_nested<W: Write>(
+        &mut self,
+        writer: &mut W,
+        description: &Description,
+        indent: &str,
+    ) -> Result<()> {
+        match description.as_slice() {
+            [] => write!(writer, "[]")?,
+            // A single value which is not nested itself is written on one line:
+            [(_, objects)] if matches!(objects.as_slice(), [object] if !self.is_nested(object)) => {
+                write!(writer, "[ ")?;
+                self.write_description(writer, description, indent)?;
+                write!(writer, " ]")?;
+            }
+            _ => {
+                write!(writer, "[\n{indent}{INDENT}")?;
+                self.write_description(writer, description, indent)?;
+                write!(writer, "\n{indent}]")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_object<W: Write>(
+        &mut self,
+        writer: &mut W,
+        object: &Term,
+        indent: &str,
+    ) -> Result<()> {
+        let bnode = match object {
+            Term::BlankNode(bnode) if self.is_nestable(bnode) => bnode,
+            Term::BlankNode(bnode) => {
+                write!(writer, "_:{}", bnode.as_str())?;
+                return Ok(());
+            }
+            Term::NamedNode(node) => {
+                write!(writer, "{}", self.terms.iri(node))?;
+                return Ok(());
+            }
+            Term::Literal(literal) => {
+                write!(writer, "{}", self.terms.literal(literal))?;
+                return Ok(());
+            }
+            Term::Triple(_) => {
+                write!(writer, "{}", self.terms.term(object))?;
+                return Ok(());
+            }
+        };
+        if self.written.contains(bnode) {
+            write!(writer, "_:{}", bnode.as_str())?;
+            return Ok(());
+        }
+
+        if let Some(items) = self.list_items(bnode) {
+            write!(writer, "(")?;
+            for item in &items {
+                write!(writer, " ")?;
+                self.write_object(writer, item, indent)?;
+            }
+            write!(writer, " )")?;
+            return Ok(());
+        }
+
+        self.written.insert(bnode.clone());
+        let subject = NamedOrBlankNode::BlankNode(bnode.clone());
+        let description = self.descriptions.get(&subject).cloned().unwrap_or_default();
+        self.write_nested(writer, &description, indent)
+    }
+
+    /// Returns the items of a well-formed list starting at the blank node (all of whose nodes are
+    /// nestable and have only `rdf:first` and `rdf:rest`), marking its nodes as written.
+    fn list_items(&mut self, head: &BlankNode) -> Option<Vec<Term>> {
+        let mut items = Vec::new();
+        let mut nodes = Vec::new();
+        let mut node = head.clone();
+        loop {
+            if !self.is_nestable(&node) || self.written.contains(&node) || nodes.contains(&node) {
+                return None;
+            }
+            let description = self
+                .descriptions
+                .get(&NamedOrBlankNode::BlankNode(node.clone()))?;
+            let [(p1, first), (p2, rest)] = description.as_slice() else {
+                return None;
+            };
+            let (first, rest) = match (p1.as_ref(), p2.as_ref()) {
+                (p1, p2) if p1 == rdf::FIRST && p2 == rdf::REST => (first, rest),
+                (p1, p2) if p1 == rdf::REST && p2 == rdf::FIRST => (rest, first),
+                _ => return None,
+            };
+            let ([first], [rest]) = (first.as_slice(), rest.as_slice()) else {
+                return None;
+            };
+            items.push(first.clone());
+            nodes.push(node);
+            match rest {
+                Term::NamedNode(nil) if nil.as_ref() == rdf::NIL => break,
+                Term::BlankNode(next) => node = next.clone(),
+                _ => return None,
+            }
+        }
+        self.written.extend(nodes);
+        Some(items)
+    }
+}
+
+/// Formats IRIs and literals as in Turtle (abbreviated where possible).
+pub(crate) struct Terms {
+    base_iri: Option<Iri<String>>,
+    // Sorted by longest namespace first:
+    prefixes: Vec<(String, String)>,
+    local_name: Regex,
+    integer: Regex,
+    decimal: Regex,
+    double: Regex,
+    /// The prefixes used so far.
+    used: RefCell<HashSet<String>>,
+}
+
+impl Terms {
+    pub(crate) fn new(base_iri: Option<&str>, prefixes: &HashMap<String, String>) -> Result<Self> {
+        let mut prefixes: Vec<_> = prefixes
+            .iter()
+            .map(|(pfx, ns)| (pfx.to_owned(), ns.to_owned()))
+            .collect();
+        prefixes.sort_by(|(apfx, ans), (bpfx, bns)| bns.len().cmp(&ans.len()).then(apfx.cmp(bpfx)));
+        Ok(Self {
+            base_iri: base_iri.map(|iri| Iri::parse(iri.to_owned())).transpose()?,
+            prefixes,
+            // (A conservative subset of the local names allowed in Turtle.)
+            local_name: Regex::new(r"^([A-Za-z0-9_]([A-Za-z0-9_.-]*[A-Za-z0-9_-])?)?$")
+                .expect("valid regex"),
+            integer: Regex::new(r"^[+-]?\d+$").expect("valid regex"),
+            decimal: Regex::new(r"^[+-]?\d*\.\d+$").expect("valid regex"),
+            double: Regex::new(r"^[+-]?(\d+\.\d*|\.\d+|\d+)[eE][+-]?\d+$").expect("valid regex"),
+            used: RefCell::default(),
+        })
+    }
+
+    /// Returns the prefixes used in the IRIs formatted so far.
+    pub(crate) fn used_prefixes(&self) -> HashSet<String> {
+        self.used.borrow().clone()
+    }
+
+    pub(crate) fn iri(&self, node: &NamedNode) -> String {
+        let iri = node.as_str();
+        let prefixed = self.prefixes.iter().find_map(|(pfx, ns)| {
+            iri.strip_prefix(ns.as_str())
+                .filter(|local| self.local_name.is_match(local))
+                .map(|local| {
+                    self.used.borrow_mut().insert(pfx.to_owned());
+                    format!("{pfx}:{local}")
+                })
+        });
+        if let Some(prefixed) = prefixed {
+            return prefixed;
+        }
+        if let Some(base_iri) = &self.base_iri {
+            if let Ok(relative) = base_iri.relativize(&Iri::parse_unchecked(iri)) {
+                return format!("<{}>", relative.as_str());
+            }
+        }
+        format!("<{iri}>")
+    }
+
+    /// Formats a term as in Turtle (with triple terms as `<<( s p o )>>`).
+    pub(crate) fn term(&self, term: &Term) -> String {
+        match term {
+            Term::NamedNode(node) => self.iri(node),
+            Term::BlankNode(node) => node.to_string(),
+            Term::Literal(literal) => self.literal(literal),
+            Term::Triple(triple) => {
+                let subject = self.term(&triple.subject.clone().into());
+                let predicate = self.iri(&triple.predicate);
+                format!(
+                    "<<( {subject} {predicate} {} )>>",
+                    self.term(&triple.object)
+                )
+            }
+        }
+    }
+
+    pub(crate) fn literal(&self, literal: &Literal) -> String {
+        let value = literal.value();
+        let quoted = if value.contains('\n') {
+            let escaped = value.replace('\\', r"\\").replace('"', r#"\""#);
+            format!(r#""""{escaped}""""#)
+        } else {
+            Literal::new_simple_literal(value).to_string()
+        };
+        if let Some(language) = literal.language() {
+            return match literal.direction() {
+                Some(direction) => format!("{quoted}@{language}--{direction}"),
+                None => format!("{quoted}@{language}"),
+            };
+        }
+        let datatype = literal.datatype();
+        let is_bare = (datatype == xsd::INTEGER && self.integer.is_match(value))
+            || (datatype == xsd::DECIMAL && self.decimal.is_match(value))
+            || (datatype == xsd::DOUBLE && self.double.is_match(value))
+            || (datatype == xsd::BOOLEAN && matches!(value, "true" | "false"));
+        if is_bare {
+            value.to_owned()
+        } else if datatype == xsd::STRING {
+            quoted
+        } else {
+            format!("{quoted}^^{}", self.iri(&datatype.into_owned()))
+        }
+    }
+}
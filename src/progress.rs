@@ -0,0 +1,62 @@
+//! Progress reporting (on stderr) while loading and serializing data.
+
+use std::time::{Duration, Instant};
+
+/// How often the progress line is redrawn.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Number of quads between checks of the time (to keep the counting cheap).
+const CHECK_INTERVAL: usize = 4096;
+
+const BAR_WIDTH: usize = 30;
+
+/// A quad counter (or, when the total is known, a progress bar), redrawn in place.
+pub(crate) struct Progress {
+    label: &'static str,
+    total: Option<usize>,
+    count: usize,
+    start: Instant,
+    shown: Instant,
+}
+
+impl Progress {
+    pub(crate) fn new(label: &'static str, total: Option<usize>) -> Self {
+        let now = Instant::now();
+        Self {
+            label,
+            total,
+            count: 0,
+            start: now,
+            shown: now,
+        }
+    }
+
+    pub(crate) fn inc(&mut self) {
+        self.count += 1;
+        if self.count.is_multiple_of(CHECK_INTERVAL) && self.shown.elapsed() >= REFRESH_INTERVAL {
+            eprint!("\r{}", self.line());
+            self.shown = Instant::now();
+        }
+    }
+
+    /// Writes the final count, along with the time taken.
+    pub(crate) fn finish(self) {
+        eprintln!("\r{} in {:.1?}", self.line(), self.start.elapsed());
+    }
+
+    fn line(&self) -> String {
+        let Some(total) = self.total.filter(|total| *total > 0) else {
+            return format!("{}: {} quads", self.label, self.count);
+        };
+        let done = (self.count * BAR_WIDTH / total).min(BAR_WIDTH);
+        format!(
+            "{}: [{}{}] {:3}% ({}/{} quads)",
+            self.label,
+            "=".repeat(done),
+            " ".repeat(BAR_WIDTH - done),
+            self.count * 100 / total,
+            self.count,
+            total
+        )
+    }
+}
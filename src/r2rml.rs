@@ -0,0 +1,875 @@
+//! Mapping of tables to RDF by R2RML (the W3C RDB to RDF Mapping Language), given as triples
+//! maps (in RDF) of the rows of logical tables to subjects, predicates, objects and graphs. The
+//! RML extension of it is also supported, for JSON sources iterated over by JSONPath, and XML
+//! sources by XPath.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use anyhow::{bail, Context as _, Result};
+use oxigraph::io::{RdfFormat, RdfParser};
+use oxigraph::model::vocab::rdf;
+use oxigraph::model::{
+    BlankNode, Graph, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term, TermRef, Triple,
+};
+use oxiri::Iri;
+
+use crate::jsonpath::JsonPath;
+use crate::xpath::{Document, Item, XPath};
+
+const RR: &str = "http://www.w3.org/ns/r2rml#";
+const RML: &str = "http://semweb.mmlab.be/ns/rml#";
+const QL: &str = "http://semweb.mmlab.be/ns/ql#";
+
+/// A row of a logical table: the values of its columns (as natural RDF literals). Null values
+/// are left out.
+pub(crate) type Row = HashMap<String, Vec<Literal>>;
+
+/// An R2RML (or RML) mapping: the triples maps of it.
+#[derive(Clone)]
+pub(crate) struct Mapping {
+    triples_maps: Vec<TriplesMap>,
+}
+
+/// Where the rows of a triples map come from.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) enum LogicalSource {
+    /// A table of a database, by name.
+    Table(String),
+    /// The values selected by a JSONPath iterator in a JSON source (named by `rml:source`).
+    Json { source: String, iterator: String },
+    /// The nodes selected by an XPath iterator in an XML source (named by `rml:source`).
+    Xml { source: String, iterator: String },
+}
+
+impl LogicalSource {
+    /// Gives the name of the source of a document (given by `rml:source`).
+    pub(crate) fn source_name(&self) -> Option<&str> {
+        match self {
+            Self::Table(_) => None,
+            Self::Json { source, .. } | Self::Xml { source, .. } => Some(source),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct TriplesMap {
+    source: LogicalSource,
+    subject: TermMap,
+    classes: Vec<NamedNode>,
+    graphs: Vec<TermMap>,
+    predicate_objects: Vec<PredicateObjectMap>,
+}
+
+#[derive(Clone)]
+struct PredicateObjectMap {
+    predicates: Vec<TermMap>,
+    objects: Vec<ObjectMap>,
+    graphs: Vec<TermMap>,
+}
+
+#[derive(Clone)]
+enum ObjectMap {
+    Term(TermMap),
+    /// The subjects of (the index of) another triples map, for the rows joined by the
+    /// conditions (of child and parent columns).
+    Join {
+        parent: usize,
+        conditions: Vec<(String, String)>,
+    },
+}
+
+#[derive(Clone)]
+struct TermMap {
+    value: TermValue,
+    term_type: TermType,
+    datatype: Option<NamedNode>,
+    language: Option<String>,
+}
+
+#[derive(Clone)]
+enum TermValue {
+    Constant(Term),
+    Column(String),
+    Template(Vec<TemplatePart>),
+}
+
+#[derive(Clone)]
+enum TemplatePart {
+    Text(String),
+    Column(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TermType {
+    Iri,
+    BlankNode,
+    Literal,
+}
+
+/// The state of applying a mapping.
+struct Context<'a> {
+    base_iri: Option<Iri<String>>,
+    graph_name: &'a GraphName,
+    /// The blank nodes made, by label (to give the same node for the same values).
+    blank_nodes: HashMap<String, BlankNode>,
+}
+
+fn rr(name: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{RR}{name}"))
+}
+
+fn rml(name: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{RML}{name}"))
+}
+
+impl Mapping {
+    /// Parses a mapping in Turtle.
+    pub(crate) fn parse<R: Read>(reader: R, base_iri: &str) -> Result<Self> {
+        let parser = RdfParser::from_format(RdfFormat::Turtle).with_base_iri(base_iri)?;
+        let graph = parser
+            .for_reader(reader)
+            .map(|quad| Ok(Triple::from(quad?)))
+            .collect::<Result<Graph>>()?;
+        Self::from_graph(&graph)
+    }
+
+    /// Reads the triples maps of an R2RML (or RML) mapping.
+    fn from_graph(graph: &Graph) -> Result<Self> {
+        let (logical_table, logical_source) = (rr("logicalTable"), rml("logicalSource"));
+        let nodes: Vec<NamedOrBlankNode> = graph
+            .triples_for_predicate(&logical_table)
+            .chain(graph.triples_for_predicate(&logical_source))
+            .map(|triple| triple.subject.into_owned())
+            .collect();
+        if nodes.is_empty() {
+            bail!("No triples maps (with an rr:logicalTable or rml:logicalSource) in the mapping");
+        }
+
+        let mut triples_maps = Vec::with_capacity(nodes.len());
+        for node in &nodes {
+            let triples_map = read_triples_map(graph, node, &nodes)
+                .with_context(|| format!("Invalid R2RML triples map {node}"))?;
+            triples_maps.push(triples_map);
+        }
+        Ok(Self { triples_maps })
+    }
+
+    /// Gives the logical sources of the triples maps.
+    pub(crate) fn sources(&self) -> impl Iterator<Item = &LogicalSource> {
+        self.triples_maps
+            .iter()
+            .map(|triples_map| &triples_map.source)
+    }
+
+    /// Applies the mapping to the rows of the logical sources (given for the columns, or
+    /// references, used of each), resolving relative IRIs against the base IRI, and putting
+    /// triples into the given graph unless the mapping says otherwise.
+    pub(crate) fn apply(
+        &self,
+        rows: impl Fn(&LogicalSource, &[String]) -> Result<Vec<Row>>,
+        base_iri: Option<&str>,
+        graph_name: &GraphName,
+    ) -> Result<Vec<Quad>> {
+        let mut tables: HashMap<&LogicalSource, Vec<Row>> = HashMap::new();
+        for triples_map in &self.triples_maps {
+            if !tables.contains_key(&triples_map.source) {
+                let references = self.references(&triples_map.source);
+                tables.insert(&triples_map.source, rows(&triples_map.source, &references)?);
+            }
+        }
+        let mut context = Context {
+            base_iri: base_iri.map(|iri| Iri::parse(iri.to_owned())).transpose()?,
+            graph_name,
+            blank_nodes: HashMap::new(),
+        };
+
+        let mut quads = Vec::new();
+        for triples_map in &self.triples_maps {
+            let rows = &tables[&triples_map.source];
+            // The objects of joins, by the values of the parent columns:
+            let mut joins = HashMap::new();
+            for (i, object) in triples_map.objects() {
+                if let ObjectMap::Join { parent, conditions } = object {
+                    let parent = &self.triples_maps[*parent];
+                    let index = parent.join_index(conditions, &tables, &mut context)?;
+                    joins.insert(i, index);
+                }
+            }
+
+            for row in rows {
+                for subject in triples_map.subject.generate(row, &mut context)? {
+                    let Some(subject) = as_subject(subject) else {
+                        continue;
+                    };
+                    let graphs = generate_graphs(&triples_map.graphs, row, &mut context)?;
+                    for class in &triples_map.classes {
+                        for graph_name in &graphs {
+                            quads.push(Quad::new(
+                                subject.clone(),
+                                rdf::TYPE,
+                                class.clone(),
+                                graph_name.clone(),
+                            ));
+                        }
+                    }
+
+                    for (i, predicate_object) in triples_map.predicate_objects.iter().enumerate() {
+                        let mut predicates = Vec::new();
+                        for predicate in &predicate_object.predicates {
+                            for term in predicate.generate(row, &mut context)? {
+                                if let Term::NamedNode(node) = term {
+                                    predicates.push(node);
+                                }
+                            }
+                        }
+                        let mut objects = Vec::new();
+                        for (j, object) in predicate_object.objects.iter().enumerate() {
+                            let join = joins.get(&(i, j));
+                            objects.extend(self.objects(
+                                triples_map,
+                                object,
+                                join,
+                                row,
+                                &mut context,
+                            )?);
+                        }
+                        let mut graphs = graphs.clone();
+                        if !predicate_object.graphs.is_empty() {
+                            let more =
+                                generate_graphs(&predicate_object.graphs, row, &mut context)?;
+                            if triples_map.graphs.is_empty() {
+                                graphs = more;
+                            } else {
+                                graphs.extend(more);
+                            }
+                        }
+                        for predicate in &predicates {
+                            for object in &objects {
+                                for graph_name in &graphs {
+                                    quads.push(Quad::new(
+                                        subject.clone(),
+                                        predicate.clone(),
+                                        object.clone(),
+                                        graph_name.clone(),
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Gives the columns (or references) used of a logical source, by the triples maps of it and
+    /// by the join conditions of those referring to them.
+    fn references(&self, source: &LogicalSource) -> Vec<String> {
+        let mut references = Vec::new();
+        for triples_map in &self.triples_maps {
+            let is_source = triples_map.source == *source;
+            for (_, object) in triples_map.objects() {
+                let ObjectMap::Join { parent, conditions } = object else {
+                    continue;
+                };
+                let is_parent_source = self.triples_maps[*parent].source == *source;
+                for (child, parent) in conditions {
+                    if is_source {
+                        references.push(child.clone());
+                    }
+                    if is_parent_source {
+                        references.push(parent.clone());
+                    }
+                }
+            }
+            if !is_source {
+                continue;
+            }
+            let term_maps = triples_map
+                .predicate_objects
+                .iter()
+                .flat_map(|predicate_object| {
+                    let objects =
+                        predicate_object
+                            .objects
+                            .iter()
+                            .filter_map(|object| match object {
+                                ObjectMap::Term(term_map) => Some(term_map),
+                                ObjectMap::Join { .. } => None,
+                            });
+                    (predicate_object.predicates.iter())
+                        .chain(objects)
+                        .chain(&predicate_object.graphs)
+                });
+            let term_maps = [&triples_map.subject]
+                .into_iter()
+                .chain(&triples_map.graphs)
+                .chain(term_maps);
+            for term_map in term_maps {
+                match &term_map.value {
+                    TermValue::Constant(_) => {}
+                    TermValue::Column(column) => references.push(column.clone()),
+                    TermValue::Template(parts) => {
+                        references.extend(parts.iter().filter_map(|part| match part {
+                            TemplatePart::Column(column) => Some(column.clone()),
+                            TemplatePart::Text(_) => None,
+                        }))
+                    }
+                }
+            }
+        }
+        references.sort();
+        references.dedup();
+        references
+    }
+
+    /// Generates the objects of an object map for a row (of the triples map), using the index of
+    /// the parent subjects for joins.
+    fn objects(
+        &self,
+        triples_map: &TriplesMap,
+        object: &ObjectMap,
+        join: Option<&HashMap<Vec<String>, Vec<Term>>>,
+        row: &Row,
+        context: &mut Context,
+    ) -> Result<Vec<Term>> {
+        let (parent, conditions) = match object {
+            ObjectMap::Term(term_map) => return term_map.generate(row, context),
+            ObjectMap::Join { parent, conditions } => (&self.triples_maps[*parent], conditions),
+        };
+        let join = join.expect("an index of the parent subjects");
+        if !conditions.is_empty() {
+            let keys = condition_values(row, conditions.iter().map(|(child, _)| child));
+            let objects = keys.iter().filter_map(|key| join.get(key)).flatten();
+            return Ok(objects.cloned().collect());
+        }
+        // (Without conditions, a parent of the same table gives the subjects of the same row.)
+        if parent.source == triples_map.source {
+            return parent.subject.generate(row, context);
+        }
+        Ok(join.values().flatten().cloned().collect())
+    }
+}
+
+impl TriplesMap {
+    /// Gives the object maps (by the index of the predicate-object map and of the object map).
+    fn objects(&self) -> impl Iterator<Item = ((usize, usize), &ObjectMap)> {
+        self.predicate_objects
+            .iter()
+            .enumerate()
+            .flat_map(|(i, predicate_object)| {
+                let objects = predicate_object.objects.iter().enumerate();
+                objects.map(move |(j, object)| ((i, j), object))
+            })
+    }
+
+    /// Gives the subjects of the rows of this (parent) triples map, by the values of the parent
+    /// columns of the join conditions.
+    fn join_index(
+        &self,
+        conditions: &[(String, String)],
+        tables: &HashMap<&LogicalSource, Vec<Row>>,
+        context: &mut Context,
+    ) -> Result<HashMap<Vec<String>, Vec<Term>>> {
+        let mut index: HashMap<Vec<String>, Vec<Term>> = HashMap::new();
+        for row in &tables[&self.source] {
+            let subjects = self.subject.generate(row, context)?;
+            for key in condition_values(row, conditions.iter().map(|c| &c.1)) {
+                index
+                    .entry(key)
+                    .or_default()
+                    .extend(subjects.iter().cloned());
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// Gives the combinations of the (lexical) values of the columns of a row (none if any column
+/// is null).
+fn condition_values<'a>(row: &Row, columns: impl Iterator<Item = &'a String>) -> Vec<Vec<String>> {
+    let mut keys = vec![Vec::new()];
+    for column in columns {
+        let values = row.get(column).map_or(&[][..], Vec::as_slice);
+        keys = keys
+            .into_iter()
+            .flat_map(|key| {
+                values.iter().map(move |value| {
+                    let mut key = key.clone();
+                    key.push(value.value().to_owned());
+                    key
+                })
+            })
+            .collect();
+    }
+    keys
+}
+
+fn generate_graphs(graphs: &[TermMap], row: &Row, context: &mut Context) -> Result<Vec<GraphName>> {
+    if graphs.is_empty() {
+        return Ok(vec![context.graph_name.clone()]);
+    }
+    let mut graph_names = Vec::new();
+    for graph in graphs {
+        for term in graph.generate(row, context)? {
+            if let Term::NamedNode(node) = term {
+                graph_names.push(match node.as_str() == format!("{RR}defaultGraph") {
+                    true => GraphName::DefaultGraph,
+                    false => node.into(),
+                });
+            }
+        }
+    }
+    Ok(graph_names)
+}
+
+fn as_subject(term: Term) -> Option<NamedOrBlankNode> {
+    match term {
+        Term::NamedNode(node) => Some(node.into()),
+        Term::BlankNode(node) => Some(node.into()),
+        _ => None,
+    }
+}
+
+impl TermMap {
+    /// Generates the terms for a row (none if any value used is null, and several if any
+    /// column has several values).
+    fn generate(&self, row: &Row, context: &mut Context) -> Result<Vec<Term>> {
+        let values: Vec<(String, Option<&Literal>)> = match &self.value {
+            TermValue::Constant(term) => return Ok(vec![term.clone()]),
+            TermValue::Column(column) => row
+                .get(column)
+                .map_or(&[][..], Vec::as_slice)
+                .iter()
+                .map(|value| (value.value().to_owned(), Some(value)))
+                .collect(),
+            TermValue::Template(parts) => {
+                let mut values = vec![String::new()];
+                for part in parts {
+                    let column = match part {
+                        TemplatePart::Text(text) => {
+                            values.iter_mut().for_each(|value| value.push_str(text));
+                            continue;
+                        }
+                        TemplatePart::Column(column) => column,
+                    };
+                    let column_values = row.get(column).map_or(&[][..], Vec::as_slice);
+                    values = values
+                        .iter()
+                        .flat_map(|value| {
+                            column_values.iter().map(move |column_value| {
+                                let column_value = match self.term_type {
+                                    TermType::Iri => iri_safe(column_value.value()),
+                                    _ => column_value.value().to_owned(),
+                                };
+                                format!("{value}{column_value}")
+                            })
+                        })
+                        .collect();
+                }
+                values.into_iter().map(|value| (value, None)).collect()
+            }
+        };
+
+        let mut terms = Vec::with_capacity(values.len());
+        for (value, natural) in values {
+            terms.push(match self.term_type {
+                TermType::Iri => match Iri::parse(value.clone()) {
+                    Ok(iri) => NamedNode::from(iri).into(),
+                    Err(_) => {
+                        let Some(base_iri) = &context.base_iri else {
+                            bail!("No base IRI to resolve IRI made by mapping against: {value}");
+                        };
+                        let iri = base_iri
+                            .resolve(&value)
+                            .with_context(|| format!("Invalid IRI made by mapping: {value}"))?;
+                        NamedNode::from(iri).into()
+                    }
+                },
+                TermType::BlankNode => context.blank_nodes.entry(value).or_default().clone().into(),
+                TermType::Literal => match (&self.datatype, &self.language, natural) {
+                    (Some(datatype), _, _) => {
+                        Literal::new_typed_literal(value, datatype.clone()).into()
+                    }
+                    (None, Some(language), _) => {
+                        Literal::new_language_tagged_literal(value, language)?.into()
+                    }
+                    (None, None, Some(natural)) => natural.clone().into(),
+                    (None, None, None) => Literal::new_simple_literal(value).into(),
+                },
+            });
+        }
+        Ok(terms)
+    }
+}
+
+/// Percent-encodes the characters of a value not allowed unencoded in IRIs (such as spaces and
+/// slashes), keeping letters, digits, `-`, `.`, `_`, `~` and non-ASCII characters.
+pub(crate) fn iri_safe(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() || "-._~".contains(c) || !c.is_ascii() {
+            encoded.push(c);
+        } else {
+            encoded.push_str(&format!("%{:02X}", c as u32));
+        }
+    }
+    encoded
+}
+
+fn read_triples_map(
+    graph: &Graph,
+    node: &NamedOrBlankNode,
+    triples_maps: &[NamedOrBlankNode],
+) -> Result<TriplesMap> {
+    let source = read_logical_source(graph, node)?;
+
+    let (subject, subject_node) = match term_maps(graph, node, "subject", TermType::Iri)?
+        .into_iter()
+        .next()
+    {
+        Some(map) => map,
+        None => bail!("No rr:subjectMap"),
+    };
+    let (classes, graphs) = match &subject_node {
+        Some(subject_node) => {
+            let classes = graph
+                .objects_for_subject_predicate(subject_node, &rr("class"))
+                .map(|class| match class {
+                    TermRef::NamedNode(class) => Ok(class.into_owned()),
+                    _ => bail!("Invalid rr:class: {class}"),
+                })
+                .collect::<Result<_>>()?;
+            let graphs = term_maps(graph, subject_node, "graph", TermType::Iri)?;
+            (classes, graphs.into_iter().map(|(map, _)| map).collect())
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let mut predicate_objects = Vec::new();
+    for predicate_object in graph.objects_for_subject_predicate(node, &rr("predicateObjectMap")) {
+        let Some(predicate_object) = as_node(predicate_object) else {
+            continue;
+        };
+        let predicates = term_maps(graph, &predicate_object, "predicate", TermType::Iri)?;
+        let mut objects: Vec<ObjectMap> = graph
+            .objects_for_subject_predicate(&predicate_object, &rr("object"))
+            .map(|constant| ObjectMap::Term(constant_map(constant.into_owned())))
+            .collect();
+        for object in graph.objects_for_subject_predicate(&predicate_object, &rr("objectMap")) {
+            let object = as_node(object).context("Invalid rr:objectMap")?;
+            objects.push(read_object_map(graph, &object, triples_maps)?);
+        }
+        let graphs = term_maps(graph, &predicate_object, "graph", TermType::Iri)?;
+        predicate_objects.push(PredicateObjectMap {
+            predicates: predicates.into_iter().map(|(map, _)| map).collect(),
+            objects,
+            graphs: graphs.into_iter().map(|(map, _)| map).collect(),
+        });
+    }
+
+    Ok(TriplesMap {
+        source,
+        subject,
+        classes,
+        graphs,
+        predicate_objects,
+    })
+}
+
+fn read_logical_source(graph: &Graph, node: &NamedOrBlankNode) -> Result<LogicalSource> {
+    let string = |node: &NamedOrBlankNode, property: &NamedNode| match graph
+        .object_for_subject_predicate(node, property)
+    {
+        Some(TermRef::Literal(literal)) => Some(literal.value().to_owned()),
+        Some(TermRef::NamedNode(iri)) => Some(iri.as_str().to_owned()),
+        _ => None,
+    };
+    if let Some(logical_table) = object_node(graph, node, &rr("logicalTable"))? {
+        if string(&logical_table, &rr("sqlQuery")).is_some() {
+            bail!("SQL queries (rr:sqlQuery) are not supported, only tables (rr:tableName)");
+        }
+        let Some(table) = string(&logical_table, &rr("tableName")) else {
+            bail!("No rr:tableName of the rr:logicalTable");
+        };
+        return Ok(LogicalSource::Table(unquote_name(&table)));
+    }
+
+    let logical_source = object_node(graph, node, &rml("logicalSource"))?
+        .context("No rr:logicalTable or rml:logicalSource")?;
+    let source = string(&logical_source, &rml("source")).unwrap_or_default();
+    let iterator = string(&logical_source, &rml("iterator"));
+    let formulation = string(&logical_source, &rml("referenceFormulation"));
+    match formulation.as_deref().and_then(|iri| iri.strip_prefix(QL)) {
+        Some("JSONPath") => {
+            let iterator = iterator.unwrap_or_else(|| "$".to_owned());
+            JsonPath::parse(&iterator)?;
+            Ok(LogicalSource::Json { source, iterator })
+        }
+        Some("XPath") => {
+            let iterator = iterator.unwrap_or_else(|| "/*".to_owned());
+            XPath::parse(&iterator)?;
+            Ok(LogicalSource::Xml { source, iterator })
+        }
+        _ => bail!(
+            "Unsupported rml:referenceFormulation (expected ql:JSONPath or ql:XPath): {}",
+            formulation.as_deref().unwrap_or("none")
+        ),
+    }
+}
+
+/// Gives the rows of the values selected by a JSONPath iterator, with the values of the
+/// references (JSONPath expressions relative to each value) as natural RDF literals.
+pub(crate) fn json_rows(
+    value: &serde_json::Value,
+    iterator: &str,
+    references: &[String],
+) -> Result<Vec<Row>> {
+    let paths = references
+        .iter()
+        .map(|reference| Ok((reference, JsonPath::parse(reference)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut rows = Vec::new();
+    for item in JsonPath::parse(iterator)?.select(value) {
+        let mut row = Row::new();
+        for (reference, path) in &paths {
+            let mut values = Vec::new();
+            for value in path.select(item) {
+                match value {
+                    // (The items of arrays are values of their own.)
+                    serde_json::Value::Array(items) => {
+                        values.extend(items.iter().filter_map(json_literal));
+                    }
+                    value => values.extend(json_literal(value)),
+                }
+            }
+            row.insert((*reference).clone(), values);
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Gives the rows of the nodes selected by an XPath iterator, with the string values of the
+/// references (XPath expressions relative to each node) as literals.
+pub(crate) fn xml_rows(
+    document: &Document,
+    iterator: &str,
+    references: &[String],
+) -> Result<Vec<Row>> {
+    let paths = references
+        .iter()
+        .map(|reference| Ok((reference, XPath::parse(reference)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut rows = Vec::new();
+    for item in XPath::parse(iterator)?.select(document, Item::Node(0)) {
+        let mut row = Row::new();
+        for (reference, path) in &paths {
+            let values = path
+                .select(document, item)
+                .into_iter()
+                .map(|value| Literal::new_simple_literal(document.string_value(value)));
+            row.insert((*reference).clone(), values.collect());
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn json_literal(value: &serde_json::Value) -> Option<Literal> {
+    use serde_json::Value;
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(value) => Literal::from(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => Literal::from(integer),
+            None => Literal::from(number.as_f64()?),
+        },
+        Value::String(value) => Literal::new_simple_literal(value),
+        value => Literal::new_simple_literal(value.to_string()),
+    })
+}
+
+/// Reads an object map: a term map, or a reference to the subjects of a parent triples map.
+fn read_object_map(
+    graph: &Graph,
+    node: &NamedOrBlankNode,
+    triples_maps: &[NamedOrBlankNode],
+) -> Result<ObjectMap> {
+    let Some(parent) = graph.object_for_subject_predicate(node, &rr("parentTriplesMap")) else {
+        let map = read_term_map(graph, node, TermType::Literal)
+            .with_context(|| format!("Invalid rr:objectMap {node}"))?;
+        return Ok(ObjectMap::Term(map));
+    };
+    let parent = as_node(parent)
+        .and_then(|parent| triples_maps.iter().position(|node| *node == parent))
+        .with_context(|| format!("No triples map {parent} (as rr:parentTriplesMap)"))?;
+    let mut conditions = Vec::new();
+    for condition in graph.objects_for_subject_predicate(node, &rr("joinCondition")) {
+        let condition = as_node(condition).context("Invalid rr:joinCondition")?;
+        let column = |name| match graph.object_for_subject_predicate(&condition, &rr(name)) {
+            Some(TermRef::Literal(column)) => Ok(unquote_name(column.value())),
+            _ => bail!("No rr:{name} of rr:joinCondition"),
+        };
+        conditions.push((column("child")?, column("parent")?));
+    }
+    Ok(ObjectMap::Join { parent, conditions })
+}
+
+/// Reads the term maps for a position (such as `subject`) of a node: given by constant
+/// shortcuts (such as `rr:subject`), or by maps (such as `rr:subjectMap`, also giving the node of
+/// each map).
+fn term_maps(
+    graph: &Graph,
+    node: &NamedOrBlankNode,
+    position: &str,
+    default_type: TermType,
+) -> Result<Vec<(TermMap, Option<NamedOrBlankNode>)>> {
+    let mut maps = Vec::new();
+    for constant in graph.objects_for_subject_predicate(node, &rr(position)) {
+        maps.push((constant_map(constant.into_owned()), None));
+    }
+    for map_node in graph.objects_for_subject_predicate(node, &rr(&format!("{position}Map"))) {
+        let map_node = as_node(map_node).with_context(|| format!("Invalid rr:{position}Map"))?;
+        let map = read_term_map(graph, &map_node, default_type)
+            .with_context(|| format!("Invalid rr:{position}Map {map_node}"))?;
+        maps.push((map, Some(map_node)));
+    }
+    Ok(maps)
+}
+
+fn constant_map(term: Term) -> TermMap {
+    let term_type = match &term {
+        Term::BlankNode(_) => TermType::BlankNode,
+        Term::Literal(_) => TermType::Literal,
+        _ => TermType::Iri,
+    };
+    TermMap {
+        value: TermValue::Constant(term),
+        term_type,
+        datatype: None,
+        language: None,
+    }
+}
+
+fn read_term_map(
+    graph: &Graph,
+    node: &NamedOrBlankNode,
+    default_type: TermType,
+) -> Result<TermMap> {
+    let string = |name| match graph.object_for_subject_predicate(node, &rr(name)) {
+        Some(TermRef::Literal(literal)) => Some(literal.value().to_owned()),
+        _ => None,
+    };
+    let datatype = match graph.object_for_subject_predicate(node, &rr("datatype")) {
+        Some(TermRef::NamedNode(datatype)) => Some(datatype.into_owned()),
+        _ => None,
+    };
+    let language = string("language");
+    let term_type = match graph.object_for_subject_predicate(node, &rr("termType")) {
+        Some(TermRef::NamedNode(term_type)) => Some(match term_type.as_str().strip_prefix(RR) {
+            Some("IRI") => TermType::Iri,
+            Some("BlankNode") => TermType::BlankNode,
+            Some("Literal") => TermType::Literal,
+            _ => bail!("Unknown rr:termType: {term_type}"),
+        }),
+        _ => None,
+    };
+
+    let value = if let Some(constant) = graph.object_for_subject_predicate(node, &rr("constant")) {
+        return Ok(constant_map(constant.into_owned()));
+    } else if let Some(column) = string("column") {
+        TermValue::Column(unquote_name(&column))
+    } else if let Some(reference) =
+        match graph.object_for_subject_predicate(node, &rml("reference")) {
+            Some(TermRef::Literal(reference)) => Some(reference.value().to_owned()),
+            _ => None,
+        }
+    {
+        TermValue::Column(reference)
+    } else if let Some(template) = string("template") {
+        TermValue::Template(parse_template(&template)?)
+    } else {
+        bail!("Expected an rr:constant, rr:column, rml:reference or rr:template");
+    };
+    // (Object maps give literals by default if by column, or if given a datatype or language.)
+    let is_literal =
+        matches!(value, TermValue::Column(_)) || datatype.is_some() || language.is_some();
+    let term_type = term_type.unwrap_or(match default_type {
+        TermType::Literal if is_literal => TermType::Literal,
+        _ => TermType::Iri,
+    });
+    Ok(TermMap {
+        value,
+        term_type,
+        datatype,
+        language,
+    })
+}
+
+/// Parses a template (such as `http://example.org/{id}`), with `\{` and `\}` for braces.
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            '{' => {
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                }
+                let mut column = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('\\') => column.extend(chars.next()),
+                        Some(c) => column.push(c),
+                        None => bail!("Unclosed {{ in template: {template}"),
+                    }
+                }
+                parts.push(TemplatePart::Column(unquote_name(&column)));
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Removes the double quotes of a delimited SQL identifier.
+fn unquote_name(name: &str) -> String {
+    match name
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+    {
+        Some(name) => name.replace("\"\"", "\""),
+        None => name.to_owned(),
+    }
+}
+
+fn object_node(
+    graph: &Graph,
+    node: &NamedOrBlankNode,
+    property: &NamedNode,
+) -> Result<Option<NamedOrBlankNode>> {
+    match graph.object_for_subject_predicate(node, property) {
+        Some(object) => {
+            Ok(Some(as_node(object).with_context(|| {
+                format!("Invalid {property}: {object}")
+            })?))
+        }
+        None => Ok(None),
+    }
+}
+
+fn as_node(term: TermRef) -> Option<NamedOrBlankNode> {
+    match term {
+        TermRef::NamedNode(node) => Some(node.into_owned().into()),
+        TermRef::BlankNode(node) => Some(node.into_owned().into()),
+        _ => None,
+    }
+}
@@ -0,0 +1,131 @@
+//! Reading and writing of RDF/JSON (the Talis format, of triples as a JSON object of subjects,
+//! each an object of predicates with arrays of objects).
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use anyhow::{bail, Context, Result};
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term};
+use serde_json::{Map, Value};
+
+/// Reads the triples of RDF/JSON into the given graph.
+pub(crate) fn parse<R: Read>(reader: R, graph_name: &GraphName) -> Result<Vec<Quad>> {
+    let value: Value = serde_json::from_reader(reader).context("Invalid RDF/JSON")?;
+    let Value::Object(subjects) = value else {
+        bail!("Expected a JSON object of subjects in RDF/JSON");
+    };
+
+    let mut blank_nodes = HashMap::new();
+    let mut quads = Vec::new();
+    for (subject, predicates) in subjects {
+        let subject = match subject.strip_prefix("_:") {
+            Some(id) => NamedOrBlankNode::from(blank_node(id, &mut blank_nodes)),
+            None => NamedNode::new(subject)?.into(),
+        };
+        let Value::Object(predicates) = predicates else {
+            bail!("Expected a JSON object of predicates for {subject} in RDF/JSON");
+        };
+        for (predicate, objects) in predicates {
+            let predicate = NamedNode::new(predicate)?;
+            let Value::Array(objects) = objects else {
+                bail!("Expected a JSON array of objects for {subject} {predicate} in RDF/JSON");
+            };
+            for object in objects {
+                let object = to_term(&object, &mut blank_nodes)
+                    .with_context(|| format!("Invalid object of {subject} {predicate}"))?;
+                quads.push(Quad::new(
+                    subject.clone(),
+                    predicate.clone(),
+                    object,
+                    graph_name.clone(),
+                ));
+            }
+        }
+    }
+
+    Ok(quads)
+}
+
+fn to_term(object: &Value, blank_nodes: &mut HashMap<String, BlankNode>) -> Result<Term> {
+    let field = |name| object.get(name).and_then(Value::as_str);
+    let Some(value) = field("value") else {
+        bail!("Expected a \"value\" in RDF/JSON object: {object}");
+    };
+    Ok(match field("type") {
+        Some("uri") => NamedNode::new(value)?.into(),
+        Some("bnode") => blank_node(value.trim_start_matches("_:"), blank_nodes).into(),
+        Some("literal") => match (field("lang"), field("datatype")) {
+            (Some(lang), _) => Literal::new_language_tagged_literal(value, lang)?.into(),
+            (None, Some(datatype)) => {
+                Literal::new_typed_literal(value, NamedNode::new(datatype)?).into()
+            }
+            (None, None) => Literal::new_simple_literal(value).into(),
+        },
+        _ => bail!("Expected a \"type\" of uri, bnode or literal: {object}"),
+    })
+}
+
+/// Gives the blank node for a label (with a new label, to not clash with those of other data).
+fn blank_node(id: &str, blank_nodes: &mut HashMap<String, BlankNode>) -> BlankNode {
+    blank_nodes.entry(id.to_owned()).or_default().clone()
+}
+
+/// Writes the triples of the quads as RDF/JSON.
+pub(crate) fn serialize<W: Write>(
+    quads: impl IntoIterator<Item = Quad>,
+    mut writer: W,
+) -> Result<()> {
+    let mut subjects = Map::new();
+    for quad in quads {
+        let subject = match &quad.subject {
+            NamedOrBlankNode::NamedNode(node) => node.as_str().to_owned(),
+            NamedOrBlankNode::BlankNode(node) => format!("_:{}", node.as_str()),
+        };
+        let predicates = subjects
+            .entry(subject)
+            .or_insert_with(|| Value::Object(Map::new()));
+        let objects = predicates
+            .as_object_mut()
+            .expect("an object of predicates")
+            .entry(quad.predicate.as_str())
+            .or_insert_with(|| Value::Array(Vec::new()));
+        let objects = objects.as_array_mut().expect("an array of objects");
+        objects.push(from_term(&quad.object)?);
+    }
+    serde_json::to_writer_pretty(&mut writer, &subjects)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn from_term(term: &Term) -> Result<Value> {
+    let mut object = Map::new();
+    match term {
+        Term::NamedNode(node) => {
+            object.insert("type".into(), "uri".into());
+            object.insert("value".into(), node.as_str().into());
+        }
+        Term::BlankNode(node) => {
+            object.insert("type".into(), "bnode".into());
+            object.insert("value".into(), format!("_:{}", node.as_str()).into());
+        }
+        Term::Literal(literal) => {
+            if literal.direction().is_some() {
+                bail!(
+                    "Directional language-tagged strings cannot be written as RDF/JSON: {literal}"
+                );
+            }
+            object.insert("type".into(), "literal".into());
+            object.insert("value".into(), literal.value().into());
+            if let Some(lang) = literal.language() {
+                object.insert("lang".into(), lang.into());
+            } else if literal.datatype() != xsd::STRING {
+                object.insert("datatype".into(), literal.datatype().as_str().into());
+            }
+        }
+        Term::Triple(triple) => {
+            bail!("Triple terms cannot be written as RDF/JSON: <<( {triple} )>>")
+        }
+    }
+    Ok(Value::Object(object))
+}
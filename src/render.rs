@@ -0,0 +1,396 @@
+//! Text output of solutions rendered through a template (in a small subset of the Handlebars
+//! syntax), for generating SQL, HTML snippets, configuration files or reports directly.
+//!
+//! A template is rendered once per solution, unless it has an `{{#each}}` block, in which case
+//! it is rendered once for the whole result set and the block once per solution. Tags are:
+//!
+//! - `{{name}}`: the plain value of a variable (IRIs and literals by their strings alone), or
+//!   nothing if unbound;
+//! - `{{helper name}}`: the value written by a helper: `term` (as in SPARQL and Turtle),
+//!   `json`, `sql` (as literals, with `null` and `NULL` if unbound), `html` (escaped) or `url`
+//!   (percent-encoded);
+//! - `{{@index}}` (counting from 0) and `{{@number}}` (from 1);
+//! - `{{#if name}}…{{else}}…{{/if}}` and `{{#unless name}}…{{/unless}}`, by whether a variable
+//!   is bound (or on `@first` and `@last`);
+//! - `{{! comment }}`.
+
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use oxigraph::model::Term;
+use oxigraph::sparql::Variable;
+use serde_json::Value;
+
+use crate::csv;
+use crate::results;
+
+/// A parsed template.
+#[derive(Clone, Debug)]
+pub(crate) struct Template {
+    nodes: Vec<Node>,
+    each: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Value(Option<Helper>, String),
+    Each(Vec<Node>),
+    If {
+        name: String,
+        negated: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Helper {
+    Term,
+    Json,
+    Sql,
+    Html,
+    Url,
+}
+
+/// A solution being rendered, along with its position in the results.
+struct Row<'a> {
+    variables: &'a [Variable],
+    values: &'a [Option<Term>],
+    index: usize,
+    last: bool,
+}
+
+impl Template {
+    pub(crate) fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let (nodes, end) = parse_nodes(&mut tokens.into_iter())?;
+        if let Some(end) = end {
+            bail!("Unexpected {{{{{end}}}}} in template");
+        }
+        let each = has_each(&nodes);
+        Ok(Self { nodes, each })
+    }
+
+    /// Writes the solutions (as rows of values, in the order of the variables) rendered through
+    /// the template.
+    pub(crate) fn write_solutions<W: Write>(
+        &self,
+        variables: &[Variable],
+        solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+        mut writer: W,
+    ) -> Result<()> {
+        if self.each {
+            let rows = solutions.collect::<Result<Vec<_>>>()?;
+            let mut out = String::new();
+            render(
+                &self.nodes,
+                None,
+                &mut |nodes, out| {
+                    for (index, values) in rows.iter().enumerate() {
+                        let row = Row {
+                            variables,
+                            values,
+                            index,
+                            last: index + 1 == rows.len(),
+                        };
+                        render(nodes, Some(&row), &mut nested_each, out)?;
+                    }
+                    Ok(())
+                },
+                &mut out,
+            )?;
+            writer.write_all(out.as_bytes())?;
+        } else {
+            let mut solutions = solutions.enumerate().peekable();
+            while let Some((index, values)) = solutions.next() {
+                let values = values?;
+                let row = Row {
+                    variables,
+                    values: &values,
+                    index,
+                    last: solutions.peek().is_none(),
+                };
+                let mut out = String::new();
+                render(&self.nodes, Some(&row), &mut nested_each, &mut out)?;
+                writer.write_all(out.as_bytes())?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Token<'a> {
+    Text(&'a str),
+    Tag(&'a str),
+}
+
+/// Splits template source into text and tags. Lines with only a block tag (or a comment) are left
+/// out, so that blocks can be written on lines of their own.
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while !rest.is_empty() {
+        match rest.find("{{") {
+            Some(0) => {
+                let Some(end) = rest.find("}}") else {
+                    bail!("Unclosed tag in template: {}", first_line(rest));
+                };
+                tokens.push(Token::Tag(rest[2..end].trim()));
+                rest = &rest[end + 2..];
+            }
+            Some(start) => {
+                tokens.push(Token::Text(&rest[..start]));
+                rest = &rest[start..];
+            }
+            None => {
+                tokens.push(Token::Text(rest));
+                rest = "";
+            }
+        }
+    }
+
+    for i in 0..tokens.len() {
+        let Token::Tag(tag) = tokens[i] else {
+            continue;
+        };
+        if !tag.starts_with(['#', '/', '!']) && tag != "else" {
+            continue;
+        }
+        let before = match i.checked_sub(1).map(|j| tokens[j]) {
+            None => Some(""),
+            Some(Token::Text(text)) => match text.rfind('\n') {
+                Some(newline) => Some(&text[..newline + 1]),
+                None if i == 1 => Some(""),
+                None => None,
+            }
+            .filter(|kept| text[kept.len()..].trim_matches([' ', '\t']).is_empty()),
+            Some(Token::Tag(_)) => None,
+        };
+        let after = match tokens.get(i + 1) {
+            None => Some(""),
+            Some(Token::Text(text)) => match text.find('\n') {
+                Some(newline) => Some(&text[newline + 1..]),
+                None if i + 2 == tokens.len() => Some(""),
+                None => None,
+            }
+            .filter(|kept| {
+                text[..text.len() - kept.len()]
+                    .trim_matches([' ', '\t', '\r', '\n'])
+                    .is_empty()
+            }),
+            Some(Token::Tag(_)) => None,
+        };
+        if let (Some(before), Some(after)) = (before, after) {
+            if i > 0 {
+                tokens[i - 1] = Token::Text(before);
+            }
+            if i + 1 < tokens.len() {
+                tokens[i + 1] = Token::Text(after);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses nodes up to the end of the template, or up to a closing (or `else`) tag, which is
+/// returned.
+fn parse_nodes<'a>(
+    tokens: &mut impl Iterator<Item = Token<'a>>,
+) -> Result<(Vec<Node>, Option<&'a str>)> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        let tag = match token {
+            Token::Text(text) => {
+                nodes.push(Node::Text(text.to_owned()));
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+        if tag.starts_with('!') {
+            continue;
+        }
+        if tag.starts_with('/') || tag == "else" {
+            return Ok((nodes, Some(tag)));
+        }
+        let mut words = tag.split_whitespace();
+        let (first, name) = (words.next().unwrap_or_default(), words.next());
+        if words.next().is_some() {
+            bail!("Unexpected {{{{{tag}}}}} in template");
+        }
+        match (first, name) {
+            ("#each", None) => {
+                let (body, end) = parse_nodes(tokens)?;
+                expect_end(end, "each")?;
+                nodes.push(Node::Each(body));
+            }
+            ("#if" | "#unless", Some(name)) => {
+                let block = &first[1..];
+                let (then, mut end) = parse_nodes(tokens)?;
+                let mut otherwise = Vec::new();
+                if end == Some("else") {
+                    (otherwise, end) = parse_nodes(tokens)?;
+                }
+                expect_end(end, block)?;
+                nodes.push(Node::If {
+                    name: name.to_owned(),
+                    negated: block == "unless",
+                    then,
+                    otherwise,
+                });
+            }
+            (name, None) if !name.starts_with('#') => {
+                nodes.push(Node::Value(None, name.to_owned()))
+            }
+            (helper, Some(name)) if !helper.starts_with('#') => {
+                let helper = match helper {
+                    "term" => Helper::Term,
+                    "json" => Helper::Json,
+                    "sql" => Helper::Sql,
+                    "html" => Helper::Html,
+                    "url" => Helper::Url,
+                    _ => bail!("Unknown template helper: {helper}"),
+                };
+                nodes.push(Node::Value(Some(helper), name.to_owned()));
+            }
+            _ => bail!("Unexpected {{{{{tag}}}}} in template"),
+        }
+    }
+    Ok((nodes, None))
+}
+
+fn expect_end(end: Option<&str>, block: &str) -> Result<()> {
+    match end {
+        Some(end) if end.strip_prefix('/').map(str::trim) == Some(block) => Ok(()),
+        Some(end) => bail!("Unexpected {{{{{end}}}}} in template"),
+        None => bail!("Unclosed {{{{#{block}}}}} in template"),
+    }
+}
+
+fn has_each(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Each(_) => true,
+        Node::If {
+            then, otherwise, ..
+        } => has_each(then) || has_each(otherwise),
+        _ => false,
+    })
+}
+
+fn nested_each(_: &[Node], _: &mut String) -> Result<()> {
+    bail!("Nested {{{{#each}}}} blocks are not supported in templates")
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+/// Renders nodes for a row (if any), calling `each` to render the body of an `{{#each}}` block.
+fn render(
+    nodes: &[Node],
+    row: Option<&Row>,
+    each: &mut dyn FnMut(&[Node], &mut String) -> Result<()>,
+    out: &mut String,
+) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value(helper, name) => {
+                let Some(row) = row else {
+                    bail!("Template variables can only be used inside {{{{#each}}}}: {name}");
+                };
+                out.push_str(&row.value(*helper, name)?);
+            }
+            Node::Each(body) => each(body, out)?,
+            Node::If {
+                name,
+                negated,
+                then,
+                otherwise,
+            } => {
+                let Some(row) = row else {
+                    bail!("Template conditions can only be used inside {{{{#each}}}}: {name}");
+                };
+                let nodes = if row.test(name)? != *negated {
+                    then
+                } else {
+                    otherwise
+                };
+                render(nodes, Some(row), each, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Row<'_> {
+    fn term(&self, name: &str) -> Result<Option<&Term>> {
+        match self
+            .variables
+            .iter()
+            .position(|variable| variable.as_str() == name)
+        {
+            Some(i) => Ok(self.values[i].as_ref()),
+            None => bail!("Unknown variable in template: {name}"),
+        }
+    }
+
+    fn test(&self, name: &str) -> Result<bool> {
+        match name {
+            "@first" => Ok(self.index == 0),
+            "@last" => Ok(self.last),
+            name => Ok(self.term(name)?.is_some()),
+        }
+    }
+
+    fn value(&self, helper: Option<Helper>, name: &str) -> Result<String> {
+        match name {
+            "@index" => return Ok(self.index.to_string()),
+            "@number" => return Ok((self.index + 1).to_string()),
+            _ => {}
+        }
+        let term = self.term(name)?;
+        let plain = term.map(plain_value).unwrap_or_default();
+        Ok(match helper {
+            None => plain,
+            Some(Helper::Term) => term.map(Term::to_string).unwrap_or_default(),
+            Some(Helper::Json) => match term {
+                Some(Term::Literal(literal)) if csv::is_turtle_shorthand(literal) => plain,
+                Some(_) => Value::from(plain).to_string(),
+                None => "null".to_owned(),
+            },
+            Some(Helper::Sql) => match term {
+                Some(Term::Literal(literal)) if csv::is_turtle_shorthand(literal) => plain,
+                Some(_) => format!("'{}'", plain.replace('\'', "''")),
+                None => "NULL".to_owned(),
+            },
+            Some(Helper::Html) => results::escape_html(&plain),
+            Some(Helper::Url) => percent_encode(&plain),
+        })
+    }
+}
+
+/// Percent-encodes all but the unreserved characters of URIs (so that spaces are written as `%20`,
+/// as in paths and query strings alike).
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn plain_value(term: &Term) -> String {
+    match term {
+        Term::NamedNode(node) => node.as_str().to_owned(),
+        Term::BlankNode(node) => node.to_string(),
+        Term::Literal(literal) => literal.value().to_owned(),
+        Term::Triple(triple) => format!("<<( {triple} )>>"),
+    }
+}
@@ -0,0 +1,337 @@
+//! Results formats for pasting into documents and feeding into other tools (besides the standard
+//! SPARQL results formats).
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::Result;
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{Literal, Term};
+use oxigraph::sparql::Variable;
+use serde_json::{json, Map, Value};
+
+use crate::csv;
+use crate::pretty::Terms;
+use crate::sqlite;
+use crate::table;
+use crate::xlsx;
+
+/// A results format other than the standard ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ResultsFormat {
+    /// A GitHub-flavored Markdown table.
+    Markdown,
+    /// A standalone HTML page with a (sortable) table.
+    Html,
+    /// An Excel workbook (with typed cells).
+    Xlsx,
+    /// A SQLite database (with a table of typed columns).
+    Sqlite,
+    /// Newline-delimited JSON (JSON Lines), with one object of bindings per solution.
+    JsonLines,
+    /// A YAML sequence of mappings from variables to plain values.
+    Yaml,
+    /// An aligned table for reading on a terminal.
+    Table,
+}
+
+impl ResultsFormat {
+    pub(crate) fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" => Some(Self::Html),
+            "xlsx" => Some(Self::Xlsx),
+            "sqlite" => Some(Self::Sqlite),
+            "ndjson" | "jsonl" => Some(Self::JsonLines),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "table" => Some(Self::Table),
+            _ => None,
+        }
+    }
+}
+
+/// Writes solutions (as rows of values, in the order of the variables) in the given format,
+/// compacting IRIs using the prefixes (and using color in formats for terminals if `color` is
+/// set).
+pub(crate) fn write_solutions<W: Write>(
+    format: ResultsFormat,
+    variables: &[Variable],
+    solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+    prefixes: &HashMap<String, String>,
+    color: bool,
+    writer: W,
+) -> Result<()> {
+    let terms = Terms::new(None, prefixes)?;
+    match format {
+        ResultsFormat::Markdown => write_markdown(variables, solutions, &terms, writer),
+        ResultsFormat::Html => write_html(variables, solutions, &terms, writer),
+        ResultsFormat::Xlsx => xlsx::write(variables, solutions, writer),
+        ResultsFormat::Sqlite => sqlite::write(variables, solutions, writer),
+        ResultsFormat::JsonLines => write_json_lines(variables, solutions, writer),
+        ResultsFormat::Yaml => write_yaml(variables, solutions, writer),
+        ResultsFormat::Table => table::write(variables, solutions, &terms, color, writer),
+    }
+}
+
+/// Writes a boolean result (of an `ASK` query) in the given format.
+pub(crate) fn write_boolean<W: Write>(
+    format: ResultsFormat,
+    value: bool,
+    mut writer: W,
+) -> Result<()> {
+    match format {
+        ResultsFormat::Markdown => writeln!(writer, "{value}")?,
+        ResultsFormat::Html => {
+            writeln!(writer, "{HTML_HEAD}")?;
+            writeln!(writer, "<p>{value}</p>\n</body>\n</html>")?;
+        }
+        ResultsFormat::Xlsx => {
+            let variables = [Variable::new_unchecked("boolean")];
+            let value = Some(Literal::from(value).into());
+            xlsx::write(&variables, std::iter::once(Ok(vec![value])), writer)?;
+        }
+        ResultsFormat::Sqlite => {
+            let variables = [Variable::new_unchecked("boolean")];
+            let value = Some(Literal::from(value).into());
+            sqlite::write(&variables, std::iter::once(Ok(vec![value])), writer)?;
+        }
+        ResultsFormat::JsonLines => writeln!(writer, "{}", json!({ "boolean": value }))?,
+        ResultsFormat::Yaml | ResultsFormat::Table => writeln!(writer, "{value}")?,
+    }
+    Ok(())
+}
+
+fn write_markdown<W: Write>(
+    variables: &[Variable],
+    solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+    terms: &Terms,
+    mut writer: W,
+) -> Result<()> {
+    let row = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+    let names = variables
+        .iter()
+        .map(|variable| variable.as_str().to_owned());
+    writeln!(writer, "{}", row(names.collect()))?;
+    writeln!(writer, "{}", row(vec!["---".to_owned(); variables.len()]))?;
+    for values in solutions {
+        let cells = values?
+            .iter()
+            .map(|value| match value {
+                Some(term) => markdown_cell(&display_term(term, terms)),
+                None => String::new(),
+            })
+            .collect();
+        writeln!(writer, "{}", row(cells))?;
+    }
+    Ok(())
+}
+
+/// Escapes text for a Markdown table cell (which cannot span lines, and may contain HTML, which
+/// is escaped as entities).
+fn markdown_cell(text: &str) -> String {
+    text.replace('\\', r"\\")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('|', r"\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Query results</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #eee; cursor: pointer; user-select: none; }
+th[data-order=asc]::after { content: " \25B4"; }
+th[data-order=desc]::after { content: " \25BE"; }
+tr:nth-child(even) td { background: #f8f8f8; }
+</style>
+</head>
+<body>"#;
+
+/// Sorts the table rows by a column when its header is clicked (numerically if all its values
+/// are numbers).
+const HTML_SCRIPT: &str = r#"<script>
+document.querySelectorAll("th").forEach((th, i) => th.addEventListener("click", () => {
+  const tbody = th.closest("table").tBodies[0];
+  const asc = th.dataset.order !== "asc";
+  th.parentElement.querySelectorAll("th").forEach(other => delete other.dataset.order);
+  th.dataset.order = asc ? "asc" : "desc";
+  const rows = Array.from(tbody.rows);
+  const text = row => row.cells[i].textContent;
+  const numeric = rows.every(row => text(row) === "" || !isNaN(Number(text(row))));
+  rows.sort((a, b) => {
+    const order = numeric
+      ? Number(text(a) || -Infinity) - Number(text(b) || -Infinity)
+      : text(a).localeCompare(text(b));
+    return asc ? order : -order;
+  });
+  tbody.append(...rows);
+}));
+</script>"#;
+
+fn write_html<W: Write>(
+    variables: &[Variable],
+    solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+    terms: &Terms,
+    mut writer: W,
+) -> Result<()> {
+    writeln!(writer, "{HTML_HEAD}")?;
+    writeln!(writer, "<table>\n<thead>\n<tr>")?;
+    for variable in variables {
+        writeln!(writer, "<th>{}</th>", escape_html(variable.as_str()))?;
+    }
+    writeln!(writer, "</tr>\n</thead>\n<tbody>")?;
+    for values in solutions {
+        write!(writer, "<tr>")?;
+        for value in values? {
+            let cell = match &value {
+                Some(Term::NamedNode(node)) => {
+                    // (Linked by the compact IRI, or else the full IRI as is.)
+                    let compacted = terms.iri(node);
+                    let text = match compacted.strip_prefix('<') {
+                        Some(_) => node.as_str(),
+                        None => &compacted,
+                    };
+                    let href = escape_html(node.as_str());
+                    format!(r#"<a href="{href}">{}</a>"#, escape_html(text))
+                }
+                Some(term) => escape_html(&display_term(term, terms)),
+                None => String::new(),
+            };
+            write!(writer, "<td>{cell}</td>")?;
+        }
+        writeln!(writer, "</tr>")?;
+    }
+    writeln!(
+        writer,
+        "</tbody>\n</table>\n{HTML_SCRIPT}\n</body>\n</html>"
+    )?;
+    Ok(())
+}
+
+/// Writes each solution as a line with a JSON object of the bound variables, with values as in
+/// SPARQL JSON results.
+fn write_json_lines<W: Write>(
+    variables: &[Variable],
+    solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+    mut writer: W,
+) -> Result<()> {
+    for values in solutions {
+        let bindings: Map<String, Value> = variables
+            .iter()
+            .zip(values?)
+            .filter_map(|(variable, value)| {
+                Some((variable.as_str().to_owned(), json_term(&value?)))
+            })
+            .collect();
+        writeln!(writer, "{}", Value::Object(bindings))?;
+    }
+    Ok(())
+}
+
+fn json_term(term: &Term) -> Value {
+    match term {
+        Term::NamedNode(node) => json!({ "type": "uri", "value": node.as_str() }),
+        Term::BlankNode(node) => json!({ "type": "bnode", "value": node.as_str() }),
+        Term::Literal(literal) => {
+            let mut object = json!({ "type": "literal", "value": literal.value() });
+            if let Some(language) = literal.language() {
+                object["xml:lang"] = language.into();
+                if let Some(direction) = literal.direction() {
+                    object["its:dir"] = direction.to_string().into();
+                }
+            } else if literal.datatype() != xsd::STRING {
+                object["datatype"] = literal.datatype().as_str().into();
+            }
+            object
+        }
+        Term::Triple(triple) => json!({
+            "type": "triple",
+            "value": {
+                "subject": json_term(&triple.subject.clone().into()),
+                "predicate": json_term(&triple.predicate.clone().into()),
+                "object": json_term(&triple.object),
+            },
+        }),
+    }
+}
+
+/// Writes the solutions as a sequence of mappings of the bound variables, with numbers and
+/// booleans as such and other values as (quoted) strings.
+fn write_yaml<W: Write>(
+    variables: &[Variable],
+    solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+    mut writer: W,
+) -> Result<()> {
+    let keys: Vec<String> = variables
+        .iter()
+        .map(|variable| yaml_key(variable.as_str()))
+        .collect();
+    let mut empty = true;
+    for values in solutions {
+        let mut first = true;
+        for (key, value) in keys.iter().zip(values?) {
+            let Some(value) = value else {
+                continue;
+            };
+            let indent = if first { "- " } else { "  " };
+            writeln!(writer, "{indent}{key}: {}", yaml_value(&value))?;
+            first = false;
+        }
+        if first {
+            writeln!(writer, "- {{}}")?;
+        }
+        empty = false;
+    }
+    if empty {
+        writeln!(writer, "[]")?;
+    }
+    Ok(())
+}
+
+/// Quotes keys which would otherwise be read as other things than strings.
+fn yaml_key(name: &str) -> String {
+    let reserved = ["true", "false", "null", "yes", "no", "on", "off", "~"];
+    if name.starts_with(|c: char| c.is_ascii_digit())
+        || reserved.contains(&name.to_ascii_lowercase().as_str())
+    {
+        Value::from(name).to_string()
+    } else {
+        name.to_owned()
+    }
+}
+
+fn yaml_value(term: &Term) -> String {
+    match term {
+        Term::Literal(literal) if csv::is_turtle_shorthand(literal) => literal.value().to_owned(),
+        Term::Literal(literal) => Value::from(literal.value()).to_string(),
+        Term::NamedNode(node) => Value::from(node.as_str()).to_string(),
+        Term::BlankNode(node) => Value::from(node.to_string()).to_string(),
+        Term::Triple(triple) => Value::from(format!("<<( {triple} )>>")).to_string(),
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Formats a term for reading: plain strings as is, and other terms as in Turtle.
+pub(crate) fn display_term(term: &Term, terms: &Terms) -> String {
+    match term {
+        Term::NamedNode(node) => terms.iri(node),
+        Term::BlankNode(node) => node.to_string(),
+        Term::Literal(literal) if literal.datatype() == xsd::STRING => literal.value().to_owned(),
+        Term::Literal(literal) => terms.literal(literal),
+        Term::Triple(_) => terms.term(term),
+    }
+}
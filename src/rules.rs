@@ -0,0 +1,122 @@
+//! Forward chaining of rules (given in N3 or as SPARQL CONSTRUCT queries), adding the triples
+//! they derive to the data until no new triples follow.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{bail, Result};
+use oxigraph::model::{BlankNode, GraphName, NamedOrBlankNode, Quad, Term};
+use oxigraph::sparql::{QueryResults, QuerySolution, SparqlEvaluator};
+use oxigraph::store::Store;
+use spargebra::algebra::GraphPattern;
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use spargebra::Query;
+
+/// The most rounds of applying the rules (to stop rules making new blank nodes without end).
+const MAX_ROUNDS: usize = 1000;
+
+/// A rule: the triples to derive for each solution of a pattern.
+pub(crate) struct Rule {
+    pub(crate) pattern: GraphPattern,
+    pub(crate) template: Vec<TriplePattern>,
+}
+
+impl Rule {
+    /// Makes a rule of a CONSTRUCT query.
+    pub(crate) fn from_query(query: Query) -> Result<Self> {
+        let Query::Construct {
+            template, pattern, ..
+        } = query
+        else {
+            bail!("Expected a CONSTRUCT query as rule");
+        };
+        Ok(Self { pattern, template })
+    }
+}
+
+/// Applies the rules to the data of the store (in all graphs) until no new triples follow,
+/// adding those derived to the default graph. Returns the number of triples added.
+///
+/// Each solution of a rule is only used once, so that rules making blank nodes (such as `[]` in
+/// the head of an N3 rule) do not make new ones for the same solution in later rounds.
+pub(crate) fn apply(store: &Store, evaluator: &SparqlEvaluator, rules: &[Rule]) -> Result<usize> {
+    let mut used: Vec<HashSet<Vec<Option<Term>>>> = rules.iter().map(|_| HashSet::new()).collect();
+    let mut added = 0;
+    for _ in 0..MAX_ROUNDS {
+        let mut derived = Vec::new();
+        for (rule, used) in rules.iter().zip(&mut used) {
+            let query = Query::Select {
+                dataset: None,
+                pattern: rule.pattern.clone(),
+                base_iri: None,
+            };
+            let mut query = evaluator.clone().for_query(query);
+            query.dataset_mut().set_default_graph_as_union();
+            let QueryResults::Solutions(solutions) = query.on_store(store).execute()? else {
+                continue;
+            };
+            for solution in solutions {
+                let solution = solution?;
+                let key = solution.values().to_vec();
+                if used.insert(key) {
+                    instantiate(&rule.template, &solution, &mut derived);
+                }
+            }
+        }
+
+        let mut new_quads = 0;
+        for quad in derived {
+            if !store.contains(&quad)? {
+                store.insert(&quad)?;
+                new_quads += 1;
+            }
+        }
+        if new_quads == 0 {
+            return Ok(added);
+        }
+        added += new_quads;
+    }
+    bail!("The rules did not reach a fixpoint in {MAX_ROUNDS} rounds (do they keep making new blank nodes?)")
+}
+
+/// Adds the triples of a template for a solution (skipping those with unbound variables or
+/// invalid terms, such as literals as subjects).
+fn instantiate(template: &[TriplePattern], solution: &QuerySolution, quads: &mut Vec<Quad>) {
+    let mut blank_nodes = HashMap::new();
+    for pattern in template {
+        let subject = match term(&pattern.subject, solution, &mut blank_nodes) {
+            Some(Term::NamedNode(node)) => NamedOrBlankNode::from(node),
+            Some(Term::BlankNode(node)) => node.into(),
+            _ => continue,
+        };
+        let predicate = match &pattern.predicate {
+            NamedNodePattern::NamedNode(node) => node.clone(),
+            NamedNodePattern::Variable(variable) => match solution.get(variable) {
+                Some(Term::NamedNode(node)) => node.clone(),
+                _ => continue,
+            },
+        };
+        let Some(object) = term(&pattern.object, solution, &mut blank_nodes) else {
+            continue;
+        };
+        quads.push(Quad::new(
+            subject,
+            predicate,
+            object,
+            GraphName::DefaultGraph,
+        ));
+    }
+}
+
+fn term(
+    pattern: &TermPattern,
+    solution: &QuerySolution,
+    blank_nodes: &mut HashMap<BlankNode, BlankNode>,
+) -> Option<Term> {
+    Some(match pattern {
+        TermPattern::NamedNode(node) => node.clone().into(),
+        TermPattern::BlankNode(node) => blank_nodes.entry(node.clone()).or_default().clone().into(),
+        TermPattern::Literal(literal) => literal.clone().into(),
+        TermPattern::Variable(variable) => solution.get(variable)?.clone(),
+        TermPattern::Triple(_) => return None,
+    })
+}
@@ -0,0 +1,164 @@
+//! Full-text search over the string literals of the data, by an index built on the fly (when first
+//! searched in a query), for ranked keyword search with the `oxrq:matches` and `oxrq:score`
+//! functions instead of scanning with `REGEX`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{Literal, Term};
+use oxigraph::store::Store;
+
+/// The namespace of the search functions (declared as the `oxrq:` prefix).
+pub(crate) const NAMESPACE: &str = "urn:x-oxrq:";
+/// Tells whether a text contains all of the keywords.
+pub(crate) const MATCHES: &str = "urn:x-oxrq:matches";
+/// Scores how relevant a text is for the keywords (by BM25, or 0 if it contains none of them).
+pub(crate) const SCORE: &str = "urn:x-oxrq:score";
+
+/// BM25 parameters (the usual ones).
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// An index of the data of a store, built when first used.
+pub(crate) struct LazyIndex {
+    store: Store,
+    index: OnceLock<Option<TextIndex>>,
+}
+
+/// An inverted index of the distinct string literals (the documents) by their words.
+struct TextIndex {
+    ids: HashMap<String, usize>,
+    lengths: Vec<usize>,
+    average_length: f64,
+    /// The documents with each word, along with the number of times it occurs in them.
+    postings: HashMap<String, Vec<(usize, usize)>>,
+    /// The documents found for each search (by its keywords), with their scores and the number of
+    /// keywords they contain.
+    searches: Mutex<HashMap<String, Arc<Hits>>>,
+}
+
+struct Hits {
+    keywords: usize,
+    documents: HashMap<usize, (f64, usize)>,
+}
+
+impl LazyIndex {
+    pub(crate) fn new(store: Store) -> Self {
+        Self {
+            store,
+            index: OnceLock::new(),
+        }
+    }
+
+    /// Evaluates `oxrq:matches(text, keywords)`.
+    pub(crate) fn matches(&self, args: &[Term]) -> Option<Term> {
+        let (hits, id) = self.search(args)?;
+        let matched = id
+            .and_then(|id| hits.documents.get(&id))
+            .is_some_and(|(_, count)| *count == hits.keywords);
+        Some(Literal::from(matched && hits.keywords > 0).into())
+    }
+
+    /// Evaluates `oxrq:score(text, keywords)`.
+    pub(crate) fn score(&self, args: &[Term]) -> Option<Term> {
+        let (hits, id) = self.search(args)?;
+        let score = id
+            .and_then(|id| hits.documents.get(&id))
+            .map_or(0.0, |(score, _)| *score);
+        Some(Literal::from(score).into())
+    }
+
+    /// Finds the documents for the keywords (the second argument), along with the document of
+    /// the text (the first argument), if indexed.
+    fn search(&self, args: &[Term]) -> Option<(Arc<Hits>, Option<usize>)> {
+        let [Term::Literal(text), Term::Literal(keywords)] = args else {
+            return None;
+        };
+        let index = self
+            .index
+            .get_or_init(|| TextIndex::build(&self.store))
+            .as_ref()?;
+        let hits = index.search(keywords.value());
+        Some((hits, index.ids.get(text.value()).copied()))
+    }
+}
+
+impl TextIndex {
+    /// Indexes the string literals of the store (or gives none if it cannot be read).
+    fn build(store: &Store) -> Option<Self> {
+        let mut index = Self {
+            ids: HashMap::new(),
+            lengths: Vec::new(),
+            average_length: 0.0,
+            postings: HashMap::new(),
+            searches: Mutex::new(HashMap::new()),
+        };
+        for quad in store.iter() {
+            let Term::Literal(literal) = quad.ok()?.object else {
+                continue;
+            };
+            let datatype = literal.datatype();
+            if (datatype != xsd::STRING && datatype != rdf::LANG_STRING)
+                || index.ids.contains_key(literal.value())
+            {
+                continue;
+            }
+            let id = index.lengths.len();
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            let mut length = 0;
+            for word in words(literal.value()) {
+                *counts.entry(word).or_default() += 1;
+                length += 1;
+            }
+            for (word, count) in counts {
+                index.postings.entry(word).or_default().push((id, count));
+            }
+            index.lengths.push(length);
+            index.ids.insert(literal.value().to_owned(), id);
+        }
+        let total: usize = index.lengths.iter().sum();
+        index.average_length = total as f64 / index.lengths.len().max(1) as f64;
+        Some(index)
+    }
+
+    fn search(&self, keywords: &str) -> Arc<Hits> {
+        let mut searches = self.searches.lock().expect("search cache lock");
+        if let Some(hits) = searches.get(keywords) {
+            return hits.clone();
+        }
+        let mut words: Vec<String> = words(keywords).collect();
+        words.sort();
+        words.dedup();
+        let count = self.lengths.len() as f64;
+        let mut documents: HashMap<usize, (f64, usize)> = HashMap::new();
+        for word in &words {
+            let Some(postings) = self.postings.get(word) else {
+                continue;
+            };
+            let frequency = postings.len() as f64;
+            let idf = ((count - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+            for (id, occurrences) in postings {
+                let tf = *occurrences as f64;
+                let length = self.lengths[*id] as f64 / self.average_length;
+                let score = idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length));
+                let hit = documents.entry(*id).or_default();
+                hit.0 += score;
+                hit.1 += 1;
+            }
+        }
+        let hits = Arc::new(Hits {
+            keywords: words.len(),
+            documents,
+        });
+        searches.insert(keywords.to_owned(), hits.clone());
+        hits
+    }
+}
+
+/// Splits text into lowercase words (of letters and digits).
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
@@ -0,0 +1,205 @@
+//! Serving of the loaded data over the SPARQL protocol.
+
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use oxhttp::model::header::{ACCEPT, CONTENT_TYPE};
+use oxhttp::model::{Body, Method, Request, Response, StatusCode};
+use oxhttp::Server;
+use oxigraph::io::{RdfFormat, RdfSerializer};
+use oxigraph::sparql::results::{QueryResultsFormat, QueryResultsSerializer};
+use oxigraph::sparql::QueryResults;
+
+use crate::Runner;
+
+const SERVER_NAME: &str = concat!("oxrq/", env!("CARGO_PKG_VERSION"));
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+const MAX_CONNECTIONS: usize = 128;
+
+/// Listens on the given address (e.g. `localhost:8000`), answering queries until terminated.
+pub(crate) fn serve(runner: Runner, addr: &str) -> Result<()> {
+    let runner = Arc::new(runner);
+    let mut server = Server::new(move |request| {
+        handle(&runner, request).unwrap_or_else(|(status, message)| {
+            Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(Body::from(message))
+                .unwrap()
+        })
+    })
+    .with_server_name(SERVER_NAME)?
+    .with_global_timeout(TIMEOUT)
+    .with_max_concurrent_connections(MAX_CONNECTIONS);
+
+    let socket_addrs = addr
+        .to_socket_addrs()
+        .with_context(|| format!("Invalid address to serve on: {addr}"))?;
+    for socket_addr in socket_addrs {
+        server = server.bind(socket_addr);
+    }
+
+    let listening = server
+        .spawn()
+        .with_context(|| format!("Unable to serve on: {addr}"))?;
+    eprintln!("Serving SPARQL endpoint at http://{addr}/");
+    listening.join()?;
+
+    Ok(())
+}
+
+type HttpError = (StatusCode, String);
+
+fn handle(runner: &Runner, request: &mut Request<Body>) -> Result<Response<Body>, HttpError> {
+    let query_body = get_query(request)?;
+    let accept = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("*/*")
+        .to_owned();
+
+    let query_str = runner.prepare_query(&query_body);
+    let query = runner
+        .parser()
+        .map_err(internal_error)?
+        .parse_query(&query_str)
+        .map_err(|e| bad_request(runner.query_error(&query_str, e).to_string()))?;
+    let results = match &runner.endpoint {
+        Some(endpoint) => runner.query_with_endpoint(endpoint, &query_str),
+        None => runner
+            .evaluator()
+            .and_then(|evaluator| runner.prepare(evaluator, query))
+            .and_then(|query| runner.execute_query(query)),
+    }
+    .map_err(internal_error)?;
+
+    let (media_type, payload) = serialize_results(runner, results, &accept)?;
+    Response::builder()
+        .header(CONTENT_TYPE, media_type)
+        .body(Body::from(payload))
+        .map_err(internal_error)
+}
+
+/// Gets the query from a GET or POST request (as defined by the SPARQL 1.1 Protocol).
+fn get_query(request: &mut Request<Body>) -> Result<String, HttpError> {
+    let from_form = |form: &[u8]| {
+        form_urlencoded::parse(form)
+            .find(|(name, _)| name == "query")
+            .map(|(_, value)| value.into_owned())
+            .ok_or_else(|| bad_request("Missing query parameter"))
+    };
+
+    match *request.method() {
+        Method::GET => from_form(request.uri().query().unwrap_or_default().as_bytes()),
+        Method::POST => {
+            let content_type = request
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(';').next())
+                .map(|value| value.trim().to_ascii_lowercase())
+                .unwrap_or_default();
+            let body = std::mem::take(request.body_mut())
+                .to_vec()
+                .map_err(|e| bad_request(e.to_string()))?;
+            match content_type.as_str() {
+                "application/sparql-query" => {
+                    String::from_utf8(body).map_err(|e| bad_request(e.to_string()))
+                }
+                "application/x-www-form-urlencoded" => from_form(&body),
+                _ => Err((
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    format!("Unsupported content type: {content_type}"),
+                )),
+            }
+        }
+        _ => Err((
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Only GET and POST are supported".to_owned(),
+        )),
+    }
+}
+
+/// Serializes the results in the preferred format of the client (by the accepted media types).
+fn serialize_results(
+    runner: &Runner,
+    results: QueryResults,
+    accept: &str,
+) -> Result<(&'static str, Vec<u8>), HttpError> {
+    let mut payload = Vec::new();
+
+    if let QueryResults::Graph(triples) = results {
+        let format = negotiate(accept, RdfFormat::from_media_type).unwrap_or(RdfFormat::Turtle);
+        let mut serializer = RdfSerializer::from_format(format);
+        for (pfx, ns) in &runner.prefixes {
+            serializer = serializer.with_prefix(pfx, ns).map_err(internal_error)?;
+        }
+        let mut serializer = serializer.for_writer(&mut payload);
+        for triple in triples {
+            let triple = triple.map_err(internal_error)?;
+            serializer
+                .serialize_triple(&triple)
+                .map_err(internal_error)?;
+        }
+        serializer.finish().map_err(internal_error)?;
+        return Ok((format.media_type(), payload));
+    }
+
+    let format =
+        negotiate(accept, QueryResultsFormat::from_media_type).unwrap_or(QueryResultsFormat::Json);
+    let serializer = QueryResultsSerializer::from_format(format);
+    match results {
+        QueryResults::Solutions(solutions) => {
+            let mut serializer = serializer
+                .serialize_solutions_to_writer(&mut payload, solutions.variables().to_vec())
+                .map_err(internal_error)?;
+            for solution in solutions {
+                let solution = solution.map_err(internal_error)?;
+                serializer.serialize(&solution).map_err(internal_error)?;
+            }
+            serializer.finish().map_err(internal_error)?;
+        }
+        QueryResults::Boolean(result) => {
+            serializer
+                .serialize_boolean_to_writer(&mut payload, result)
+                .map_err(internal_error)?;
+        }
+        QueryResults::Graph(_) => unreachable!(),
+    }
+
+    Ok((format.media_type(), payload))
+}
+
+/// Picks the supported format with the highest quality value in an Accept header.
+fn negotiate<F>(accept: &str, from_media_type: impl Fn(&str) -> Option<F>) -> Option<F> {
+    let mut best: Option<(f32, F)> = None;
+    for entry in accept.split(',') {
+        let mut parts = entry.split(';');
+        let media_type = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|value| value.parse().ok())
+            .unwrap_or(1.0);
+        if quality <= 0.0 || best.as_ref().is_some_and(|(q, _)| *q >= quality) {
+            continue;
+        }
+        if let Some(format) = from_media_type(&media_type) {
+            best = Some((quality, format));
+        }
+    }
+
+    best.map(|(_, format)| format)
+}
+
+fn bad_request(message: impl Into<String>) -> HttpError {
+    (StatusCode::BAD_REQUEST, message.into())
+}
+
+fn internal_error(error: impl std::fmt::Display) -> HttpError {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("{error:#}"))
+}
@@ -0,0 +1,834 @@
+//! Validation of data against SHACL shapes (the constraint components of SHACL Core), producing
+//! a validation report.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use oxigraph::model::vocab::{rdf, rdfs, xsd};
+use oxigraph::model::{
+    BlankNode, Graph, Literal, NamedNode, NamedNodeRef, NamedOrBlankNodeRef, Term, Triple,
+};
+use regex::Regex;
+
+/// A parameter name, its constraint component, and the valid orderings of value to bound.
+type Comparison = (&'static str, &'static str, fn(Ordering) -> bool);
+
+/// Checks the number of conforming shapes (of the total) for a logical constraint.
+type CountCheck = fn(usize, usize) -> bool;
+
+const SH: &str = "http://www.w3.org/ns/shacl#";
+
+fn sh(name: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{SH}{name}"))
+}
+
+/// A violation of a constraint by a focus node (or one of its values).
+struct ValidationResult {
+    focus_node: Term,
+    path: Option<Term>,
+    value: Option<Term>,
+    source_shape: Term,
+    component: &'static str,
+    severity: Term,
+    messages: Vec<Term>,
+}
+
+/// Validates the data graph against the shapes graph, returning whether it conforms along with
+/// the triples of the validation report.
+pub(crate) fn validate(shapes: &Graph, data: &Graph) -> Result<(bool, Vec<Triple>)> {
+    let validator = Validator {
+        shapes,
+        data,
+        in_progress: RefCell::new(HashSet::new()),
+    };
+    let mut results = Vec::new();
+    for shape in validator.shapes_with_targets() {
+        for focus_node in validator.focus_nodes(&shape) {
+            validator.validate_shape(&shape, &focus_node, &mut results)?;
+        }
+    }
+    let conforms = results.is_empty();
+
+    Ok((conforms, validator.report(results)))
+}
+
+struct Validator<'a> {
+    shapes: &'a Graph,
+    data: &'a Graph,
+    /// The shapes and focus nodes being validated (to stop at recursive shapes).
+    in_progress: RefCell<HashSet<(Term, Term)>>,
+}
+
+impl Validator<'_> {
+    fn shapes_with_targets(&self) -> Vec<Term> {
+        let mut shapes = Vec::new();
+        for target in [
+            "targetClass",
+            "targetNode",
+            "targetSubjectsOf",
+            "targetObjectsOf",
+        ] {
+            for triple in self.shapes.triples_for_predicate(&sh(target)) {
+                shapes.push(Term::from(triple.subject.into_owned()));
+            }
+        }
+        // Shapes which are also classes target their instances:
+        for shape_type in [sh("NodeShape"), sh("PropertyShape")] {
+            for shape in subjects(self.shapes, rdf::TYPE, &shape_type.into()) {
+                if self.is_class(&shape) {
+                    shapes.push(shape);
+                }
+            }
+        }
+        shapes.sort_by_cached_key(Term::to_string);
+        shapes.dedup();
+        shapes
+    }
+
+    fn is_class(&self, node: &Term) -> bool {
+        objects(self.shapes, node, rdf::TYPE)
+            .iter()
+            .any(|class| matches!(class, Term::NamedNode(class) if class.as_ref() == rdfs::CLASS))
+    }
+
+    fn focus_nodes(&self, shape: &Term) -> Vec<Term> {
+        let mut nodes = objects(self.shapes, shape, sh("targetNode").as_ref());
+        let mut classes = objects(self.shapes, shape, sh("targetClass").as_ref());
+        if self.is_class(shape) {
+            classes.push(shape.clone());
+        }
+        for class in classes {
+            nodes.extend(self.instances(&class));
+        }
+        for property in objects(self.shapes, shape, sh("targetSubjectsOf").as_ref()) {
+            if let Term::NamedNode(property) = property {
+                for triple in self.data.triples_for_predicate(&property) {
+                    nodes.push(triple.subject.into_owned().into());
+                }
+            }
+        }
+        for property in objects(self.shapes, shape, sh("targetObjectsOf").as_ref()) {
+            if let Term::NamedNode(property) = property {
+                for triple in self.data.triples_for_predicate(&property) {
+                    nodes.push(triple.object.into_owned());
+                }
+            }
+        }
+        dedup(nodes)
+    }
+
+    /// Finds the instances of a class (including those of its subclasses).
+    fn instances(&self, class: &Term) -> Vec<Term> {
+        let mut classes = vec![class.clone()];
+        let mut i = 0;
+        while let Some(class) = classes.get(i).cloned() {
+            for subclass in subjects(self.data, rdfs::SUB_CLASS_OF, &class) {
+                if !classes.contains(&subclass) {
+                    classes.push(subclass);
+                }
+            }
+            i += 1;
+        }
+        dedup(
+            classes
+                .iter()
+                .flat_map(|class| subjects(self.data, rdf::TYPE, class))
+                .collect(),
+        )
+    }
+
+    fn is_instance(&self, node: &Term, class: &Term) -> bool {
+        let mut types = objects(self.data, node, rdf::TYPE);
+        let mut i = 0;
+        while let Some(node_type) = types.get(i).cloned() {
+            if &node_type == class {
+                return true;
+            }
+            for superclass in objects(self.data, &node_type, rdfs::SUB_CLASS_OF) {
+                if !types.contains(&superclass) {
+                    types.push(superclass);
+                }
+            }
+            i += 1;
+        }
+        false
+    }
+
+    /// Checks whether a node conforms to a shape (assuming it does for recursive shapes).
+    fn conforms(&self, node: &Term, shape: &Term) -> Result<bool> {
+        let key = (shape.clone(), node.clone());
+        if self.in_progress.borrow().contains(&key) {
+            return Ok(true);
+        }
+        let mut results = Vec::new();
+        self.validate_shape(shape, node, &mut results)?;
+        Ok(results.is_empty())
+    }
+
+    fn validate_shape(
+        &self,
+        shape: &Term,
+        focus_node: &Term,
+        results: &mut Vec<ValidationResult>,
+    ) -> Result<()> {
+        if self.param(shape, "deactivated") == Some(Literal::from(true).into()) {
+            return Ok(());
+        }
+        let key = (shape.clone(), focus_node.clone());
+        if !self.in_progress.borrow_mut().insert(key.clone()) {
+            return Ok(());
+        }
+        let checked = self.check_constraints(shape, focus_node, results);
+        self.in_progress.borrow_mut().remove(&key);
+        checked
+    }
+
+    fn check_constraints(
+        &self,
+        shape: &Term,
+        focus_node: &Term,
+        results: &mut Vec<ValidationResult>,
+    ) -> Result<()> {
+        let path = self.param(shape, "path");
+        let values = match &path {
+            Some(path) => self.eval_path(focus_node, path, false),
+            None => vec![focus_node.clone()],
+        };
+        let severity = self
+            .param(shape, "severity")
+            .unwrap_or_else(|| sh("Violation").into());
+        let messages = objects(self.shapes, shape, sh("message").as_ref());
+        let mut report = |component, path: Option<Term>, value: Option<&Term>| {
+            results.push(ValidationResult {
+                focus_node: focus_node.clone(),
+                path,
+                value: value.cloned(),
+                source_shape: shape.clone(),
+                component,
+                severity: severity.clone(),
+                messages: messages.clone(),
+            });
+        };
+        let params = |name: &str| objects(self.shapes, shape, sh(name).as_ref());
+
+        // Value type constraints:
+        for class in params("class") {
+            for value in values
+                .iter()
+                .filter(|value| !self.is_instance(value, &class))
+            {
+                report("ClassConstraintComponent", path.clone(), Some(value));
+            }
+        }
+        for datatype in params("datatype") {
+            for value in &values {
+                let is_valid = matches!(value, Term::Literal(literal)
+                    if Term::from(literal.datatype().into_owned()) == datatype
+                        && is_well_formed(literal));
+                if !is_valid {
+                    report("DatatypeConstraintComponent", path.clone(), Some(value));
+                }
+            }
+        }
+        for node_kind in params("nodeKind") {
+            for value in &values {
+                if !has_node_kind(value, &node_kind) {
+                    report("NodeKindConstraintComponent", path.clone(), Some(value));
+                }
+            }
+        }
+
+        // Cardinality constraints:
+        for min_count in params("minCount").iter().filter_map(as_integer) {
+            if (values.len() as i64) < min_count {
+                report("MinCountConstraintComponent", path.clone(), None);
+            }
+        }
+        for max_count in params("maxCount").iter().filter_map(as_integer) {
+            if (values.len() as i64) > max_count {
+                report("MaxCountConstraintComponent", path.clone(), None);
+            }
+        }
+
+        // Value range constraints:
+        let ranges: [Comparison; 4] = [
+            (
+                "minExclusive",
+                "MinExclusiveConstraintComponent",
+                Ordering::is_gt,
+            ),
+            (
+                "minInclusive",
+                "MinInclusiveConstraintComponent",
+                Ordering::is_ge,
+            ),
+            (
+                "maxExclusive",
+                "MaxExclusiveConstraintComponent",
+                Ordering::is_lt,
+            ),
+            (
+                "maxInclusive",
+                "MaxInclusiveConstraintComponent",
+                Ordering::is_le,
+            ),
+        ];
+        for (name, component, is_in_range) in ranges {
+            for bound in params(name) {
+                for value in &values {
+                    if !compare(value, &bound).is_some_and(is_in_range) {
+                        report(component, path.clone(), Some(value));
+                    }
+                }
+            }
+        }
+
+        // String-based constraints:
+        for min_length in params("minLength").iter().filter_map(as_integer) {
+            for value in &values {
+                if lexical_form(value).is_none_or(|s| (s.chars().count() as i64) < min_length) {
+                    report("MinLengthConstraintComponent", path.clone(), Some(value));
+                }
+            }
+        }
+        for max_length in params("maxLength").iter().filter_map(as_integer) {
+            for value in &values {
+                if lexical_form(value).is_none_or(|s| s.chars().count() as i64 > max_length) {
+                    report("MaxLengthConstraintComponent", path.clone(), Some(value));
+                }
+            }
+        }
+        for pattern in params("pattern") {
+            let flags = self.param(shape, "flags");
+            let regex = build_regex(&pattern, flags.as_ref())?;
+            for value in &values {
+                if !lexical_form(value).is_some_and(|s| regex.is_match(s)) {
+                    report("PatternConstraintComponent", path.clone(), Some(value));
+                }
+            }
+        }
+        for list in params("languageIn") {
+            let ranges = self.list_items(&list);
+            for value in &values {
+                let is_valid = matches!(value, Term::Literal(literal)
+                if literal.language().is_some_and(|lang| ranges.iter().any(|range| {
+                    lexical_form(range).is_some_and(|range| lang_matches(lang, range))
+                })));
+                if !is_valid {
+                    report("LanguageInConstraintComponent", path.clone(), Some(value));
+                }
+            }
+        }
+        if self.param(shape, "uniqueLang") == Some(Literal::from(true).into()) {
+            let mut seen = HashSet::new();
+            let mut duplicates = HashSet::new();
+            for value in &values {
+                if let Term::Literal(literal) = value {
+                    if let Some(lang) = literal.language() {
+                        let lang = lang.to_ascii_lowercase();
+                        if !seen.insert(lang.clone()) && duplicates.insert(lang) {
+                            report("UniqueLangConstraintComponent", path.clone(), None);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Property pair constraints:
+        for property in params("equals") {
+            let others = self.eval_path(focus_node, &property, false);
+            for value in values.iter().filter(|value| !others.contains(value)) {
+                report("EqualsConstraintComponent", path.clone(), Some(value));
+            }
+            for value in others.iter().filter(|value| !values.contains(value)) {
+                report("EqualsConstraintComponent", path.clone(), Some(value));
+            }
+        }
+        for property in params("disjoint") {
+            let others = self.eval_path(focus_node, &property, false);
+            for value in values.iter().filter(|value| others.contains(value)) {
+                report("DisjointConstraintComponent", path.clone(), Some(value));
+            }
+        }
+        let comparisons: [Comparison; 2] = [
+            ("lessThan", "LessThanConstraintComponent", Ordering::is_lt),
+            (
+                "lessThanOrEquals",
+                "LessThanOrEqualsConstraintComponent",
+                Ordering::is_le,
+            ),
+        ];
+        for (name, component, is_valid) in comparisons {
+            for property in params(name) {
+                let others = self.eval_path(focus_node, &property, false);
+                for value in &values {
+                    if !others
+                        .iter()
+                        .all(|other| compare(value, other).is_some_and(is_valid))
+                    {
+                        report(component, path.clone(), Some(value));
+                    }
+                }
+            }
+        }
+
+        // Logical constraints:
+        for other_shape in params("not") {
+            for value in &values {
+                if self.conforms(value, &other_shape)? {
+                    report("NotConstraintComponent", path.clone(), Some(value));
+                }
+            }
+        }
+        let logical: [(&str, &str, CountCheck); 3] = [
+            ("and", "AndConstraintComponent", |count, total| {
+                count == total
+            }),
+            ("or", "OrConstraintComponent", |count, _| count > 0),
+            ("xone", "XoneConstraintComponent", |count, _| count == 1),
+        ];
+        for (name, component, is_valid) in logical {
+            for list in params(name) {
+                let shapes = self.list_items(&list);
+                for value in &values {
+                    let mut count = 0;
+                    for other_shape in &shapes {
+                        if self.conforms(value, other_shape)? {
+                            count += 1;
+                        }
+                    }
+                    if !is_valid(count, shapes.len()) {
+                        report(component, path.clone(), Some(value));
+                    }
+                }
+            }
+        }
+
+        // Shape-based constraints:
+        for node_shape in params("node") {
+            for value in &values {
+                if !self.conforms(value, &node_shape)? {
+                    report("NodeConstraintComponent", path.clone(), Some(value));
+                }
+            }
+        }
+        let mut nested_results = Vec::new();
+        for property_shape in params("property") {
+            for value in &values {
+                self.validate_shape(&property_shape, value, &mut nested_results)?;
+            }
+        }
+        for qualified_shape in params("qualifiedValueShape") {
+            let siblings = if self.param(shape, "qualifiedValueShapesDisjoint")
+                == Some(Literal::from(true).into())
+            {
+                self.sibling_shapes(shape, &qualified_shape)
+            } else {
+                Vec::new()
+            };
+            let mut count = 0;
+            for value in &values {
+                if self.conforms(value, &qualified_shape)? {
+                    let mut in_sibling = false;
+                    for sibling in &siblings {
+                        in_sibling |= self.conforms(value, sibling)?;
+                    }
+                    if !in_sibling {
+                        count += 1;
+                    }
+                }
+            }
+            for min_count in params("qualifiedMinCount").iter().filter_map(as_integer) {
+                if count < min_count {
+                    report("QualifiedMinCountConstraintComponent", path.clone(), None);
+                }
+            }
+            for max_count in params("qualifiedMaxCount").iter().filter_map(as_integer) {
+                if count > max_count {
+                    report("QualifiedMaxCountConstraintComponent", path.clone(), None);
+                }
+            }
+        }
+
+        // Other constraints:
+        if self.param(shape, "closed") == Some(Literal::from(true).into()) {
+            let mut allowed = params("property")
+                .iter()
+                .filter_map(|property_shape| self.param(property_shape, "path"))
+                .collect::<Vec<_>>();
+            for list in params("ignoredProperties") {
+                allowed.extend(self.list_items(&list));
+            }
+            for value in &values {
+                let Some(subject) = as_subject(value) else {
+                    continue;
+                };
+                for triple in self.data.triples_for_subject(subject) {
+                    let predicate = Term::from(triple.predicate.into_owned());
+                    if !allowed.contains(&predicate) {
+                        let object = triple.object.into_owned();
+                        report("ClosedConstraintComponent", Some(predicate), Some(&object));
+                    }
+                }
+            }
+        }
+        for value in params("hasValue") {
+            if !values.contains(&value) {
+                report("HasValueConstraintComponent", path.clone(), None);
+            }
+        }
+        for list in params("in") {
+            let members = self.list_items(&list);
+            for value in values.iter().filter(|value| !members.contains(value)) {
+                report("InConstraintComponent", path.clone(), Some(value));
+            }
+        }
+
+        results.extend(nested_results);
+        Ok(())
+    }
+
+    /// Gets the other qualified value shapes of the property shapes of the shapes having the
+    /// given property shape.
+    fn sibling_shapes(&self, property_shape: &Term, qualified_shape: &Term) -> Vec<Term> {
+        let mut siblings = Vec::new();
+        for parent in subjects(self.shapes, sh("property").as_ref(), property_shape) {
+            for sibling_property in objects(self.shapes, &parent, sh("property").as_ref()) {
+                for sibling in objects(
+                    self.shapes,
+                    &sibling_property,
+                    sh("qualifiedValueShape").as_ref(),
+                ) {
+                    if &sibling != qualified_shape {
+                        siblings.push(sibling);
+                    }
+                }
+            }
+        }
+        siblings
+    }
+
+    /// Gets the nodes reached from a focus node by a SHACL property path (or by its inverse).
+    fn eval_path(&self, focus_node: &Term, path: &Term, inverse: bool) -> Vec<Term> {
+        if let Term::NamedNode(predicate) = path {
+            return if inverse {
+                subjects(self.data, predicate.as_ref(), focus_node)
+            } else {
+                objects(self.data, focus_node, predicate.as_ref())
+            };
+        }
+
+        if object(self.shapes, path, rdf::FIRST).is_some() {
+            let mut steps = self.list_items(path);
+            if inverse {
+                steps.reverse();
+            }
+            let mut nodes = vec![focus_node.clone()];
+            for step in steps {
+                nodes = dedup(
+                    nodes
+                        .iter()
+                        .flat_map(|node| self.eval_path(node, &step, inverse))
+                        .collect(),
+                );
+            }
+            return nodes;
+        }
+        if let Some(inverse_path) = self.param(path, "inversePath") {
+            return self.eval_path(focus_node, &inverse_path, !inverse);
+        }
+        if let Some(list) = self.param(path, "alternativePath") {
+            return dedup(
+                self.list_items(&list)
+                    .iter()
+                    .flat_map(|alternative| self.eval_path(focus_node, alternative, inverse))
+                    .collect(),
+            );
+        }
+        if let Some(step) = self.param(path, "zeroOrMorePath") {
+            return self.eval_closure(focus_node, &step, inverse, true);
+        }
+        if let Some(step) = self.param(path, "oneOrMorePath") {
+            return self.eval_closure(focus_node, &step, inverse, false);
+        }
+        if let Some(step) = self.param(path, "zeroOrOnePath") {
+            let mut nodes = vec![focus_node.clone()];
+            nodes.extend(self.eval_path(focus_node, &step, inverse));
+            return dedup(nodes);
+        }
+
+        Vec::new()
+    }
+
+    fn eval_closure(
+        &self,
+        focus_node: &Term,
+        step: &Term,
+        inverse: bool,
+        include_start: bool,
+    ) -> Vec<Term> {
+        let mut nodes = Vec::new();
+        if include_start {
+            nodes.push(focus_node.clone());
+        }
+        let mut frontier = vec![focus_node.clone()];
+        while let Some(node) = frontier.pop() {
+            for next in self.eval_path(&node, step, inverse) {
+                if !nodes.contains(&next) {
+                    nodes.push(next.clone());
+                    frontier.push(next);
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Gets the members of an RDF list in the shapes graph.
+    fn list_items(&self, list: &Term) -> Vec<Term> {
+        let mut items = Vec::new();
+        let mut node = list.clone();
+        while let Some(first) = object(self.shapes, &node, rdf::FIRST) {
+            items.push(first);
+            match object(self.shapes, &node, rdf::REST) {
+                Some(rest) if !items.contains(&rest) => node = rest,
+                _ => break,
+            }
+        }
+        items
+    }
+
+    /// Gets a parameter of a shape (or a property path) from the shapes graph.
+    fn param(&self, node: &Term, name: &str) -> Option<Term> {
+        object(self.shapes, node, sh(name).as_ref())
+    }
+
+    /// Describes the results as a validation report.
+    fn report(&self, results: Vec<ValidationResult>) -> Vec<Triple> {
+        let mut triples = Vec::new();
+        let report = BlankNode::default();
+        triples.push(Triple::new(
+            report.clone(),
+            rdf::TYPE,
+            sh("ValidationReport"),
+        ));
+        triples.push(Triple::new(
+            report.clone(),
+            sh("conforms"),
+            Literal::from(results.is_empty()),
+        ));
+
+        for result in results {
+            let node = BlankNode::default();
+            triples.push(Triple::new(report.clone(), sh("result"), node.clone()));
+            triples.push(Triple::new(node.clone(), rdf::TYPE, sh("ValidationResult")));
+            triples.push(Triple::new(
+                node.clone(),
+                sh("focusNode"),
+                result.focus_node,
+            ));
+            if let Some(path) = result.path {
+                self.copy_blank_nodes(&path, &mut triples);
+                triples.push(Triple::new(node.clone(), sh("resultPath"), path));
+            }
+            if let Some(value) = result.value {
+                triples.push(Triple::new(node.clone(), sh("value"), value));
+            }
+            triples.push(Triple::new(
+                node.clone(),
+                sh("sourceShape"),
+                result.source_shape,
+            ));
+            triples.push(Triple::new(
+                node.clone(),
+                sh("sourceConstraintComponent"),
+                sh(result.component),
+            ));
+            triples.push(Triple::new(
+                node.clone(),
+                sh("resultSeverity"),
+                result.severity,
+            ));
+            for message in result.messages {
+                triples.push(Triple::new(node.clone(), sh("resultMessage"), message));
+            }
+        }
+
+        dedup(triples)
+    }
+
+    /// Copies the description of a blank node (such as a complex path) from the shapes graph.
+    fn copy_blank_nodes(&self, node: &Term, triples: &mut Vec<Triple>) {
+        let Term::BlankNode(bnode) = node else {
+            return;
+        };
+        for triple in self.shapes.triples_for_subject(bnode) {
+            let triple = triple.into_owned();
+            if !triples.contains(&triple) {
+                let object = triple.object.clone();
+                triples.push(triple);
+                self.copy_blank_nodes(&object, triples);
+            }
+        }
+    }
+}
+
+pub(crate) fn as_subject(term: &Term) -> Option<NamedOrBlankNodeRef<'_>> {
+    match term {
+        Term::NamedNode(node) => Some(node.into()),
+        Term::BlankNode(node) => Some(node.into()),
+        _ => None,
+    }
+}
+
+fn objects(graph: &Graph, subject: &Term, predicate: NamedNodeRef<'_>) -> Vec<Term> {
+    let Some(subject) = as_subject(subject) else {
+        return Vec::new();
+    };
+    graph
+        .objects_for_subject_predicate(subject, predicate)
+        .map(|object| object.into_owned())
+        .collect()
+}
+
+fn object(graph: &Graph, subject: &Term, predicate: NamedNodeRef<'_>) -> Option<Term> {
+    graph
+        .object_for_subject_predicate(as_subject(subject)?, predicate)
+        .map(|object| object.into_owned())
+}
+
+fn subjects(graph: &Graph, predicate: NamedNodeRef<'_>, object: &Term) -> Vec<Term> {
+    graph
+        .subjects_for_predicate_object(predicate, object)
+        .map(|subject| subject.into_owned().into())
+        .collect()
+}
+
+fn dedup<T: Clone + Eq + std::hash::Hash>(items: Vec<T>) -> Vec<T> {
+    let mut seen = HashSet::new();
+    items
+        .into_iter()
+        .filter(|item| seen.insert(item.clone()))
+        .collect()
+}
+
+fn as_integer(term: &Term) -> Option<i64> {
+    match term {
+        Term::Literal(literal) => literal.value().parse().ok(),
+        _ => None,
+    }
+}
+
+pub(crate) fn lexical_form(term: &Term) -> Option<&str> {
+    match term {
+        Term::NamedNode(node) => Some(node.as_str()),
+        Term::Literal(literal) => Some(literal.value()),
+        _ => None,
+    }
+}
+
+fn has_node_kind(value: &Term, node_kind: &Term) -> bool {
+    let Term::NamedNode(node_kind) = node_kind else {
+        return false;
+    };
+    let kind = node_kind.as_str().strip_prefix(SH).unwrap_or_default();
+    match value {
+        Term::NamedNode(_) => matches!(kind, "IRI" | "BlankNodeOrIRI" | "IRIOrLiteral"),
+        Term::BlankNode(_) => matches!(kind, "BlankNode" | "BlankNodeOrIRI" | "BlankNodeOrLiteral"),
+        Term::Literal(_) => matches!(kind, "Literal" | "BlankNodeOrLiteral" | "IRIOrLiteral"),
+        Term::Triple(_) => false,
+    }
+}
+
+const NUMERIC_TYPES: [&str; 16] = [
+    "integer",
+    "decimal",
+    "float",
+    "double",
+    "int",
+    "long",
+    "short",
+    "byte",
+    "nonNegativeInteger",
+    "positiveInteger",
+    "nonPositiveInteger",
+    "negativeInteger",
+    "unsignedInt",
+    "unsignedLong",
+    "unsignedShort",
+    "unsignedByte",
+];
+
+pub(crate) fn numeric_value(literal: &Literal) -> Option<f64> {
+    let datatype = literal.datatype().as_str();
+    let local_name = datatype.strip_prefix("http://www.w3.org/2001/XMLSchema#")?;
+    if !NUMERIC_TYPES.contains(&local_name) {
+        return None;
+    }
+    match literal.value() {
+        "INF" => Some(f64::INFINITY),
+        "-INF" => Some(f64::NEG_INFINITY),
+        value => value.parse().ok(),
+    }
+}
+
+/// Checks the lexical form of literals of numeric and boolean datatypes.
+pub(crate) fn is_well_formed(literal: &Literal) -> bool {
+    let datatype = literal.datatype();
+    if datatype == xsd::BOOLEAN {
+        return matches!(literal.value(), "true" | "false" | "1" | "0");
+    }
+    if datatype == xsd::DOUBLE || datatype == xsd::FLOAT {
+        return numeric_value(literal).is_some() || literal.value() == "NaN";
+    }
+    if datatype == xsd::DECIMAL {
+        let value = literal.value().trim_start_matches(['+', '-']);
+        return !value.is_empty()
+            && value.chars().all(|c| c.is_ascii_digit() || c == '.')
+            && value.matches('.').count() <= 1;
+    }
+    let datatype = datatype.as_str();
+    match datatype.strip_prefix("http://www.w3.org/2001/XMLSchema#") {
+        Some(local_name) if NUMERIC_TYPES.contains(&local_name) => {
+            literal.value().parse::<i128>().is_ok()
+        }
+        _ => true,
+    }
+}
+
+/// Compares literals by numeric value (for numeric datatypes), or else by lexical form (for
+/// literals of the same datatype).
+pub(crate) fn compare(a: &Term, b: &Term) -> Option<Ordering> {
+    let (Term::Literal(a), Term::Literal(b)) = (a, b) else {
+        return None;
+    };
+    if let (Some(a), Some(b)) = (numeric_value(a), numeric_value(b)) {
+        return a.partial_cmp(&b);
+    }
+    if a.datatype() == b.datatype() && a.language() == b.language() {
+        return Some(a.value().cmp(b.value()));
+    }
+    None
+}
+
+/// Checks whether a language tag matches a basic language range (as in SPARQL `langMatches`).
+pub(crate) fn lang_matches(lang: &str, range: &str) -> bool {
+    let (lang, range) = (lang.to_ascii_lowercase(), range.to_ascii_lowercase());
+    range == "*" || lang == range || lang.starts_with(&format!("{range}-"))
+}
+
+fn build_regex(pattern: &Term, flags: Option<&Term>) -> Result<Regex> {
+    let pattern = lexical_form(pattern).unwrap_or_default();
+    let flags = flags.and_then(lexical_form).unwrap_or_default();
+    let flags = flags
+        .chars()
+        .filter(|c| matches!(c, 'i' | 'm' | 's' | 'x'))
+        .collect::<String>();
+    let pattern = if flags.is_empty() {
+        pattern.to_owned()
+    } else {
+        format!("(?{flags}){pattern}")
+    };
+    Regex::new(&pattern).with_context(|| format!("Invalid pattern in shapes: {pattern}"))
+}
@@ -0,0 +1,1211 @@
+//! Validation of data against [ShEx](https://shex.io/) schemas (in the ShExC syntax), for the
+//! nodes and shapes associated by a shape map, producing a result shape map.
+//!
+//! Imports, external shapes, shape extensions and semantic actions are not supported (semantic
+//! actions and annotations are skipped).
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, Graph, Literal, NamedNode, Term};
+use oxiri::Iri;
+use regex::Regex;
+
+use crate::shacl::{
+    as_subject, compare, is_well_formed, lang_matches, lexical_form, numeric_value,
+};
+
+/// The number of candidate triples beyond which a triple constraint is matched against them in
+/// order (instead of trying each).
+const MAX_BRANCHING: usize = 12;
+
+/// A shape label in a shape map: a shape declared in the schema, or its start shape.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Label {
+    Start,
+    Shape(Term),
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Label::Start => write!(f, "START"),
+            Label::Shape(label) => write!(f, "{label}"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Schema {
+    shapes: HashMap<Term, ShapeExpr>,
+    start: Option<ShapeExpr>,
+    triple_exprs: HashMap<Term, TripleExpr>,
+}
+
+#[derive(Clone)]
+enum ShapeExpr {
+    Or(Vec<ShapeExpr>),
+    And(Vec<ShapeExpr>),
+    Not(Box<ShapeExpr>),
+    Ref(Term),
+    Node(NodeConstraint),
+    Shape(Shape),
+    Any,
+}
+
+#[derive(Clone, Default)]
+struct NodeConstraint {
+    kind: Option<NodeKind>,
+    datatype: Option<NamedNode>,
+    values: Option<Vec<ValueSetItem>>,
+    facets: Vec<Facet>,
+}
+
+#[derive(Clone, Copy)]
+enum NodeKind {
+    Iri,
+    BNode,
+    Literal,
+    NonLiteral,
+}
+
+#[derive(Clone)]
+struct ValueSetItem {
+    value: ValueSetValue,
+    exclusions: Vec<ValueSetValue>,
+}
+
+#[derive(Clone)]
+enum ValueSetValue {
+    Object(Term),
+    IriStem(String),
+    LiteralStem(String),
+    Language(String),
+    LanguageStem(String),
+    Wildcard,
+}
+
+#[derive(Clone)]
+enum Facet {
+    Length(usize),
+    MinLength(usize),
+    MaxLength(usize),
+    Pattern(Regex),
+    MinInclusive(Term),
+    MinExclusive(Term),
+    MaxInclusive(Term),
+    MaxExclusive(Term),
+    TotalDigits(usize),
+    FractionDigits(usize),
+}
+
+#[derive(Clone)]
+struct Shape {
+    closed: bool,
+    extra: Vec<NamedNode>,
+    expression: Option<TripleExpr>,
+}
+
+#[derive(Clone)]
+enum TripleExpr {
+    EachOf(Vec<TripleExpr>),
+    OneOf(Vec<TripleExpr>),
+    Repeat(Box<TripleExpr>, usize, Option<usize>),
+    Constraint(TripleConstraint),
+    Include(Term),
+}
+
+/// A triple constraint (numbered, for caching which triples match it).
+#[derive(Clone)]
+struct TripleConstraint {
+    id: usize,
+    inverse: bool,
+    predicate: NamedNode,
+    value: Option<Box<ShapeExpr>>,
+}
+
+/// A triple in the neighbourhood of a node (from it, or to it if inverse).
+struct Arc {
+    inverse: bool,
+    predicate: NamedNode,
+    node: Term,
+}
+
+/// The triples (by index in the neighbourhood) remaining after each possible match so far.
+type States = HashSet<BTreeSet<usize>>;
+
+/// Validates the data for each association of node and shape in the shape map (using the
+/// prefixes of the schema), writing the result shape map (with `@!` marking nonconformance), and
+/// returning whether all nodes conform.
+pub(crate) fn validate<W: Write>(
+    schema: &str,
+    base_iri: Option<&str>,
+    shape_map: &str,
+    data: &Graph,
+    mut writer: W,
+) -> Result<bool> {
+    let mut parser = Parser::new(schema, base_iri)?;
+    let schema = parser.parse_schema().context("Invalid ShEx schema")?;
+    let mut map_parser = Parser {
+        text: shape_map,
+        pos: 0,
+        ..parser
+    };
+    let associations = map_parser
+        .parse_shape_map(data)
+        .context("Invalid shape map")?;
+
+    let validator = Validator {
+        schema: &schema,
+        data,
+        in_progress: RefCell::new(HashSet::new()),
+    };
+    let mut all_conform = true;
+    for (node, label) in associations {
+        let expr = match &label {
+            Label::Start => schema.start.as_ref().context("No start shape in schema")?,
+            Label::Shape(term) => schema
+                .shapes
+                .get(term)
+                .with_context(|| format!("Unknown shape in shape map: {term}"))?,
+        };
+        let conforms = validator.conforms(&node, expr);
+        all_conform &= conforms;
+        writeln!(writer, "{node}@{}{label}", if conforms { "" } else { "!" })?;
+    }
+
+    Ok(all_conform)
+}
+
+struct Validator<'a> {
+    schema: &'a Schema,
+    data: &'a Graph,
+    /// The nodes and shapes being validated (assumed to conform if reached again by recursion).
+    in_progress: RefCell<HashSet<(Term, Term)>>,
+}
+
+impl Validator<'_> {
+    fn conforms(&self, node: &Term, expr: &ShapeExpr) -> bool {
+        match expr {
+            ShapeExpr::Or(exprs) => exprs.iter().any(|expr| self.conforms(node, expr)),
+            ShapeExpr::And(exprs) => exprs.iter().all(|expr| self.conforms(node, expr)),
+            ShapeExpr::Not(expr) => !self.conforms(node, expr),
+            ShapeExpr::Ref(label) => {
+                let key = (node.clone(), label.clone());
+                if !self.in_progress.borrow_mut().insert(key.clone()) {
+                    return true;
+                }
+                let conforms = self
+                    .schema
+                    .shapes
+                    .get(label)
+                    .is_some_and(|expr| self.conforms(node, expr));
+                self.in_progress.borrow_mut().remove(&key);
+                conforms
+            }
+            ShapeExpr::Node(constraint) => satisfies(node, constraint),
+            ShapeExpr::Shape(shape) => self.matches_shape(node, shape),
+            ShapeExpr::Any => true,
+        }
+    }
+
+    fn matches_shape(&self, node: &Term, shape: &Shape) -> bool {
+        let mut constraints = Vec::new();
+        if let Some(expr) = &shape.expression {
+            self.collect_constraints(expr, &mut constraints, &mut HashSet::new());
+        }
+        let arcs = self.neighbourhood(node, &constraints);
+
+        let all = (0..arcs.len()).collect::<BTreeSet<_>>();
+        let mut cache = HashMap::new();
+        let states = match &shape.expression {
+            Some(expr) => self.match_expr(expr, States::from([all]), &arcs, &mut cache),
+            None => States::from([all]),
+        };
+
+        let mentioned = constraints
+            .iter()
+            .filter(|tc| !tc.inverse)
+            .map(|tc| &tc.predicate)
+            .collect::<HashSet<_>>();
+        states.iter().any(|remaining| {
+            remaining.iter().all(|&i| {
+                let arc = &arcs[i];
+                if arc.inverse {
+                    true
+                } else if mentioned.contains(&arc.predicate) {
+                    shape.extra.contains(&arc.predicate)
+                        && !constraints
+                            .iter()
+                            .any(|tc| self.matches_arc(tc, arc, i, &mut cache))
+                } else {
+                    !shape.closed
+                }
+            })
+        })
+    }
+
+    fn collect_constraints<'e>(
+        &'e self,
+        expr: &'e TripleExpr,
+        constraints: &mut Vec<&'e TripleConstraint>,
+        included: &mut HashSet<&'e Term>,
+    ) {
+        match expr {
+            TripleExpr::EachOf(exprs) | TripleExpr::OneOf(exprs) => {
+                for expr in exprs {
+                    self.collect_constraints(expr, constraints, included);
+                }
+            }
+            TripleExpr::Repeat(expr, _, _) => {
+                self.collect_constraints(expr, constraints, included);
+            }
+            TripleExpr::Constraint(tc) => constraints.push(tc),
+            TripleExpr::Include(label) => {
+                if let Some(expr) = self.schema.triple_exprs.get(label) {
+                    if included.insert(label) {
+                        self.collect_constraints(expr, constraints, included);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collects the triples from the node, and those to it with a predicate of an inverse
+    /// triple constraint.
+    fn neighbourhood(&self, node: &Term, constraints: &[&TripleConstraint]) -> Vec<Arc> {
+        let mut arcs = Vec::new();
+        if let Some(subject) = as_subject(node) {
+            for triple in self.data.triples_for_subject(subject) {
+                arcs.push(Arc {
+                    inverse: false,
+                    predicate: triple.predicate.into_owned(),
+                    node: triple.object.into_owned(),
+                });
+            }
+        }
+        let inverse_predicates = constraints
+            .iter()
+            .filter(|tc| tc.inverse)
+            .map(|tc| &tc.predicate)
+            .collect::<HashSet<_>>();
+        for predicate in inverse_predicates {
+            for subject in self.data.subjects_for_predicate_object(predicate, node) {
+                arcs.push(Arc {
+                    inverse: true,
+                    predicate: predicate.clone(),
+                    node: subject.into_owned().into(),
+                });
+            }
+        }
+        arcs
+    }
+
+    fn match_expr(
+        &self,
+        expr: &TripleExpr,
+        states: States,
+        arcs: &[Arc],
+        cache: &mut HashMap<(usize, usize), bool>,
+    ) -> States {
+        match expr {
+            TripleExpr::EachOf(exprs) => exprs.iter().fold(states, |states, expr| {
+                self.match_expr(expr, states, arcs, cache)
+            }),
+            TripleExpr::OneOf(exprs) => exprs
+                .iter()
+                .flat_map(|expr| self.match_expr(expr, states.clone(), arcs, cache))
+                .collect(),
+            TripleExpr::Repeat(expr, min, max) => {
+                let mut matched = if *min == 0 {
+                    states.clone()
+                } else {
+                    States::new()
+                };
+                let mut current = states;
+                let mut count = 0;
+                while max.is_none_or(|max| count < max) && count <= arcs.len() {
+                    current = self.match_expr(expr, current, arcs, cache);
+                    count += 1;
+                    if current.is_empty() {
+                        break;
+                    }
+                    if count >= *min {
+                        matched.extend(current.iter().cloned());
+                    }
+                }
+                matched
+            }
+            TripleExpr::Constraint(tc) => {
+                let mut matched = States::new();
+                for remaining in states {
+                    let candidates = remaining
+                        .iter()
+                        .copied()
+                        .filter(|&i| self.matches_arc(tc, &arcs[i], i, cache))
+                        .collect::<Vec<_>>();
+                    let tried = if candidates.len() > MAX_BRANCHING {
+                        &candidates[..1]
+                    } else {
+                        &candidates[..]
+                    };
+                    for i in tried {
+                        let mut remaining = remaining.clone();
+                        remaining.remove(i);
+                        matched.insert(remaining);
+                    }
+                }
+                matched
+            }
+            TripleExpr::Include(label) => match self.schema.triple_exprs.get(label) {
+                Some(expr) => self.match_expr(expr, states, arcs, cache),
+                None => States::new(),
+            },
+        }
+    }
+
+    fn matches_arc(
+        &self,
+        tc: &TripleConstraint,
+        arc: &Arc,
+        i: usize,
+        cache: &mut HashMap<(usize, usize), bool>,
+    ) -> bool {
+        if arc.inverse != tc.inverse || arc.predicate != tc.predicate {
+            return false;
+        }
+        if let Some(&matches) = cache.get(&(tc.id, i)) {
+            return matches;
+        }
+        let matches = tc
+            .value
+            .as_ref()
+            .is_none_or(|value| self.conforms(&arc.node, value));
+        cache.insert((tc.id, i), matches);
+        matches
+    }
+}
+
+fn satisfies(value: &Term, constraint: &NodeConstraint) -> bool {
+    if let Some(kind) = constraint.kind {
+        let has_kind = match kind {
+            NodeKind::Iri => matches!(value, Term::NamedNode(_)),
+            NodeKind::BNode => matches!(value, Term::BlankNode(_)),
+            NodeKind::Literal => matches!(value, Term::Literal(_)),
+            NodeKind::NonLiteral => !matches!(value, Term::Literal(_)),
+        };
+        if !has_kind {
+            return false;
+        }
+    }
+    if let Some(datatype) = &constraint.datatype {
+        let Term::Literal(literal) = value else {
+            return false;
+        };
+        if literal.datatype() != datatype.as_ref() || !is_well_formed(literal) {
+            return false;
+        }
+    }
+    if let Some(values) = &constraint.values {
+        let in_values = values.iter().any(|item| {
+            matches_value(value, &item.value)
+                && !item
+                    .exclusions
+                    .iter()
+                    .any(|exclusion| matches_value(value, exclusion))
+        });
+        if !in_values {
+            return false;
+        }
+    }
+    constraint
+        .facets
+        .iter()
+        .all(|facet| satisfies_facet(value, facet))
+}
+
+fn matches_value(value: &Term, set_value: &ValueSetValue) -> bool {
+    match (set_value, value) {
+        (ValueSetValue::Object(object), _) => object == value,
+        (ValueSetValue::IriStem(stem), Term::NamedNode(node)) => node.as_str().starts_with(stem),
+        (ValueSetValue::LiteralStem(stem), Term::Literal(literal)) => {
+            literal.value().starts_with(stem)
+        }
+        (ValueSetValue::Language(tag), Term::Literal(literal)) => literal
+            .language()
+            .is_some_and(|lang| lang.eq_ignore_ascii_case(tag)),
+        (ValueSetValue::LanguageStem(stem), Term::Literal(literal)) => literal
+            .language()
+            .is_some_and(|lang| stem.is_empty() || lang_matches(lang, stem)),
+        (ValueSetValue::Wildcard, _) => true,
+        _ => false,
+    }
+}
+
+fn satisfies_facet(value: &Term, facet: &Facet) -> bool {
+    let length = || lexical_form(value).map(|form| form.chars().count());
+    let numeric = || match value {
+        Term::Literal(literal) => numeric_value(literal).is_some(),
+        _ => false,
+    };
+    match facet {
+        Facet::Length(bound) => length() == Some(*bound),
+        Facet::MinLength(bound) => length().is_some_and(|length| length >= *bound),
+        Facet::MaxLength(bound) => length().is_some_and(|length| length <= *bound),
+        Facet::Pattern(regex) => lexical_form(value).is_some_and(|form| regex.is_match(form)),
+        Facet::MinInclusive(bound) => numeric() && compare(value, bound).is_some_and(|o| o.is_ge()),
+        Facet::MinExclusive(bound) => numeric() && compare(value, bound).is_some_and(|o| o.is_gt()),
+        Facet::MaxInclusive(bound) => numeric() && compare(value, bound).is_some_and(|o| o.is_le()),
+        Facet::MaxExclusive(bound) => numeric() && compare(value, bound).is_some_and(|o| o.is_lt()),
+        Facet::TotalDigits(bound) => digits(value).is_some_and(|(total, _)| total <= *bound),
+        Facet::FractionDigits(bound) => {
+            digits(value).is_some_and(|(_, fraction)| fraction <= *bound)
+        }
+    }
+}
+
+/// Counts the total and fraction digits of a decimal (or integer) literal.
+fn digits(value: &Term) -> Option<(usize, usize)> {
+    let Term::Literal(literal) = value else {
+        return None;
+    };
+    if literal.datatype() == xsd::DOUBLE || literal.datatype() == xsd::FLOAT {
+        return None;
+    }
+    numeric_value(literal)?;
+    let form = literal.value().trim_start_matches(['+', '-']);
+    let (integer, fraction) = form.split_once('.').unwrap_or((form, ""));
+    let integer = integer.trim_start_matches('0');
+    let fraction = fraction.trim_end_matches('0');
+    Some(((integer.len() + fraction.len()).max(1), fraction.len()))
+}
+
+/// A parser of ShExC schemas (and of shape maps, using the prefixes of the schema).
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    base_iri: Option<Iri<String>>,
+    prefixes: HashMap<String, String>,
+    next_id: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str, base_iri: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            text,
+            pos: 0,
+            base_iri: base_iri.map(|iri| Iri::parse(iri.to_owned())).transpose()?,
+            prefixes: HashMap::new(),
+            next_id: 0,
+        })
+    }
+
+    fn parse_schema(&mut self) -> Result<Schema> {
+        let mut schema = Schema::default();
+        while self.peek().is_some() {
+            if self.eat_keyword("PREFIX") {
+                let prefix = self.take_while(|c| c != ':').trim().to_owned();
+                self.expect(":")?;
+                let iri = self.parse_iriref()?;
+                self.prefixes.insert(prefix, iri.into_string());
+            } else if self.eat_keyword("BASE") {
+                self.base_iri = Some(Iri::parse(self.parse_iriref()?.into_string())?);
+            } else if self.eat_keyword("IMPORT") {
+                return Err(self.error("Imports are not supported"));
+            } else if self.eat_keyword("START") {
+                self.expect("=")?;
+                schema.start = Some(self.parse_shape_expr(&mut schema)?);
+            } else if self.peek() == Some('%') {
+                self.skip_semantic_actions()?;
+            } else {
+                self.eat_keyword("ABSTRACT");
+                let label = self.parse_label()?;
+                if self.eat_keyword("EXTERNAL") {
+                    return Err(self.error("External shapes are not supported"));
+                }
+                let expr = self.parse_shape_expr(&mut schema)?;
+                schema.shapes.insert(label, expr);
+            }
+        }
+        Ok(schema)
+    }
+
+    /// Parses a shape map of fixed nodes (or triple patterns selecting them in the data) and
+    /// shape labels.
+    fn parse_shape_map(&mut self, data: &Graph) -> Result<Vec<(Term, Label)>> {
+        let mut associations = Vec::new();
+        while self.peek().is_some() {
+            let nodes = if self.eat("{") {
+                self.parse_node_pattern(data)?
+            } else {
+                vec![self.parse_term()?]
+            };
+            self.expect("@")?;
+            let label = if self.eat_keyword("START") {
+                Label::Start
+            } else {
+                Label::Shape(self.parse_label()?)
+            };
+            for node in nodes {
+                let association = (node, label.clone());
+                if !associations.contains(&association) {
+                    associations.push(association);
+                }
+            }
+            if !self.eat(",") && self.peek().is_some() {
+                return Err(self.error("Expected ','"));
+            }
+        }
+        Ok(associations)
+    }
+
+    /// Parses a triple pattern with a `FOCUS` subject or object (and `_` for any other), and
+    /// selects the focus nodes in the data.
+    fn parse_node_pattern(&mut self, data: &Graph) -> Result<Vec<Term>> {
+        let parse_position = |parser: &mut Self| -> Result<Option<Term>> {
+            if parser.eat_keyword("FOCUS") || parser.eat_keyword("_") {
+                Ok(None)
+            } else {
+                parser.parse_term().map(Some)
+            }
+        };
+        let subject_is_focus = self.is_keyword("FOCUS");
+        let subject = parse_position(self)?;
+        let predicate = self.parse_predicate()?;
+        let object_is_focus = self.is_keyword("FOCUS");
+        let object = parse_position(self)?;
+        self.expect("}")?;
+        if subject_is_focus == object_is_focus {
+            return Err(self.error("Expected FOCUS as either subject or object"));
+        }
+
+        let mut nodes = Vec::new();
+        for triple in data.triples_for_predicate(&predicate) {
+            let (focus, other) = if subject_is_focus {
+                (Term::from(triple.subject.into_owned()), &object)
+            } else {
+                (triple.object.into_owned(), &subject)
+            };
+            let other_matches = other.as_ref().is_none_or(|other| {
+                if subject_is_focus {
+                    triple.object == other.as_ref()
+                } else {
+                    Term::from(triple.subject.into_owned()) == *other
+                }
+            });
+            if other_matches && !nodes.contains(&focus) {
+                nodes.push(focus);
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn parse_shape_expr(&mut self, schema: &mut Schema) -> Result<ShapeExpr> {
+        let mut exprs = vec![self.parse_shape_and(schema)?];
+        while self.eat_keyword("OR") {
+            exprs.push(self.parse_shape_and(schema)?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            ShapeExpr::Or(exprs)
+        })
+    }
+
+    fn parse_shape_and(&mut self, schema: &mut Schema) -> Result<ShapeExpr> {
+        let mut exprs = vec![self.parse_shape_not(schema)?];
+        while self.eat_keyword("AND") {
+            exprs.push(self.parse_shape_not(schema)?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            ShapeExpr::And(exprs)
+        })
+    }
+
+    fn parse_shape_not(&mut self, schema: &mut Schema) -> Result<ShapeExpr> {
+        if self.eat_keyword("NOT") {
+            return Ok(ShapeExpr::Not(Box::new(self.parse_shape_atom(schema)?)));
+        }
+        self.parse_shape_atom(schema)
+    }
+
+    fn parse_shape_atom(&mut self, schema: &mut Schema) -> Result<ShapeExpr> {
+        if self.eat("(") {
+            let expr = self.parse_shape_expr(schema)?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        if self.eat(".") {
+            return Ok(ShapeExpr::Any);
+        }
+        if let Some(expr) = self.parse_shape_or_ref(schema)? {
+            return Ok(expr);
+        }
+        let constraint = ShapeExpr::Node(self.parse_node_constraint()?);
+        match self.parse_shape_or_ref(schema)? {
+            Some(expr) => Ok(ShapeExpr::And(vec![constraint, expr])),
+            None => Ok(constraint),
+        }
+    }
+
+    fn parse_shape_or_ref(&mut self, schema: &mut Schema) -> Result<Option<ShapeExpr>> {
+        if self.eat("@") {
+            return Ok(Some(ShapeExpr::Ref(self.parse_label()?)));
+        }
+        let is_shape = self.peek() == Some('{')
+            || ["CLOSED", "EXTRA", "EXTENDS"]
+                .iter()
+                .any(|keyword| self.is_keyword(keyword));
+        if !is_shape {
+            return Ok(None);
+        }
+
+        let mut shape = Shape {
+            closed: false,
+            extra: Vec::new(),
+            expression: None,
+        };
+        loop {
+            if self.eat_keyword("CLOSED") {
+                shape.closed = true;
+            } else if self.eat_keyword("EXTRA") {
+                while self.peek() == Some('<') || self.is_prefixed_name() || self.is_keyword("a") {
+                    shape.extra.push(self.parse_predicate()?);
+                }
+            } else if self.is_keyword("EXTENDS") {
+                return Err(self.error("Shape extensions are not supported"));
+            } else {
+                break;
+            }
+        }
+        self.expect("{")?;
+        if !self.eat("}") {
+            shape.expression = Some(self.parse_triple_expr(schema)?);
+            self.expect("}")?;
+        }
+        self.skip_annotations()?;
+        Ok(Some(ShapeExpr::Shape(shape)))
+    }
+
+    fn parse_node_constraint(&mut self) -> Result<NodeConstraint> {
+        let mut constraint = NodeConstraint::default();
+        let kinds = [
+            ("IRI", NodeKind::Iri),
+            ("BNODE", NodeKind::BNode),
+            ("LITERAL", NodeKind::Literal),
+            ("NONLITERAL", NodeKind::NonLiteral),
+        ];
+        if let Some((_, kind)) = kinds.iter().find(|(keyword, _)| self.eat_keyword(keyword)) {
+            constraint.kind = Some(*kind);
+        } else if self.eat("[") {
+            constraint.values = Some(self.parse_value_set()?);
+        } else if self.peek() == Some('<') || self.is_prefixed_name() {
+            constraint.datatype = Some(self.parse_iri()?);
+        }
+        self.parse_facets(&mut constraint.facets)?;
+        if constraint.kind.is_none()
+            && constraint.datatype.is_none()
+            && constraint.values.is_none()
+            && constraint.facets.is_empty()
+        {
+            return Err(self.error("Expected a shape expression"));
+        }
+        Ok(constraint)
+    }
+
+    fn parse_facets(&mut self, facets: &mut Vec<Facet>) -> Result<()> {
+        loop {
+            if self.peek() == Some('/') && !self.rest().starts_with("//") {
+                facets.push(Facet::Pattern(self.parse_regex()?));
+                continue;
+            }
+            let facet = if self.eat_keyword("LENGTH") {
+                Facet::Length(self.parse_count()?)
+            } else if self.eat_keyword("MINLENGTH") {
+                Facet::MinLength(self.parse_count()?)
+            } else if self.eat_keyword("MAXLENGTH") {
+                Facet::MaxLength(self.parse_count()?)
+            } else if self.eat_keyword("TOTALDIGITS") {
+                Facet::TotalDigits(self.parse_count()?)
+            } else if self.eat_keyword("FRACTIONDIGITS") {
+                Facet::FractionDigits(self.parse_count()?)
+            } else if self.eat_keyword("MININCLUSIVE") {
+                Facet::MinInclusive(self.parse_number()?)
+            } else if self.eat_keyword("MINEXCLUSIVE") {
+                Facet::MinExclusive(self.parse_number()?)
+            } else if self.eat_keyword("MAXINCLUSIVE") {
+                Facet::MaxInclusive(self.parse_number()?)
+            } else if self.eat_keyword("MAXEXCLUSIVE") {
+                Facet::MaxExclusive(self.parse_number()?)
+            } else {
+                return Ok(());
+            };
+            facets.push(facet);
+        }
+    }
+
+    fn parse_value_set(&mut self) -> Result<Vec<ValueSetItem>> {
+        let mut items = Vec::new();
+        while !self.eat("]") {
+            let value = if self.eat(".") {
+                ValueSetValue::Wildcard
+            } else {
+                self.parse_value_set_value()?
+            };
+            let mut exclusions = Vec::new();
+            while self.eat("-") {
+                exclusions.push(self.parse_value_set_value()?);
+            }
+            items.push(ValueSetItem { value, exclusions });
+        }
+        Ok(items)
+    }
+
+    fn parse_value_set_value(&mut self) -> Result<ValueSetValue> {
+        if self.eat("@") {
+            let tag = self.take_while(|c| c.is_ascii_alphanumeric() || c == '-');
+            let tag = tag.to_owned();
+            return Ok(if self.eat("~") {
+                ValueSetValue::LanguageStem(tag)
+            } else {
+                ValueSetValue::Language(tag)
+            });
+        }
+        let term = self.parse_term()?;
+        if !self.rest().starts_with('~') {
+            return Ok(ValueSetValue::Object(term));
+        }
+        self.pos += 1;
+        match term {
+            Term::NamedNode(node) => Ok(ValueSetValue::IriStem(node.into_string())),
+            Term::Literal(literal) => Ok(ValueSetValue::LiteralStem(literal.value().to_owned())),
+            Term::BlankNode(_) | Term::Triple(_) => Err(self.error("Invalid stem")),
+        }
+    }
+
+    fn parse_triple_expr(&mut self, schema: &mut Schema) -> Result<TripleExpr> {
+        let mut exprs = vec![self.parse_each_of(schema)?];
+        while self.eat("|") {
+            exprs.push(self.parse_each_of(schema)?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            TripleExpr::OneOf(exprs)
+        })
+    }
+
+    fn parse_each_of(&mut self, schema: &mut Schema) -> Result<TripleExpr> {
+        let mut exprs = vec![self.parse_unary_triple_expr(schema)?];
+        while self.eat(";") {
+            if matches!(self.peek(), None | Some('}' | ')' | '|')) {
+                break;
+            }
+            exprs.push(self.parse_unary_triple_expr(schema)?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            TripleExpr::EachOf(exprs)
+        })
+    }
+
+    fn parse_unary_triple_expr(&mut self, schema: &mut Schema) -> Result<TripleExpr> {
+        let name = if self.eat("$") {
+            Some(self.parse_label()?)
+        } else {
+            None
+        };
+        if self.eat("&") {
+            return Ok(TripleExpr::Include(self.parse_label()?));
+        }
+
+        let expr = if self.eat("(") {
+            let expr = self.parse_triple_expr(schema)?;
+            self.expect(")")?;
+            expr
+        } else {
+            let inverse = self.eat("^");
+            let predicate = self.parse_predicate()?;
+            let value = match self.parse_shape_expr(schema)? {
+                ShapeExpr::Any => None,
+                expr => Some(Box::new(expr)),
+            };
+            self.next_id += 1;
+            TripleExpr::Constraint(TripleConstraint {
+                id: self.next_id,
+                inverse,
+                predicate,
+                value,
+            })
+        };
+        let expr = match self.parse_cardinality()? {
+            Some((min, max)) => TripleExpr::Repeat(Box::new(expr), min, max),
+            None => expr,
+        };
+        self.skip_annotations()?;
+
+        if let Some(name) = name {
+            schema.triple_exprs.insert(name, expr.clone());
+        }
+        Ok(expr)
+    }
+
+    fn parse_cardinality(&mut self) -> Result<Option<(usize, Option<usize>)>> {
+        if self.eat("*") {
+            return Ok(Some((0, None)));
+        }
+        if self.eat("+") {
+            return Ok(Some((1, None)));
+        }
+        if self.eat("?") {
+            return Ok(Some((0, Some(1))));
+        }
+        let is_range = self.peek() == Some('{')
+            && self.rest()[1..]
+                .trim_start()
+                .starts_with(|c: char| c.is_ascii_digit());
+        if !is_range {
+            return Ok(None);
+        }
+        self.expect("{")?;
+        let min = self.parse_count()?;
+        let max = if self.eat(",") {
+            if self.eat("*") || self.peek() == Some('}') {
+                None
+            } else {
+                Some(self.parse_count()?)
+            }
+        } else {
+            Some(min)
+        };
+        self.expect("}")?;
+        Ok(Some((min, max)))
+    }
+
+    /// Skips any annotations (`// predicate object`) and semantic actions.
+    fn skip_annotations(&mut self) -> Result<()> {
+        while self.eat("//") {
+            self.parse_predicate()?;
+            self.parse_term()?;
+        }
+        self.skip_semantic_actions()
+    }
+
+    fn skip_semantic_actions(&mut self) -> Result<()> {
+        while self.eat("%") {
+            self.parse_iri()?;
+            if self.eat("{") {
+                let end = self
+                    .rest()
+                    .find("%}")
+                    .ok_or_else(|| self.error("Unterminated semantic action"))?;
+                self.pos += end + 2;
+            } else {
+                self.expect("%")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_label(&mut self) -> Result<Term> {
+        if self.peek() == Some('_') && self.rest().starts_with("_:") {
+            self.pos += 2;
+            let id = self.take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-'));
+            return Ok(BlankNode::new(id)?.into());
+        }
+        Ok(self.parse_iri()?.into())
+    }
+
+    fn parse_predicate(&mut self) -> Result<NamedNode> {
+        if self.eat_keyword("a") {
+            return Ok(rdf::TYPE.into_owned());
+        }
+        self.parse_iri()
+    }
+
+    /// Parses an IRI, blank node or literal.
+    fn parse_term(&mut self) -> Result<Term> {
+        match self.peek() {
+            Some('"' | '\'') => {
+                let value = self.parse_string()?;
+                if self.rest().starts_with('@') {
+                    self.pos += 1;
+                    let tag = self.take_while(|c| c.is_ascii_alphanumeric() || c == '-');
+                    return Ok(Literal::new_language_tagged_literal(value, tag)?.into());
+                }
+                if self.rest().starts_with("^^") {
+                    self.pos += 2;
+                    let datatype = self.parse_iri()?;
+                    return Ok(Literal::new_typed_literal(value, datatype).into());
+                }
+                Ok(Literal::new_simple_literal(value).into())
+            }
+            Some(c) if c.is_ascii_digit() || matches!(c, '+' | '-' | '.') => self.parse_number(),
+            _ if self.eat_keyword("true") => Ok(Literal::from(true).into()),
+            _ if self.eat_keyword("false") => Ok(Literal::from(false).into()),
+            _ => self.parse_label(),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Term> {
+        self.skip_space();
+        let start = self.pos;
+        let mut end = start;
+        let bytes = self.text.as_bytes();
+        if matches!(bytes.get(end), Some(b'+' | b'-')) {
+            end += 1;
+        }
+        let mut has_dot = false;
+        let mut has_exponent = false;
+        while let Some(&b) = bytes.get(end) {
+            if b.is_ascii_digit() {
+                end += 1;
+            } else if b == b'.' && !has_dot && !has_exponent {
+                if !bytes.get(end + 1).is_some_and(u8::is_ascii_digit) {
+                    break;
+                }
+                has_dot = true;
+                end += 1;
+            } else if matches!(b, b'e' | b'E') && !has_exponent {
+                has_exponent = true;
+                end += 1;
+                if matches!(bytes.get(end), Some(b'+' | b'-')) {
+                    end += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        let number = &self.text[start..end];
+        if !number.bytes().any(|b| b.is_ascii_digit()) {
+            return Err(self.error("Expected a number"));
+        }
+        self.pos = end;
+        let datatype = if has_exponent {
+            xsd::DOUBLE
+        } else if has_dot {
+            xsd::DECIMAL
+        } else {
+            xsd::INTEGER
+        };
+        Ok(Literal::new_typed_literal(number, datatype).into())
+    }
+
+    fn parse_count(&mut self) -> Result<usize> {
+        self.skip_space();
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        digits
+            .parse()
+            .map_err(|_| self.error("Expected a non-negative integer"))
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.skip_space();
+        let rest = self.rest();
+        let quote = if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+            &rest[..3]
+        } else {
+            &rest[..1]
+        };
+        self.pos += quote.len();
+        let mut value = String::new();
+        loop {
+            let rest = self.rest();
+            if rest.starts_with(quote) {
+                self.pos += quote.len();
+                return Ok(value);
+            }
+            let mut chars = rest.chars();
+            let c = chars
+                .next()
+                .ok_or_else(|| self.error("Unterminated string"))?;
+            self.pos += c.len_utf8();
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            let escape = chars
+                .next()
+                .ok_or_else(|| self.error("Unterminated string"))?;
+            self.pos += escape.len_utf8();
+            match escape {
+                't' => value.push('\t'),
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                'b' => value.push('\u{8}'),
+                'f' => value.push('\u{c}'),
+                'u' | 'U' => {
+                    let len = if escape == 'u' { 4 } else { 8 };
+                    let hex = self.rest().get(..len).unwrap_or_default();
+                    let c = u32::from_str_radix(hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| self.error("Invalid escape in string"))?;
+                    self.pos += len;
+                    value.push(c);
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn parse_regex(&mut self) -> Result<Regex> {
+        self.expect("/")?;
+        let mut pattern = String::new();
+        let mut chars = self.rest().char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((i, '/')) => break i,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, '/')) => pattern.push('/'),
+                    Some((_, c)) => {
+                        pattern.push('\\');
+                        pattern.push(c);
+                    }
+                    None => return Err(self.error("Unterminated pattern")),
+                },
+                Some((_, c)) => pattern.push(c),
+                None => return Err(self.error("Unterminated pattern")),
+            }
+        };
+        self.pos += end + 1;
+        let flags = self.take_while(|c| matches!(c, 'i' | 'm' | 's' | 'x' | 'q'));
+        let flags = flags.replace('q', "");
+        if !flags.is_empty() {
+            pattern = format!("(?{flags}){pattern}");
+        }
+        Regex::new(&pattern).map_err(|e| self.error(&format!("Invalid pattern: {e}")))
+    }
+
+    fn parse_iri(&mut self) -> Result<NamedNode> {
+        if self.peek() == Some('<') {
+            return self.parse_iriref();
+        }
+        if !self.is_prefixed_name() {
+            return Err(self.error("Expected an IRI"));
+        }
+        let name = self.take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+        let trimmed = name.trim_end_matches('.');
+        self.pos -= name.len() - trimmed.len();
+        let name = trimmed;
+        let (prefix, local) = name.split_once(':').unwrap_or_default();
+        let ns = self
+            .prefixes
+            .get(prefix)
+            .ok_or_else(|| self.error(&format!("Unknown prefix: {prefix}")))?;
+        Ok(NamedNode::new(format!("{ns}{local}"))?)
+    }
+
+    fn parse_iriref(&mut self) -> Result<NamedNode> {
+        self.expect("<")?;
+        let end = self
+            .rest()
+            .find('>')
+            .ok_or_else(|| self.error("Unterminated IRI"))?;
+        let iri = &self.rest()[..end];
+        self.pos += end + 1;
+        let iri = match &self.base_iri {
+            Some(base_iri) => base_iri
+                .resolve(iri)
+                .map_err(|e| anyhow!("Invalid IRI <{iri}>: {e}"))?
+                .into_inner(),
+            None => iri.to_owned(),
+        };
+        Ok(NamedNode::new(iri)?)
+    }
+
+    fn is_prefixed_name(&mut self) -> bool {
+        self.skip_space();
+        let rest = self.rest();
+        let prefix_len = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+            .unwrap_or(rest.len());
+        rest[prefix_len..].starts_with(':')
+            && !rest.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '.'))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_space(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with('#') {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if trimmed.starts_with("/*") {
+                self.pos += trimmed.find("*/").map_or(trimmed.len(), |end| end + 2);
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{token}'")))
+        }
+    }
+
+    /// Checks for a (case insensitive) keyword, not followed by more of a name.
+    fn is_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_space();
+        let rest = self.rest();
+        rest.get(..keyword.len())
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+            && !rest[keyword.len()..]
+                .starts_with(|c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | ':'))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        self.skip_space();
+        let rest = self.rest();
+        let len = rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        let line = self.text[..self.pos].lines().count().max(1);
+        match self.rest().lines().next() {
+            Some(near) if !near.trim().is_empty() => anyhow!("{message} at line {line}: {near}"),
+            _ => anyhow!("{message} at end of line {line}"),
+        }
+    }
+}
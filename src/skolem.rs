@@ -0,0 +1,55 @@
+//! Replacement of blank nodes with skolem IRIs.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use oxigraph::model::dataset::CanonicalizationAlgorithm;
+use oxigraph::model::{BlankNode, Dataset, GraphName, NamedNode, NamedOrBlankNode, Quad, Term};
+use oxigraph::store::Store;
+
+/// Maps blank nodes to IRIs made from a base IRI and the canonical labels of the blank nodes in
+/// the data (so that they are the same across runs over the same data). Blank nodes not in the
+/// data (such as from a remote endpoint) keep their labels.
+pub(crate) struct Skolemizer {
+    base: String,
+    labels: HashMap<BlankNode, BlankNode>,
+}
+
+impl Skolemizer {
+    pub(crate) fn new(base: &str, store: &Store) -> Result<Self> {
+        let dataset = store.iter().collect::<Result<Dataset, _>>()?;
+        let labels = dataset
+            .canonicalize_blank_nodes(CanonicalizationAlgorithm::Unstable)
+            .into_iter()
+            .map(|(bnode, label)| (bnode.into_owned(), label))
+            .collect();
+        Ok(Self {
+            base: base.to_owned(),
+            labels,
+        })
+    }
+
+    pub(crate) fn iri(&self, bnode: &BlankNode) -> NamedNode {
+        let label = self.labels.get(bnode).unwrap_or(bnode);
+        NamedNode::new_unchecked(format!("{}{}", self.base, label.as_str()))
+    }
+
+    pub(crate) fn term(&self, term: Term) -> Term {
+        match term {
+            Term::BlankNode(bnode) => self.iri(&bnode).into(),
+            term => term,
+        }
+    }
+
+    pub(crate) fn quad(&self, quad: Quad) -> Quad {
+        let subject = match quad.subject {
+            NamedOrBlankNode::BlankNode(bnode) => self.iri(&bnode).into(),
+            subject => subject,
+        };
+        let graph_name = match quad.graph_name {
+            GraphName::BlankNode(bnode) => self.iri(&bnode).into(),
+            graph_name => graph_name,
+        };
+        Quad::new(subject, quad.predicate, self.term(quad.object), graph_name)
+    }
+}
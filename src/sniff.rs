@@ -0,0 +1,120 @@
+//! Detection of input formats from the first bytes of data.
+
+use oxigraph::io::{JsonLdProfileSet, RdfFormat};
+use regex::Regex;
+
+use crate::sqlite::Database;
+use crate::InputFormat;
+
+/// Guesses the input format of data from its beginning (up to a few kilobytes).
+pub(crate) fn sniff(content: &[u8]) -> Option<InputFormat> {
+    if content.starts_with(b"$HDT") {
+        return Some(InputFormat::Hdt);
+    }
+    if Database::is_database(content) {
+        return Some(InputFormat::Sqlite);
+    }
+
+    let text = String::from_utf8_lossy(content);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let lower = text.to_ascii_lowercase();
+
+    if lower.starts_with("<!doctype html")
+        || lower.starts_with("<html")
+        || (lower.starts_with("<?xml") && lower.contains("<html"))
+    {
+        return Some(InputFormat::Html);
+    }
+    if lower.starts_with("<?xml") || lower.starts_with("<rdf:rdf") {
+        return Some(InputFormat::Rdf(RdfFormat::RdfXml));
+    }
+    if is_rdf_json(text) {
+        return Some(InputFormat::RdfJson);
+    }
+    if text.starts_with('{') || text.starts_with('[') {
+        return Some(InputFormat::Rdf(RdfFormat::JsonLd {
+            profile: JsonLdProfileSet::empty(),
+        }));
+    }
+
+    // The first statement (skipping comments) tells the syntax family:
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    let line_lower = line.to_ascii_lowercase();
+    let is_turtle_family = ["@prefix", "@base", "prefix", "base"]
+        .iter()
+        .any(|keyword| line_lower.starts_with(keyword))
+        || !(line.starts_with('<') || line.starts_with("_:"));
+
+    Some(InputFormat::Rdf(if is_turtle_family {
+        if is_n3_rule(text) {
+            RdfFormat::N3
+        } else if text.contains('{') {
+            RdfFormat::TriG
+        } else {
+            RdfFormat::Turtle
+        }
+    } else {
+        match count_terms(line) {
+            Some(3) => RdfFormat::NTriples,
+            Some(4) => RdfFormat::NQuads,
+            _ if text.contains('{') => RdfFormat::TriG,
+            _ => RdfFormat::Turtle,
+        }
+    }))
+}
+
+/// Tells whether the text begins like RDF/JSON: an object with a subject (not a JSON-LD keyword)
+/// of predicates with arrays of objects.
+fn is_rdf_json(text: &str) -> bool {
+    let start = Regex::new(
+        r#"^\{\s*"[^"@][^"]*"\s*:\s*\{\s*"[^"]*"\s*:\s*\[\s*\{\s*"(type|value|lang|datatype)""#,
+    )
+    .expect("valid regex");
+    start.is_match(text)
+}
+
+/// Tells whether the text has an N3 rule (a formula implying, or implied by, another).
+fn is_n3_rule(text: &str) -> bool {
+    let rule = Regex::new(r"\}\s*(=>|<=)\s*\{").expect("valid regex");
+    rule.is_match(text)
+}
+
+/// Counts the terms of a line-based (N-Triples or N-Quads) statement.
+fn count_terms(line: &str) -> Option<usize> {
+    let mut rest = line.trim().strip_suffix('.')?.trim_end();
+    let mut count = 0;
+    while !rest.is_empty() {
+        let end = if rest.starts_with('<') {
+            rest.find('>')? + 1
+        } else if rest.starts_with("_:") {
+            rest.find(char::is_whitespace).unwrap_or(rest.len())
+        } else if let Some(literal) = rest.strip_prefix('"') {
+            let close = find_closing_quote(literal)? + 2;
+            close
+                + rest[close..]
+                    .find(char::is_whitespace)
+                    .unwrap_or(rest.len() - close)
+        } else {
+            return None;
+        };
+        count += 1;
+        rest = rest[end..].trim_start();
+    }
+    Some(count)
+}
+
+pub(crate) fn find_closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
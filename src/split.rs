@@ -0,0 +1,167 @@
+//! Partitioning of loaded data into descriptions of resources, for writing these to a file each.
+
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+use oxigraph::model::vocab::rdf;
+use oxigraph::model::{NamedNode, NamedOrBlankNode, Quad, Term, Triple};
+use oxigraph::store::Store;
+
+/// What to partition data by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitBy {
+    /// A part for each subject IRI, with the triples about it.
+    Subject,
+    /// A part for each class, with the triples about its instances.
+    Class,
+}
+
+impl SplitBy {
+    /// Looks up what to partition by, by name (`subject` or `class`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "subject" => Some(Self::Subject),
+            "class" => Some(Self::Class),
+            _ => None,
+        }
+    }
+}
+
+/// The parts of data, each named by a subject IRI or a class and holding the resources it
+/// describes. The triples of a part are read from the data when asked for.
+pub struct Partition {
+    store: Store,
+    parts: BTreeMap<NamedNode, Vec<NamedOrBlankNode>>,
+    left_out: usize,
+}
+
+impl Partition {
+    /// Gives the IRIs naming the parts, in order.
+    pub fn iris(&self) -> impl Iterator<Item = &NamedNode> {
+        self.parts.keys()
+    }
+
+    /// Gives the parts, in order, each with its triples.
+    pub fn parts(&self) -> impl Iterator<Item = Result<(&NamedNode, Vec<Triple>)>> {
+        self.parts
+            .iter()
+            .map(|(iri, resources)| Ok((iri, describe(&self.store, resources)?)))
+    }
+
+    /// Gives the number of triples in no part (as they are about neither a resource of one nor a
+    /// blank node referred to from it).
+    pub fn left_out(&self) -> usize {
+        self.left_out
+    }
+}
+
+/// Partitions the triples of the store (in any graph, counting each triple once) by subject IRI or
+/// by class (of the instances being IRIs, or blank nodes not used as objects), sorted by those.
+/// The triples about a resource include those about the blank nodes it refers to (recursively),
+/// which are thus written along with it. (Triples about other resources are left out.)
+pub(crate) fn partition(store: &Store, by: SplitBy) -> Result<Partition> {
+    let mut subjects = HashSet::new();
+    for quad in store.iter() {
+        subjects.insert(quad?.subject);
+    }
+
+    let mut parts: BTreeMap<NamedNode, HashSet<NamedOrBlankNode>> = BTreeMap::new();
+    match by {
+        SplitBy::Subject => {
+            for subject in &subjects {
+                if let NamedOrBlankNode::NamedNode(iri) = subject {
+                    parts
+                        .entry(iri.clone())
+                        .or_default()
+                        .insert(subject.clone());
+                }
+            }
+        }
+        SplitBy::Class => {
+            for quad in store.quads_for_pattern(None, Some(rdf::TYPE), None, None) {
+                let quad = quad?;
+                let Term::NamedNode(class) = quad.object else {
+                    continue;
+                };
+                if let NamedOrBlankNode::BlankNode(node) = &quad.subject {
+                    let is_object = store
+                        .quads_for_pattern(None, None, Some(node.as_ref().into()), None)
+                        .next()
+                        .is_some();
+                    if is_object {
+                        continue;
+                    }
+                }
+                parts.entry(class).or_default().insert(quad.subject);
+            }
+        }
+    }
+    let parts = parts
+        .into_iter()
+        .map(|(iri, resources)| {
+            let mut resources = resources.into_iter().collect::<Vec<_>>();
+            resources.sort_by_cached_key(|resource| resource.to_string());
+            (iri, resources)
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut described = HashSet::new();
+    for resources in parts.values() {
+        visit(store, resources, &mut described, |_| {})?;
+    }
+    let mut left_out = 0;
+    for subject in subjects
+        .iter()
+        .filter(|subject| !described.contains(*subject))
+    {
+        let mut about = HashSet::new();
+        for quad in store.quads_for_pattern(Some(subject.as_ref()), None, None, None) {
+            let quad = quad?;
+            about.insert((quad.predicate, quad.object));
+        }
+        left_out += about.len();
+    }
+
+    Ok(Partition {
+        store: store.clone(),
+        parts,
+        left_out,
+    })
+}
+
+/// Gives the triples about the resources and the blank nodes they refer to (recursively).
+fn describe(store: &Store, resources: &[NamedOrBlankNode]) -> Result<Vec<Triple>> {
+    let mut seen = HashSet::new();
+    let mut triples = Vec::new();
+    visit(store, resources, &mut HashSet::new(), |quad| {
+        let triple = Triple::new(quad.subject, quad.predicate, quad.object);
+        if seen.insert(triple.clone()) {
+            triples.push(triple);
+        }
+    })?;
+    Ok(triples)
+}
+
+/// Visits the quads about the resources and the blank nodes they refer to (recursively), except
+/// those about resources already described (adding the others to these).
+fn visit(
+    store: &Store,
+    resources: &[NamedOrBlankNode],
+    described: &mut HashSet<NamedOrBlankNode>,
+    mut visitor: impl FnMut(Quad),
+) -> Result<()> {
+    let mut queue = resources.to_vec();
+    while let Some(resource) = queue.pop() {
+        if !described.insert(resource.clone()) {
+            continue;
+        }
+        for quad in store.quads_for_pattern(Some(resource.as_ref()), None, None, None) {
+            let quad = quad?;
+            if let Term::BlankNode(node) = &quad.object {
+                queue.push(node.clone().into());
+            }
+            visitor(quad);
+        }
+    }
+    Ok(())
+}
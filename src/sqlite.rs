@@ -0,0 +1,1086 @@
+//! Reading of SQLite database files (the tables with rowids, in UTF-8 databases), and their
+//! mapping to RDF by the W3C Direct Mapping, as well as writing of query results as a database.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use anyhow::{bail, Context, Result};
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term};
+use oxigraph::sparql::Variable;
+
+use crate::r2rml::{self, Row};
+
+const MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// A database, read in full.
+pub(crate) struct Database {
+    data: Vec<u8>,
+    page_size: usize,
+    usable_size: usize,
+    pub(crate) tables: Vec<Table>,
+}
+
+/// A table, as declared in its `CREATE TABLE` statement.
+pub(crate) struct Table {
+    pub(crate) name: String,
+    pub(crate) columns: Vec<Column>,
+    pub(crate) primary_key: Vec<String>,
+    pub(crate) foreign_keys: Vec<ForeignKey>,
+    root_page: u32,
+    /// The column which is the rowid (declared as `INTEGER PRIMARY KEY`), if any.
+    rowid_column: Option<usize>,
+}
+
+pub(crate) struct Column {
+    pub(crate) name: String,
+    pub(crate) declared_type: String,
+}
+
+pub(crate) struct ForeignKey {
+    pub(crate) columns: Vec<String>,
+    pub(crate) table: String,
+    /// The referenced columns (if none are given, those of the primary key).
+    pub(crate) referenced: Vec<String>,
+}
+
+enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Database {
+    /// Tells whether data is (the beginning of) a SQLite database file.
+    pub(crate) fn is_database(content: &[u8]) -> bool {
+        content.starts_with(MAGIC)
+    }
+
+    pub(crate) fn read<R: Read>(mut reader: R) -> Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if !Self::is_database(&data) || data.len() < 100 {
+            bail!("Not a SQLite database");
+        }
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            size => usize::from(size),
+        };
+        if !page_size.is_power_of_two() || page_size < 512 {
+            bail!("Invalid page size in SQLite database: {page_size}");
+        }
+        let usable_size = page_size
+            .checked_sub(usize::from(data[20]))
+            .filter(|size| *size >= 480)
+            .context("Invalid reserved space in SQLite database")?;
+        if u32::from_be_bytes([data[56], data[57], data[58], data[59]]) > 1 {
+            bail!("Only SQLite databases in UTF-8 are supported");
+        }
+        let mut database = Self {
+            data,
+            page_size,
+            usable_size,
+            tables: Vec::new(),
+        };
+
+        // The schema table has the columns type, name, tbl_name, rootpage and sql:
+        for (_, record) in database.records(1)? {
+            let [Value::Text(kind), Value::Text(name), _, Value::Integer(root_page), Value::Text(sql)] =
+                &record[..]
+            else {
+                continue;
+            };
+            if kind != "table" || name.starts_with("sqlite_") {
+                continue;
+            }
+            let table = parse_create_table(sql, *root_page as u32)
+                .with_context(|| format!("Unable to read the schema of table {name}"))?;
+            if let Some(table) = table {
+                database.tables.push(table);
+            }
+        }
+        Ok(database)
+    }
+
+    /// Finds a table by name (ignoring case, as SQL does).
+    pub(crate) fn table(&self, name: &str) -> Option<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Reads the rows of a table, with the values of each column as natural RDF literals (by
+    /// the declared types and the types of the values stored).
+    pub(crate) fn rows(&self, table: &Table) -> Result<Vec<Vec<Option<Literal>>>> {
+        let mut rows = Vec::new();
+        for (rowid, mut record) in self.records(table.root_page)? {
+            if let Some(i) = table.rowid_column {
+                if i < record.len() {
+                    record[i] = Value::Integer(rowid);
+                }
+            }
+            record.resize_with(table.columns.len(), || Value::Null);
+            let row = record
+                .into_iter()
+                .zip(&table.columns)
+                .map(|(value, column)| natural_literal(value, &column.declared_type))
+                .collect();
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// Reads the rows of a table by name, as rows of named values (for R2RML).
+    pub(crate) fn named_rows(&self, name: &str) -> Result<Vec<Row>> {
+        let Some(table) = self.table(name) else {
+            bail!("No table in the database named: {name}");
+        };
+        let rows = self.rows(table)?.into_iter().map(|values| {
+            let values = table.columns.iter().zip(values);
+            values
+                .filter_map(|(column, value)| Some((column.name.clone(), vec![value?])))
+                .collect()
+        });
+        Ok(rows.collect())
+    }
+
+    fn page(&self, number: u32) -> Result<&[u8]> {
+        let start = (number as usize)
+            .checked_sub(1)
+            .map(|i| i * self.page_size)
+            .filter(|start| start + self.page_size <= self.data.len())
+            .with_context(|| format!("Invalid page number in SQLite database: {number}"))?;
+        Ok(&self.data[start..start + self.page_size])
+    }
+
+    /// Reads the records of a table B-tree (by rowid).
+    fn records(&self, root_page: u32) -> Result<Vec<(i64, Vec<Value>)>> {
+        let mut records = Vec::new();
+        let mut pages = vec![root_page];
+        let mut visited = 0;
+        while let Some(number) = pages.pop() {
+            visited += 1;
+            if visited > self.data.len() / self.page_size + 1 {
+                bail!("Cyclic pages in SQLite database");
+            }
+            let page = self.page(number)?;
+            let read = |at: usize, len: usize| {
+                page.get(at..at + len)
+                    .context("Invalid cell in SQLite database")
+            };
+            // (The first page begins with the database header.)
+            let header = if number == 1 { 100 } else { 0 };
+            let kind = page[header];
+            let cells = read(header + 3, 2)?;
+            let cells = usize::from(u16::from_be_bytes([cells[0], cells[1]]));
+            let pointers = header + if kind == 0x05 { 12 } else { 8 };
+            let cell_offsets = (0..cells)
+                .map(|i| {
+                    let offset = read(pointers + 2 * i, 2)?;
+                    Ok(usize::from(u16::from_be_bytes([offset[0], offset[1]])))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            match kind {
+                // An interior page (with children reached in order, hence pushed in reverse):
+                0x05 => {
+                    let right = read(header + 8, 4)?;
+                    pages.push(u32::from_be_bytes([right[0], right[1], right[2], right[3]]));
+                    let mut children = cell_offsets
+                        .into_iter()
+                        .map(|at| {
+                            let child = read(at, 4)?;
+                            Ok(u32::from_be_bytes([child[0], child[1], child[2], child[3]]))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    children.reverse();
+                    pages.extend(children);
+                }
+                0x0d => {
+                    for at in cell_offsets {
+                        let cell = page.get(at..).context("Invalid cell in SQLite database")?;
+                        let (size, len) = varint(cell);
+                        let (rowid, rowid_len) = varint(&cell[len..]);
+                        let payload = self.payload(page, at + len + rowid_len, size as usize)?;
+                        records.push((rowid as i64, record(&payload)?));
+                    }
+                }
+                _ => bail!("Unexpected page in table of SQLite database (of type {kind})"),
+            }
+        }
+        Ok(records)
+    }
+
+    /// Reads the payload of a cell, along with any part of it spilled to overflow pages.
+    fn payload(&self, page: &[u8], at: usize, size: usize) -> Result<Vec<u8>> {
+        let usable = self.usable_size;
+        if size > self.data.len() {
+            bail!("Invalid cell in SQLite database");
+        }
+        let max_local = usable - 35;
+        let local = if size <= max_local {
+            size
+        } else {
+            let min_local = (usable - 12) * 32 / 255 - 23;
+            let local = min_local + (size - min_local) % (usable - 4);
+            if local <= max_local {
+                local
+            } else {
+                min_local
+            }
+        };
+        let mut payload = page
+            .get(at..at + local)
+            .context("Invalid cell in SQLite database")?
+            .to_vec();
+        let mut next = match local < size {
+            true => {
+                let link = page
+                    .get(at + local..at + local + 4)
+                    .context("Invalid cell in SQLite database")?;
+                u32::from_be_bytes([link[0], link[1], link[2], link[3]])
+            }
+            false => 0,
+        };
+        while next != 0 && payload.len() < size {
+            let overflow = self.page(next)?;
+            next = u32::from_be_bytes([overflow[0], overflow[1], overflow[2], overflow[3]]);
+            let len = (size - payload.len()).min(usable - 4);
+            payload.extend_from_slice(&overflow[4..4 + len]);
+        }
+        Ok(payload)
+    }
+}
+
+/// Decodes a variable-length integer, giving it along with its length.
+fn varint(data: &[u8]) -> (u64, usize) {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().take(9).enumerate() {
+        if i == 8 {
+            return ((value << 8) | u64::from(*byte), 9);
+        }
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return (value, i + 1);
+        }
+    }
+    (value, data.len().min(9))
+}
+
+/// Decodes a record (of a header of serial types, followed by the values).
+fn record(payload: &[u8]) -> Result<Vec<Value>> {
+    let (header_size, mut at) = varint(payload);
+    let header_size = header_size as usize;
+    let mut types = Vec::new();
+    while at < header_size.min(payload.len()) {
+        let (serial_type, len) = varint(&payload[at..]);
+        types.push(serial_type);
+        at += len;
+    }
+    let mut body = payload.get(header_size..).context("Invalid record")?;
+    let mut values = Vec::with_capacity(types.len());
+    for serial_type in types {
+        let len = match serial_type {
+            0 | 8 | 9 => 0,
+            1..=4 => serial_type as usize,
+            5 => 6,
+            6 | 7 => 8,
+            n if n >= 12 => (n as usize - 12) / 2,
+            _ => bail!("Invalid serial type in record: {serial_type}"),
+        };
+        let bytes = body.get(..len).context("Truncated record")?;
+        body = &body[len..];
+        let integer = || {
+            let mut value = if bytes[0] & 0x80 != 0 { -1i64 } else { 0 };
+            for byte in bytes {
+                value = (value << 8) | i64::from(*byte);
+            }
+            value
+        };
+        values.push(match serial_type {
+            0 => Value::Null,
+            1..=6 => Value::Integer(integer()),
+            7 => Value::Real(f64::from_be_bytes(bytes.try_into()?)),
+            8 => Value::Integer(0),
+            9 => Value::Integer(1),
+            n if n % 2 == 0 => Value::Blob(bytes.to_vec()),
+            _ => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+        });
+    }
+    Ok(values)
+}
+
+/// Gives the natural RDF literal of a value (as in the Direct Mapping and R2RML), using the
+/// declared type of the column for dates, booleans and decimals.
+fn natural_literal(value: Value, declared_type: &str) -> Option<Literal> {
+    let declared_type = declared_type.to_ascii_uppercase();
+    let is = |name| declared_type.contains(name);
+    Some(match value {
+        Value::Null => return None,
+        Value::Integer(value) if is("BOOL") => Literal::from(value != 0),
+        Value::Integer(value) if is("DEC") || is("NUMERIC") => {
+            Literal::new_typed_literal(value.to_string(), xsd::DECIMAL)
+        }
+        Value::Integer(value) => Literal::from(value),
+        Value::Real(value) if is("DEC") || is("NUMERIC") => {
+            Literal::new_typed_literal(value.to_string(), xsd::DECIMAL)
+        }
+        Value::Real(value) => Literal::from(value),
+        Value::Text(value) if is("DATETIME") || is("TIMESTAMP") => {
+            Literal::new_typed_literal(value.replacen(' ', "T", 1), xsd::DATE_TIME)
+        }
+        Value::Text(value) if is("DATE") => Literal::new_typed_literal(value, xsd::DATE),
+        Value::Text(value) if is("TIME") => Literal::new_typed_literal(value, xsd::TIME),
+        Value::Text(value) => Literal::new_simple_literal(value),
+        Value::Blob(bytes) => {
+            let hex: String = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            Literal::new_typed_literal(hex, xsd::HEX_BINARY)
+        }
+    })
+}
+
+/// Maps the tables of the database to RDF by the W3C Direct Mapping, with IRIs made from the
+/// base IRI: `<TABLE/KEY=VALUE>` for rows with a primary key (or else blank nodes), of type
+/// `<TABLE>`, with `<TABLE#COLUMN>` for values and `<TABLE#ref-COLUMN>` for foreign keys.
+pub(crate) fn direct_mapping(
+    database: &Database,
+    base_iri: &str,
+    graph_name: &GraphName,
+) -> Result<Vec<Quad>> {
+    let iri = |path: String| NamedNode::new(format!("{base_iri}{path}"));
+    let mut tables = Vec::new();
+    for table in &database.tables {
+        let rows = database.rows(table)?;
+        let name = r2rml::iri_safe(&table.name);
+        let nodes = rows
+            .iter()
+            .map(|row| {
+                let key = key_values(table, &table.primary_key, row);
+                Ok(match key.filter(|key| !key.is_empty()) {
+                    Some(key) => {
+                        let parts: Vec<String> = table
+                            .primary_key
+                            .iter()
+                            .zip(key)
+                            .map(|(column, value)| {
+                                let value = r2rml::iri_safe(value.value());
+                                format!("{}={value}", r2rml::iri_safe(column))
+                            })
+                            .collect();
+                        NamedOrBlankNode::from(iri(format!("{name}/{}", parts.join(";")))?)
+                    }
+                    None => BlankNode::default().into(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        tables.push((table, rows, nodes));
+    }
+
+    let mut quads = Vec::new();
+    for (table, rows, nodes) in &tables {
+        let name = r2rml::iri_safe(&table.name);
+        let class = iri(name.clone())?;
+        let properties = table
+            .columns
+            .iter()
+            .map(|column| iri(format!("{name}#{}", r2rml::iri_safe(&column.name))))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (row, node) in rows.iter().zip(nodes) {
+            quads.push(Quad::new(
+                node.clone(),
+                rdf::TYPE,
+                class.clone(),
+                graph_name.clone(),
+            ));
+            for (value, property) in row.iter().zip(&properties) {
+                if let Some(value) = value {
+                    quads.push(Quad::new(
+                        node.clone(),
+                        property.clone(),
+                        value.clone(),
+                        graph_name.clone(),
+                    ));
+                }
+            }
+        }
+
+        for foreign_key in &table.foreign_keys {
+            let Some((referenced, referenced_rows, referenced_nodes)) = tables
+                .iter()
+                .find(|(other, _, _)| other.name.eq_ignore_ascii_case(&foreign_key.table))
+            else {
+                continue;
+            };
+            let referenced_columns = match foreign_key.referenced.is_empty() {
+                true => &referenced.primary_key,
+                false => &foreign_key.referenced,
+            };
+            // (The rows are matched by the lexical forms of the key values.)
+            let index: HashMap<Vec<&str>, &NamedOrBlankNode> = referenced_rows
+                .iter()
+                .zip(referenced_nodes)
+                .filter_map(|(row, node)| {
+                    let key = key_values(referenced, referenced_columns, row)?;
+                    Some((key.iter().map(|value| value.value()).collect(), node))
+                })
+                .collect();
+            let columns: Vec<String> = foreign_key
+                .columns
+                .iter()
+                .map(|column| r2rml::iri_safe(column))
+                .collect();
+            let property = iri(format!("{name}#ref-{}", columns.join(";")))?;
+            for (row, node) in rows.iter().zip(nodes) {
+                let Some(key) = key_values(table, &foreign_key.columns, row) else {
+                    continue;
+                };
+                let key: Vec<&str> = key.iter().map(|value| value.value()).collect();
+                if let Some(target) = index.get(&key) {
+                    quads.push(Quad::new(
+                        node.clone(),
+                        property.clone(),
+                        (*target).clone(),
+                        graph_name.clone(),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(quads)
+}
+
+/// Gives the values of the key columns of a row, unless any of them is null.
+fn key_values<'a>(
+    table: &Table,
+    columns: &[String],
+    row: &'a [Option<Literal>],
+) -> Option<Vec<&'a Literal>> {
+    columns
+        .iter()
+        .map(|name| {
+            let i = table
+                .columns
+                .iter()
+                .position(|column| column.name.eq_ignore_ascii_case(name))?;
+            row.get(i)?.as_ref()
+        })
+        .collect()
+}
+
+/// Parses a `CREATE TABLE` statement (giving none for tables without rowid, or defined by a
+/// query, which are not supported).
+fn parse_create_table(sql: &str, root_page: u32) -> Result<Option<Table>> {
+    let tokens = tokenize(sql)?;
+    let upper = |i: usize| tokens.get(i).map(|t| t.to_ascii_uppercase());
+    let Some(open) = tokens.iter().position(|token| token == "(") else {
+        return Ok(None);
+    };
+    let Some(name) = open.checked_sub(1).map(|i| unquote(&tokens[i])) else {
+        return Ok(None);
+    };
+    let close = match_paren(&tokens, open).context("Unclosed parenthesis")?;
+    if tokens[close + 1..]
+        .iter()
+        .any(|token| token.eq_ignore_ascii_case("ROWID"))
+    {
+        return Ok(None);
+    }
+
+    let mut table = Table {
+        name,
+        columns: Vec::new(),
+        primary_key: Vec::new(),
+        foreign_keys: Vec::new(),
+        root_page,
+        rowid_column: None,
+    };
+    for (start, end) in split_items(&tokens, open + 1, close) {
+        let item = &tokens[start..end];
+        let keyword = upper(start).unwrap_or_default();
+        let constraint_start = match keyword.as_str() {
+            // (A named table constraint:)
+            "CONSTRAINT" => 2,
+            "PRIMARY" | "FOREIGN" | "UNIQUE" | "CHECK" => 0,
+            _ => {
+                parse_column(item, &mut table);
+                continue;
+            }
+        };
+        let constraint = &item[constraint_start.min(item.len())..];
+        let names = |from: usize| -> Vec<String> {
+            match constraint.get(from).map(String::as_str) {
+                Some("(") => match_paren(constraint, from)
+                    .map(|to| {
+                        constraint[from + 1..to]
+                            .iter()
+                            .filter(|t| *t != ",")
+                            .map(|t| unquote(t))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            }
+        };
+        let first = constraint.first().map(|t| t.to_ascii_uppercase());
+        match first.as_deref() {
+            Some("PRIMARY") => table.primary_key = strip_order(names(2)),
+            Some("FOREIGN") => {
+                let columns = names(2);
+                let references = constraint
+                    .iter()
+                    .position(|t| t.eq_ignore_ascii_case("REFERENCES"));
+                if let Some(at) = references {
+                    table.foreign_keys.push(ForeignKey {
+                        columns,
+                        table: constraint
+                            .get(at + 1)
+                            .map(|t| unquote(t))
+                            .unwrap_or_default(),
+                        referenced: names(at + 2),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    // (Only a single column of type INTEGER as primary key is the rowid.)
+    if table.primary_key.len() == 1 {
+        let key = &table.primary_key[0];
+        table.rowid_column = table.columns.iter().position(|column| {
+            column.name.eq_ignore_ascii_case(key)
+                && column.declared_type.eq_ignore_ascii_case("INTEGER")
+        });
+    }
+    Ok(Some(table))
+}
+
+/// Parses a column definition, adding the column (and any key it is declared as) to the table.
+fn parse_column(item: &[String], table: &mut Table) {
+    const CONSTRAINTS: &[&str] = &[
+        "CONSTRAINT",
+        "PRIMARY",
+        "NOT",
+        "NULL",
+        "UNIQUE",
+        "CHECK",
+        "DEFAULT",
+        "COLLATE",
+        "REFERENCES",
+        "GENERATED",
+        "AS",
+    ];
+    let name = item.first().map(|t| unquote(t)).unwrap_or_default();
+    let type_end = item
+        .iter()
+        .skip(1)
+        .position(|t| CONSTRAINTS.contains(&t.to_ascii_uppercase().as_str()))
+        .map_or(item.len(), |i| i + 1);
+    let declared_type = item[1.min(item.len())..type_end].join(" ");
+    let constraints = &item[type_end..];
+    for (i, token) in constraints.iter().enumerate() {
+        match token.to_ascii_uppercase().as_str() {
+            "PRIMARY" => {
+                table.primary_key = vec![name.clone()];
+            }
+            "REFERENCES" => {
+                let referenced = match constraints.get(i + 2).map(String::as_str) {
+                    Some("(") => constraints.get(i + 3).map(|t| vec![unquote(t)]),
+                    _ => None,
+                };
+                table.foreign_keys.push(ForeignKey {
+                    columns: vec![name.clone()],
+                    table: constraints
+                        .get(i + 1)
+                        .map(|t| unquote(t))
+                        .unwrap_or_default(),
+                    referenced: referenced.unwrap_or_default(),
+                });
+            }
+            _ => {}
+        }
+    }
+    table.columns.push(Column {
+        name,
+        declared_type,
+    });
+}
+
+/// Leaves out any sort orders given for the columns of a key.
+fn strip_order(names: Vec<String>) -> Vec<String> {
+    names
+        .into_iter()
+        .filter(|name| !["ASC", "DESC"].contains(&name.to_ascii_uppercase().as_str()))
+        .collect()
+}
+
+/// Splits SQL into tokens: words, quoted names and strings (kept quoted), numbers and single
+/// punctuation characters (skipping whitespace and comments).
+fn tokenize(sql: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '-' if sql[start..].starts_with("--") => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            '/' if sql[start..].starts_with("/*") => {
+                let end = sql[start + 2..]
+                    .find("*/")
+                    .map_or(sql.len(), |i| start + 4 + i);
+                while chars.next_if(|(i, _)| *i < end).is_some() {}
+            }
+            '"' | '`' | '[' | '\'' => {
+                let close = if c == '[' { ']' } else { c };
+                let mut end = None;
+                while let Some((i, next)) = chars.next() {
+                    if next == close {
+                        // (A doubled quote is an escaped quote.)
+                        if close != ']' && chars.next_if(|(_, c)| *c == close).is_some() {
+                            continue;
+                        }
+                        end = Some(i);
+                        break;
+                    }
+                }
+                let end = end.with_context(|| format!("Unclosed quote in SQL: {sql}"))?;
+                tokens.push(sql[start..=end].to_owned());
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '$' => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars
+                    .next_if(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '$' || *c == '.')
+                {
+                    end = i + c.len_utf8();
+                }
+                tokens.push(sql[start..end].to_owned());
+            }
+            c => tokens.push(c.to_string()),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Removes the quotes of a quoted name (and any schema name before it).
+fn unquote(token: &str) -> String {
+    let unquoted = match token.chars().next() {
+        Some('"') => token[1..token.len() - 1].replace("\"\"", "\""),
+        Some('`') => token[1..token.len() - 1].replace("``", "`"),
+        Some('[') => token[1..token.len() - 1].to_owned(),
+        Some('\'') => token[1..token.len() - 1].replace("''", "'"),
+        _ => return token.rsplit('.').next().unwrap_or(token).to_owned(),
+    };
+    unquoted
+}
+
+fn match_paren(tokens: &[String], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.as_str() {
+            "(" => depth += 1,
+            ")" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the tokens between the parentheses by the commas at the top level.
+fn split_items(tokens: &[String], start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut item_start = start;
+    for (i, token) in tokens.iter().enumerate().take(end).skip(start) {
+        match token.as_str() {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            "," if depth == 0 => {
+                items.push((item_start, i));
+                item_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if item_start < end {
+        items.push((item_start, end));
+    }
+    items
+}
+
+/// The page size of written databases.
+const PAGE_SIZE: usize = 4096;
+
+/// Writes solutions (as rows of values, in the order of the variables) as a SQLite database of
+/// one table, `results`, with a column per variable. Columns of numbers, booleans, dates or
+/// date-times only are declared (and stored) as such, and others as text (with IRIs and literals
+/// by their plain values), with unbound values as null.
+pub(crate) fn write<W: Write>(
+    variables: &[Variable],
+    solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+    mut writer: W,
+) -> Result<()> {
+    if variables.is_empty() {
+        bail!("No variables to make columns of a SQLite table from");
+    }
+    let rows = solutions.collect::<Result<Vec<_>>>()?;
+    let types: Vec<_> = (0..variables.len())
+        .map(|i| column_type(rows.iter().filter_map(|row| row[i].as_ref())))
+        .collect();
+    let columns: Vec<_> = variables
+        .iter()
+        .zip(&types)
+        .map(|(variable, column_type)| {
+            let name = variable.as_str().replace('"', "\"\"");
+            format!("\"{name}\" {}", column_type.name())
+        })
+        .collect();
+    let sql = format!("CREATE TABLE results ({})", columns.join(", "));
+
+    // Page 1 has the schema (after the file header), and page 2 the root of the table:
+    let mut pages = vec![Vec::new(), Vec::new()];
+    let mut leaves = Vec::new();
+    let mut cells = Vec::new();
+    let mut last_rowid = 0;
+    for (rowid, row) in (1..).zip(&rows) {
+        let values = row
+            .iter()
+            .zip(&types)
+            .map(|(term, column_type)| term.as_ref().map(|term| column_type.value(term)));
+        let cell = leaf_cell(rowid, &encode_record(values), &mut pages);
+        if !fits(8, 0, cells.iter().chain([&cell])) {
+            leaves.push((btree_page(0x0d, 0, &cells, None), last_rowid));
+            cells.clear();
+        }
+        cells.push(cell);
+        last_rowid = rowid;
+    }
+    leaves.push((btree_page(0x0d, 0, &cells, None), last_rowid));
+
+    // (Interior pages are added level by level, up to one which fits in the root page.)
+    let mut level = leaves;
+    while level.len() > 1 {
+        let mut children = Vec::new();
+        for (page, last_rowid) in level {
+            pages.push(page);
+            children.push((pages.len() as u32, last_rowid));
+        }
+        level = Vec::new();
+        let mut start = 0;
+        for end in 1..=children.len() {
+            let group = &children[start..end];
+            let more = end < children.len();
+            if more && fits(12, 0, interior_cells(&children[start..=end]).iter()) {
+                continue;
+            }
+            let (right, _) = *group.last().expect("a child page");
+            let cells = interior_cells(group);
+            level.push((
+                btree_page(0x05, 0, &cells, Some(right)),
+                group[group.len() - 1].1,
+            ));
+            start = end;
+        }
+    }
+    pages[1] = level.pop().expect("a root page").0;
+
+    let schema = [
+        Some(Value::Text("table".into())),
+        Some(Value::Text("results".into())),
+        Some(Value::Text("results".into())),
+        Some(Value::Integer(2)),
+        Some(Value::Text(sql)),
+    ];
+    let cell = leaf_cell(1, &encode_record(schema.into_iter()), &mut pages);
+    if !fits(8, 100, [&cell].into_iter()) {
+        bail!("Too many variables to write as a SQLite table");
+    }
+    pages[0] = btree_page(0x0d, 100, &[cell], None);
+    let header = file_header(pages.len() as u32);
+    pages[0][..100].copy_from_slice(&header);
+    for page in pages {
+        writer.write_all(&page)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// The type of a column, given by the values in it.
+#[derive(Clone, Copy, PartialEq)]
+enum ColumnType {
+    Integer,
+    Real,
+    Boolean,
+    Date,
+    DateTime,
+    Text,
+}
+
+impl ColumnType {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Integer => "INTEGER",
+            Self::Real => "REAL",
+            Self::Boolean => "BOOLEAN",
+            Self::Date => "DATE",
+            Self::DateTime => "DATETIME",
+            Self::Text => "TEXT",
+        }
+    }
+
+    /// Gives the value to store for a term in a column of this type.
+    fn value(self, term: &Term) -> Value {
+        match (self, typed(term)) {
+            (Self::Text, _) => Value::Text(plain_value(term)),
+            (Self::Real, (Value::Integer(number), _)) => Value::Real(number as f64),
+            (_, (value, _)) => value,
+        }
+    }
+}
+
+/// Gives the plain value of a term (an IRI, a blank node label or the lexical form of a literal).
+fn plain_value(term: &Term) -> String {
+    match term {
+        Term::NamedNode(node) => node.as_str().to_owned(),
+        Term::BlankNode(node) => node.to_string(),
+        Term::Literal(literal) => literal.value().to_owned(),
+        Term::Triple(triple) => format!("<<( {triple} )>>"),
+    }
+}
+
+/// Gives the value of a term (with the type of column it would be in by itself).
+fn typed(term: &Term) -> (Value, ColumnType) {
+    let Term::Literal(literal) = term else {
+        return (Value::Text(plain_value(term)), ColumnType::Text);
+    };
+    let value = literal.value();
+    let datatype = literal.datatype();
+    let integers = [
+        xsd::INTEGER,
+        xsd::INT,
+        xsd::LONG,
+        xsd::SHORT,
+        xsd::BYTE,
+        xsd::NON_NEGATIVE_INTEGER,
+        xsd::POSITIVE_INTEGER,
+        xsd::NON_POSITIVE_INTEGER,
+        xsd::NEGATIVE_INTEGER,
+        xsd::UNSIGNED_INT,
+        xsd::UNSIGNED_LONG,
+        xsd::UNSIGNED_SHORT,
+        xsd::UNSIGNED_BYTE,
+    ];
+    if integers.contains(&datatype) {
+        if let Ok(number) = value.trim_start_matches('+').parse() {
+            return (Value::Integer(number), ColumnType::Integer);
+        }
+    }
+    if [xsd::DECIMAL, xsd::DOUBLE, xsd::FLOAT].contains(&datatype) {
+        if let Ok(number) = value.parse::<f64>() {
+            if number.is_finite() {
+                return (Value::Real(number), ColumnType::Real);
+            }
+        }
+    } else if datatype == xsd::BOOLEAN {
+        match value {
+            "true" | "1" => return (Value::Integer(1), ColumnType::Boolean),
+            "false" | "0" => return (Value::Integer(0), ColumnType::Boolean),
+            _ => {}
+        }
+    } else if datatype == xsd::DATE {
+        return (Value::Text(value.into()), ColumnType::Date);
+    } else if datatype == xsd::DATE_TIME {
+        return (Value::Text(value.into()), ColumnType::DateTime);
+    }
+    (Value::Text(value.into()), ColumnType::Text)
+}
+
+/// Gives the type of a column of terms: that of their values if they have the same, real for
+/// integers along with other numbers, and otherwise text.
+fn column_type<'a>(terms: impl Iterator<Item = &'a Term>) -> ColumnType {
+    let mut column_type = None;
+    for term in terms {
+        column_type = Some(match (column_type, typed(term).1) {
+            (None, value_type) => value_type,
+            (Some(known), value_type) if known == value_type => known,
+            (
+                Some(ColumnType::Integer | ColumnType::Real),
+                ColumnType::Integer | ColumnType::Real,
+            ) => ColumnType::Real,
+            _ => return ColumnType::Text,
+        });
+    }
+    column_type.unwrap_or(ColumnType::Text)
+}
+
+/// Encodes a variable-length integer.
+fn encode_varint(value: u64) -> Vec<u8> {
+    if value >> 56 != 0 {
+        let mut bytes: Vec<u8> = (0..8)
+            .map(|i| ((value >> (57 - i * 7)) & 0x7f) as u8 | 0x80)
+            .collect();
+        bytes.push(value as u8);
+        return bytes;
+    }
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest != 0 {
+        bytes.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
+
+/// Encodes a record (of a header of serial types, followed by the values).
+fn encode_record(values: impl Iterator<Item = Option<Value>>) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for value in values {
+        let serial_type = match value.unwrap_or(Value::Null) {
+            Value::Null => 0,
+            Value::Integer(0) => 8,
+            Value::Integer(1) => 9,
+            Value::Integer(number) => {
+                let (serial_type, len) = match number {
+                    -0x80..=0x7f => (1, 1),
+                    -0x8000..=0x7fff => (2, 2),
+                    -0x80_0000..=0x7f_ffff => (3, 3),
+                    -0x8000_0000..=0x7fff_ffff => (4, 4),
+                    -0x8000_0000_0000..=0x7fff_ffff_ffff => (5, 6),
+                    _ => (6, 8),
+                };
+                body.extend_from_slice(&number.to_be_bytes()[8 - len..]);
+                serial_type
+            }
+            Value::Real(number) => {
+                body.extend_from_slice(&number.to_be_bytes());
+                7
+            }
+            Value::Text(text) => {
+                body.extend_from_slice(text.as_bytes());
+                13 + 2 * text.len() as u64
+            }
+            Value::Blob(bytes) => {
+                body.extend_from_slice(&bytes);
+                12 + 2 * bytes.len() as u64
+            }
+        };
+        types.extend(encode_varint(serial_type));
+    }
+    // (The size of the header includes the size itself.)
+    let mut header_size = types.len() + 1;
+    while encode_varint(header_size as u64).len() + types.len() != header_size {
+        header_size += 1;
+    }
+    let mut record = encode_varint(header_size as u64);
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+/// Makes the cell of a row in a table leaf page, adding overflow pages for any part of the record
+/// which does not fit in the cell.
+fn leaf_cell(rowid: i64, record: &[u8], pages: &mut Vec<Vec<u8>>) -> Vec<u8> {
+    let size = record.len();
+    let max_local = PAGE_SIZE - 35;
+    let local = if size <= max_local {
+        size
+    } else {
+        let min_local = (PAGE_SIZE - 12) * 32 / 255 - 23;
+        let local = min_local + (size - min_local) % (PAGE_SIZE - 4);
+        if local <= max_local {
+            local
+        } else {
+            min_local
+        }
+    };
+    let mut cell = encode_varint(size as u64);
+    cell.extend(encode_varint(rowid as u64));
+    cell.extend_from_slice(&record[..local]);
+    if local < size {
+        cell.extend_from_slice(&(pages.len() as u32 + 1).to_be_bytes());
+        let chunks: Vec<_> = record[local..].chunks(PAGE_SIZE - 4).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let next = match i + 1 < chunks.len() {
+                true => pages.len() as u32 + 2,
+                false => 0,
+            };
+            let mut page = next.to_be_bytes().to_vec();
+            page.extend_from_slice(chunk);
+            page.resize(PAGE_SIZE, 0);
+            pages.push(page);
+        }
+    }
+    cell
+}
+
+/// Makes the cells of an interior table page for child pages (each with its last rowid), except
+/// for the last one (which is the right-most pointer).
+fn interior_cells(children: &[(u32, i64)]) -> Vec<Vec<u8>> {
+    let (_, left) = children.split_last().expect("a child page");
+    left.iter()
+        .map(|(page, last_rowid)| {
+            let mut cell = page.to_be_bytes().to_vec();
+            cell.extend(encode_varint(*last_rowid as u64));
+            cell
+        })
+        .collect()
+}
+
+/// Tells whether cells fit in a page (after any file header, and the page header).
+fn fits<'a>(header_size: usize, offset: usize, cells: impl Iterator<Item = &'a Vec<u8>>) -> bool {
+    let size: usize = cells.map(|cell| cell.len() + 2).sum();
+    offset + header_size + size <= PAGE_SIZE
+}
+
+/// Makes a B-tree page of cells (after room for the file header on page 1), with the cell
+/// contents at the end of the page.
+fn btree_page(kind: u8, offset: usize, cells: &[Vec<u8>], right: Option<u32>) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    let mut content_start = PAGE_SIZE;
+    let mut pointers = Vec::new();
+    for cell in cells {
+        content_start -= cell.len();
+        page[content_start..content_start + cell.len()].copy_from_slice(cell);
+        pointers.extend_from_slice(&(content_start as u16).to_be_bytes());
+    }
+    page[offset] = kind;
+    page[offset + 3..offset + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    page[offset + 5..offset + 7].copy_from_slice(&(content_start as u16).to_be_bytes());
+    let mut at = offset + 8;
+    if let Some(right) = right {
+        page[at..at + 4].copy_from_slice(&right.to_be_bytes());
+        at += 4;
+    }
+    page[at..at + pointers.len()].copy_from_slice(&pointers);
+    page
+}
+
+/// Makes the file header for a database of the given number of pages.
+fn file_header(page_count: u32) -> [u8; 100] {
+    let mut header = [0; 100];
+    header[..16].copy_from_slice(MAGIC);
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    // File format versions, reserved space, and the payload fractions:
+    header[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+    for (at, value) in [
+        // The change counter, page count, schema cookie, schema format and text encoding:
+        (24, 1),
+        (28, page_count),
+        (40, 1),
+        (44, 4),
+        (56, 1),
+        // The change counter the page count is valid for, and the SQLite version number:
+        (92, 1),
+        (96, 3_046_000),
+    ] {
+        header[at..at + 4].copy_from_slice(&u32::to_be_bytes(value));
+    }
+    header
+}
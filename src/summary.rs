@@ -0,0 +1,81 @@
+//! A summary of loaded data for reading: counts of quads per graph, instances per class, usages
+//! per predicate, and literals per datatype and language.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::Result;
+use oxigraph::model::vocab::rdf;
+use oxigraph::model::{GraphName, Quad, Term};
+use oxigraph::store::StorageError;
+
+use crate::pretty::Terms;
+
+/// Writes the summary of the quads, with a section for each kind of count (sorted by count,
+/// largest first), using the terms to format IRIs (compacted by prefixes).
+pub(crate) fn write<W: Write>(
+    quads: impl IntoIterator<Item = Result<Quad, StorageError>>,
+    terms: &Terms,
+    mut writer: W,
+) -> Result<()> {
+    let mut quad_count = 0;
+    let mut graphs: HashMap<String, usize> = HashMap::new();
+    let mut instances: HashMap<String, HashSet<_>> = HashMap::new();
+    let mut predicates: HashMap<String, usize> = HashMap::new();
+    let mut datatypes: HashMap<String, usize> = HashMap::new();
+    let mut languages: HashMap<String, usize> = HashMap::new();
+    for quad in quads {
+        let quad = quad?;
+        quad_count += 1;
+        let graph = match &quad.graph_name {
+            GraphName::NamedNode(node) => terms.iri(node),
+            _ => "(default graph)".to_owned(),
+        };
+        *graphs.entry(graph).or_default() += 1;
+        *predicates.entry(terms.iri(&quad.predicate)).or_default() += 1;
+        match &quad.object {
+            Term::NamedNode(class) if quad.predicate == rdf::TYPE => {
+                let subjects = instances.entry(terms.iri(class)).or_default();
+                subjects.insert(quad.subject);
+            }
+            Term::Literal(literal) => {
+                let datatype = terms.iri(&literal.datatype().into_owned());
+                *datatypes.entry(datatype).or_default() += 1;
+                if let Some(language) = literal.language() {
+                    *languages.entry(language.to_owned()).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    let classes = instances
+        .into_iter()
+        .map(|(class, subjects)| (class, subjects.len()))
+        .collect();
+
+    let in_graphs = match graphs.len() {
+        1 => "in 1 graph".to_owned(),
+        n => format!("in {n} graphs"),
+    };
+    writeln!(writer, "{quad_count} quads {in_graphs}")?;
+    for (title, counts) in [
+        ("Quads per graph", graphs),
+        ("Instances per class", classes),
+        ("Usages per predicate", predicates),
+        ("Literals per datatype", datatypes),
+        ("Literals per language", languages),
+    ] {
+        if counts.is_empty() {
+            continue;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        let width = counts[0].1.to_string().len();
+        writeln!(writer, "\n{title}:")?;
+        for (name, count) in counts {
+            writeln!(writer, "  {count:>width$}  {name}")?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Output of solutions as an aligned table, for reading on a terminal.
+
+use std::io::Write;
+
+use anyhow::Result;
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::Term;
+use oxigraph::sparql::Variable;
+
+use crate::highlight::{BLANK_NODE_COLOR, BOLD, DIM, IRI_COLOR, LITERAL_COLOR, RESET};
+use crate::pretty::Terms;
+use crate::results;
+
+/// The widest a column gets (unless the header is wider).
+const MAX_COLUMN_WIDTH: usize = 50;
+/// The narrowest a column gets when fitting the table to the terminal width.
+const MIN_COLUMN_WIDTH: usize = 8;
+const SEPARATOR: &str = "  ";
+
+/// Writes a header row of the variables, a rule, one row per solution (with values as in
+/// Turtle, except plain strings, truncated if too long), and a count of the rows. The table is
+/// fitted to the terminal width if it is set (in `COLUMNS`). IRIs, blank nodes and typed
+/// literals are set apart by color if `color` is set.
+pub(crate) fn write<W: Write>(
+    variables: &[Variable],
+    solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+    terms: &Terms,
+    color: bool,
+    mut writer: W,
+) -> Result<()> {
+    let mut rows = Vec::new();
+    for values in solutions {
+        let cells: Vec<_> = values?
+            .iter()
+            .map(|value| value.as_ref().map(|term| cell(term, terms)))
+            .collect();
+        rows.push(cells);
+    }
+
+    let names: Vec<&str> = variables.iter().map(Variable::as_str).collect();
+    let mut widths: Vec<usize> = names.iter().map(|name| width(name)).collect();
+    for cells in &rows {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            if let Some((text, _)) = cell {
+                *width = (*width).max(self::width(text).min(MAX_COLUMN_WIDTH));
+            }
+        }
+    }
+    if let Some(columns) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse::<usize>().ok())
+    {
+        fit(&mut widths, columns);
+    }
+
+    let (bold, dim, reset) = match color {
+        true => (BOLD, DIM, RESET),
+        false => ("", "", ""),
+    };
+    let header = names.iter().map(|name| Some((name.to_string(), bold)));
+    write_line(&mut writer, header, &widths, color)?;
+    let rule: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+    writeln!(writer, "{dim}{}{reset}", rule.join(SEPARATOR))?;
+    for cells in &rows {
+        write_line(&mut writer, cells.iter().cloned(), &widths, color)?;
+    }
+    let count = rows.len();
+    let plural = if count == 1 { "" } else { "s" };
+    writeln!(writer, "{dim}({count} row{plural}){reset}")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes cells truncated and padded to the widths (with no padding after the last one).
+fn write_line<W: Write>(
+    writer: &mut W,
+    cells: impl Iterator<Item = Option<(String, &'static str)>>,
+    widths: &[usize],
+    color: bool,
+) -> Result<()> {
+    let mut line = String::new();
+    let mut padding = 0;
+    for (i, (cell, width)) in cells.zip(widths).enumerate() {
+        if i > 0 {
+            line.push_str(&" ".repeat(padding));
+            line.push_str(SEPARATOR);
+        }
+        let text = match cell {
+            Some((text, start)) => {
+                let text = truncate(&text, *width);
+                match color && !start.is_empty() {
+                    true => line.push_str(&format!("{start}{text}{RESET}")),
+                    false => line.push_str(&text),
+                }
+                text
+            }
+            None => String::new(),
+        };
+        padding = width - self::width(&text);
+    }
+    writeln!(writer, "{}", line.trim_end())?;
+    Ok(())
+}
+
+/// Formats a term on one line, along with the color to set it apart by (if any).
+fn cell(term: &Term, terms: &Terms) -> (String, &'static str) {
+    let start = match term {
+        Term::NamedNode(_) => IRI_COLOR,
+        Term::BlankNode(_) => BLANK_NODE_COLOR,
+        Term::Literal(literal) if literal.datatype() == xsd::STRING => "",
+        Term::Literal(_) | Term::Triple(_) => LITERAL_COLOR,
+    };
+    let text = results::display_term(term, terms)
+        .replace('\t', "  ")
+        .replace("\r\n", "↵")
+        .replace(['\n', '\r'], "↵");
+    (text, start)
+}
+
+/// Narrows the widest columns until the table fits the given width (or the columns cannot be
+/// narrowed further).
+fn fit(widths: &mut [usize], columns: usize) {
+    let separators = SEPARATOR.len() * widths.len().saturating_sub(1);
+    while widths.iter().sum::<usize>() + separators > columns {
+        let Some(widest) = widths.iter_mut().max() else {
+            break;
+        };
+        if *widest <= MIN_COLUMN_WIDTH {
+            break;
+        }
+        *widest -= 1;
+    }
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Shortens text to the width, ending it with an ellipsis if cut.
+fn truncate(text: &str, width: usize) -> String {
+    if self::width(text) <= width {
+        return text.to_owned();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,192 @@
+//! Filling of `{{name}}` placeholders in query templates.
+
+use anyhow::{bail, Result};
+use oxigraph::model::{Literal, NamedNode};
+use regex::Regex;
+
+use crate::shacl::is_well_formed;
+
+/// Datatypes (of XSD) that placeholders can be typed by (as in `{{name:integer}}`).
+const TYPES: &[&str] = &["integer", "decimal", "double", "boolean"];
+
+/// Replaces placeholders with values, escaped depending on where they occur.
+///
+/// Placeholders within IRIs (e.g. `<person/{{id}}>`) are percent-encoded, and placeholders within
+/// strings (e.g. `"Dear {{name}}"`) are escaped. Elsewhere, values are filled in as quoted string
+/// literals, or as literals of the type of placeholders with one (e.g. `{{limit:integer}}`,
+/// giving `10`), the values of which must then be valid for it.
+pub(crate) fn fill(template: &str, value_of: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let (before, after) = rest.split_at(start);
+        let Some((name, datatype, after)) =
+            after[2..].split_once("}}").and_then(|(name, after)| {
+                let (name, datatype) = match name.trim().split_once(':') {
+                    Some((name, datatype)) => (name.trim(), Some(datatype.trim())),
+                    None => (name.trim(), None),
+                };
+                (is_name(name) && datatype.is_none_or(is_name)).then_some((name, datatype, after))
+            })
+        else {
+            // Not a placeholder (e.g. nested group graph patterns):
+            filled.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        };
+        let Some(value) = value_of(name) else {
+            bail!("No value given for template parameter: {name}");
+        };
+        let literal = datatype
+            .map(|datatype| typed_value(name, &value, datatype))
+            .transpose()?;
+
+        filled.push_str(before);
+        filled.push_str(&match (context_of(&filled, after), literal) {
+            (Context::Iri, _) => escape_iri(&value),
+            (Context::String, _) => escape_string(&value),
+            (Context::Code, Some(literal)) => literal,
+            (Context::Code, None) => format!("\"{}\"", escape_string(&value)),
+        });
+        rest = after;
+    }
+    filled.push_str(rest);
+
+    Ok(filled)
+}
+
+/// Gives the value of a placeholder as a SPARQL literal of its type (written as a plain number or
+/// boolean where possible, as needed after `LIMIT`), failing if not valid for it.
+fn typed_value(name: &str, value: &str, datatype: &str) -> Result<String> {
+    if !TYPES.contains(&datatype) {
+        bail!(
+            "Unknown type of template parameter {name}: {datatype} (expected one of {})",
+            TYPES.join(", ")
+        );
+    }
+    let iri = NamedNode::new_unchecked(format!("http://www.w3.org/2001/XMLSchema#{datatype}"));
+    let literal = Literal::new_typed_literal(value, iri);
+    let number = Regex::new(r"^[+-]?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?$").expect("valid regex");
+    if !is_well_formed(&literal) || (datatype != "boolean" && !number.is_match(value)) {
+        if datatype == "double" && ["INF", "+INF", "-INF", "NaN"].contains(&value) {
+            return Ok(literal.to_string());
+        }
+        bail!("Invalid value of template parameter {name}: {literal}");
+    }
+    Ok(match datatype {
+        "boolean" => (value == "true" || value == "1").to_string(),
+        "decimal" if !value.contains('.') => format!("{value}.0"),
+        "decimal" if value.ends_with('.') => format!("{value}0"),
+        "double" if !value.contains(['e', 'E']) => format!("{value}e0"),
+        _ => value.to_owned(),
+    })
+}
+
+enum Context {
+    Iri,
+    String,
+    Code,
+}
+
+/// Determines whether the end of the (filled) query is within an IRI, a string or neither, given
+/// the rest of the template. (A `<` only starts an IRI if closed by a `>` before any character
+/// not allowed in IRIs, and is otherwise an operator, as in `?a<{{n}}`.)
+fn context_of(query: &str, rest: &str) -> Context {
+    let line = query.rsplit('\n').next().unwrap_or(query);
+    let mut quote = None;
+    let mut escaped = false;
+    let mut in_iri = false;
+    for c in line.chars() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            _ if in_iri && is_iri_char(c) => {}
+            '"' | '\'' => {
+                in_iri = false;
+                quote = Some(c);
+            }
+            '<' => in_iri = true,
+            _ => in_iri = false,
+        }
+    }
+
+    if quote.is_some() {
+        Context::String
+    } else if in_iri && closes_iri(rest) {
+        Context::Iri
+    } else {
+        Context::Code
+    }
+}
+
+/// Tells whether the text (after the start of an IRI) closes it, skipping any placeholders.
+fn closes_iri(mut text: &str) -> bool {
+    loop {
+        if let Some((_, after)) = text
+            .strip_prefix("{{")
+            .and_then(|text| text.split_once("}}"))
+        {
+            text = after;
+            continue;
+        }
+        let mut chars = text.chars();
+        match chars.next() {
+            Some('>') => return true,
+            Some(c) if is_iri_char(c) => text = chars.as_str(),
+            _ => return false,
+        }
+    }
+}
+
+fn is_iri_char(c: char) -> bool {
+    !c.is_control() && !c.is_whitespace() && !"<>\"{}|^`\\".contains(c)
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Percent-encodes characters not allowed in IRIs.
+fn escape_iri(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_control() || c.is_whitespace() || "<>\"{}|^`\\".contains(c) {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                escaped.push_str(&format!("%{byte:02X}"));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\'' => escaped.push_str("\\'"),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,97 @@
+//! Description of loaded data as a VoID dataset (with statistics of its triples, classes and
+//! properties).
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, Literal, NamedNode, NamedOrBlankNode, Quad, Term, Triple};
+
+use crate::namespaces::namespace;
+
+const VOID: &str = "http://rdfs.org/ns/void#";
+
+struct Partition<'a> {
+    triples: usize,
+    subjects: HashSet<&'a NamedOrBlankNode>,
+    objects: HashSet<&'a Term>,
+}
+
+/// Describes the triples of the quads (in any graph, counting each triple once) as a VoID
+/// dataset: with the number of triples, entities (subjects which are IRIs), distinct subjects
+/// and objects, classes and properties, a partition for each class (of its instances) and each
+/// property (of the triples using it), and the vocabularies (the namespaces) of them.
+pub(crate) fn describe(quads: &[Quad]) -> Vec<Triple> {
+    let triples: HashSet<_> = quads
+        .iter()
+        .map(|quad| (&quad.subject, &quad.predicate, &quad.object))
+        .collect();
+
+    let mut subjects = HashSet::new();
+    let mut objects = HashSet::new();
+    let mut classes: BTreeMap<&NamedNode, HashSet<&NamedOrBlankNode>> = BTreeMap::new();
+    let mut properties: BTreeMap<&NamedNode, Partition> = BTreeMap::new();
+    for &(subject, predicate, object) in &triples {
+        subjects.insert(subject);
+        objects.insert(object);
+        if let (true, Term::NamedNode(class)) = (predicate == &rdf::TYPE, object) {
+            classes.entry(class).or_default().insert(subject);
+        }
+        let partition = properties.entry(predicate).or_insert_with(|| Partition {
+            triples: 0,
+            subjects: HashSet::new(),
+            objects: HashSet::new(),
+        });
+        partition.triples += 1;
+        partition.subjects.insert(subject);
+        partition.objects.insert(object);
+    }
+    let entities = subjects
+        .iter()
+        .filter(|subject| subject.is_named_node())
+        .count();
+    let vocabularies: BTreeSet<_> = classes
+        .keys()
+        .chain(properties.keys())
+        .map(|iri| namespace(iri.as_str()))
+        .collect();
+
+    let dataset = NamedOrBlankNode::from(BlankNode::default());
+    let mut description = vec![Triple::new(
+        dataset.clone(),
+        rdf::TYPE,
+        NamedNode::new_unchecked(format!("{VOID}Dataset")),
+    )];
+    let mut add = |subject: &NamedOrBlankNode, name: &str, object: Term| {
+        let predicate = NamedNode::new_unchecked(format!("{VOID}{name}"));
+        description.push(Triple::new(subject.clone(), predicate, object));
+    };
+    let count = |n: usize| Term::from(Literal::new_typed_literal(n.to_string(), xsd::INTEGER));
+    add(&dataset, "triples", count(triples.len()));
+    add(&dataset, "entities", count(entities));
+    add(&dataset, "distinctSubjects", count(subjects.len()));
+    add(&dataset, "distinctObjects", count(objects.len()));
+    add(&dataset, "classes", count(classes.len()));
+    add(&dataset, "properties", count(properties.len()));
+    for vocabulary in vocabularies {
+        add(
+            &dataset,
+            "vocabulary",
+            NamedNode::new_unchecked(vocabulary).into(),
+        );
+    }
+    for (class, instances) in &classes {
+        let partition = NamedOrBlankNode::from(BlankNode::default());
+        add(&dataset, "classPartition", partition.clone().into());
+        add(&partition, "class", (*class).clone().into());
+        add(&partition, "entities", count(instances.len()));
+    }
+    for (property, stats) in &properties {
+        let partition = NamedOrBlankNode::from(BlankNode::default());
+        add(&dataset, "propertyPartition", partition.clone().into());
+        add(&partition, "property", (*property).clone().into());
+        add(&partition, "triples", count(stats.triples));
+        add(&partition, "distinctSubjects", count(stats.subjects.len()));
+        add(&partition, "distinctObjects", count(stats.objects.len()));
+    }
+    description
+}
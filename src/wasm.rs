@@ -0,0 +1,1151 @@
+//! WebAssembly plugins: modules whose exported functions are used as SPARQL functions, run by a
+//! small interpreter (of the core instruction set, for modules without imports).
+//!
+//! Numbers and booleans are passed as the parameter types of a function. Strings (and IRIs) are
+//! passed as a pointer and a length (two `i32` parameters), after being written into the exported
+//! memory at the address given by an exported `alloc(len) -> ptr` function. Integer results are
+//! given as `xsd:integer`, floating point ones as `xsd:double`, and pairs of `i32` (a pointer and
+//! a length) as strings. A function that traps (or runs too long) gives no value.
+//!
+//! (The interpreter is to be replaced by an existing one, such as `wasmi` with its fuel metering,
+//! leaving only this marshalling in [`Plugin`], once that dependency can be added to the build.)
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{Literal, Term};
+
+const MAGIC: &[u8] = b"\0asm\x01\0\0\0";
+const PAGE_SIZE: usize = 65_536;
+/// The most memory pages a module can use (1 GiB).
+const MAX_PAGES: usize = 16_384;
+/// The most instructions run per call (as a guard against endless loops).
+const FUEL: u64 = 100_000_000;
+const MAX_CALL_DEPTH: usize = 200;
+
+/// A loaded module, along with the state of its instance (reset to the initial globals after
+/// each call, and entirely if a call traps).
+pub(crate) struct Plugin {
+    module: Module,
+    initial: Instance,
+    instance: Mutex<Instance>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct FuncType {
+    params: Vec<ValType>,
+    results: Vec<ValType>,
+}
+
+struct Function {
+    type_index: usize,
+    locals: usize,
+    code: Vec<Op>,
+}
+
+#[derive(Default)]
+struct Module {
+    types: Vec<FuncType>,
+    functions: Vec<Function>,
+    max_pages: usize,
+    data: Vec<Vec<u8>>,
+    exports: Vec<(String, u32)>,
+    start: Option<u32>,
+}
+
+/// The mutable state of a module: its memory, globals and table (of function indexes).
+#[derive(Clone, Default)]
+struct Instance {
+    memory: Vec<u8>,
+    globals: Vec<u64>,
+    table: Vec<Option<u32>>,
+    dropped: Vec<bool>,
+}
+
+/// A decoded instruction (with the positions of the ends of blocks resolved).
+enum Op {
+    Unreachable,
+    Nop,
+    Block {
+        params: usize,
+        results: usize,
+        end: usize,
+    },
+    Loop {
+        params: usize,
+    },
+    If {
+        params: usize,
+        results: usize,
+        els: usize,
+        end: usize,
+    },
+    Else {
+        end: usize,
+    },
+    End,
+    Br(usize),
+    BrIf(usize),
+    BrTable(Box<[usize]>, usize),
+    Return,
+    Call(u32),
+    CallIndirect(usize),
+    Drop,
+    Select,
+    LocalGet(usize),
+    LocalSet(usize),
+    LocalTee(usize),
+    GlobalGet(usize),
+    GlobalSet(usize),
+    Load(u8, u32),
+    Store(u8, u32),
+    MemorySize,
+    MemoryGrow,
+    Const(u64),
+    Numeric(u8),
+    Saturating(u8),
+    MemoryInit(usize),
+    DataDrop(usize),
+    MemoryCopy,
+    MemoryFill,
+}
+
+/// A branch target: the height of the value stack when entered, the number of values it takes,
+/// and where to continue.
+struct Label {
+    height: usize,
+    arity: usize,
+    target: usize,
+}
+
+impl Plugin {
+    /// Loads and instantiates a module (running its start function, if any).
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            fs::read(path).with_context(|| format!("Unable to read plugin: {}", path.display()))?;
+        let (module, mut instance) = parse(&bytes)
+            .with_context(|| format!("Invalid WebAssembly module: {}", path.display()))?;
+        if let Some(start) = module.start {
+            let mut fuel = FUEL;
+            if module
+                .invoke(&mut instance, start, &[], 0, &mut fuel)
+                .is_none()
+            {
+                bail!("The start function of {} failed", path.display());
+            }
+        }
+        Ok(Self {
+            module,
+            initial: instance.clone(),
+            instance: Mutex::new(instance),
+        })
+    }
+
+    /// The names of the exported functions (other than `alloc`).
+    pub(crate) fn functions(&self) -> impl Iterator<Item = &str> {
+        self.module
+            .exports
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| *name != "alloc")
+    }
+
+    /// Calls an exported function with SPARQL terms as arguments.
+    pub(crate) fn call(&self, name: &str, args: &[Term]) -> Option<Term> {
+        let index = self.module.export(name)?;
+        let mut instance = self.instance.lock().ok()?;
+        let result = self.call_with(&mut instance, index, args);
+        if result.is_some() {
+            instance.globals.clone_from(&self.initial.globals);
+        } else {
+            *instance = self.initial.clone();
+        }
+        result
+    }
+
+    fn call_with(&self, instance: &mut Instance, index: u32, args: &[Term]) -> Option<Term> {
+        let module = &self.module;
+        let ty = module.func_type(index)?;
+        let mut fuel = FUEL;
+        let mut params = ty.params.iter();
+        let mut values = Vec::new();
+        for arg in args {
+            let text = match arg {
+                Term::Literal(literal) if literal.datatype() == xsd::BOOLEAN => {
+                    let value = u64::from(matches!(literal.value(), "true" | "1"));
+                    match params.next()? {
+                        ValType::I32 | ValType::I64 => values.push(value),
+                        _ => return None,
+                    }
+                    continue;
+                }
+                Term::Literal(literal) if is_number(literal) => {
+                    let value = literal.value();
+                    values.push(match params.next()? {
+                        ValType::I32 => u64::from(value.parse::<i32>().ok()? as u32),
+                        ValType::I64 => value.parse::<i64>().ok()? as u64,
+                        ValType::F32 => u64::from(value.parse::<f32>().ok()?.to_bits()),
+                        ValType::F64 => value.parse::<f64>().ok()?.to_bits(),
+                    });
+                    continue;
+                }
+                Term::Literal(literal) => literal.value(),
+                Term::NamedNode(node) => node.as_str(),
+                _ => return None,
+            };
+            if (params.next(), params.next()) != (Some(&ValType::I32), Some(&ValType::I32)) {
+                return None;
+            }
+            let len = u64::from(u32::try_from(text.len()).ok()?);
+            let alloc = module.export("alloc")?;
+            let ptr = *module
+                .invoke(instance, alloc, &[len], 0, &mut fuel)?
+                .first()?;
+            let start = usize::try_from(ptr as u32).ok()?;
+            instance
+                .memory
+                .get_mut(start..start.checked_add(text.len())?)?
+                .copy_from_slice(text.as_bytes());
+            values.extend([ptr, len]);
+        }
+        if params.next().is_some() {
+            return None;
+        }
+        let results = module.invoke(instance, index, &values, 0, &mut fuel)?;
+        let literal = match (ty.results.as_slice(), results.as_slice()) {
+            ([ValType::I32], [value]) => Literal::from(i64::from(*value as u32 as i32)),
+            ([ValType::I64], [value]) => Literal::from(*value as i64),
+            ([ValType::F32], [value]) => Literal::from(f64::from(f32::from_bits(*value as u32))),
+            ([ValType::F64], [value]) => Literal::from(f64::from_bits(*value)),
+            ([ValType::I32, ValType::I32], [ptr, len]) => {
+                let start = *ptr as u32 as usize;
+                let bytes = instance.memory.get(start..start + *len as u32 as usize)?;
+                Literal::new_simple_literal(std::str::from_utf8(bytes).ok()?)
+            }
+            _ => return None,
+        };
+        Some(literal.into())
+    }
+}
+
+fn is_number(literal: &Literal) -> bool {
+    [
+        xsd::INTEGER,
+        xsd::DECIMAL,
+        xsd::DOUBLE,
+        xsd::FLOAT,
+        xsd::LONG,
+        xsd::INT,
+        xsd::SHORT,
+        xsd::BYTE,
+        xsd::NON_NEGATIVE_INTEGER,
+        xsd::NON_POSITIVE_INTEGER,
+        xsd::POSITIVE_INTEGER,
+        xsd::NEGATIVE_INTEGER,
+        xsd::UNSIGNED_LONG,
+        xsd::UNSIGNED_INT,
+        xsd::UNSIGNED_SHORT,
+        xsd::UNSIGNED_BYTE,
+    ]
+    .contains(&literal.datatype())
+}
+
+impl Module {
+    fn export(&self, name: &str) -> Option<u32> {
+        self.exports
+            .iter()
+            .find(|(export, _)| export == name)
+            .map(|(_, index)| *index)
+    }
+
+    fn func_type(&self, index: u32) -> Option<&FuncType> {
+        let function = self.functions.get(usize::try_from(index).ok()?)?;
+        self.types.get(function.type_index)
+    }
+
+    /// Runs a function, giving its results (or none if it traps).
+    fn invoke(
+        &self,
+        instance: &mut Instance,
+        index: u32,
+        args: &[u64],
+        depth: usize,
+        fuel: &mut u64,
+    ) -> Option<Vec<u64>> {
+        if depth > MAX_CALL_DEPTH {
+            return None;
+        }
+        let function = self.functions.get(index as usize)?;
+        let ty = self.types.get(function.type_index)?;
+        let mut locals = args.to_vec();
+        locals.resize(args.len() + function.locals, 0);
+        let mut stack: Vec<u64> = Vec::new();
+        let mut labels: Vec<Label> = Vec::new();
+        let mut pc = 0;
+        loop {
+            *fuel = fuel.checked_sub(1)?;
+            let mut next = pc + 1;
+            let mut branch = None;
+            match function.code.get(pc)? {
+                Op::Unreachable => return None,
+                Op::Nop => {}
+                Op::Block {
+                    params,
+                    results,
+                    end,
+                } => labels.push(Label {
+                    height: stack.len().checked_sub(*params)?,
+                    arity: *results,
+                    target: end + 1,
+                }),
+                Op::Loop { params } => labels.push(Label {
+                    height: stack.len().checked_sub(*params)?,
+                    arity: *params,
+                    target: pc,
+                }),
+                Op::If {
+                    params,
+                    results,
+                    els,
+                    end,
+                } => {
+                    let condition = stack.pop()? as u32;
+                    labels.push(Label {
+                        height: stack.len().checked_sub(*params)?,
+                        arity: *results,
+                        target: end + 1,
+                    });
+                    if condition == 0 {
+                        next = if els == end { *end } else { els + 1 };
+                    }
+                }
+                Op::Else { end } => next = *end,
+                Op::End => {
+                    if labels.pop().is_none() {
+                        break;
+                    }
+                }
+                Op::Br(depth) => branch = Some(*depth),
+                Op::BrIf(depth) => {
+                    if stack.pop()? as u32 != 0 {
+                        branch = Some(*depth);
+                    }
+                }
+                Op::BrTable(depths, default) => {
+                    let i = stack.pop()? as u32 as usize;
+                    branch = Some(*depths.get(i).unwrap_or(default));
+                }
+                Op::Return => break,
+                Op::Call(callee) => {
+                    let params = self.func_type(*callee)?.params.len();
+                    let args = stack.split_off(stack.len().checked_sub(params)?);
+                    let results = self.invoke(instance, *callee, &args, depth + 1, fuel)?;
+                    stack.extend(results);
+                }
+                Op::CallIndirect(type_index) => {
+                    let i = stack.pop()? as u32 as usize;
+                    let callee = (*instance.table.get(i)?)?;
+                    let callee_type = self.func_type(callee)?;
+                    if callee_type != self.types.get(*type_index)? {
+                        return None;
+                    }
+                    let args = stack.split_off(stack.len().checked_sub(callee_type.params.len())?);
+                    let results = self.invoke(instance, callee, &args, depth + 1, fuel)?;
+                    stack.extend(results);
+                }
+                Op::Drop => {
+                    stack.pop()?;
+                }
+                Op::Select => {
+                    let condition = stack.pop()? as u32;
+                    let second = stack.pop()?;
+                    let first = stack.pop()?;
+                    stack.push(if condition != 0 { first } else { second });
+                }
+                Op::LocalGet(i) => stack.push(*locals.get(*i)?),
+                Op::LocalSet(i) => *locals.get_mut(*i)? = stack.pop()?,
+                Op::LocalTee(i) => *locals.get_mut(*i)? = *stack.last()?,
+                Op::GlobalGet(i) => stack.push(*instance.globals.get(*i)?),
+                Op::GlobalSet(i) => *instance.globals.get_mut(*i)? = stack.pop()?,
+                Op::Load(op, offset) => {
+                    let size = [4, 8, 4, 8, 1, 1, 2, 2, 1, 1, 2, 2, 4, 4][usize::from(op - 0x28)];
+                    let address = address(stack.pop()?, *offset);
+                    let bytes = instance.memory.get(address..address.checked_add(size)?)?;
+                    let mut raw = [0; 8];
+                    raw[..size].copy_from_slice(bytes);
+                    let raw = u64::from_le_bytes(raw);
+                    stack.push(match op {
+                        0x2C => u64::from(raw as u8 as i8 as i32 as u32),
+                        0x2E => u64::from(raw as u16 as i16 as i32 as u32),
+                        0x30 => raw as u8 as i8 as i64 as u64,
+                        0x32 => raw as u16 as i16 as i64 as u64,
+                        0x34 => raw as u32 as i32 as i64 as u64,
+                        _ => raw,
+                    });
+                }
+                Op::Store(op, offset) => {
+                    let size = [4, 8, 4, 8, 1, 2, 1, 2, 4][usize::from(op - 0x36)];
+                    let value = stack.pop()?;
+                    let address = address(stack.pop()?, *offset);
+                    instance
+                        .memory
+                        .get_mut(address..address.checked_add(size)?)?
+                        .copy_from_slice(&value.to_le_bytes()[..size]);
+                }
+                Op::MemorySize => stack.push((instance.memory.len() / PAGE_SIZE) as u64),
+                Op::MemoryGrow => {
+                    let pages = instance.memory.len() / PAGE_SIZE;
+                    let added = stack.pop()? as u32 as usize;
+                    if pages + added > self.max_pages {
+                        stack.push(u64::from(u32::MAX));
+                    } else {
+                        instance.memory.resize((pages + added) * PAGE_SIZE, 0);
+                        stack.push(pages as u64);
+                    }
+                }
+                Op::Const(value) => stack.push(*value),
+                Op::Numeric(op) => numeric(*op, &mut stack)?,
+                Op::Saturating(op) => {
+                    let value = stack.pop()?;
+                    let (x32, x64) = (f32::from_bits(value as u32), f64::from_bits(value));
+                    stack.push(match op {
+                        0 => u64::from(x32 as i32 as u32),
+                        1 => u64::from(x32 as u32),
+                        2 => u64::from(x64 as i32 as u32),
+                        3 => u64::from(x64 as u32),
+                        4 => x32 as i64 as u64,
+                        5 => x32 as u64,
+                        6 => x64 as i64 as u64,
+                        _ => x64 as u64,
+                    });
+                }
+                Op::MemoryInit(segment) => {
+                    let len = stack.pop()? as u32 as usize;
+                    let from = stack.pop()? as u32 as usize;
+                    let to = stack.pop()? as u32 as usize;
+                    let data = match instance.dropped.get(*segment)? {
+                        true => &[][..],
+                        false => self.data.get(*segment)?,
+                    };
+                    let bytes = data.get(from..from.checked_add(len)?)?;
+                    instance
+                        .memory
+                        .get_mut(to..to.checked_add(len)?)?
+                        .copy_from_slice(bytes);
+                }
+                Op::DataDrop(segment) => *instance.dropped.get_mut(*segment)? = true,
+                Op::MemoryCopy => {
+                    let len = stack.pop()? as u32 as usize;
+                    let from = stack.pop()? as u32 as usize;
+                    let to = stack.pop()? as u32 as usize;
+                    let memory = &mut instance.memory;
+                    if from.checked_add(len)? > memory.len() || to.checked_add(len)? > memory.len()
+                    {
+                        return None;
+                    }
+                    memory.copy_within(from..from + len, to);
+                }
+                Op::MemoryFill => {
+                    let len = stack.pop()? as u32 as usize;
+                    let value = stack.pop()? as u8;
+                    let to = stack.pop()? as u32 as usize;
+                    instance
+                        .memory
+                        .get_mut(to..to.checked_add(len)?)?
+                        .fill(value);
+                }
+            }
+            if let Some(depth) = branch {
+                let Some(i) = labels.len().checked_sub(depth + 1) else {
+                    // (Branching out of the function body returns from it.)
+                    break;
+                };
+                let label = labels.swap_remove(i);
+                labels.truncate(i);
+                let values = stack.split_off(stack.len().checked_sub(label.arity)?);
+                stack.truncate(label.height);
+                stack.extend(values);
+                next = label.target;
+            }
+            pc = next;
+        }
+        Some(stack.split_off(stack.len().checked_sub(ty.results.len())?))
+    }
+}
+
+fn address(base: u64, offset: u32) -> usize {
+    (base as u32 as usize) + offset as usize
+}
+
+/// Runs a numeric instruction (comparisons, arithmetic and conversions), on values as bits (with
+/// 32-bit values in the lower half).
+fn numeric(op: u8, stack: &mut Vec<u64>) -> Option<()> {
+    let i32s = |value: u64| value as u32;
+    let f32s = |value: u64| f32::from_bits(value as u32);
+    let f64s = f64::from_bits;
+    let of_i32 = |value: u32| u64::from(value);
+    let of_f32 = |value: f32| u64::from(value.to_bits());
+    let of_f64 = f64::to_bits;
+    let of_bool = u64::from;
+    let binary = matches!(op,
+        0x46..=0x4F | 0x51..=0x5A | 0x5B..=0x66 | 0x6A..=0x78 | 0x7C..=0x8A | 0x92..=0x98
+        | 0xA0..=0xA6);
+    let b = if binary { stack.pop()? } else { 0 };
+    let a = stack.pop()?;
+    let value = match op {
+        0x45 => of_bool(i32s(a) == 0),
+        0x46..=0x4F => {
+            let (x, y) = (i32s(a), i32s(b));
+            let (sx, sy) = (x as i32, y as i32);
+            of_bool(match op {
+                0x46 => x == y,
+                0x47 => x != y,
+                0x48 => sx < sy,
+                0x49 => x < y,
+                0x4A => sx > sy,
+                0x4B => x > y,
+                0x4C => sx <= sy,
+                0x4D => x <= y,
+                0x4E => sx >= sy,
+                _ => x >= y,
+            })
+        }
+        0x50 => of_bool(a == 0),
+        0x51..=0x5A => {
+            let (sx, sy) = (a as i64, b as i64);
+            of_bool(match op {
+                0x51 => a == b,
+                0x52 => a != b,
+                0x53 => sx < sy,
+                0x54 => a < b,
+                0x55 => sx > sy,
+                0x56 => a > b,
+                0x57 => sx <= sy,
+                0x58 => a <= b,
+                0x59 => sx >= sy,
+                _ => a >= b,
+            })
+        }
+        0x5B..=0x60 => compare(op - 0x5B, f64::from(f32s(a)), f64::from(f32s(b))),
+        0x61..=0x66 => compare(op - 0x61, f64s(a), f64s(b)),
+        0x67 => of_i32(i32s(a).leading_zeros()),
+        0x68 => of_i32(i32s(a).trailing_zeros()),
+        0x69 => of_i32(i32s(a).count_ones()),
+        0x6A..=0x78 => {
+            let (x, y) = (i32s(a), i32s(b));
+            let (sx, sy) = (x as i32, y as i32);
+            of_i32(match op {
+                0x6A => x.wrapping_add(y),
+                0x6B => x.wrapping_sub(y),
+                0x6C => x.wrapping_mul(y),
+                0x6D => sx.checked_div(sy)? as u32,
+                0x6E => x.checked_div(y)?,
+                0x6F if sy == 0 => return None,
+                0x6F => sx.wrapping_rem(sy) as u32,
+                0x70 => x.checked_rem(y)?,
+                0x71 => x & y,
+                0x72 => x | y,
+                0x73 => x ^ y,
+                0x74 => x.wrapping_shl(y),
+                0x75 => sx.wrapping_shr(y) as u32,
+                0x76 => x.wrapping_shr(y),
+                0x77 => x.rotate_left(y % 32),
+                _ => x.rotate_right(y % 32),
+            })
+        }
+        0x79 => u64::from(a.leading_zeros()),
+        0x7A => u64::from(a.trailing_zeros()),
+        0x7B => u64::from(a.count_ones()),
+        0x7C..=0x8A => {
+            let (sx, sy) = (a as i64, b as i64);
+            let shift = (b % 64) as u32;
+            match op {
+                0x7C => a.wrapping_add(b),
+                0x7D => a.wrapping_sub(b),
+                0x7E => a.wrapping_mul(b),
+                0x7F => sx.checked_div(sy)? as u64,
+                0x80 => a.checked_div(b)?,
+                0x81 if sy == 0 => return None,
+                0x81 => sx.wrapping_rem(sy) as u64,
+                0x82 => a.checked_rem(b)?,
+                0x83 => a & b,
+                0x84 => a | b,
+                0x85 => a ^ b,
+                0x86 => a.wrapping_shl(shift),
+                0x87 => sx.wrapping_shr(shift) as u64,
+                0x88 => a.wrapping_shr(shift),
+                0x89 => a.rotate_left(shift),
+                _ => a.rotate_right(shift),
+            }
+        }
+        0x8B..=0x98 => {
+            let (x, y) = (f32s(a), f32s(b));
+            of_f32(match op {
+                0x8B => x.abs(),
+                0x8C => -x,
+                0x8D => x.ceil(),
+                0x8E => x.floor(),
+                0x8F => x.trunc(),
+                0x90 => x.round_ties_even(),
+                0x91 => x.sqrt(),
+                0x92 => x + y,
+                0x93 => x - y,
+                0x94 => x * y,
+                0x95 => x / y,
+                0x96 => min(f64::from(x), f64::from(y)) as f32,
+                0x97 => max(f64::from(x), f64::from(y)) as f32,
+                _ => x.copysign(y),
+            })
+        }
+        0x99..=0xA6 => {
+            let (x, y) = (f64s(a), f64s(b));
+            of_f64(match op {
+                0x99 => x.abs(),
+                0x9A => -x,
+                0x9B => x.ceil(),
+                0x9C => x.floor(),
+                0x9D => x.trunc(),
+                0x9E => x.round_ties_even(),
+                0x9F => x.sqrt(),
+                0xA0 => x + y,
+                0xA1 => x - y,
+                0xA2 => x * y,
+                0xA3 => x / y,
+                0xA4 => min(x, y),
+                0xA5 => max(x, y),
+                _ => x.copysign(y),
+            })
+        }
+        0xA7 => of_i32(a as u32),
+        0xA8 => {
+            of_i32(truncate(f64::from(f32s(a)), -2_147_483_649.0, 2_147_483_648.0)? as i32 as u32)
+        }
+        0xA9 => of_i32(truncate(f64::from(f32s(a)), -1.0, 4_294_967_296.0)? as u32),
+        0xAA => of_i32(truncate(f64s(a), -2_147_483_649.0, 2_147_483_648.0)? as i32 as u32),
+        0xAB => of_i32(truncate(f64s(a), -1.0, 4_294_967_296.0)? as u32),
+        0xAC => i32s(a) as i32 as i64 as u64,
+        0xAD => u64::from(i32s(a)),
+        0xAE => truncate(
+            f64::from(f32s(a)),
+            -9_223_372_036_854_777_856.0,
+            9_223_372_036_854_775_808.0,
+        )? as i64 as u64,
+        0xAF => truncate(f64::from(f32s(a)), -1.0, 18_446_744_073_709_551_616.0)? as u64,
+        0xB0 => truncate(
+            f64s(a),
+            -9_223_372_036_854_777_856.0,
+            9_223_372_036_854_775_808.0,
+        )? as i64 as u64,
+        0xB1 => truncate(f64s(a), -1.0, 18_446_744_073_709_551_616.0)? as u64,
+        0xB2 => of_f32(i32s(a) as i32 as f32),
+        0xB3 => of_f32(i32s(a) as f32),
+        0xB4 => of_f32(a as i64 as f32),
+        0xB5 => of_f32(a as f32),
+        0xB6 => of_f32(f64s(a) as f32),
+        0xB7 => of_f64(f64::from(i32s(a) as i32)),
+        0xB8 => of_f64(f64::from(i32s(a))),
+        0xB9 => of_f64(a as i64 as f64),
+        0xBA => of_f64(a as f64),
+        0xBB => of_f64(f64::from(f32s(a))),
+        // (Reinterpretations keep the bits as they are.)
+        0xBC..=0xBF => a,
+        0xC0 => of_i32(a as u8 as i8 as i32 as u32),
+        0xC1 => of_i32(a as u16 as i16 as i32 as u32),
+        0xC2 => a as u8 as i8 as i64 as u64,
+        0xC3 => a as u16 as i16 as i64 as u64,
+        _ => a as u32 as i32 as i64 as u64,
+    };
+    stack.push(value);
+    Some(())
+}
+
+/// Compares floating point numbers (by `eq`, `ne`, `lt`, `gt`, `le` or `ge`).
+fn compare(op: u8, x: f64, y: f64) -> u64 {
+    u64::from(match op {
+        0 => x == y,
+        1 => x != y,
+        2 => x < y,
+        3 => x > y,
+        4 => x <= y,
+        _ => x >= y,
+    })
+}
+
+/// Truncates a floating point number, unless not a number or not between the (exclusive) bounds.
+fn truncate(x: f64, lower: f64, upper: f64) -> Option<f64> {
+    let x = x.trunc();
+    (x > lower && x < upper).then_some(x)
+}
+
+/// The minimum of two floating point numbers (as NaN if either is, and with -0 below 0).
+fn min(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        f64::NAN
+    } else if x == y && x.is_sign_negative() {
+        x
+    } else {
+        x.min(y)
+    }
+}
+
+/// The maximum of two floating point numbers (as NaN if either is, and with 0 above -0).
+fn max(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        f64::NAN
+    } else if x == y && x.is_sign_positive() {
+        x
+    } else {
+        x.max(y)
+    }
+}
+
+/// Parses a module in the binary format, along with its initial instance.
+fn parse(bytes: &[u8]) -> Result<(Module, Instance)> {
+    let Some(mut reader) = bytes.strip_prefix(MAGIC).map(Reader::new) else {
+        bail!("Expected the WebAssembly (version 1) magic number");
+    };
+    let mut module = Module::default();
+    let mut instance = Instance::default();
+    let mut function_types = Vec::new();
+    while !reader.at_end() {
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let mut section = Reader::new(reader.take(size)?);
+        match id {
+            1 => {
+                for _ in 0..section.u32()? {
+                    if section.byte()? != 0x60 {
+                        bail!("Expected a function type");
+                    }
+                    let params = section.val_types()?;
+                    let results = section.val_types()?;
+                    module.types.push(FuncType { params, results });
+                }
+            }
+            2 => {
+                let module_name = section.name()?;
+                let name = section.name()?;
+                bail!("Imports are not supported (of {module_name}.{name})");
+            }
+            3 => {
+                for _ in 0..section.u32()? {
+                    let index = section.u32()? as usize;
+                    if index >= module.types.len() {
+                        bail!("Unknown function type: {index}");
+                    }
+                    function_types.push(index);
+                }
+            }
+            4 => {
+                for i in 0..section.u32()? {
+                    section.byte()?;
+                    let (min, _) = section.limits()?;
+                    if i == 0 {
+                        instance.table = vec![None; min];
+                    }
+                }
+            }
+            5 => {
+                for _ in 0..section.u32()? {
+                    let (min, max) = section.limits()?;
+                    module.max_pages = max.unwrap_or(MAX_PAGES).min(MAX_PAGES);
+                    if min > module.max_pages {
+                        bail!("Too much memory required: {min} pages");
+                    }
+                    instance.memory = vec![0; min * PAGE_SIZE];
+                }
+            }
+            6 => {
+                for _ in 0..section.u32()? {
+                    section.val_type()?;
+                    section.byte()?;
+                    let value = section.const_expr(&instance.globals)?;
+                    instance.globals.push(value);
+                }
+            }
+            7 => {
+                for _ in 0..section.u32()? {
+                    let name = section.name()?;
+                    let kind = section.byte()?;
+                    let index = section.u32()?;
+                    if kind == 0 {
+                        module.exports.push((name, index));
+                    }
+                }
+            }
+            8 => module.start = Some(section.u32()?),
+            9 => {
+                for _ in 0..section.u32()? {
+                    let flags = section.u32()?;
+                    let offset = match flags {
+                        0 => Some(section.const_expr(&instance.globals)?),
+                        2 => {
+                            section.u32()?;
+                            Some(section.const_expr(&instance.globals)?)
+                        }
+                        1 | 3 => None,
+                        _ => bail!("Unsupported element segment (of kind {flags})"),
+                    };
+                    if flags != 0 {
+                        section.byte()?;
+                    }
+                    let indexes = (0..section.u32()?)
+                        .map(|_| section.u32())
+                        .collect::<Result<Vec<_>>>()?;
+                    if let Some(offset) = offset {
+                        let start = offset as u32 as usize;
+                        let Some(slots) = instance.table.get_mut(start..start + indexes.len())
+                        else {
+                            bail!("Element segment out of bounds");
+                        };
+                        for (slot, index) in slots.iter_mut().zip(indexes) {
+                            *slot = Some(index);
+                        }
+                    }
+                }
+            }
+            10 => {
+                let count = section.u32()? as usize;
+                if count != function_types.len() {
+                    bail!("Expected code for {} functions", function_types.len());
+                }
+                for (i, type_index) in function_types.iter().enumerate() {
+                    let size = section.u32()? as usize;
+                    let mut body = Reader::new(section.take(size)?);
+                    let mut locals = 0;
+                    for _ in 0..body.u32()? {
+                        locals += body.u32()? as usize;
+                        body.val_type()?;
+                    }
+                    let code = body
+                        .code(&module.types)
+                        .with_context(|| format!("Error in function {i}"))?;
+                    module.functions.push(Function {
+                        type_index: *type_index,
+                        locals,
+                        code,
+                    });
+                }
+            }
+            11 => {
+                for _ in 0..section.u32()? {
+                    let flags = section.u32()?;
+                    let offset = match flags {
+                        0 => Some(section.const_expr(&instance.globals)?),
+                        1 => None,
+                        2 => {
+                            section.u32()?;
+                            Some(section.const_expr(&instance.globals)?)
+                        }
+                        _ => bail!("Unsupported data segment (of kind {flags})"),
+                    };
+                    let len = section.u32()? as usize;
+                    let data = section.take(len)?;
+                    if let Some(offset) = offset {
+                        let start = offset as u32 as usize;
+                        let Some(memory) = instance.memory.get_mut(start..start + len) else {
+                            bail!("Data segment out of bounds");
+                        };
+                        memory.copy_from_slice(data);
+                    }
+                    module.data.push(data.to_vec());
+                }
+                instance.dropped = vec![false; module.data.len()];
+            }
+            _ => {}
+        }
+    }
+    if function_types.len() != module.functions.len() {
+        bail!("Expected code for {} functions", function_types.len());
+    }
+    for (name, index) in &module.exports {
+        if *index as usize >= module.functions.len() {
+            bail!("Unknown function exported as {name}: {index}");
+        }
+    }
+    Ok((module, instance))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let Some(byte) = self.bytes.get(self.pos) else {
+            bail!("Unexpected end of module");
+        };
+        self.pos += 1;
+        Ok(*byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some(bytes) = self.bytes.get(self.pos..self.pos.saturating_add(len)) else {
+            bail!("Unexpected end of module");
+        };
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Reads an unsigned LEB128 integer.
+    fn u32(&mut self) -> Result<u32> {
+        let mut value = 0u64;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(value).context("Integer too large");
+            }
+        }
+        bail!("Integer too long")
+    }
+
+    /// Reads a signed LEB128 integer (of up to 64 bits).
+    fn signed(&mut self, bits: u32) -> Result<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= bits.div_ceil(7) * 7 {
+                bail!("Integer too long");
+            }
+            value |= i64::from(byte & 0x7F).wrapping_shl(shift);
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1i64 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        Ok(std::str::from_utf8(bytes)
+            .context("Invalid name")?
+            .to_owned())
+    }
+
+    fn val_type(&mut self) -> Result<ValType> {
+        Ok(match self.byte()? {
+            0x7F => ValType::I32,
+            0x7E => ValType::I64,
+            0x7D => ValType::F32,
+            0x7C => ValType::F64,
+            byte => bail!("Unsupported value type: 0x{byte:02X}"),
+        })
+    }
+
+    fn val_types(&mut self) -> Result<Vec<ValType>> {
+        (0..self.u32()?).map(|_| self.val_type()).collect()
+    }
+
+    fn limits(&mut self) -> Result<(usize, Option<usize>)> {
+        let flags = self.byte()?;
+        let min = self.u32()? as usize;
+        let max = match flags {
+            0 => None,
+            1 => Some(self.u32()? as usize),
+            _ => bail!("Unsupported limits (of kind {flags})"),
+        };
+        Ok((min, max))
+    }
+
+    /// Evaluates a constant expression (a constant, or the value of an earlier global).
+    fn const_expr(&mut self, globals: &[u64]) -> Result<u64> {
+        let value = match self.byte()? {
+            0x41 => u64::from(self.signed(32)? as u32),
+            0x42 => self.signed(64)? as u64,
+            0x43 => u64::from(u32::from_le_bytes(self.array()?)),
+            0x44 => u64::from_le_bytes(self.array()?),
+            0x23 => match globals.get(self.u32()? as usize) {
+                Some(value) => *value,
+                None => bail!("Unknown global in constant expression"),
+            },
+            op => bail!("Unsupported constant expression: 0x{op:02X}"),
+        };
+        if self.byte()? != 0x0B {
+            bail!("Expected the end of a constant expression");
+        }
+        Ok(value)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("taken length"))
+    }
+
+    /// Reads the type of a block: its number of parameters and results.
+    fn block_type(&mut self, types: &[FuncType]) -> Result<(usize, usize)> {
+        Ok(match self.bytes.get(self.pos) {
+            Some(0x40) => {
+                self.pos += 1;
+                (0, 0)
+            }
+            Some(0x7C..=0x7F) => {
+                self.val_type()?;
+                (0, 1)
+            }
+            _ => {
+                let index = self.signed(33)?;
+                let Some(ty) = usize::try_from(index).ok().and_then(|i| types.get(i)) else {
+                    bail!("Unknown block type: {index}");
+                };
+                (ty.params.len(), ty.results.len())
+            }
+        })
+    }
+
+    /// Decodes an expression (a function body), resolving where blocks end.
+    fn code(&mut self, types: &[FuncType]) -> Result<Vec<Op>> {
+        let mut code = Vec::new();
+        let mut blocks: Vec<usize> = Vec::new();
+        loop {
+            let pc = code.len();
+            let op = self.byte()?;
+            let instruction = match op {
+                0x00 => Op::Unreachable,
+                0x01 => Op::Nop,
+                0x02..=0x04 => {
+                    let (params, results) = self.block_type(types)?;
+                    blocks.push(pc);
+                    match op {
+                        0x02 => Op::Block {
+                            params,
+                            results,
+                            end: 0,
+                        },
+                        0x03 => Op::Loop { params },
+                        _ => Op::If {
+                            params,
+                            results,
+                            els: 0,
+                            end: 0,
+                        },
+                    }
+                }
+                0x05 => {
+                    let Some(Op::If { els, .. }) = blocks.last().map(|i| &mut code[*i]) else {
+                        bail!("Unexpected else");
+                    };
+                    *els = pc;
+                    Op::Else { end: 0 }
+                }
+                0x0B => {
+                    let Some(start) = blocks.pop() else {
+                        code.push(Op::End);
+                        break;
+                    };
+                    let mut else_start = None;
+                    match &mut code[start] {
+                        Op::Block { end, .. } => *end = pc,
+                        Op::If { els, end, .. } => {
+                            *end = pc;
+                            if *els == 0 {
+                                *els = pc;
+                            } else {
+                                else_start = Some(*els);
+                            }
+                        }
+                        _ => {}
+                    }
+                    if let Some(Op::Else { end }) = else_start.map(|i| &mut code[i]) {
+                        *end = pc;
+                    }
+                    Op::End
+                }
+                0x0C => Op::Br(self.u32()? as usize),
+                0x0D => Op::BrIf(self.u32()? as usize),
+                0x0E => {
+                    let depths = (0..self.u32()?)
+                        .map(|_| Ok(self.u32()? as usize))
+                        .collect::<Result<_>>()?;
+                    Op::BrTable(depths, self.u32()? as usize)
+                }
+                0x0F => Op::Return,
+                0x10 => Op::Call(self.u32()?),
+                0x11 => {
+                    let index = self.u32()? as usize;
+                    self.u32()?;
+                    Op::CallIndirect(index)
+                }
+                0x1A => Op::Drop,
+                0x1B => Op::Select,
+                0x1C => {
+                    self.val_types()?;
+                    Op::Select
+                }
+                0x20 => Op::LocalGet(self.u32()? as usize),
+                0x21 => Op::LocalSet(self.u32()? as usize),
+                0x22 => Op::LocalTee(self.u32()? as usize),
+                0x23 => Op::GlobalGet(self.u32()? as usize),
+                0x24 => Op::GlobalSet(self.u32()? as usize),
+                0x28..=0x3E => {
+                    self.u32()?;
+                    let offset = self.u32()?;
+                    match op {
+                        0x28..=0x35 => Op::Load(op, offset),
+                        _ => Op::Store(op, offset),
+                    }
+                }
+                0x3F | 0x40 => {
+                    self.byte()?;
+                    match op {
+                        0x3F => Op::MemorySize,
+                        _ => Op::MemoryGrow,
+                    }
+                }
+                0x41 => Op::Const(u64::from(self.signed(32)? as u32)),
+                0x42 => Op::Const(self.signed(64)? as u64),
+                0x43 => Op::Const(u64::from(u32::from_le_bytes(self.array()?))),
+                0x44 => Op::Const(u64::from_le_bytes(self.array()?)),
+                0x45..=0xC4 => Op::Numeric(op),
+                0xFC => match self.u32()? {
+                    sub @ 0..=7 => Op::Saturating(sub as u8),
+                    8 => {
+                        let segment = self.u32()? as usize;
+                        self.byte()?;
+                        Op::MemoryInit(segment)
+                    }
+                    9 => Op::DataDrop(self.u32()? as usize),
+                    10 => {
+                        self.take(2)?;
+                        Op::MemoryCopy
+                    }
+                    11 => {
+                        self.byte()?;
+                        Op::MemoryFill
+                    }
+                    sub => bail!("Unsupported instruction: 0xFC {sub}"),
+                },
+                op => bail!("Unsupported instruction: 0x{op:02X}"),
+            };
+            code.push(instruction);
+        }
+        if !self.at_end() {
+            bail!("Unexpected code after the end of a function");
+        }
+        Ok(code)
+    }
+}
@@ -0,0 +1,255 @@
+//! XLSX (Excel workbook) output of solutions, as one worksheet with typed cells.
+
+use std::io::{Cursor, Write};
+
+use anyhow::Result;
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{Literal, Term};
+use oxigraph::sparql::Variable;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>
+<Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>
+<Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>
+</Types>"#;
+
+const RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>
+</Relationships>"#;
+
+const WORKBOOK: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+<sheets><sheet name="Results" sheetId="1" r:id="rId1"/></sheets>
+</workbook>"#;
+
+const WORKBOOK_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>
+<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+</Relationships>"#;
+
+/// Cell styles: the default, bold (for the header row), and the built-in date and date-time
+/// number formats.
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+<fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts>
+<fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills>
+<borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders>
+<cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs>
+<cellXfs count="4">
+<xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/>
+<xf numFmtId="0" fontId="1" fillId="0" borderId="0" xfId="0" applyFont="1"/>
+<xf numFmtId="14" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/>
+<xf numFmtId="22" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/>
+</cellXfs>
+</styleSheet>"#;
+
+const BOLD_STYLE: usize = 1;
+const DATE_STYLE: usize = 2;
+const DATE_TIME_STYLE: usize = 3;
+
+/// A typed cell value.
+enum Cell {
+    Text(String),
+    Number(String),
+    Boolean(bool),
+    /// A date (or date and time), as a serial number of days in the 1900 date system.
+    Date(f64, usize),
+}
+
+/// Writes a workbook with the variables as header row and one row per solution. Numbers,
+/// booleans, dates and date-times are written as such (and other values as text, with IRIs and
+/// literals by their plain values).
+pub(crate) fn write<W: Write>(
+    variables: &[Variable],
+    solutions: impl Iterator<Item = Result<Vec<Option<Term>>>>,
+    mut writer: W,
+) -> Result<()> {
+    let mut sheet = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        "\n",
+        r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">"#,
+        "<sheetData>\n"
+    ));
+    let header = variables
+        .iter()
+        .map(|variable| Some((Cell::Text(variable.as_str().to_owned()), BOLD_STYLE)));
+    write_row(&mut sheet, 1, header);
+    for (i, values) in solutions.enumerate() {
+        let cells = values?
+            .into_iter()
+            .map(|value| value.map(|term| cell(&term)));
+        write_row(&mut sheet, i + 2, cells);
+    }
+    sheet.push_str("</sheetData></worksheet>");
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, content) in [
+        ("[Content_Types].xml", CONTENT_TYPES),
+        ("_rels/.rels", RELS),
+        ("xl/workbook.xml", WORKBOOK),
+        ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS),
+        ("xl/styles.xml", STYLES),
+        ("xl/worksheets/sheet1.xml", &sheet),
+    ] {
+        zip.start_file(name, options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    writer.write_all(&zip.finish()?.into_inner())?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_row(sheet: &mut String, row: usize, cells: impl Iterator<Item = Option<(Cell, usize)>>) {
+    sheet.push_str(&format!(r#"<row r="{row}">"#));
+    for (column, cell) in cells.enumerate() {
+        let Some((cell, style)) = cell else {
+            continue;
+        };
+        let reference = format!("{}{row}", column_name(column));
+        let style = match style {
+            0 => String::new(),
+            style => format!(r#" s="{style}""#),
+        };
+        sheet.push_str(&match cell {
+            Cell::Text(text) => format!(
+                r#"<c r="{reference}"{style} t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#,
+                escape_xml(&text)
+            ),
+            Cell::Number(number) => format!(r#"<c r="{reference}"{style}><v>{number}</v></c>"#),
+            Cell::Boolean(value) => format!(
+                r#"<c r="{reference}"{style} t="b"><v>{}</v></c>"#,
+                u8::from(value)
+            ),
+            Cell::Date(serial, _) => format!(r#"<c r="{reference}"{style}><v>{serial}</v></c>"#),
+        });
+    }
+    sheet.push_str("</row>\n");
+}
+
+/// Names a column by letters (A to Z, then AA and so on).
+fn column_name(mut column: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (column % 26) as u8);
+        if column < 26 {
+            break;
+        }
+        column = column / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).expect("ASCII letters")
+}
+
+fn cell(term: &Term) -> (Cell, usize) {
+    let cell = match term {
+        Term::NamedNode(node) => Cell::Text(node.as_str().to_owned()),
+        Term::BlankNode(node) => Cell::Text(node.to_string()),
+        Term::Literal(literal) => literal_cell(literal),
+        Term::Triple(triple) => Cell::Text(format!("<<( {triple} )>>")),
+    };
+    let style = match cell {
+        Cell::Date(_, style) => style,
+        _ => 0,
+    };
+    (cell, style)
+}
+
+fn literal_cell(literal: &Literal) -> Cell {
+    let value = literal.value();
+    let datatype = literal.datatype();
+    let numeric = [
+        xsd::INTEGER,
+        xsd::DECIMAL,
+        xsd::DOUBLE,
+        xsd::FLOAT,
+        xsd::INT,
+        xsd::LONG,
+        xsd::SHORT,
+        xsd::BYTE,
+        xsd::NON_NEGATIVE_INTEGER,
+        xsd::POSITIVE_INTEGER,
+        xsd::NON_POSITIVE_INTEGER,
+        xsd::NEGATIVE_INTEGER,
+        xsd::UNSIGNED_INT,
+        xsd::UNSIGNED_LONG,
+        xsd::UNSIGNED_SHORT,
+        xsd::UNSIGNED_BYTE,
+    ];
+    if numeric.contains(&datatype) {
+        if let Ok(number) = value.parse::<f64>() {
+            if number.is_finite() {
+                return Cell::Number(number.to_string());
+            }
+        }
+    } else if datatype == xsd::BOOLEAN {
+        match value {
+            "true" | "1" => return Cell::Boolean(true),
+            "false" | "0" => return Cell::Boolean(false),
+            _ => {}
+        }
+    } else if datatype == xsd::DATE {
+        if let Some(days) = date_serial(value) {
+            return Cell::Date(days as f64, DATE_STYLE);
+        }
+    } else if datatype == xsd::DATE_TIME {
+        if let Some((date, time)) = value.split_once('T') {
+            if let (Some(days), Some(fraction)) = (date_serial(date), time_fraction(time)) {
+                return Cell::Date(days as f64 + fraction, DATE_TIME_STYLE);
+            }
+        }
+    }
+    Cell::Text(value.to_owned())
+}
+
+/// Returns the serial number of a date (`YYYY-MM-DD`, ignoring any timezone), counting days
+/// from 1899-12-30 (as spreadsheets do, for dates after February 1900).
+fn date_serial(value: &str) -> Option<i64> {
+    let date = value.get(..10)?;
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days since 1970-01-01 (from the proleptic Gregorian calendar):
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some(days + 25_569)
+}
+
+/// Returns the fraction of a day of a time (`hh:mm:ss`, ignoring fractional seconds and any
+/// timezone).
+fn time_fraction(value: &str) -> Option<f64> {
+    let mut parts = value.get(..8)?.splitn(3, ':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some((hours * 3600.0 + minutes * 60.0 + seconds) / 86_400.0)
+}
+
+/// Escapes text for XML, leaving out characters not allowed in XML.
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
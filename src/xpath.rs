@@ -0,0 +1,393 @@
+//! Reading of XML into a tree, and selection of nodes in it by XPath (the common subset of it:
+//! paths of steps like `name`, `*`, `@name`, `text()`, `.`, `..` and `//`, with predicates
+//! like `[1]`, `[@type='x']` or `[name]`). Namespace prefixes of names are ignored.
+
+use std::io::BufRead;
+
+use anyhow::{Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// An XML document, with its nodes by index (the first being the root).
+pub(crate) struct Document {
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    parent: Option<usize>,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Root(Vec<usize>),
+    Element {
+        name: String,
+        attributes: Vec<(String, String)>,
+        children: Vec<usize>,
+    },
+    Text(String),
+}
+
+/// A selected item: a node, or an attribute (by index) of an element.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Item {
+    Node(usize),
+    Attribute(usize, usize),
+}
+
+impl Document {
+    /// Reads a document (leaving out whitespace between elements, comments and processing
+    /// instructions).
+    pub(crate) fn parse<R: BufRead>(reader: R) -> Result<Self> {
+        let mut reader = Reader::from_reader(reader);
+        let mut document = Self {
+            nodes: vec![Node {
+                parent: None,
+                kind: NodeKind::Root(Vec::new()),
+            }],
+        };
+        let mut open = vec![0];
+        let mut buf = Vec::new();
+        loop {
+            let event = reader
+                .read_event_into(&mut buf)
+                .with_context(|| format!("Invalid XML at {}", reader.buffer_position()))?;
+            let parent = *open.last().expect("an open node");
+            match event {
+                Event::Start(start) => {
+                    let element = element(&start)?;
+                    open.push(document.add(parent, element));
+                }
+                Event::Empty(start) => {
+                    let element = element(&start)?;
+                    document.add(parent, element);
+                }
+                Event::End(_) if open.len() > 1 => {
+                    open.pop();
+                }
+                Event::Text(text) => {
+                    let text = text.unescape()?;
+                    if !text.trim().is_empty() {
+                        document.add(parent, NodeKind::Text(text.into_owned()));
+                    }
+                }
+                Event::CData(data) => {
+                    let text = String::from_utf8_lossy(&data.into_inner()).into_owned();
+                    document.add(parent, NodeKind::Text(text));
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+        Ok(document)
+    }
+
+    fn add(&mut self, parent: usize, kind: NodeKind) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            parent: Some(parent),
+            kind,
+        });
+        match &mut self.nodes[parent].kind {
+            NodeKind::Root(children) | NodeKind::Element { children, .. } => children.push(index),
+            NodeKind::Text(_) => {}
+        }
+        index
+    }
+
+    fn children(&self, node: usize) -> &[usize] {
+        match &self.nodes[node].kind {
+            NodeKind::Root(children) | NodeKind::Element { children, .. } => children,
+            NodeKind::Text(_) => &[],
+        }
+    }
+
+    /// Gives the string value of an item: the text of an attribute or a text node, or all the
+    /// text in an element.
+    pub(crate) fn string_value(&self, item: Item) -> String {
+        match item {
+            Item::Attribute(node, i) => match &self.nodes[node].kind {
+                NodeKind::Element { attributes, .. } => attributes[i].1.clone(),
+                _ => String::new(),
+            },
+            Item::Node(node) => match &self.nodes[node].kind {
+                NodeKind::Text(text) => text.clone(),
+                _ => {
+                    let children = self.children(node).iter();
+                    children
+                        .map(|&child| self.string_value(Item::Node(child)))
+                        .collect()
+                }
+            },
+        }
+    }
+}
+
+fn element(start: &BytesStart) -> Result<NodeKind> {
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        if key == "xmlns" || key.starts_with("xmlns:") {
+            continue;
+        }
+        attributes.push((key, attribute.unescape_value()?.into_owned()));
+    }
+    Ok(NodeKind::Element {
+        name,
+        attributes,
+        children: Vec::new(),
+    })
+}
+
+/// Gives a name without any namespace prefix.
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+enum Test {
+    /// An element by name (or any, for `*`).
+    Element(Option<String>),
+    /// An attribute by name (or any, for `@*`).
+    Attribute(Option<String>),
+    Text,
+    Node,
+    SelfNode,
+    Parent,
+}
+
+enum Predicate {
+    Position(usize),
+    /// Has a child element or attribute (by a test), with the given value if any.
+    Has(Test, Option<String>),
+}
+
+struct Step {
+    /// Whether the step is for any descendants (after `//`), rather than children.
+    descendants: bool,
+    test: Test,
+    predicates: Vec<Predicate>,
+}
+
+/// A parsed path.
+pub(crate) struct XPath {
+    absolute: bool,
+    steps: Vec<Step>,
+}
+
+impl XPath {
+    pub(crate) fn parse(path: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid or unsupported XPath: {path}");
+        let path = path.trim();
+        let absolute = path.starts_with('/');
+        if path == "/" {
+            return Ok(Self {
+                absolute,
+                steps: Vec::new(),
+            });
+        }
+        let mut steps = Vec::new();
+        let mut descendants = false;
+        for (i, part) in split_steps(path).into_iter().enumerate() {
+            if part.is_empty() {
+                // (Between the slashes of `//`, or before the first one.)
+                descendants = i > 0 || path.starts_with("//");
+                continue;
+            }
+            let (test, predicates) = match part.find('[') {
+                Some(at) => (&part[..at], &part[at..]),
+                None => (part, ""),
+            };
+            steps.push(Step {
+                descendants,
+                test: parse_test(test).ok_or_else(invalid)?,
+                predicates: parse_predicates(predicates).ok_or_else(invalid)?,
+            });
+            descendants = false;
+        }
+        if descendants {
+            return Err(invalid());
+        }
+        Ok(Self { absolute, steps })
+    }
+
+    /// Selects the items at the path, from the given node (or from the root, if the path is
+    /// absolute), in document order.
+    pub(crate) fn select(&self, document: &Document, context: Item) -> Vec<Item> {
+        let start = match (self.absolute, context) {
+            (true, _) => Item::Node(0),
+            (false, item) => item,
+        };
+        let mut items = vec![start];
+        for step in &self.steps {
+            let mut selected = Vec::new();
+            for item in items {
+                let Item::Node(node) = item else {
+                    continue;
+                };
+                let mut candidates = Vec::new();
+                match step.descendants {
+                    true => {
+                        for context in self_and_descendants(document, node) {
+                            candidates.extend(step_items(document, &step.test, context));
+                        }
+                    }
+                    false => candidates = step_items(document, &step.test, node),
+                }
+                for predicate in &step.predicates {
+                    candidates = filter(document, predicate, candidates);
+                }
+                for candidate in candidates {
+                    if !selected.contains(&candidate) {
+                        selected.push(candidate);
+                    }
+                }
+            }
+            items = selected;
+        }
+        items
+    }
+}
+
+fn self_and_descendants(document: &Document, node: usize) -> Vec<usize> {
+    let mut nodes = vec![node];
+    let mut i = 0;
+    while i < nodes.len() {
+        let children = document.children(nodes[i]);
+        nodes.extend(children);
+        i += 1;
+    }
+    nodes.sort_unstable();
+    nodes
+}
+
+/// Gives the items matched by a test from a node (its children, attributes, itself or parent).
+fn step_items(document: &Document, test: &Test, node: usize) -> Vec<Item> {
+    let children = document.children(node).iter().copied();
+    match test {
+        Test::SelfNode => vec![Item::Node(node)],
+        Test::Parent => document.nodes[node]
+            .parent
+            .map(Item::Node)
+            .into_iter()
+            .collect(),
+        Test::Node => children.map(Item::Node).collect(),
+        Test::Text => children
+            .filter(|&child| matches!(document.nodes[child].kind, NodeKind::Text(_)))
+            .map(Item::Node)
+            .collect(),
+        Test::Element(name) => children
+            .filter(|&child| match &document.nodes[child].kind {
+                NodeKind::Element { name: element, .. } => name
+                    .as_deref()
+                    .is_none_or(|name| local_name(name) == local_name(element)),
+                _ => false,
+            })
+            .map(Item::Node)
+            .collect(),
+        Test::Attribute(name) => match &document.nodes[node].kind {
+            NodeKind::Element { attributes, .. } => (0..attributes.len())
+                .filter(|&i| {
+                    name.as_deref()
+                        .is_none_or(|name| local_name(name) == local_name(&attributes[i].0))
+                })
+                .map(|i| Item::Attribute(node, i))
+                .collect(),
+            _ => Vec::new(),
+        },
+    }
+}
+
+fn filter(document: &Document, predicate: &Predicate, items: Vec<Item>) -> Vec<Item> {
+    match predicate {
+        Predicate::Position(position) => items.into_iter().skip(position - 1).take(1).collect(),
+        Predicate::Has(test, value) => items
+            .into_iter()
+            .filter(|item| {
+                let Item::Node(node) = item else {
+                    return false;
+                };
+                step_items(document, test, *node).into_iter().any(|found| {
+                    value
+                        .as_ref()
+                        .is_none_or(|value| document.string_value(found) == *value)
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Splits a path by its slashes (outside of predicates).
+fn split_steps(path: &str) -> Vec<&str> {
+    let mut steps = Vec::new();
+    let (mut depth, mut quote, mut start) = (0, None, 0);
+    for (i, c) in path.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            (None, '/') if depth == 0 => {
+                steps.push(path[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    steps.push(path[start..].trim());
+    steps
+}
+
+fn parse_test(test: &str) -> Option<Test> {
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || "_-.:".contains(c))
+    };
+    Some(match test.trim() {
+        "." => Test::SelfNode,
+        ".." => Test::Parent,
+        "*" => Test::Element(None),
+        "@*" => Test::Attribute(None),
+        "text()" => Test::Text,
+        "node()" => Test::Node,
+        test => match test.strip_prefix('@') {
+            Some(name) if is_name(name) => Test::Attribute(Some(name.to_owned())),
+            None if is_name(test) => Test::Element(Some(test.to_owned())),
+            _ => return None,
+        },
+    })
+}
+
+fn parse_predicates(mut text: &str) -> Option<Vec<Predicate>> {
+    let mut predicates = Vec::new();
+    while let Some(rest) = text.strip_prefix('[') {
+        let end = rest.find(']')?;
+        let content = rest[..end].trim();
+        text = rest[end + 1..].trim_start();
+        if let Ok(position) = content.parse::<usize>() {
+            predicates.push(Predicate::Position(position.max(1)));
+            continue;
+        }
+        let (test, value) = match content.split_once('=') {
+            Some((test, value)) => {
+                let value = value.trim();
+                let unquoted = ['\'', '"']
+                    .iter()
+                    .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))?;
+                (test.trim(), Some(unquoted.to_owned()))
+            }
+            None => (content, None),
+        };
+        match parse_test(test)? {
+            test @ (Test::Element(_) | Test::Attribute(_) | Test::Text) => {
+                predicates.push(Predicate::Has(test, value));
+            }
+            _ => return None,
+        }
+    }
+    text.is_empty().then_some(predicates)
+}
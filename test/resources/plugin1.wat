@@ -0,0 +1,113 @@
+;; A plugin of SPARQL functions (compiled to plugin1.wasm).
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+
+  ;; Allocates memory for string arguments.
+  (func (export "alloc") (param $len i32) (result i32)
+    global.get $next
+    global.get $next
+    local.get $len
+    i32.add
+    global.set $next)
+
+  ;; Tells whether a string of digits passes the Luhn check (1) or not (0).
+  (func (export "luhn") (param $ptr i32) (param $len i32) (result i32)
+    (local $sum i32) (local $digit i32) (local $double i32)
+    block $invalid
+      local.get $len
+      i32.eqz
+      br_if $invalid
+      loop $next
+        local.get $ptr
+        local.get $len
+        i32.const 1
+        i32.sub
+        local.tee $len
+        i32.add
+        i32.load8_u
+        i32.const 48
+        i32.sub
+        local.tee $digit
+        i32.const 9
+        i32.gt_u
+        br_if $invalid
+        local.get $double
+        if
+          local.get $digit
+          i32.const 2
+          i32.mul
+          local.tee $digit
+          i32.const 9
+          i32.gt_u
+          if
+            local.get $digit
+            i32.const 9
+            i32.sub
+            local.set $digit
+          end
+        end
+        local.get $sum
+        local.get $digit
+        i32.add
+        local.set $sum
+        local.get $double
+        i32.eqz
+        local.set $double
+        local.get $len
+        br_if $next
+      end
+      local.get $sum
+      i32.const 10
+      i32.rem_u
+      i32.eqz
+      return
+    end
+    i32.const 0)
+
+  ;; Converts degrees Fahrenheit to Celsius.
+  (func (export "celsius") (param $f f64) (result f64)
+    local.get $f
+    f64.const 32
+    f64.sub
+    f64.const 5
+    f64.mul
+    f64.const 9
+    f64.div)
+
+  ;; Gives a string in upper case (for ASCII letters).
+  (func (export "upper") (param $ptr i32) (param $len i32) (result i32 i32)
+    (local $i i32) (local $c i32)
+    block $done
+      loop $next
+        local.get $i
+        local.get $len
+        i32.ge_u
+        br_if $done
+        local.get $ptr
+        local.get $i
+        i32.add
+        i32.load8_u
+        local.tee $c
+        i32.const 97
+        i32.sub
+        i32.const 26
+        i32.lt_u
+        if
+          local.get $ptr
+          local.get $i
+          i32.add
+          local.get $c
+          i32.const 32
+          i32.sub
+          i32.store8
+        end
+        local.get $i
+        i32.const 1
+        i32.add
+        local.set $i
+        br $next
+      end
+    end
+    local.get $ptr
+    local.get $len))
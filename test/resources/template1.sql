@@ -0,0 +1,6 @@
+{{! One insert for all solutions }}
+INSERT INTO items (iri, name) VALUES
+{{#each}}
+  ({{sql item}}, {{sql name}}){{#unless @last}},{{/unless}}
+{{/each}}
+;
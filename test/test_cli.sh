@@ -26,10 +26,164 @@ echo "# Query from file"
 oxrq -f resources/query1.rq resources/file1.ttl
 echo
 
+echo "# Bind variables"
+oxrq --bind 'item=<http://example.org/item/1>' --bind 'name="Item 1"' 'ask { ?item :name ?name }' resources/file1.ttl
+oxrq --bind 'item=<http://example.org/item/1>' 'select ?name { ?item :name ?name }' resources/file1.ttl
+oxrq --bind 'item=<http://example.org/item/1>' 'delete { graph ?g { ?item :name ?name } } where { graph ?g { ?item :name ?name } }' resources/file1.ttl -o nt
+echo
+
+echo "# Fill query template"
+oxrq --param item=item/1 'select ?name { <http://example.org/{{item}}> :name ?name }' resources/file1.ttl
+oxrq --param n=1 'select ?item { ?item :name ?name filter(strlen(?name) > {{n:integer}}) }' resources/file1.ttl
+oxrq --param n=10 'select ?item { ?item :name ?name filter(strlen(?name)<{{n:integer}}) }' resources/file1.ttl
+oxrq --param n=1 'select ?item { ?item ?p ?o } limit {{n:integer}}' resources/file1.ttl
+oxrq --param n=ten 'select ?item { ?item :name ?name filter(strlen(?name) > {{n:integer}}) }' resources/file1.ttl 2>&1 | head -1
+echo
+
+echo "# Query by a graph pattern"
+oxrq '?item a :Item ; :name ?name' resources/file1.ttl
+echo
+
+echo "# Report the error of an invalid graph pattern (such as an undeclared prefix)"
+oxrq '?item a ex:Item' resources/file1.ttl 2>&1 | head -1
+echo
+
+echo "# Search literals by keywords"
+oxrq --text-search '?item :name ?name filter(oxrq:matches(?name, "item"))' resources/file1.ttl
+echo
+
+echo "# Call functions of a WebAssembly plugin"
+oxrq --plugin resources/plugin1.wasm 'select ?item (oxrq:upper(?name) as ?upper) (oxrq:celsius(212) as ?celsius)
+    { ?item :name ?name filter(!oxrq:luhn("79927398710")) }' resources/file1.ttl
+echo
+
+echo "# Filter and join by GeoSPARQL functions"
+oxrq 'select ?place { ?place geo:asWKT ?wkt . :svealand geo:asWKT ?area
+    filter(?place != :svealand && geof:sfWithin(?wkt, ?area)) } order by ?place' resources/places1.ttl
+oxrq 'select ?place (round(?m / 1000) as ?km) { :stockholm geo:asWKT ?here . ?place geo:asWKT ?wkt
+    bind(geof:distance(?here, ?wkt, <http://www.opengis.net/def/uom/OGC/1.0/metre>) as ?m) } order by ?m' resources/places1.ttl
+echo
+
+echo "# Query a pattern by shortcuts"
+oxrq --subject http://example.org/item/1 resources/file1.ttl
+oxrq --predicate a resources/file1.ttl -o tsv
+echo
+
+echo "# Filter quads by a pattern (without a query)"
+oxrq --match-predicate a -o nq resources/file1.ttl resources/places1.ttl
+oxrq --match-graph resources/places1.ttl --match-subject https://example.org/places/uppsala -o nt resources/file1.ttl resources/places1.ttl
+echo
+
+echo "# Grep for terms by a regular expression (with the context of matching subjects)"
+oxrq --grep 'upp' -o nt resources/file1.ttl resources/places1.ttl
+oxrq --grep '(?i)item 1' --grep-context -o nt resources/file1.ttl resources/places1.ttl
+echo
+
+echo "# Describe a resource"
+oxrq --describe '<item/1>' --base-iri http://example.org/ resources/file1.ttl -o nt
+echo
+
+echo "# Run updates in sequence before query"
+oxrq -u 'delete { graph ?g { ?s a ?type } } where { graph ?g { ?s a ?type } }' 'select ?s ?p ?o { ?s ?p ?o }' resources/update1.ru resources/file1.ttl
+echo
+
+echo "# Run query and update files in order"
+oxrq -n resources/query1.rq resources/update1.ru resources/count1.rq resources/file1.ttl
+echo
+
+echo "# Pipeline of queries"
+oxrq -p 'construct { ?item a :Thing } { ?item a :Item }' resources/query1.rq resources/file1.ttl
+echo
+
+echo "# Query from stdin"
+echo 'select ?item { ?item a :Item }' | oxrq - resources/file1.ttl
+echo
+
 echo "# Output CSV"
 oxrq -f resources/query1.rq resources/file1.ttl -ocsv
 echo
 
+echo "# Output Markdown table"
+oxrq 'select ?item ?type ?name { ?item a ?type ; :name ?name }' resources/file1.ttl -o md
+oxrq -n 'select ?html { values ?html { "<b>a&b</b> | c" } }' -o md
+echo
+
+echo "# Output HTML table (rows only)"
+oxrq 'select ?item ?type ?name { ?item a ?type ; :name ?name }' resources/file1.ttl -o html | grep '^<tr><td>'
+echo
+
+echo "# Output XLSX (a zip file)"
+outdir=$(mktemp -d)
+oxrq -O $outdir/results.xlsx 'select ?item ?name { ?item :name ?name }' resources/file1.ttl
+od -c $outdir/results.xlsx | head -1 | cut -c1-24
+rm -r $outdir
+echo
+
+echo "# Output SQLite database (read back by the Direct Mapping)"
+outdir=$(mktemp -d)
+oxrq -O $outdir/results.db 'select ?item ?name ?count { ?item :name ?name } values ?count { 3 }' resources/file1.ttl
+oxrq -b http://example.org/db/ 'select ?item ?name ?count { [] <results#item> ?item ; <results#name> ?name ; <results#count> ?count }' $outdir/results.db
+rm -r $outdir
+echo
+
+echo "# Output JSON Lines"
+oxrq 'select ?item ?name { ?item :name ?name }' resources/file1.ttl -o jsonl
+echo
+
+echo "# Output YAML"
+oxrq 'select ?item ?name { ?item :name ?name }' resources/file1.ttl -o yaml
+echo
+
+echo "# Use prefixes from a file"
+oxrq --prefixes resources/prefixes1.ttl 'select ?item { ?item ex:name ?name }' resources/file1.ttl -o table
+echo
+
+echo "# Use built-in prefixes"
+echo '<http://example.org/a> a <http://schema.org/Person> .' | oxrq 'construct { ?s a schema:Thing } { ?s a schema:Person }' -o ttl
+echo
+
+echo "# Look up undeclared prefixes (in the cache)"
+XDG_CACHE_HOME=resources/cache oxrq --lookup-prefixes 'select ?item { ?item a ns:Item }' resources/file1.ttl
+echo
+
+echo "# Use defaults and a named query from a config file"
+XDG_CONFIG_HOME=resources/config oxrq @names resources/file1.ttl
+echo
+
+echo "# Convert the input (when no query is given)"
+oxrq -n resources/file1.ttl -o nt
+echo
+
+echo "# Run a saved query"
+XDG_CONFIG_HOME=resources/config oxrq --list-saved
+XDG_CONFIG_HOME=resources/config oxrq --saved graphs resources/file1.ttl
+echo
+
+echo "# Run the default query from a config file (when no query is given)"
+XDG_CONFIG_HOME=resources/config oxrq resources/file1.ttl
+echo
+
+echo "# Use options from environment variables"
+OXRQ_OUTPUT_FORMAT=csv XDG_CONFIG_HOME=resources/config oxrq @names resources/file1.ttl
+echo
+
+echo "# Output aligned table"
+oxrq 'select ?item ?name { ?item :name ?name }' resources/file1.ttl -o table
+echo
+
+echo "# Output results through a template"
+oxrq 'select ?item ?name { ?item :name ?name } order by ?item' resources/file1.ttl --template resources/template1.sql
+outdir=$(mktemp -d)
+printf 'https://example.org/search?q={{url name}}\n' > $outdir/template.txt
+oxrq 'select ?name { ?item :name ?name }' resources/file1.ttl --template $outdir/template.txt
+oxrq 'construct { ?item :name ?name } { ?item :name ?name }' resources/file1.ttl --template $outdir/template.txt
+rm -r $outdir
+echo
+
+echo "# Output CSV with semicolons and no header"
+oxrq 'select ?item ?name { ?item :name ?name }' resources/file1.ttl -o csv --delimiter ';' --no-header --line-terminator lf
+echo
+
 echo "# Read RDF/XML"
 oxrq "select ?s ?p ?o { ?s ?p ?o }" resources/file1.rdf
 echo
@@ -38,14 +192,351 @@ echo "# Read RDF/XML from stdin"
 cat resources/file1.rdf | oxrq -irdf "select ?s ?p ?o { ?s ?p ?o }"
 echo
 
+echo "# Detect RDF/XML from stdin"
+cat resources/file1.rdf | oxrq "select ?s ?p ?o { ?s ?p ?o }"
+echo
+
+echo "# Read JSON-LD"
+oxrq "select ?s ?p ?o { ?s ?p ?o }" resources/file1.jsonld
+echo
+
+echo "# Read JSON-LD from stdin"
+cat resources/file1.jsonld | oxrq -ijsonld "select ?s ?p ?o { ?s ?p ?o }"
+echo
+
+echo "# Read HDT"
+oxrq "select ?s ?p ?o { ?s ?p ?o }" resources/file2.hdt
+echo
+
+echo "# Read N3 (skipping rules)"
+oxrq 'select ?s ?p ?o { ?s ?p ?o } order by ?s ?p' resources/family1.n3
+echo
+
+echo "# Read RDF/JSON"
+oxrq "select ?s ?p ?o { ?s ?p ?o }" resources/file1.rj
+echo
+
+echo "# Output RDF/JSON"
+oxrq -o rj 'construct { ?s ?p ?o } { ?s ?p ?o }' resources/file1.ttl
+echo
+
+echo "# Read CSV (by a mapping of columns to properties, and by CSVW metadata)"
+oxrq --csv-mapping resources/mapping1.json 'select ?person ?name ?born { ?person a schema:Person ; schema:name ?name optional { ?person schema:birthDate ?born } } order by ?person' resources/people1.csv
+oxrq --csv-mapping resources/people1.csv-metadata.json -o ttl resources/people1.csv
+echo
+
+echo "# Read SQLite database (by the Direct Mapping, and by an R2RML mapping)"
+oxrq -b http://example.org/db/ 'base <http://example.org/db/> select ?name ?city { ?person <People#fname> ?name optional { ?person <People#ref-addr>/<Addresses#city> ?city } } order by ?name' resources/people1.sqlite
+oxrq --r2rml resources/people1.r2rml.ttl -o ttl 'construct where { ?s ?p ?o }' resources/people1.sqlite
+echo
+
+echo "# Report a corrupt SQLite database (with a page size of 0)"
+outdir=$(mktemp -d)
+(head -c 16 resources/people1.sqlite; printf '\000\000'; tail -c +19 resources/people1.sqlite) > $outdir/bad.sqlite
+oxrq -o nt $outdir/bad.sqlite 2>&1 | sed "s|$outdir/||"
+rm -r $outdir
+echo
+
+echo "# Read JSON by an RML mapping"
+oxrq --rml resources/api1.rml.ttl 'select ?person ?name ?tag { ?person a schema:Person ; schema:name ?name optional { ?person schema:keywords ?tag } } order by ?name ?tag' resources/api1.json
+echo
+
+echo "# Read XML records by a mapping"
+oxrq --xml-mapping resources/records1.rml.ttl 'select ?book ?name ?author ?year { ?book a schema:Book ; schema:name ?name ; schema:author ?author ; schema:datePublished ?year } order by ?year' resources/records1.xml
+echo
+
+echo "# Read gzipped Turtle"
+oxrq 'select ?s ?p ?o { ?s ?p ?o }' resources/file3.ttl.gz
+echo
+
+echo "# Read RDF files from zip archive"
+oxrq 'select ?g (count(*) as ?n) { graph ?g { ?s ?p ?o } } group by ?g order by ?g' resources/files.zip
+echo
+
+echo "# Apply RDF Patch to data"
+oxrq -d 'select ?s ?p ?o { ?s ?p ?o }' resources/file1.ttl resources/patch1.rdp
+echo
+
+echo "# Query statements about triples (RDF-star)"
+oxrq 'select ?name ?source { ?r rdf:reifies <<( ?s :name ?name )>> ; :source ?source }' resources/statements1.ttl
+oxrq -o ttl 'construct { ?s :claims ?t } { ?s :claims ?t }' resources/statements1.ttl
+echo
+
+echo "# Read RDFa from HTML"
+oxrq "select ?s ?p ?o { ?s ?p ?o }" resources/page1.html
+echo
+
+echo "# Read microdata and embedded JSON-LD from HTML"
+oxrq "select ?s ?name { ?s a schema:Product ; schema:name ?name }" resources/page2.html
+echo
+
+echo "# Output to file (in format given by suffix)"
+outdir=$(mktemp -d)
+oxrq -O $outdir/result.nt 'construct { ?s ?p ?o } { ?s ?p ?o }' resources/file1.ttl
+cat $outdir/result.nt
+rm -r $outdir
+echo
+
+echo "# Update file in place"
+outdir=$(mktemp -d)
+cp resources/file1.ttl $outdir/
+oxrq --in-place 'delete { graph ?g { ?s :name ?o } } insert { graph ?g { ?s :name "Item One" } } where { graph ?g { ?s :name ?o } }' $outdir/file1.ttl
+cat $outdir/file1.ttl
+rm -r $outdir
+echo
+
+echo "# Update file in place without adding a base IRI, warning about updates of the default graph"
+outdir=$(mktemp -d)
+printf '@prefix : <http://example.org/ns#> .\n<http://example.org/a> :name "A" .\n' > $outdir/file.ttl
+oxrq --in-place 'delete where { ?s :name ?o }' $outdir/file.ttl 2>&1
+oxrq --in-place 'insert { graph ?g { ?s a :Item } } where { graph ?g { ?s :name ?o } }' $outdir/file.ttl
+cat $outdir/file.ttl
+rm -r $outdir
+echo
+
+echo "# Update files in place, leaving the graphs of compressed files unwritten"
+outdir=$(mktemp -d)
+cp resources/file1.ttl $outdir/
+gzip -c resources/file1.ttl > $outdir/file2.ttl.gz
+oxrq --in-place 'insert { graph ?g { ?s :note "updated" } } where { graph ?g { ?s :name ?o } }' $outdir/file1.ttl $outdir/file2.ttl.gz 2>&1 | sed "s|$outdir/||"
+grep -c updated $outdir/file1.ttl
+gzip -dc $outdir/file2.ttl.gz | grep -c updated
+rm -r $outdir
+echo
+
+echo "# Compare inputs"
+sed 's/Item 1/Item One/' resources/file1.ttl | oxrq --diff resources/file1.ttl -
+echo
+
+echo "# Combine inputs by set operations"
+sed 's/Item 1/Item One/' resources/file1.ttl | oxrq --union resources/file1.ttl resources/file1.rdf - -o nt --sort
+sed 's/Item 1/Item One/' resources/file1.ttl | oxrq --intersect resources/file1.ttl - -o nt
+sed 's/Item 1/Item One/' resources/file1.ttl | oxrq --subtract resources/file1.ttl - -o nt
+echo
+
+echo "# Limit and offset results"
+oxrq --limit 1 --offset 1 'select ?p { ?s ?p ?o } order by ?p' resources/file1.ttl
+echo
+
+echo "# Count solutions"
+oxrq --count 'select * { ?s ?p ?o }' resources/file1.ttl
+echo
+
+echo "# Check syntax of query and data"
+oxrq --check 'select * { ?item :name ?name ' resources/file1.ttl || echo "Exit status: $?"
+oxrq 'select * { ?item :name ?name ' resources/file1.ttl 2>&1 | head -1
+echo
+
+echo "# Lint data"
+oxrq --lint resources/file1.ttl resources/vocab1.ttl || echo "Exit status: $?"
+echo
+
+echo "# Summarize data"
+oxrq --stats resources/file1.ttl resources/places1.ttl resources/vocab1.ttl
+echo
+
+echo "# Report namespaces"
+oxrq --namespaces resources/namespaces1.ttl
+echo
+
+echo "# Describe data as a VoID dataset"
+oxrq --void resources/file1.ttl resources/places1.ttl | oxrq 'select ?triples ?entities ?property ?count { [] void:triples ?triples ; void:entities ?entities ; void:propertyPartition [ void:property ?property ; void:triples ?count ] } order by ?property'
+echo
+
+echo "# Explain query plan"
+oxrq --explain 'select ?name { ?item a :Item ; :name ?name }' resources/file1.ttl
+echo
+
+echo "# Abort query after timeout"
+oxrq --timeout 1 'select (count(*) as ?n) { ?a ?b ?c . ?d ?e ?f . ?g ?h ?i . ?j ?k ?l . ?m ?n ?o . ?p ?q ?r . ?s ?t ?u . ?v ?w ?x }' resources/file1.ttl resources/ontology1.ttl resources/vocab1.ttl || echo "Exit status: $?"
+echo
+
+echo "# Use ASK query in shell conditional"
+if oxrq -q 'ask { ?item a :Item }' resources/file1.ttl; then echo "Has items"; fi
+oxrq -q 'ask { ?item a :Nothing }' resources/file1.ttl || echo "Exit status: $?"
+echo
+
+echo "# Skolemize blank nodes"
+echo '@prefix : <http://example.org/ns#> . <http://example.org/item/1> :part [ :name "A" ], [ :name "B" ] .' | oxrq --skolemize=https://example.org/.well-known/genid/ -o nt
+echo
+
+echo "# Load imported ontologies (by a catalog)"
+oxrq --imports --catalog resources/catalog-v001.xml 'select ?g (count(*) as ?n) { graph ?g { ?s ?p ?o } } group by ?g order by ?g' resources/ontology2.ttl
+echo
+
+echo "# Query with RDFS inference"
+oxrq --inference rdfs 'select ?type ?label { ?item a ?type ; rdfs:label ?label } order by ?type' resources/file1.ttl resources/vocab1.ttl
+echo
+
+echo "# Query with OWL 2 RL inference"
+oxrq --inference owl-rl 'select ?part { <http://example.org/collection/1> :hasPart ?part } order by ?part' resources/ontology1.ttl
+echo
+
+echo "# Query with N3 rules and CONSTRUCT rules applied"
+oxrq --rules resources/family1.n3 'select ?x ?z { ?x :grandparent ?z }' resources/file0.ttl
+oxrq --rules resources/family1.n3 --rules resources/rules1.rq 'select ?x ?y { ?x :ancestor ?y } order by ?x ?y' resources/file0.ttl
+echo
+
+echo "# Validate against SHACL shapes (querying the validation report)"
+oxrq --shacl resources/shapes1.ttl resources/file1.ttl | oxrq 'select ?focus ?component { [] sh:focusNode ?focus ; sh:sourceConstraintComponent ?component } order by ?component'
+echo
+
+echo "# Validate against ShEx shapes"
+oxrq --shex resources/schema1.shex --shape-map '{FOCUS a :Item}@:Item, <http://example.org/item/1>@:Named' resources/file1.ttl
+echo
+
+echo "# Check isomorphism"
+oxrq --isomorphic resources/file1.ttl resources/file1.rdf && echo "isomorphic"
+sed 's/Item 1/Item One/' resources/file1.ttl | oxrq --isomorphic resources/file1.ttl -
+echo
+
+echo "# Output changes of update as RDF Patch"
+oxrq -o rdp 'delete { graph ?g { ?s :name ?o } } insert { graph ?g { ?s :name "Item One" } } where { graph ?g { ?s :name ?o } }' resources/file1.ttl
+echo
+
 echo "# Output RDF/XML"
 oxrq resources/file1.ttl -fo rdf
 echo
 
+echo "# Output HDT (read back as N-Triples)"
+oxrq resources/file1.ttl -fo hdt | oxrq -i hdt -o nt
+echo
+
+echo "# Output only used prefixes"
+echo '@prefix ex: <http://example.org/> . @prefix foaf: <http://xmlns.com/foaf/0.1/> . ex:a foaf:name "A" ; ex:b 1 .' | oxrq 'construct { ?s ?p ?o } { ?s ex:b ?o ; ?p ?o }' --prune-prefixes -o ttl
+echo
+
+echo "# Output highlighted Turtle"
+cat resources/file1.ttl | oxrq --color always -o ttl
+echo
+
+echo "# Output pretty-printed Turtle"
+echo '@prefix : <http://example.org/ns#> . <http://example.org/item/1> a :Item ; :part [ :name "A" ], [ :name "B" ] ; :tags ("x" "y") .' | oxrq --pretty -o ttl
+echo
+
+echo "# Output sorted N-Triples"
+echo '@prefix : <http://example.org/ns#> . <http://example.org/item/2> :name "B" . <http://example.org/item/1> :part [ :name "A" ] ; a :Item .' | oxrq --sort -o nt
+echo
+
+echo "# Output one named graph as Turtle"
+oxrq -f -o ttl --output-graph resources/vocab1.ttl resources/file1.ttl resources/vocab1.ttl
+echo
+
+echo "# Output all graphs merged (without duplicate triples)"
+oxrq -f --merge-graphs resources/file1.ttl resources/file1.rdf
+echo
+
+echo "# Output each named graph to its own file"
+outdir=$(mktemp -d)
+oxrq -f -o nt --split-graphs $outdir resources/file1.ttl resources/file1.rdf=http://example.org/graph/1
+(cd $outdir && find . -type f | sort && cat example.org/graph/1.nt)
+rm -r $outdir
+echo
+
+echo "# Output each resource, and the instances of each class, to their own files"
+for mode in subject class; do
+  outdir=$(mktemp -d)
+  oxrq --split-by $mode -O $outdir resources/file1.ttl resources/places1.ttl
+  (cd $outdir && find . -type f | sort)
+  rm -r $outdir
+done
+echo
+
+echo "# Warn about triples written to no file when splitting by resource"
+outdir=$(mktemp -d)
+echo '<http://example.org/a> a <http://example.org/C> . [] <http://example.org/r> 2 .' | oxrq --split-by subject -O $outdir 2>&1
+(cd $outdir && find . -type f | sort)
+rm -r $outdir
+echo
+
+echo "# Output compacted JSON-LD"
+oxrq 'construct { ?s ?p ?o } { ?s ?p ?o }' resources/file1.ttl -o jsonld
+echo
+
+echo "# Output framed JSON-LD"
+oxrq --frame resources/frame1.jsonld 'construct { <list> a :List ; :hasPart ?item . ?item ?p ?o } { ?item a :Item ; ?p ?o }' resources/file1.ttl
+echo
+
+echo "# Read data from URL"
+oxrq -n 'select (count(*) as ?count) { ?s ?p ?o }' https://www.w3.org/1999/02/22-rdf-syntax-ns
+echo
+
+echo "# Use cached data from URL (when the server cannot be reached)"
+XDG_CACHE_HOME=resources/cache oxrq -n 'select ?item { ?item a :Item }' http://localhost:9/items.ttl
+echo
+
+echo "# Follow links to fetch more data"
+echo '<http://example.org/a> <http://www.w3.org/2000/01/rdf-schema#seeAlso> <https://www.w3.org/1999/02/22-rdf-syntax-ns#type> .' | oxrq --follow 1:rdfs:seeAlso 'select ?g { graph ?g { ?s ?p ?o } } group by ?g' -
+echo
+
+echo "# Query remote endpoint"
+oxrq -e https://query.wikidata.org/sparql 'select ?label {
+  <http://www.wikidata.org/entity/Q42> <http://www.w3.org/2000/01/rdf-schema#label> ?label
+  filter(lang(?label) = "en")
+}'
+echo
+
+echo "# Join with remote data using federation"
+oxrq --federation 'select ?item ?label {
+  ?item a :Item .
+  service <https://query.wikidata.org/sparql> {
+    <http://www.wikidata.org/entity/Q42> <http://www.w3.org/2000/01/rdf-schema#label> ?label
+    filter(lang(?label) = "en")
+  }
+}' resources/file1.ttl
+echo
+
+echo "# Join with data loaded apart as a virtual endpoint"
+oxrq --service vocab=resources/vocab1.ttl 'select ?item ?super {
+    ?item a ?type service <urn:oxrq:vocab> { ?type rdfs:subClassOf ?super } }' resources/file1.ttl
+echo
+
+echo "# Serve data as an endpoint (and query it)"
+oxrq --serve localhost:8765 resources/file1.ttl &
+sleep 1
+oxrq -e http://localhost:8765/ 'select ?item { ?item a <http://example.org/ns#Item> }'
+kill $!
+echo
+
+echo "# Load files into chosen named graphs"
+oxrq 'select distinct ?g { graph ?g { ?s ?p ?o } }' resources/file1.ttl=http://example.org/graph/1 resources/file1.rdf
+echo
+
+echo "# Load files into the default graph"
+oxrq -d -fo nq resources/file1.ttl resources/file1.rdf
+echo
+
+echo "# Skip a file with an error after its first batch of quads (kept if added to a graph with other data)"
+outdir=$(mktemp -d)
+seq 100001 | sed 's|.*|<urn:x:&> <urn:x:p> "&" .|' > $outdir/bad.nt
+echo '<urn:x:bad> .' >> $outdir/bad.nt
+oxrq 'select (count(*) as ?n) { ?s ?p ?o }' resources/file1.ttl $outdir/bad.nt
+oxrq -d 'select (count(*) as ?n) { ?s ?p ?o }' resources/file1.ttl $outdir/bad.nt
+rm -r $outdir
+echo
+
+echo "# Merge blank nodes of files by label"
+for mode in standard shared; do
+  oxrq --bnode-merge $mode 'select ?name ?size { ?s <http://example.org/name> ?name ; <http://example.org/size> ?size }' resources/part1.nt resources/part2.nt
+done
+echo
+
+echo "# Query the default graph apart from named graphs"
+oxrq --no-union 'ask { ?item a :Item }' resources/file1.ttl
+echo
+
 echo "# Find all files matching query"
 oxrq 'select ?g {graph ?g {?item a :Item}}' resources/file1.*
 echo
 
+echo "# Find all files in directory matching query"
+oxrq 'select distinct ?g {graph ?g {?item a :Item}} order by ?g' resources
+echo
+
+echo "# Find all files matching glob pattern"
+oxrq 'select ?g {graph ?g {?item a :Item}} order by ?g' 'resources/*.ttl*'
+echo
+
 echo "# Use prefixes from empty file, then read from stdin"
 cat resources/file1.ttl | oxrq -onq | oxrq -f resources/file0.ttl -
 echo